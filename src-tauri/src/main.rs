@@ -1,31 +1,304 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::sync::Mutex;
-
-use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LockResult, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, Months, NaiveDate, NaiveTime, Offset, TimeZone, Timelike, Utc, Weekday};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{FromSql, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, DatabaseName, OptionalExtension, ToSql, TransactionBehavior};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, LogicalPosition, LogicalSize, Manager, Position, Size, State, WebviewWindow, WindowEvent};
+use sha2::{Digest, Sha256};
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{
+  App, AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, Monitor, PhysicalSize, Position, Size, State, WebviewUrl,
+  WebviewWindow, WebviewWindowBuilder, WindowEvent,
+};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
 use uuid::Uuid;
 
-const MIGRATION_KEY: &str = "legacy_migration_done";
+// Each legacy payload is recorded under this prefix plus its fingerprint; the
+// pre-fingerprint global flag (`legacy_migration_done`) shares the prefix.
+const MIGRATION_KEY_PREFIX: &str = "legacy_migration_";
 const WINDOW_PREFS_KEY: &str = "window_prefs_json";
+const WINDOW_LAYOUT_KEY_PREFIX: &str = "window_layout_";
 const UI_PREFS_KEY: &str = "ui_prefs_json";
+const AUTOSTART_DISABLED_KEY: &str = "autostart_disabled";
+const DB_PATH_OVERRIDE_KEY: &str = "db_path_override";
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+// Ordered schema steps; step N brings a database to version N. Append new
+// steps instead of editing shipped ones.
+const SCHEMA_MIGRATIONS: &[fn(&Connection) -> CommandResult<()>] = &[
+  migrate_schema_v1,
+  migrate_schema_v2,
+  migrate_schema_v3,
+  migrate_schema_v4,
+  migrate_schema_v5,
+  migrate_schema_v6,
+];
+const WIPE_TOKEN_KEY: &str = "wipe_token_json";
+const FOCUS_TODO_KEY: &str = "focus_todo_id";
+const DAILY_SUMMARY_LAST_KEY: &str = "daily_summary_last_date";
+const ACTIVE_CONTEXT_KEY: &str = "active_context";
+const WIPE_TOKEN_TTL_SECONDS: i64 = 120;
+const AUTOSTART_NAME: &str = "SimpleTodoNote";
+#[cfg(target_os = "macos")]
+const AUTOSTART_LABEL: &str = "com.shiho.simpletodonote";
 const RECURRENCE_NONE: &str = "none";
 const RECURRENCE_DAILY: &str = "daily";
 const RECURRENCE_WEEKLY: &str = "weekly";
 const RECURRENCE_BI_WEEKLY: &str = "bi-weekly";
+const RECURRENCE_MONTHLY: &str = "monthly";
+// Every recurrence tag the backend understands; grouping and validation derive
+// from this list.
+const RECURRENCE_TAGS: &[&str] = &[
+  RECURRENCE_NONE,
+  RECURRENCE_DAILY,
+  RECURRENCE_WEEKLY,
+  RECURRENCE_BI_WEEKLY,
+  RECURRENCE_MONTHLY,
+];
+const ENERGY_HIGH: &str = "high";
+const ENERGY_MEDIUM: &str = "medium";
+const ENERGY_LOW: &str = "low";
+const MAX_CONTEXT_CHARS: usize = 40;
+const NOTE_FORMAT_PLAIN: &str = "plain";
+const NOTE_FORMAT_MARKDOWN: &str = "markdown";
 const MINI_WIDTH: f64 = 380.0;
 const MINI_HEIGHT: f64 = 520.0;
+const MIN_PANEL_DIMENSION: f64 = 200.0;
+const MIN_WINDOW_OPACITY: f64 = 0.2;
 const STANDARD_WIDTH: f64 = 760.0;
 const STANDARD_HEIGHT: f64 = 620.0;
 const WIDE_WIDTH: f64 = 920.0;
 const WIDE_HEIGHT: f64 = 680.0;
+const CLOCK_SKEW_THRESHOLD_SECONDS: i64 = 5 * 60;
+const SORT_ORDER_GAP: i64 = 1024;
+const UNDO_LIMIT: usize = 50;
+const MIGRATION_PROGRESS_INTERVAL: usize = 100;
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+const DEFAULT_MAX_ATTACHMENTS_PER_TODO: u32 = 10;
+const DEFAULT_DUE_CHECK_INTERVAL_MINUTES: u32 = 5;
+const DEFAULT_DAILY_SUMMARY_TIME: &str = "18:00";
+const DAILY_SUMMARY_CHECK_SECONDS: u64 = 60;
+const DEFAULT_REMINDER_SNOOZE_MINUTES: u32 = 30;
+const DEFAULT_BLURRED_OPACITY: f64 = 0.6;
+const DEFAULT_HTTP_SERVER_PORT: u16 = 47_831;
+const MAX_HTTP_CONNECTIONS: usize = 8;
+const QUICK_CAPTURE_LABEL: &str = "quick-capture";
+const DEFAULT_QUICK_CAPTURE_SHORTCUT: &str = "CommandOrControl+Shift+Space";
+// Counted in Unicode scalar values (`chars`), not bytes.
+const MAX_TITLE_CHARS: usize = 500;
+const MAX_NOTE_CHARS: usize = 50_000;
+// Notes longer than this start out collapsed.
+const NOTE_COLLAPSE_THRESHOLD_CHARS: usize = 280;
+// Thirty days; also keeps the due-time arithmetic far from overflow.
+const MAX_REMINDER_OFFSET_MINUTES: i64 = 30 * 24 * 60;
+const SANDBOX_ENV: &str = "SIMPLE_TODO_SANDBOX";
+const SANDBOX_DB_URI: &str = "file:simple_todo_note_sandbox?mode=memory&cache=shared";
+// Child table and the predicate selecting its rows whose parent is gone.
+const ORPHAN_CHECKS: &[(&str, &str)] = &[
+  ("subtasks", "todo_id NOT IN (SELECT id FROM todos)"),
+  ("todo_completions", "todo_id NOT IN (SELECT id FROM todos)"),
+  ("todo_attachments", "todo_id NOT IN (SELECT id FROM todos)"),
+  ("time_entries", "todo_id NOT IN (SELECT id FROM todos)"),
+  (
+    "todo_dependencies",
+    "todo_id NOT IN (SELECT id FROM todos) OR depends_on_id NOT IN (SELECT id FROM todos)",
+  ),
+  (
+    "todo_tags",
+    "todo_id NOT IN (SELECT id FROM todos) OR tag_id NOT IN (SELECT id FROM tags)",
+  ),
+];
+const TODO_COLUMNS: &str = "id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, created_at, updated_at, reminder_enabled, last_reminded_on, sort_order, completed_at, series_id, recurrence_until, note_format, rolled_at, priority, energy, deleted_at, recurrence_max_count, recurrence_completed_count, notified_at, remind_after, context, snooze_count, color, archived, pinned, reminder_offset_minutes, estimate_minutes, note_collapsed";
+
+type CommandResult<T> = Result<T, AppError>;
+type DbPool = r2d2::Pool<SqliteConnectionManager>;
+type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// Error returned to the frontend as `{ code, message }` so callers can branch
+/// on `code` instead of matching message text.
+#[derive(Debug)]
+enum AppError {
+  NotFound(String),
+  Validation(String),
+  /// A validation failure pointing at the todo it clashed with, sent as
+  /// `{ code: "validation", message, existingId }`.
+  Duplicate { message: String, existing_id: String },
+  /// The stored data changed underneath the request; re-fetch and retry.
+  Conflict(String),
+  /// A configured cap was already reached, e.g. attachments per todo.
+  LimitReached(String),
+  Database(String),
+  Lock(String),
+  Serialization(String),
+  Window(String),
+  Io(String),
+}
+
+impl AppError {
+  fn code(&self) -> &'static str {
+    match self {
+      AppError::NotFound(_) => "notFound",
+      AppError::Validation(_) | AppError::Duplicate { .. } => "validation",
+      AppError::Conflict(_) => "conflict",
+      AppError::LimitReached(_) => "limitReached",
+      AppError::Database(_) => "database",
+      AppError::Lock(_) => "lock",
+      AppError::Serialization(_) => "serialization",
+      AppError::Window(_) => "window",
+      AppError::Io(_) => "io",
+    }
+  }
+
+  fn message(&self) -> &str {
+    match self {
+      AppError::NotFound(message)
+      | AppError::Validation(message)
+      | AppError::Duplicate { message, .. }
+      | AppError::Conflict(message)
+      | AppError::LimitReached(message)
+      | AppError::Database(message)
+      | AppError::Lock(message)
+      | AppError::Serialization(message)
+      | AppError::Window(message)
+      | AppError::Io(message) => message,
+    }
+  }
+}
+
+impl Serialize for AppError {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+
+    let existing_id = match self {
+      AppError::Duplicate { existing_id, .. } => Some(existing_id),
+      _ => None,
+    };
+    let mut error = serializer.serialize_struct("AppError", 2 + usize::from(existing_id.is_some()))?;
+    error.serialize_field("code", self.code())?;
+    error.serialize_field("message", self.message())?;
+    if let Some(existing_id) = existing_id {
+      error.serialize_field("existingId", existing_id)?;
+    }
+    error.end()
+  }
+}
+
+impl std::fmt::Display for AppError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.message())
+  }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<rusqlite::Error> for AppError {
+  fn from(err: rusqlite::Error) -> Self {
+    AppError::Database(err.to_string())
+  }
+}
+
+impl From<serde_json::Error> for AppError {
+  fn from(err: serde_json::Error) -> Self {
+    AppError::Serialization(err.to_string())
+  }
+}
+
+impl From<std::io::Error> for AppError {
+  fn from(err: std::io::Error) -> Self {
+    AppError::Io(err.to_string())
+  }
+}
 
-type CommandResult<T> = Result<T, String>;
+impl From<tauri::Error> for AppError {
+  fn from(err: tauri::Error) -> Self {
+    AppError::Window(err.to_string())
+  }
+}
 
 struct AppState {
-  db: Mutex<Connection>,
+  db: RwLock<Database>,
+  window_persistence_enabled: AtomicBool,
+  window_focused: AtomicBool,
+  undo_stack: Mutex<Vec<UndoEntry>>,
+  http_server: Mutex<Option<HttpServer>>,
+}
+
+// A panic while a lock is held poisons it, and propagating that as an error
+// would leave every later command failing. The guarded data is still usable
+// (SQLite rolls back any transaction the panic interrupted), so take it back,
+// clear the poison flag and log that it happened.
+fn recover_poisoned<G>(result: LockResult<G>, name: &str, clear_poison: impl FnOnce()) -> G {
+  result.unwrap_or_else(|poisoned| {
+    eprintln!("Recovered the {name} lock after a panic poisoned it");
+    clear_poison();
+    poisoned.into_inner()
+  })
+}
+
+impl AppState {
+  fn read_db(&self) -> RwLockReadGuard<'_, Database> {
+    recover_poisoned(self.db.read(), "database", || self.db.clear_poison())
+  }
+
+  fn write_db(&self) -> RwLockWriteGuard<'_, Database> {
+    recover_poisoned(self.db.write(), "database", || self.db.clear_poison())
+  }
+
+  fn lock_undo_stack(&self) -> MutexGuard<'_, Vec<UndoEntry>> {
+    recover_poisoned(self.undo_stack.lock(), "undo stack", || self.undo_stack.clear_poison())
+  }
+
+  fn lock_http_server(&self) -> MutexGuard<'_, Option<HttpServer>> {
+    recover_poisoned(self.http_server.lock(), "HTTP server", || self.http_server.clear_poison())
+  }
+}
+
+struct HttpServer {
+  stop: Arc<AtomicBool>,
+  thread: std::thread::JoinHandle<()>,
+  port: u16,
+  // Fresh each time the server starts; only required while LAN mode is on.
+  token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WipeToken {
+  token: String,
+  expires_at: String,
+}
+
+// The live pool and the file behind it (`None` for the in-memory sandbox).
+// Both are swapped together when `set_database_path` moves the database.
+// `passphrase` is the SQLCipher key every pooled connection is opened with;
+// `locked` means the file is encrypted and no key has been supplied yet.
+struct Database {
+  pool: DbPool,
+  path: Option<std::path::PathBuf>,
+  passphrase: Option<String>,
+  locked: bool,
+}
+
+// Snapshots taken just before a destructive change, newest last.
+enum UndoEntry {
+  Delete(Vec<Todo>),
+  Toggle(Box<Todo>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UndoDescription {
+  kind: &'static str,
+  restored_ids: Vec<String>,
+  skipped_ids: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -45,15 +318,50 @@ struct Todo {
   last_reminded_on: Option<String>,
   #[serde(skip_serializing, skip_deserializing)]
   sort_order: i64,
+  completed_at: Option<String>,
+  series_id: Option<String>,
+  recurrence_until: Option<String>,
+  note_format: String,
+  rolled_at: Option<String>,
+  #[serde(default)]
+  priority: TodoPriority,
+  energy: Option<String>,
+  deleted_at: Option<String>,
+  recurrence_max_count: Option<i64>,
+  recurrence_completed_count: i64,
+  notified_at: Option<String>,
+  remind_after: Option<String>,
+  context: Option<String>,
+  #[serde(default)]
+  snooze_count: i64,
+  color: Option<String>,
+  #[serde(default)]
+  archived: bool,
+  #[serde(default)]
+  pinned: bool,
+  reminder_offset_minutes: Option<i64>,
+  estimate_minutes: Option<i64>,
+  #[serde(default)]
+  note_collapsed: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CreateTodoInput {
   title: String,
   recurrence_tag: Option<String>,
   note: Option<String>,
   due_date: Option<String>,
+  note_format: Option<String>,
+  priority: Option<TodoPriority>,
+  energy: Option<String>,
+  context: Option<String>,
+  color: Option<String>,
+  reminder_offset_minutes: Option<i64>,
+  estimate_minutes: Option<i64>,
+  note_collapsed: Option<bool>,
+  #[serde(default)]
+  reject_duplicates: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,6 +374,32 @@ struct UpdateTodoInput {
   completed: Option<bool>,
   due_date: Option<Option<String>>,
   reminder_enabled: Option<bool>,
+  note_format: Option<String>,
+  priority: Option<TodoPriority>,
+  energy: Option<String>,
+  context: Option<String>,
+  color: Option<String>,
+  reminder_offset_minutes: Option<Option<i64>>,
+  estimate_minutes: Option<Option<i64>>,
+  note_collapsed: Option<bool>,
+}
+
+// The subset of `UpdateTodoInput` that can be applied to many todos at once.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkPatch {
+  recurrence_tag: Option<String>,
+  due_date: Option<Option<String>>,
+  completed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListOptions {
+  limit: Option<i64>,
+  offset: Option<i64>,
+  completed: Option<bool>,
+  created_after: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -87,6 +421,10 @@ struct LegacyTodo {
 struct MigrationResult {
   migrated_count: usize,
   already_migrated: bool,
+  total_received: usize,
+  skipped_empty_title: usize,
+  // Ids already present in the database or repeated within the payload.
+  skipped_duplicate_id: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -96,6 +434,32 @@ struct DailyCompletionHeatmapDay {
   count: u32,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoStats {
+  total: u32,
+  completed: u32,
+  overdue: u32,
+  by_recurrence_tag: HashMap<String, u32>,
+  completions_per_day: Vec<DailyCompletionHeatmapDay>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduledLoadDay {
+  date: String,
+  count: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DailySummary {
+  date: String,
+  completed_today: u32,
+  overdue: u32,
+  due_tomorrow: u32,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct DueReminder {
@@ -106,1117 +470,13078 @@ struct DueReminder {
   recurrence_tag: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-enum PanelMode {
-  Mini,
-  Expanded,
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcrastinationScore {
+  age_component: f64,
+  snooze_component: f64,
+  overdue_component: f64,
+  total: f64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-enum WindowSizeClass {
-  Mini,
-  Standard,
-  Wide,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProcrastinationEntry {
+  todo: Todo,
+  score: ProcrastinationScore,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-enum MotionMode {
-  Balanced,
-  High,
-  Low,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangeEntry {
+  id: i64,
+  todo_id: String,
+  op: String,
+  at: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-enum ReadabilityMode {
-  Adaptive,
-  Pure,
-  Strong,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoDelta {
+  hash: String,
+  // Pass back as `since`; inclusive, so the last batch may be listed again.
+  cursor: String,
+  unchanged: bool,
+  changed: Vec<Todo>,
+  // Trashed or purged since the cursor.
+  deleted_ids: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "lowercase")]
-enum ReduceMotionOverride {
-  System,
-  On,
-  Off,
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EditFrequencyEntry {
+  todo: Todo,
+  edit_count: u32,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct WindowPrefs {
-  x: f64,
-  y: f64,
-  width: f64,
-  height: f64,
-  mode: PanelMode,
-  size_class: WindowSizeClass,
-  always_on_top: bool,
+struct StorageInfo {
+  app_data_dir: String,
+  database_path: Option<String>,
+  database_size_bytes: u64,
+  todo_count: u32,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct LegacyWindowPrefs {
-  x: f64,
-  y: f64,
-  width: f64,
-  height: f64,
-  mode: PanelMode,
-  always_on_top: bool,
+struct VacuumReport {
+  size_before_bytes: u64,
+  size_after_bytes: u64,
+  wal_checkpointed: bool,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct UiPrefs {
-  motion_mode: MotionMode,
-  readability_mode: ReadabilityMode,
-  reduce_motion_override: ReduceMotionOverride,
+struct CompletionStats {
+  total_completions: u32,
+  current_streak: u32,
+  longest_streak: u32,
 }
 
-impl Default for WindowPrefs {
-  fn default() -> Self {
-    Self {
-      x: 80.0,
-      y: 80.0,
-      width: MINI_WIDTH,
-      height: MINI_HEIGHT,
-      mode: PanelMode::Mini,
-      size_class: WindowSizeClass::Mini,
-      always_on_top: true,
-    }
-  }
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TagCompletionStats {
+  tag: String,
+  total: u32,
+  completed: u32,
+  ratio: f64,
 }
 
-impl Default for UiPrefs {
-  fn default() -> Self {
-    Self {
-      motion_mode: MotionMode::Balanced,
-      readability_mode: ReadabilityMode::Adaptive,
-      reduce_motion_override: ReduceMotionOverride::System,
-    }
-  }
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageSummary {
+  created_this_week: u32,
+  completed_this_week: u32,
+  average_completions_per_day: f64,
+  most_active_weekday: Option<String>,
 }
 
-fn now_iso() -> String {
-  Utc::now().to_rfc3339()
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecurrenceUntilReport {
+  updated: usize,
+  skipped_ids: Vec<String>,
 }
 
-fn local_day_key() -> String {
-  Local::now().format("%Y-%m-%d").to_string()
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoLinks {
+  todo: Todo,
+  urls: Vec<String>,
 }
 
-fn local_today_naive() -> NaiveDate {
-  Local::now().date_naive()
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteApplyReport {
+  applied: usize,
+  skipped: usize,
+  deleted: usize,
 }
 
-fn parse_iso_to_local_datetime(value: &str) -> Option<DateTime<Local>> {
-  DateTime::parse_from_rfc3339(value)
-    .ok()
-    .map(|parsed| parsed.with_timezone(&Local))
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessibilityAudit {
+  motion_reduced: bool,
+  follows_system_motion: bool,
+  high_readability: bool,
 }
 
-fn is_recurrence_cycle_checked_at(recurrence_tag: &str, recurrence_checked_at: Option<&str>) -> bool {
-  if recurrence_tag == RECURRENCE_NONE {
-    return false;
-  }
-
-  let Some(raw_checked_at) = recurrence_checked_at else {
-    return false;
-  };
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DanglingRows {
+  table: String,
+  count: usize,
+}
 
-  let Some(checked_at) = parse_iso_to_local_datetime(raw_checked_at) else {
-    return false;
-  };
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IntegrityReport {
+  foreign_key_violations: Vec<DanglingRows>,
+  orphans: Vec<DanglingRows>,
+  // Only filled by `check_integrity`.
+  integrity_errors: Vec<String>,
+  duplicate_sort_orders: usize,
+  repaired: bool,
+}
 
-  let now = Local::now();
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrationProgress {
+  done: usize,
+  total: usize,
+}
 
-  match recurrence_tag {
-    RECURRENCE_DAILY => checked_at.date_naive() == now.date_naive(),
-    RECURRENCE_WEEKLY | RECURRENCE_BI_WEEKLY => {
-      let elapsed = now.signed_duration_since(checked_at);
-      if elapsed < Duration::zero() {
-        return false;
-      }
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TodosChanged<'a> {
+  kind: &'a str,
+  id: Option<&'a str>,
+}
 
-      let cycle_days = if recurrence_tag == RECURRENCE_WEEKLY { 7 } else { 14 };
-      elapsed < Duration::days(cycle_days)
-    }
-    _ => false,
-  }
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClockSkew {
+  latest_stored_at: Option<String>,
+  skew_seconds: i64,
+  exceeds_threshold: bool,
 }
 
-fn normalize_date(value: Option<String>) -> Option<String> {
-  value.and_then(|candidate| {
-    let trimmed = candidate.trim();
-    if trimmed.is_empty() {
-      None
-    } else {
-      Some(trimmed.to_string())
-    }
-  })
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Subtask {
+  id: String,
+  todo_id: String,
+  title: String,
+  completed: bool,
+  sort_order: i64,
+  created_at: String,
+  updated_at: String,
 }
 
-fn normalize_recurrence_tag(value: Option<String>) -> String {
-  match value.as_deref().map(str::trim) {
-    Some(RECURRENCE_DAILY) => RECURRENCE_DAILY.to_string(),
-    Some(RECURRENCE_WEEKLY) => RECURRENCE_WEEKLY.to_string(),
-    Some(RECURRENCE_BI_WEEKLY) => RECURRENCE_BI_WEEKLY.to_string(),
-    _ => RECURRENCE_NONE.to_string(),
-  }
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Attachment {
+  id: String,
+  todo_id: String,
+  path: String,
+  label: Option<String>,
+  created_at: String,
 }
 
-fn mode_from_size_class(size_class: &WindowSizeClass) -> PanelMode {
-  match size_class {
-    WindowSizeClass::Mini => PanelMode::Mini,
-    WindowSizeClass::Standard | WindowSizeClass::Wide => PanelMode::Expanded,
-  }
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeEntry {
+  id: i64,
+  todo_id: String,
+  started_at: String,
+  stopped_at: Option<String>,
 }
 
-fn dimensions_for_size_class(size_class: &WindowSizeClass) -> (f64, f64) {
-  match size_class {
-    WindowSizeClass::Mini => (MINI_WIDTH, MINI_HEIGHT),
-    WindowSizeClass::Standard => (STANDARD_WIDTH, STANDARD_HEIGHT),
-    WindowSizeClass::Wide => (WIDE_WIDTH, WIDE_HEIGHT),
-  }
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedFilter {
+  name: String,
+  options: ListOptions,
+  created_at: String,
 }
 
-fn infer_size_class_from_dimensions(width: f64, height: f64) -> WindowSizeClass {
-  let candidates = [
-    (WindowSizeClass::Mini, MINI_WIDTH, MINI_HEIGHT),
-    (WindowSizeClass::Standard, STANDARD_WIDTH, STANDARD_HEIGHT),
-    (WindowSizeClass::Wide, WIDE_WIDTH, WIDE_HEIGHT),
-  ];
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListedTodo {
+  #[serde(flatten)]
+  todo: Todo,
+  subtask_count: u32,
+  completed_subtask_count: u32,
+  tags: Vec<String>,
+}
 
-  let mut best = WindowSizeClass::Mini;
-  let mut best_score = f64::MAX;
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SubtaskInconsistency {
+  todo: Todo,
+  subtask_count: u32,
+  completed_subtask_count: u32,
+}
 
-  for (candidate, target_width, target_height) in candidates {
-    let width_score = (width - target_width).abs();
-    let height_score = (height - target_height).abs();
-    let score = (width_score * 0.65) + (height_score * 0.35);
-    if score < best_score {
-      best_score = score;
-      best = candidate;
-    }
-  }
+// Which side is trusted when reconciling a parent with its subtasks.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum SubtaskConsistencyPolicy {
+  ParentWins,
+  SubtasksWin,
+}
 
-  best
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedTodo {
+  todo: Todo,
+  inserted_index: Option<usize>,
 }
 
-fn normalize_window_prefs(mut prefs: WindowPrefs) -> WindowPrefs {
-  let (width, height) = dimensions_for_size_class(&prefs.size_class);
-  prefs.width = width;
-  prefs.height = height;
-  prefs.mode = mode_from_size_class(&prefs.size_class);
-  prefs
+// Debugging view of a todo that also exposes the otherwise hidden `sort_order`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TodoWithOrder {
+  #[serde(flatten)]
+  todo: Todo,
+  sort_order: i64,
 }
 
-fn to_db_bool(value: bool) -> i64 {
-  if value {
-    1
-  } else {
-    0
-  }
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+  Json,
+  Markdown,
 }
 
-fn map_todo_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Todo> {
-  Ok(Todo {
-    id: row.get(0)?,
-    title: row.get(1)?,
-    recurrence_tag: row.get(2)?,
-    recurrence_checked_at: row.get(3)?,
-    note: row.get(4)?,
-    completed: row.get::<_, i64>(5)? != 0,
-    due_date: row.get(6)?,
-    created_at: row.get(7)?,
-    updated_at: row.get(8)?,
-    reminder_enabled: row.get::<_, i64>(9)? != 0,
-    last_reminded_on: row.get(10)?,
-    sort_order: row.get(11)?,
-  })
+// `sort_order` is hidden from the regular wire shape, so backups wrap each
+// todo to carry it explicitly.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedTodo<'a> {
+  #[serde(flatten)]
+  todo: &'a Todo,
+  sort_order: i64,
 }
 
-fn ensure_schema(conn: &Connection) -> CommandResult<()> {
-  conn
-    .execute_batch(
-      r#"
-      CREATE TABLE IF NOT EXISTS todos (
-        id TEXT PRIMARY KEY,
-        title TEXT NOT NULL,
-        recurrence_tag TEXT NOT NULL DEFAULT 'none',
-        recurrence_checked_at TEXT NULL,
-        note TEXT NOT NULL DEFAULT '',
-        completed INTEGER NOT NULL DEFAULT 0,
-        due_date TEXT NULL,
-        reminder_enabled INTEGER NOT NULL DEFAULT 1,
-        last_reminded_on TEXT NULL,
-        sort_order INTEGER NOT NULL,
-        created_at TEXT NOT NULL,
-        updated_at TEXT NOT NULL
-      );
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ImportStrategy {
+  Skip,
+  Overwrite,
+  Duplicate,
+}
 
-      CREATE TABLE IF NOT EXISTS app_meta (
-        key TEXT PRIMARY KEY,
-        value TEXT NOT NULL
-      );
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportedTodo {
+  #[serde(flatten)]
+  todo: Todo,
+  #[serde(default)]
+  sort_order: Option<i64>,
+}
 
-      CREATE TABLE IF NOT EXISTS daily_completion_events (
-        id INTEGER PRIMARY KEY AUTOINCREMENT,
-        todo_id TEXT NOT NULL,
-        event_day TEXT NOT NULL,
-        created_at TEXT NOT NULL,
-        UNIQUE(todo_id, event_day)
-      );
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportResult {
+  imported: usize,
+  skipped: usize,
+  overwritten: usize,
+}
 
-      CREATE INDEX IF NOT EXISTS idx_todos_sort_order ON todos(sort_order);
-      CREATE INDEX IF NOT EXISTS idx_todos_completed_sort ON todos(completed, sort_order);
-      CREATE INDEX IF NOT EXISTS idx_daily_completion_event_day ON daily_completion_events(event_day);
-    "#,
-    )
-    .map_err(|err| err.to_string())?;
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum TodoPriority {
+  Low,
+  #[default]
+  Normal,
+  High,
+}
 
-  if let Err(err) = conn.execute(
-    "ALTER TABLE todos ADD COLUMN recurrence_tag TEXT NOT NULL DEFAULT 'none'",
-    [],
-  ) {
-    let message = err.to_string();
-    if !message.contains("duplicate column name") {
-      return Err(message);
+impl TodoPriority {
+  fn as_str(self) -> &'static str {
+    match self {
+      TodoPriority::Low => "low",
+      TodoPriority::Normal => "normal",
+      TodoPriority::High => "high",
     }
   }
+}
 
-  if let Err(err) = conn.execute("ALTER TABLE todos ADD COLUMN recurrence_checked_at TEXT NULL", []) {
-    let message = err.to_string();
-    if !message.contains("duplicate column name") {
-      return Err(message);
-    }
+impl ToSql for TodoPriority {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    Ok(ToSqlOutput::from(self.as_str()))
   }
+}
 
-  if let Err(err) = conn.execute(
-    "ALTER TABLE todos ADD COLUMN reminder_enabled INTEGER NOT NULL DEFAULT 1",
-    [],
-  ) {
-    let message = err.to_string();
-    if !message.contains("duplicate column name") {
-      return Err(message);
-    }
+// Unknown stored values read back as Normal rather than failing the whole row.
+impl FromSql for TodoPriority {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    Ok(match value.as_str()? {
+      "low" => TodoPriority::Low,
+      "high" => TodoPriority::High,
+      _ => TodoPriority::Normal,
+    })
   }
+}
 
-  if let Err(err) = conn.execute("ALTER TABLE todos ADD COLUMN last_reminded_on TEXT NULL", []) {
-    let message = err.to_string();
-    if !message.contains("duplicate column name") {
-      return Err(message);
-    }
-  }
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum PanelMode {
+  Mini,
+  Expanded,
+}
 
-  Ok(())
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum WindowSizeClass {
+  Mini,
+  Standard,
+  Wide,
 }
 
-fn get_todo_by_id(conn: &Connection, id: &str) -> CommandResult<Option<Todo>> {
-  conn
-    .query_row(
-      "SELECT id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, created_at, updated_at, reminder_enabled, last_reminded_on, sort_order
-       FROM todos WHERE id = ?1",
-      params![id],
-      map_todo_row,
-    )
-    .optional()
-    .map_err(|err| err.to_string())
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DockEdge {
+  Left,
+  Right,
+  Top,
+  Bottom,
 }
 
-fn set_meta(conn: &Connection, key: &str, value: &str) -> CommandResult<()> {
-  conn
-    .execute(
-      "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
-       ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-      params![key, value],
-    )
-    .map_err(|err| err.to_string())?;
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum MotionMode {
+  Balanced,
+  High,
+  Low,
+}
 
-  Ok(())
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReadabilityMode {
+  Adaptive,
+  Pure,
+  Strong,
 }
 
-fn get_meta(conn: &Connection, key: &str) -> CommandResult<Option<String>> {
-  conn
-    .query_row("SELECT value FROM app_meta WHERE key = ?1", params![key], |row| {
-      row.get(0)
-    })
-    .optional()
-    .map_err(|err| err.to_string())
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReduceMotionOverride {
+  System,
+  On,
+  Off,
 }
 
-fn get_window_prefs_from_conn(conn: &Connection) -> CommandResult<WindowPrefs> {
-  let raw = get_meta(conn, WINDOW_PREFS_KEY)?;
+// Mirrors the frontend's `Filter` values.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ListFilter {
+  #[default]
+  All,
+  Open,
+  Done,
+}
 
-  match raw {
-    Some(value) => match serde_json::from_str::<WindowPrefs>(&value) {
-      Ok(parsed) => Ok(normalize_window_prefs(parsed)),
-      Err(_) => {
-        let legacy = serde_json::from_str::<LegacyWindowPrefs>(&value).map_err(|err| err.to_string())?;
-        let size_class = infer_size_class_from_dimensions(legacy.width, legacy.height);
-        Ok(normalize_window_prefs(WindowPrefs {
-          x: legacy.x,
-          y: legacy.y,
-          width: legacy.width,
-          height: legacy.height,
-          mode: legacy.mode,
-          size_class,
-          always_on_top: legacy.always_on_top,
-        }))
-      }
-    },
-    None => Ok(WindowPrefs::default()),
-  }
+// `Manual` is the stored `sort_order`; the other modes are computed in Rust
+// since due dates are free-form text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum SortMode {
+  #[default]
+  Manual,
+  DueDateAsc,
+  PriorityThenDue,
+  CreatedDesc,
+  PriorityDesc,
+  EstimateAsc,
 }
 
-fn save_window_prefs_to_conn(conn: &Connection, prefs: &WindowPrefs) -> CommandResult<()> {
-  let value = serde_json::to_string(prefs).map_err(|err| err.to_string())?;
-  set_meta(conn, WINDOW_PREFS_KEY, &value)
+// `{ "unit": "days", "amount": 3 }` or a bare keyword such as "tomorrow".
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged)]
+enum RelativeDueSpec {
+  Offset { unit: RelativeDueUnit, amount: i64 },
+  Keyword(RelativeDueKeyword),
 }
 
-fn get_ui_prefs_from_conn(conn: &Connection) -> CommandResult<UiPrefs> {
-  let raw = get_meta(conn, UI_PREFS_KEY)?;
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RelativeDueUnit {
+  Minutes,
+  Hours,
+  Days,
+  Weeks,
+  Months,
+}
 
-  match raw {
-    Some(value) => serde_json::from_str::<UiPrefs>(&value).map_err(|err| err.to_string()),
-    None => Ok(UiPrefs::default()),
-  }
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RelativeDueKeyword {
+  Today,
+  Tomorrow,
+  NextWeek,
 }
 
-fn save_ui_prefs_to_conn(conn: &Connection, prefs: &UiPrefs) -> CommandResult<()> {
-  let value = serde_json::to_string(prefs).map_err(|err| err.to_string())?;
-  set_meta(conn, UI_PREFS_KEY, &value)
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DateField {
+  Created,
+  Updated,
+  CompletedDuring,
 }
 
-fn apply_window_prefs(window: &WebviewWindow, prefs: &WindowPrefs) -> CommandResult<()> {
-  window
-    .set_size(Size::Logical(LogicalSize::new(prefs.width, prefs.height)))
-    .map_err(|err| err.to_string())?;
+// Titles are compared exactly as stored.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DedupeKey {
+  TitleOnly,
+  TitleAndDueDate,
+}
 
-  window
-    .set_position(Position::Logical(LogicalPosition::new(prefs.x, prefs.y)))
-    .map_err(|err| err.to_string())?;
+// Where completed todos go in `list_todos`. The computed sort modes always sink
+// them, so `Inline` only differs from `Bottom` in manual order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CompletedPlacement {
+  #[default]
+  Inline,
+  Bottom,
+  Hidden,
+}
 
-  window
-    .set_always_on_top(prefs.always_on_top)
-    .map_err(|err| err.to_string())?;
+// `Schedule` keeps a series on its original cadence however late it is
+// completed; `Completion` restarts the interval from the completion day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RecurrenceAnchor {
+  #[default]
+  Schedule,
+  Completion,
+}
 
-  Ok(())
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum FirstDayOfWeek {
+  #[default]
+  Monday,
+  Sunday,
 }
 
-fn save_window_position(app: &AppHandle, x: f64, y: f64) -> CommandResult<()> {
-  let Some(state) = app.try_state::<AppState>() else {
-    return Ok(());
-  };
+// Display only; the backend always stores and parses ISO dates.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DateFormat {
+  #[default]
+  Locale,
+  Iso,
+  DayMonthYear,
+  MonthDayYear,
+}
 
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
-  let mut prefs = get_window_prefs_from_conn(&conn)?;
-  prefs.x = x;
-  prefs.y = y;
-  save_window_prefs_to_conn(&conn, &prefs)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+enum DateLocale {
+  #[default]
+  #[serde(rename = "system")]
+  System,
+  #[serde(rename = "en-US")]
+  EnUs,
+  #[serde(rename = "en-GB")]
+  EnGb,
+  #[serde(rename = "de-DE")]
+  DeDe,
+  #[serde(rename = "ja-JP")]
+  JaJp,
 }
 
-fn save_window_size(app: &AppHandle, width: f64, height: f64) -> CommandResult<()> {
-  let Some(state) = app.try_state::<AppState>() else {
-    return Ok(());
-  };
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WindowPrefs {
+  x: f64,
+  y: f64,
+  width: f64,
+  height: f64,
+  mode: PanelMode,
+  size_class: WindowSizeClass,
+  always_on_top: bool,
+  #[serde(default)]
+  aspect_ratio: Option<f64>,
+  #[serde(default)]
+  start_minimized: bool,
+  #[serde(default = "default_mini_size")]
+  mini_size: PanelSize,
+  #[serde(default = "default_expanded_size")]
+  expanded_size: PanelSize,
+  #[serde(default)]
+  dock_edge: Option<DockEdge>,
+  // Missing in prefs saved before the per-mode split; filled from
+  // `always_on_top` by `normalize_window_prefs`.
+  #[serde(default)]
+  always_on_top_mini: Option<bool>,
+  #[serde(default)]
+  always_on_top_expanded: Option<bool>,
+  #[serde(default = "default_window_opacity")]
+  opacity: f64,
+  #[serde(default)]
+  ignore_cursor_events: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PanelSize {
+  width: f64,
+  height: f64,
+}
+
+fn default_window_opacity() -> f64 {
+  1.0
+}
+
+fn default_mini_size() -> PanelSize {
+  PanelSize {
+    width: MINI_WIDTH,
+    height: MINI_HEIGHT,
+  }
+}
+
+fn default_expanded_size() -> PanelSize {
+  PanelSize {
+    width: WIDE_WIDTH,
+    height: WIDE_HEIGHT,
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyWindowPrefs {
+  x: f64,
+  y: f64,
+  width: f64,
+  height: f64,
+  mode: PanelMode,
+  always_on_top: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UiPrefs {
+  motion_mode: MotionMode,
+  readability_mode: ReadabilityMode,
+  reduce_motion_override: ReduceMotionOverride,
+  #[serde(default)]
+  locale: DateLocale,
+  #[serde(default = "default_due_notifications_enabled")]
+  due_notifications_enabled: bool,
+  #[serde(default = "default_due_check_interval_minutes")]
+  due_check_interval_minutes: u32,
+  #[serde(default = "default_reminder_snooze_minutes")]
+  reminder_snooze_default_minutes: u32,
+  #[serde(default)]
+  recurrence_anchor: RecurrenceAnchor,
+  #[serde(default)]
+  dim_on_blur: bool,
+  #[serde(default = "default_blurred_opacity")]
+  blurred_opacity: f64,
+  #[serde(default)]
+  auto_complete_parent: bool,
+  #[serde(default)]
+  last_filter: ListFilter,
+  #[serde(default)]
+  last_scroll_offset: f64,
+  #[serde(default)]
+  http_server_enabled: bool,
+  #[serde(default = "default_http_server_port")]
+  http_server_port: u16,
+  #[serde(default)]
+  http_server_lan: bool,
+  #[serde(default)]
+  sort_mode: SortMode,
+  // Empty disables the shortcut.
+  #[serde(default = "default_quick_capture_shortcut")]
+  quick_capture_shortcut: String,
+  #[serde(default)]
+  first_day_of_week: FirstDayOfWeek,
+  #[serde(default)]
+  date_format: DateFormat,
+  #[serde(default)]
+  completed_placement: CompletedPlacement,
+  // 0 turns off the purge at launch.
+  #[serde(default = "default_trash_retention_days")]
+  trash_retention_days: u32,
+  // 0 lifts the cap.
+  #[serde(default = "default_max_attachments_per_todo")]
+  max_attachments_per_todo: u32,
+  // 0 turns off auto-archiving.
+  #[serde(default)]
+  auto_archive_days: u32,
+  #[serde(default)]
+  daily_summary_enabled: bool,
+  // "HH:MM" in `daily_summary_tz_offset_minutes` (minutes east of UTC), or in
+  // the system timezone when that is unset.
+  #[serde(default = "default_daily_summary_time")]
+  daily_summary_time: String,
+  #[serde(default)]
+  daily_summary_tz_offset_minutes: Option<i32>,
+}
+
+fn default_daily_summary_time() -> String {
+  DEFAULT_DAILY_SUMMARY_TIME.to_string()
+}
+
+fn default_trash_retention_days() -> u32 {
+  DEFAULT_TRASH_RETENTION_DAYS
+}
+
+fn default_max_attachments_per_todo() -> u32 {
+  DEFAULT_MAX_ATTACHMENTS_PER_TODO
+}
+
+fn default_quick_capture_shortcut() -> String {
+  DEFAULT_QUICK_CAPTURE_SHORTCUT.to_string()
+}
+
+fn default_http_server_port() -> u16 {
+  DEFAULT_HTTP_SERVER_PORT
+}
+
+fn default_blurred_opacity() -> f64 {
+  DEFAULT_BLURRED_OPACITY
+}
+
+fn default_reminder_snooze_minutes() -> u32 {
+  DEFAULT_REMINDER_SNOOZE_MINUTES
+}
+
+fn default_due_notifications_enabled() -> bool {
+  true
+}
+
+fn default_due_check_interval_minutes() -> u32 {
+  DEFAULT_DUE_CHECK_INTERVAL_MINUTES
+}
+
+impl Default for WindowPrefs {
+  fn default() -> Self {
+    Self {
+      x: 80.0,
+      y: 80.0,
+      width: MINI_WIDTH,
+      height: MINI_HEIGHT,
+      mode: PanelMode::Mini,
+      size_class: WindowSizeClass::Mini,
+      always_on_top: true,
+      aspect_ratio: None,
+      start_minimized: false,
+      mini_size: default_mini_size(),
+      expanded_size: default_expanded_size(),
+      dock_edge: None,
+      always_on_top_mini: Some(true),
+      always_on_top_expanded: Some(true),
+      opacity: default_window_opacity(),
+      ignore_cursor_events: false,
+    }
+  }
+}
+
+impl Default for UiPrefs {
+  fn default() -> Self {
+    Self {
+      motion_mode: MotionMode::Balanced,
+      readability_mode: ReadabilityMode::Adaptive,
+      reduce_motion_override: ReduceMotionOverride::System,
+      locale: DateLocale::System,
+      due_notifications_enabled: default_due_notifications_enabled(),
+      due_check_interval_minutes: default_due_check_interval_minutes(),
+      reminder_snooze_default_minutes: default_reminder_snooze_minutes(),
+      recurrence_anchor: RecurrenceAnchor::Schedule,
+      dim_on_blur: false,
+      blurred_opacity: default_blurred_opacity(),
+      auto_complete_parent: false,
+      last_filter: ListFilter::All,
+      last_scroll_offset: 0.0,
+      http_server_enabled: false,
+      http_server_port: default_http_server_port(),
+      http_server_lan: false,
+      sort_mode: SortMode::Manual,
+      quick_capture_shortcut: default_quick_capture_shortcut(),
+      first_day_of_week: FirstDayOfWeek::Monday,
+      date_format: DateFormat::Locale,
+      completed_placement: CompletedPlacement::Inline,
+      trash_retention_days: default_trash_retention_days(),
+      max_attachments_per_todo: default_max_attachments_per_todo(),
+      auto_archive_days: 0,
+      daily_summary_enabled: false,
+      daily_summary_time: default_daily_summary_time(),
+      daily_summary_tz_offset_minutes: None,
+    }
+  }
+}
+
+// With the override on "system" the OS setting is only visible to the webview,
+// so motion counts as reduced here only when the motion mode itself is low.
+fn audit_accessibility_prefs(prefs: &UiPrefs) -> AccessibilityAudit {
+  let motion_reduced = match prefs.reduce_motion_override {
+    ReduceMotionOverride::On => true,
+    ReduceMotionOverride::Off => false,
+    ReduceMotionOverride::System => matches!(prefs.motion_mode, MotionMode::Low),
+  };
+
+  AccessibilityAudit {
+    motion_reduced,
+    follows_system_motion: matches!(prefs.reduce_motion_override, ReduceMotionOverride::System),
+    high_readability: matches!(prefs.readability_mode, ReadabilityMode::Strong),
+  }
+}
+
+// Windows reports reduced motion as client-area animations being switched off.
+#[cfg(target_os = "windows")]
+fn system_reduce_motion() -> Option<bool> {
+  use windows_sys::Win32::UI::WindowsAndMessaging::{SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION};
+
+  let mut animations_enabled: i32 = 1;
+  // SAFETY: SPI_GETCLIENTAREAANIMATION writes a single BOOL into the pointed-to i32.
+  let ok = unsafe {
+    SystemParametersInfoW(
+      SPI_GETCLIENTAREAANIMATION,
+      0,
+      &mut animations_enabled as *mut i32 as *mut std::ffi::c_void,
+      0,
+    )
+  };
+
+  (ok != 0).then_some(animations_enabled == 0)
+}
+
+#[cfg(target_os = "macos")]
+fn system_reduce_motion() -> Option<bool> {
+  let output = std::process::Command::new("defaults")
+    .args(["read", "com.apple.universalaccess", "reduceMotion"])
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+
+  Some(String::from_utf8_lossy(&output.stdout).trim() == "1")
+}
+
+// Only GNOME-style desktops expose this; other Linux desktops fall through to None.
+#[cfg(target_os = "linux")]
+fn system_reduce_motion() -> Option<bool> {
+  let output = std::process::Command::new("gsettings")
+    .args(["get", "org.gnome.desktop.interface", "enable-animations"])
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+
+  match String::from_utf8_lossy(&output.stdout).trim() {
+    "true" => Some(false),
+    "false" => Some(true),
+    _ => None,
+  }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn system_reduce_motion() -> Option<bool> {
+  None
+}
+
+// When the OS setting can't be read, "system" resolves to motion enabled.
+fn effective_reduce_motion(prefs: &UiPrefs) -> bool {
+  match prefs.reduce_motion_override {
+    ReduceMotionOverride::On => true,
+    ReduceMotionOverride::Off => false,
+    ReduceMotionOverride::System => system_reduce_motion().unwrap_or(false),
+  }
+}
+
+fn now_iso() -> String {
+  Utc::now().to_rfc3339()
+}
+
+fn local_day_key() -> String {
+  Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn local_today_naive() -> NaiveDate {
+  Local::now().date_naive()
+}
+
+fn parse_iso_to_local_datetime(value: &str) -> Option<DateTime<Local>> {
+  DateTime::parse_from_rfc3339(value)
+    .ok()
+    .map(|parsed| parsed.with_timezone(&Local))
+}
+
+fn parse_day_key(value: &str, label: &str) -> CommandResult<NaiveDate> {
+  NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+    .map_err(|_| AppError::Validation(format!("Invalid {label} date (expected YYYY-MM-DD): {value}")))
+}
+
+fn bucket_by_local_hour<Tz: TimeZone>(timestamps: &[DateTime<Utc>], tz: &Tz, start_day: NaiveDate, end_day: NaiveDate) -> Vec<u32> {
+  let mut buckets = vec![0u32; 24];
+
+  for timestamp in timestamps {
+    let timestamp = timestamp.with_timezone(tz);
+    let day = timestamp.date_naive();
+    if day < start_day || day > end_day {
+      continue;
+    }
+
+    buckets[timestamp.hour() as usize] += 1;
+  }
+
+  buckets
+}
+
+fn parse_now_to_local_date(value: &str) -> CommandResult<NaiveDate> {
+  parse_iso_to_local_datetime(value)
+    .map(|parsed| parsed.date_naive())
+    .ok_or_else(|| AppError::Validation(format!("Invalid timestamp (expected RFC3339): {value}")))
+}
+
+// Character trigrams of the lowercased, whitespace-collapsed title, matching
+// how the search index tokenizes text.
+fn title_trigrams(title: &str) -> HashSet<[char; 3]> {
+  let normalized: Vec<char> = title
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase()
+    .chars()
+    .collect();
+
+  normalized.windows(3).map(|window| [window[0], window[1], window[2]]).collect()
+}
+
+// Dice coefficient over title trigrams, from 0.0 (nothing shared) to 1.0.
+fn title_similarity(a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f64 {
+  if a.is_empty() || b.is_empty() {
+    return 0.0;
+  }
+
+  let shared = a.intersection(b).count();
+  (2 * shared) as f64 / (a.len() + b.len()) as f64
+}
+
+fn find_root(parent: &mut [usize], mut index: usize) -> usize {
+  while parent[index] != index {
+    parent[index] = parent[parent[index]];
+    index = parent[index];
+  }
+  index
+}
+
+// Returns (current, longest) runs of consecutive days. The current streak
+// still counts when the latest completion was yesterday, since today may not
+// be done yet.
+fn completion_streaks(days: &[NaiveDate], today: NaiveDate) -> (u32, u32) {
+  let mut days = days.to_vec();
+  days.sort_unstable();
+  days.dedup();
+
+  let mut longest = 0;
+  let mut run = 0;
+  let mut previous: Option<NaiveDate> = None;
+  for day in &days {
+    run = match previous {
+      Some(prev) if *day - prev == Duration::days(1) => run + 1,
+      _ => 1,
+    };
+    longest = longest.max(run);
+    previous = Some(*day);
+  }
+
+  let current = match previous {
+    Some(last) if last >= today - Duration::days(1) => run,
+    _ => 0,
+  };
+
+  (current, longest)
+}
+
+// Each day of age adds a point, each snooze three and each day overdue two;
+// todos without a due date pass `None` and score on age and snoozes alone.
+fn procrastination_score(age_days: f64, snooze_count: i64, days_overdue: Option<i64>) -> ProcrastinationScore {
+  let age_component = age_days.max(0.0);
+  let snooze_component = snooze_count.max(0) as f64 * 3.0;
+  let overdue_component = days_overdue.unwrap_or(0).max(0) as f64 * 2.0;
+
+  ProcrastinationScore {
+    age_component,
+    snooze_component,
+    overdue_component,
+    total: age_component + snooze_component + overdue_component,
+  }
+}
+
+// Mean completions per day over the `window_days` ending at `end_day`. Every
+// day in the window counts, including empty ones before the first completion.
+fn rolling_daily_average(day_counts: &[(NaiveDate, u32)], end_day: NaiveDate, window_days: i64) -> f64 {
+  let window_days = window_days.max(1);
+  let start_day = end_day - Duration::days(window_days - 1);
+
+  let total: u32 = day_counts
+    .iter()
+    .filter(|(day, _)| *day >= start_day && *day <= end_day)
+    .map(|(_, count)| count)
+    .sum();
+
+  total as f64 / window_days as f64
+}
+
+// Overdue items outrank everything and grow with lateness, items due today come next,
+// upcoming items decay with distance, and undated items score zero.
+fn urgency_score(todo: &Todo, today: NaiveDate) -> i64 {
+  let Some(due_day) = todo
+    .due_date
+    .as_deref()
+    .and_then(|value| parse_due_day(value, &Local))
+  else {
+    return 0;
+  };
+
+  let days_until_due = due_day.signed_duration_since(today).num_days();
+  if days_until_due < 0 {
+    1000 + (-days_until_due).min(365) * 10
+  } else if days_until_due == 0 {
+    800
+  } else {
+    (500 - days_until_due * 50).max(100)
+  }
+}
+
+fn date_format_for_locale(locale: &DateLocale) -> &'static str {
+  match locale {
+    DateLocale::System => system_locale_setting()
+      .as_deref()
+      .and_then(date_locale_from_posix)
+      .map_or("%Y-%m-%d", |resolved| date_format_for_locale(&resolved)),
+    DateLocale::EnUs => "%m/%d/%Y",
+    DateLocale::EnGb => "%d/%m/%Y",
+    DateLocale::DeDe => "%d.%m.%Y",
+    DateLocale::JaJp => "%Y/%m/%d",
+  }
+}
+
+// The first of LC_ALL, LC_TIME and LANG that is set, in POSIX precedence.
+// GUI launches on macOS and Windows usually have none, so `System` falls back
+// to ISO there.
+fn system_locale_setting() -> Option<String> {
+  ["LC_ALL", "LC_TIME", "LANG"]
+    .into_iter()
+    .filter_map(|key| std::env::var(key).ok())
+    .find(|value| !value.trim().is_empty())
+}
+
+// `de_DE.UTF-8`, `en-GB` and the like; `None` for `C`, `POSIX` and locales
+// without a dedicated variant.
+fn date_locale_from_posix(value: &str) -> Option<DateLocale> {
+  let tag = value.trim().split(['.', '@']).next().unwrap_or_default();
+  let mut parts = tag.split(['_', '-']);
+  let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+  let region = parts.next().map(str::to_ascii_uppercase);
+
+  match (language.as_str(), region.as_deref()) {
+    ("en", Some("US")) => Some(DateLocale::EnUs),
+    ("en", Some("GB")) => Some(DateLocale::EnGb),
+    ("de", _) => Some(DateLocale::DeDe),
+    ("ja", _) => Some(DateLocale::JaJp),
+    _ => None,
+  }
+}
+
+fn format_date_for_locale(date: NaiveDate, locale: &DateLocale) -> String {
+  date.format(date_format_for_locale(locale)).to_string()
+}
+
+fn extract_urls(text: &str) -> Vec<String> {
+  let mut urls = Vec::new();
+
+  for token in text.split_whitespace() {
+    let Some(start) = token.find("https://").or_else(|| token.find("http://")) else {
+      continue;
+    };
+
+    let candidate = token[start..].trim_end_matches(|ch: char| ".,;:!?)]}>'\"".contains(ch));
+    let has_host = candidate
+      .split_once("://")
+      .is_some_and(|(_, rest)| !rest.is_empty());
+    if has_host {
+      urls.push(candidate.to_string());
+    }
+  }
+
+  urls
+}
+
+fn most_active_weekday(days: &[NaiveDate]) -> Option<Weekday> {
+  let mut counts = [0u32; 7];
+  for day in days {
+    counts[day.weekday().num_days_from_monday() as usize] += 1;
+  }
+
+  let (index, count) = counts
+    .iter()
+    .enumerate()
+    .fold((0, 0), |best, (index, count)| if *count > best.1 { (index, *count) } else { best });
+
+  if count == 0 {
+    None
+  } else {
+    Weekday::try_from(index as u8).ok()
+  }
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+// Escapes a value for a double-quoted XML attribute. Line breaks become
+// character references so multi-line notes survive attribute normalization,
+// and control characters XML 1.0 forbids are dropped.
+fn xml_attr(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for ch in value.chars() {
+    match ch {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      '\n' => escaped.push_str("&#10;"),
+      '\r' => escaped.push_str("&#13;"),
+      '\t' => escaped.push_str("&#9;"),
+      ch if (ch as u32) < 0x20 => {}
+      ch => escaped.push(ch),
+    }
+  }
+  escaped
+}
+
+fn is_recurrence_cycle_checked_at(recurrence_tag: &str, recurrence_checked_at: Option<&str>) -> bool {
+  if recurrence_tag == RECURRENCE_NONE {
+    return false;
+  }
+
+  let Some(raw_checked_at) = recurrence_checked_at else {
+    return false;
+  };
+
+  let Some(checked_at) = parse_iso_to_local_datetime(raw_checked_at) else {
+    return false;
+  };
+
+  let now = Local::now();
+
+  match recurrence_tag {
+    RECURRENCE_DAILY => checked_at.date_naive() == now.date_naive(),
+    RECURRENCE_WEEKLY | RECURRENCE_BI_WEEKLY => {
+      let elapsed = now.signed_duration_since(checked_at);
+      if elapsed < Duration::zero() {
+        return false;
+      }
+
+      let cycle_days = if recurrence_tag == RECURRENCE_WEEKLY { 7 } else { 14 };
+      elapsed < Duration::days(cycle_days)
+    }
+    RECURRENCE_MONTHLY => checked_at
+      .date_naive()
+      .checked_add_months(Months::new(1))
+      .is_some_and(|cycle_end| now.date_naive() < cycle_end),
+    _ => false,
+  }
+}
+
+// Stores due dates as `YYYY-MM-DD` or RFC3339, so canonical values pass through
+// unchanged. `MM/DD/YYYY` is accepted and rewritten as a plain day; blank
+// means no due date.
+fn normalize_date(value: Option<String>) -> CommandResult<Option<String>> {
+  let Some(candidate) = value else {
+    return Ok(None);
+  };
+  let trimmed = candidate.trim();
+  if trimmed.is_empty() {
+    return Ok(None);
+  }
+
+  if let Ok(day) = NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+    return Ok(Some(day.format("%Y-%m-%d").to_string()));
+  }
+  if let Ok(instant) = DateTime::parse_from_rfc3339(trimmed) {
+    return Ok(Some(instant.to_rfc3339()));
+  }
+  if let Ok(day) = NaiveDate::parse_from_str(trimmed, "%m/%d/%Y") {
+    return Ok(Some(day.format("%Y-%m-%d").to_string()));
+  }
+
+  Err(AppError::Validation(format!("Invalid due date: {trimmed}")))
+}
+
+// Due dates are free-form text; accept a plain day or an RFC3339 timestamp
+// (read in `tz`) and treat anything else as undated.
+fn parse_due_day<Tz: TimeZone>(value: &str, tz: &Tz) -> Option<NaiveDate> {
+  let trimmed = value.trim();
+  NaiveDate::parse_from_str(trimmed, "%Y-%m-%d").ok().or_else(|| {
+    DateTime::parse_from_rfc3339(trimmed)
+      .ok()
+      .map(|parsed| parsed.with_timezone(tz).date_naive())
+  })
+}
+
+// Plain days fall due at local midnight; timestamps (e.g. from snoozing) at
+// their exact instant.
+fn due_instant(value: &str) -> Option<DateTime<Local>> {
+  let trimmed = value.trim();
+  match NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+    Ok(day) => day.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest(),
+    Err(_) => parse_iso_to_local_datetime(trimmed),
+  }
+}
+
+fn list_open_dated_todos(conn: &Connection) -> CommandResult<Vec<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE completed = 0 AND deleted_at IS NULL AND due_date IS NOT NULL
+       ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+fn next_due_date(recurrence_tag: &str, due_day: NaiveDate) -> Option<NaiveDate> {
+  nth_due_date(recurrence_tag, due_day, 1)
+}
+
+// Steps are always counted from `due_day` itself, so a month-end anchor that
+// clamps once (Jan 31 -> Feb 28) still lands on Mar 31 two steps out.
+fn nth_due_date(recurrence_tag: &str, due_day: NaiveDate, steps: u32) -> Option<NaiveDate> {
+  match recurrence_tag {
+    RECURRENCE_DAILY => Some(due_day + Duration::days(i64::from(steps))),
+    RECURRENCE_WEEKLY => Some(due_day + Duration::days(7 * i64::from(steps))),
+    RECURRENCE_BI_WEEKLY => Some(due_day + Duration::days(14 * i64::from(steps))),
+    // chrono clamps to the last day of a shorter month, so Jan 31 -> Feb 28/29.
+    RECURRENCE_MONTHLY => due_day.checked_add_months(Months::new(steps)),
+    _ => None,
+  }
+}
+
+// Picks the due date of the occurrence that follows one due on `due_day` and
+// completed on `completed_on`. With the schedule anchor this is the first step
+// of the original cadence after the completion day, so a daily todo finished
+// two days late is next due tomorrow rather than on an already-missed day.
+fn next_anchored_due_date(
+  recurrence_tag: &str,
+  due_day: NaiveDate,
+  completed_on: NaiveDate,
+  anchor: RecurrenceAnchor,
+) -> Option<NaiveDate> {
+  match anchor {
+    RecurrenceAnchor::Completion => next_due_date(recurrence_tag, completed_on),
+    RecurrenceAnchor::Schedule => {
+      let mut steps = 1;
+      loop {
+        let next = nth_due_date(recurrence_tag, due_day, steps)?;
+        if next > completed_on {
+          return Some(next);
+        }
+        steps += 1;
+      }
+    }
+  }
+}
+
+fn normalize_recurrence_tag(value: Option<String>) -> String {
+  let trimmed = value.as_deref().map(str::trim);
+  RECURRENCE_TAGS
+    .iter()
+    .find(|tag| Some(**tag) == trimmed)
+    .unwrap_or(&RECURRENCE_NONE)
+    .to_string()
+}
+
+fn normalize_note_format(value: Option<String>) -> String {
+  match value.as_deref().map(str::trim) {
+    Some(NOTE_FORMAT_MARKDOWN) => NOTE_FORMAT_MARKDOWN.to_string(),
+    _ => NOTE_FORMAT_PLAIN.to_string(),
+  }
+}
+
+// Trimmed, lowercased, sorted and deduplicated; blank tags are dropped.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+  let mut normalized: Vec<String> = tags
+    .into_iter()
+    .map(|tag| tag.trim().to_lowercase())
+    .filter(|tag| !tag.is_empty())
+    .collect();
+  normalized.sort();
+  normalized.dedup();
+  normalized
+}
+
+// "none" and unrecognized levels are stored as NULL.
+fn normalize_energy(value: Option<String>) -> Option<String> {
+  match value.as_deref().map(str::trim) {
+    Some(ENERGY_HIGH) => Some(ENERGY_HIGH.to_string()),
+    Some(ENERGY_MEDIUM) => Some(ENERGY_MEDIUM.to_string()),
+    Some(ENERGY_LOW) => Some(ENERGY_LOW.to_string()),
+    _ => None,
+  }
+}
+
+// Contexts are free-form (e.g. "@home"); blank values clear the field and long
+// ones are cut to `MAX_CONTEXT_CHARS`.
+fn normalize_context(value: Option<String>) -> Option<String> {
+  let trimmed = value.as_deref()?.trim();
+  if trimmed.is_empty() {
+    return None;
+  }
+
+  Some(trimmed.chars().take(MAX_CONTEXT_CHARS).collect::<String>().trim_end().to_string())
+}
+
+// Accepts `#RRGGBB` (stored lowercase); blank clears back to the theme color.
+fn normalize_color(value: Option<String>) -> CommandResult<Option<String>> {
+  let Some(candidate) = value else {
+    return Ok(None);
+  };
+  let trimmed = candidate.trim();
+  if trimmed.is_empty() {
+    return Ok(None);
+  }
+
+  let is_hex = trimmed.len() == 7
+    && trimmed.starts_with('#')
+    && trimmed[1..].chars().all(|c| c.is_ascii_hexdigit());
+  if !is_hex {
+    return Err(AppError::Validation(format!("Invalid color (expected #RRGGBB): {trimmed}")));
+  }
+
+  Ok(Some(trimmed.to_ascii_lowercase()))
+}
+
+// Lead time in minutes before the due date; `None` notifies at the due time.
+fn normalize_reminder_offset(value: Option<i64>) -> CommandResult<Option<i64>> {
+  match value {
+    Some(minutes) if minutes < 0 => Err(AppError::Validation(format!(
+      "Reminder offset cannot be negative: {minutes} minutes"
+    ))),
+    Some(minutes) if minutes > MAX_REMINDER_OFFSET_MINUTES => Err(AppError::Validation(format!(
+      "Reminder offset cannot exceed {MAX_REMINDER_OFFSET_MINUTES} minutes"
+    ))),
+    Some(0) | None => Ok(None),
+    Some(minutes) => Ok(Some(minutes)),
+  }
+}
+
+fn normalize_estimate_minutes(value: Option<i64>) -> CommandResult<Option<i64>> {
+  match value {
+    Some(minutes) if minutes < 0 => Err(AppError::Validation(format!(
+      "Estimate cannot be negative: {minutes} minutes"
+    ))),
+    Some(0) | None => Ok(None),
+    Some(minutes) => Ok(Some(minutes)),
+  }
+}
+
+// Tracks completions across a recurring chain; each rolled occurrence carries
+// the running count forward so `recurrence_max_count` can end the series.
+fn count_recurring_completion(todo: &mut Todo) {
+  if todo.recurrence_tag == RECURRENCE_NONE {
+    return;
+  }
+
+  if todo.completed {
+    todo.recurrence_completed_count += 1;
+  } else {
+    todo.recurrence_completed_count = (todo.recurrence_completed_count - 1).max(0);
+  }
+}
+
+// Logs a completion, or drops the latest one when the todo is reopened.
+fn record_completion_change(conn: &Connection, todo: &Todo) -> CommandResult<()> {
+  match &todo.completed_at {
+    Some(completed_at) if todo.completed => conn.execute(
+      "INSERT INTO todo_completions (todo_id, completed_at) VALUES (?1, ?2)",
+      params![&todo.id, completed_at],
+    ),
+    _ => conn.execute(
+      "DELETE FROM todo_completions
+       WHERE id = (SELECT id FROM todo_completions WHERE todo_id = ?1 ORDER BY completed_at DESC, id DESC LIMIT 1)",
+      params![&todo.id],
+    ),
+  }
+  .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn is_safe_link_target(url: &str) -> bool {
+  let lower = url.trim().to_ascii_lowercase();
+  !lower.contains(':') || ["http:", "https:", "mailto:"].iter().any(|scheme| lower.starts_with(scheme))
+}
+
+fn render_markdown_html(markdown: &str) -> String {
+  let parser = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS).map(|event| {
+    match event {
+      // Raw HTML is shown as text so notes cannot inject markup into the preview.
+      Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+      Event::Start(Tag::Link {
+        link_type,
+        dest_url,
+        title,
+        id,
+      }) if !is_safe_link_target(&dest_url) => Event::Start(Tag::Link {
+        link_type,
+        dest_url: "".into(),
+        title,
+        id,
+      }),
+      Event::Start(Tag::Image {
+        link_type,
+        dest_url,
+        title,
+        id,
+      }) if !is_safe_link_target(&dest_url) => Event::Start(Tag::Image {
+        link_type,
+        dest_url: "".into(),
+        title,
+        id,
+      }),
+      other => other,
+    }
+  });
+
+  let mut html = String::new();
+  pulldown_cmark::html::push_html(&mut html, parser);
+  html
+}
+
+fn mode_from_size_class(size_class: &WindowSizeClass) -> PanelMode {
+  match size_class {
+    WindowSizeClass::Mini => PanelMode::Mini,
+    WindowSizeClass::Standard | WindowSizeClass::Wide => PanelMode::Expanded,
+  }
+}
+
+fn dimensions_for_size_class(size_class: &WindowSizeClass) -> (f64, f64) {
+  match size_class {
+    WindowSizeClass::Mini => (MINI_WIDTH, MINI_HEIGHT),
+    WindowSizeClass::Standard => (STANDARD_WIDTH, STANDARD_HEIGHT),
+    WindowSizeClass::Wide => (WIDE_WIDTH, WIDE_HEIGHT),
+  }
+}
+
+fn size_class_for_panel_mode(mode: &PanelMode) -> WindowSizeClass {
+  match mode {
+    PanelMode::Mini => WindowSizeClass::Mini,
+    PanelMode::Expanded => WindowSizeClass::Wide,
+  }
+}
+
+// Mini and wide follow the user's remembered panel sizes; standard stays fixed.
+fn dimensions_for_prefs(prefs: &WindowPrefs, size_class: &WindowSizeClass) -> (f64, f64) {
+  match size_class {
+    WindowSizeClass::Mini => (prefs.mini_size.width, prefs.mini_size.height),
+    WindowSizeClass::Wide => (prefs.expanded_size.width, prefs.expanded_size.height),
+    WindowSizeClass::Standard => dimensions_for_size_class(size_class),
+  }
+}
+
+fn infer_size_class_from_dimensions(width: f64, height: f64) -> WindowSizeClass {
+  let candidates = [
+    (WindowSizeClass::Mini, MINI_WIDTH, MINI_HEIGHT),
+    (WindowSizeClass::Standard, STANDARD_WIDTH, STANDARD_HEIGHT),
+    (WindowSizeClass::Wide, WIDE_WIDTH, WIDE_HEIGHT),
+  ];
+
+  let mut best = WindowSizeClass::Mini;
+  let mut best_score = f64::MAX;
+
+  for (candidate, target_width, target_height) in candidates {
+    let width_score = (width - target_width).abs();
+    let height_score = (height - target_height).abs();
+    let score = (width_score * 0.65) + (height_score * 0.35);
+    if score < best_score {
+      best_score = score;
+      best = candidate;
+    }
+  }
+
+  best
+}
+
+fn aspect_corrected_size(width: f64, height: f64, ratio: f64) -> (f64, f64) {
+  if !ratio.is_finite() || ratio <= 0.0 || width <= 0.0 {
+    return (width, height);
+  }
+
+  (width, (width / ratio).round())
+}
+
+fn normalize_window_prefs(mut prefs: WindowPrefs) -> WindowPrefs {
+  let (width, height) = dimensions_for_prefs(&prefs, &prefs.size_class);
+  prefs.width = width;
+  prefs.height = height;
+  prefs.mode = mode_from_size_class(&prefs.size_class);
+  prefs.always_on_top_mini.get_or_insert(prefs.always_on_top);
+  prefs.always_on_top_expanded.get_or_insert(prefs.always_on_top);
+  prefs
+}
+
+// The tray icon and quick-capture hotkey can always bring a hidden window back.
+fn should_start_hidden(prefs: &WindowPrefs) -> bool {
+  prefs.start_minimized
+}
+
+fn always_on_top_flag<'a>(prefs: &'a mut WindowPrefs, mode: &PanelMode) -> &'a mut Option<bool> {
+  match mode {
+    PanelMode::Mini => &mut prefs.always_on_top_mini,
+    PanelMode::Expanded => &mut prefs.always_on_top_expanded,
+  }
+}
+
+fn to_db_bool(value: bool) -> i64 {
+  if value {
+    1
+  } else {
+    0
+  }
+}
+
+fn map_todo_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Todo> {
+  Ok(Todo {
+    id: row.get(0)?,
+    title: row.get(1)?,
+    recurrence_tag: row.get(2)?,
+    recurrence_checked_at: row.get(3)?,
+    note: row.get(4)?,
+    completed: row.get::<_, i64>(5)? != 0,
+    due_date: row.get(6)?,
+    created_at: row.get(7)?,
+    updated_at: row.get(8)?,
+    reminder_enabled: row.get::<_, i64>(9)? != 0,
+    last_reminded_on: row.get(10)?,
+    sort_order: row.get(11)?,
+    completed_at: row.get(12)?,
+    series_id: row.get(13)?,
+    recurrence_until: row.get(14)?,
+    note_format: row.get(15)?,
+    rolled_at: row.get(16)?,
+    priority: row.get(17)?,
+    energy: row.get(18)?,
+    deleted_at: row.get(19)?,
+    recurrence_max_count: row.get(20)?,
+    recurrence_completed_count: row.get(21)?,
+    notified_at: row.get(22)?,
+    remind_after: row.get(23)?,
+    context: row.get(24)?,
+    snooze_count: row.get(25)?,
+    color: row.get(26)?,
+    archived: row.get::<_, i64>(27)? != 0,
+    pinned: row.get::<_, i64>(28)? != 0,
+    reminder_offset_minutes: row.get(29)?,
+    estimate_minutes: row.get(30)?,
+    note_collapsed: row.get::<_, i64>(31)? != 0,
+  })
+}
+
+fn add_column_if_missing(conn: &Connection, statement: &str) -> CommandResult<bool> {
+  match conn.execute(statement, []) {
+    Ok(_) => Ok(true),
+    Err(err) => {
+      if err.to_string().contains("duplicate column name") {
+        Ok(false)
+      } else {
+        Err(AppError::from(err))
+      }
+    }
+  }
+}
+
+fn ensure_schema(conn: &Connection) -> CommandResult<()> {
+  // WAL lets pooled readers proceed while a single writer holds the lock. Some
+  // filesystems silently keep the old mode, so check what SQLite settled on;
+  // the in-memory sandbox database always reports "memory".
+  let journal_mode: String = conn
+    .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+  if !journal_mode.eq_ignore_ascii_case("wal") && !journal_mode.eq_ignore_ascii_case("memory") {
+    return Err(AppError::Database(format!(
+      "Could not enable WAL journal mode (SQLite reported \"{journal_mode}\")"
+    )));
+  }
+
+  run_schema_migrations(conn)?;
+  ensure_search_index(conn)?;
+  ensure_scheduled_load(conn)?;
+
+  Ok(())
+}
+
+// Applies every step in `SCHEMA_MIGRATIONS` past the recorded version, each in
+// its own transaction together with the version bump, so a failed step is
+// retried on the next launch instead of being half-applied.
+fn run_schema_migrations(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute_batch("CREATE TABLE IF NOT EXISTS app_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);")
+    .map_err(AppError::from)?;
+
+  let current = match get_meta(conn, SCHEMA_VERSION_KEY)? {
+    Some(value) => value
+      .parse::<usize>()
+      .map_err(|_| AppError::Database(format!("Invalid schema version: {value}")))?,
+    None => 0,
+  };
+  if current > SCHEMA_MIGRATIONS.len() {
+    return Err(AppError::Database(format!(
+      "Database schema version {current} is newer than this app supports ({})",
+      SCHEMA_MIGRATIONS.len()
+    )));
+  }
+
+  for (index, migrate) in SCHEMA_MIGRATIONS.iter().enumerate().skip(current) {
+    let tx = conn.unchecked_transaction().map_err(AppError::from)?;
+    migrate(&tx)?;
+    set_meta(&tx, SCHEMA_VERSION_KEY, &(index + 1).to_string())?;
+    tx.commit().map_err(AppError::from)?;
+  }
+
+  Ok(())
+}
+
+// Version 1 is the layout as it stood before versioning. Databases from that
+// era carry no version at all and may be missing any of these columns, so this
+// step stays idempotent; every later step runs exactly once.
+fn migrate_schema_v1(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute_batch(
+      r#"
+      CREATE TABLE IF NOT EXISTS todos (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        recurrence_tag TEXT NOT NULL DEFAULT 'none',
+        recurrence_checked_at TEXT NULL,
+        note TEXT NOT NULL DEFAULT '',
+        completed INTEGER NOT NULL DEFAULT 0,
+        due_date TEXT NULL,
+        reminder_enabled INTEGER NOT NULL DEFAULT 1,
+        last_reminded_on TEXT NULL,
+        completed_at TEXT NULL,
+        series_id TEXT NULL,
+        recurrence_until TEXT NULL,
+        note_format TEXT NOT NULL DEFAULT 'plain',
+        rolled_at TEXT NULL,
+        priority TEXT NOT NULL DEFAULT 'normal',
+        energy TEXT NULL,
+        deleted_at TEXT NULL,
+        recurrence_max_count INTEGER NULL,
+        recurrence_completed_count INTEGER NOT NULL DEFAULT 0,
+        notified_at TEXT NULL,
+        remind_after TEXT NULL,
+        context TEXT NULL,
+        snooze_count INTEGER NOT NULL DEFAULT 0,
+        color TEXT NULL,
+        archived INTEGER NOT NULL DEFAULT 0,
+        pinned INTEGER NOT NULL DEFAULT 0,
+        reminder_offset_minutes INTEGER NULL,
+        sort_order INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+      );
+
+      CREATE TABLE IF NOT EXISTS app_meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+      );
+
+      CREATE TABLE IF NOT EXISTS daily_completion_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        todo_id TEXT NOT NULL,
+        event_day TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        UNIQUE(todo_id, event_day)
+      );
+
+      CREATE TABLE IF NOT EXISTS subtasks (
+        id TEXT PRIMARY KEY,
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        title TEXT NOT NULL,
+        completed INTEGER NOT NULL DEFAULT 0,
+        sort_order INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+      );
+
+      CREATE TABLE IF NOT EXISTS todo_completions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        completed_at TEXT NOT NULL
+      );
+
+      CREATE TABLE IF NOT EXISTS todo_attachments (
+        id TEXT PRIMARY KEY,
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        label TEXT,
+        created_at TEXT NOT NULL
+      );
+
+      CREATE TABLE IF NOT EXISTS todo_dependencies (
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        depends_on_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        PRIMARY KEY (todo_id, depends_on_id)
+      );
+
+      CREATE TABLE IF NOT EXISTS tags (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL UNIQUE
+      );
+
+      CREATE TABLE IF NOT EXISTS todo_tags (
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+        PRIMARY KEY (todo_id, tag_id)
+      );
+
+      CREATE INDEX IF NOT EXISTS idx_subtasks_todo_sort ON subtasks(todo_id, sort_order);
+      CREATE INDEX IF NOT EXISTS idx_todo_completions_todo ON todo_completions(todo_id, completed_at);
+      CREATE INDEX IF NOT EXISTS idx_todo_attachments_todo ON todo_attachments(todo_id, created_at);
+      CREATE INDEX IF NOT EXISTS idx_todo_dependencies_depends_on ON todo_dependencies(depends_on_id);
+      CREATE INDEX IF NOT EXISTS idx_todo_tags_tag ON todo_tags(tag_id);
+      CREATE INDEX IF NOT EXISTS idx_todos_sort_order ON todos(sort_order);
+      CREATE INDEX IF NOT EXISTS idx_todos_completed_sort ON todos(completed, sort_order);
+      CREATE INDEX IF NOT EXISTS idx_daily_completion_event_day ON daily_completion_events(event_day);
+    "#,
+    )
+    .map_err(AppError::from)?;
+
+  add_column_if_missing(
+    conn,
+    "ALTER TABLE todos ADD COLUMN recurrence_tag TEXT NOT NULL DEFAULT 'none'",
+  )?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN recurrence_checked_at TEXT NULL")?;
+  add_column_if_missing(
+    conn,
+    "ALTER TABLE todos ADD COLUMN reminder_enabled INTEGER NOT NULL DEFAULT 1",
+  )?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN last_reminded_on TEXT NULL")?;
+
+  if add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN completed_at TEXT NULL")? {
+    // Best available approximation for rows completed before the column existed.
+    conn
+      .execute(
+        "UPDATE todos SET completed_at = updated_at WHERE completed = 1 AND completed_at IS NULL",
+        [],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN series_id TEXT NULL")?;
+  conn
+    .execute("CREATE INDEX IF NOT EXISTS idx_todos_series_id ON todos(series_id)", [])
+    .map_err(AppError::from)?;
+
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN recurrence_until TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN note_format TEXT NOT NULL DEFAULT 'plain'")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN rolled_at TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal'")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN energy TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN deleted_at TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN recurrence_max_count INTEGER NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN recurrence_completed_count INTEGER NOT NULL DEFAULT 0")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN notified_at TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN remind_after TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN context TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN snooze_count INTEGER NOT NULL DEFAULT 0")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN color TEXT NULL")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN archived INTEGER NOT NULL DEFAULT 0")?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0")?;
+  conn
+    .execute("CREATE INDEX IF NOT EXISTS idx_todos_pinned_sort ON todos(pinned DESC, sort_order)", [])
+    .map_err(AppError::from)?;
+  add_column_if_missing(conn, "ALTER TABLE todos ADD COLUMN reminder_offset_minutes INTEGER NULL")?;
+
+  Ok(())
+}
+
+// Triggers feed the change log so every write path is covered, including
+// cascades. Timestamps use a fixed-width UTC format so they compare as text.
+// Soft deletes are logged as deletes; subtask and tag edits as updates of
+// their todo, unless the todo itself is gone.
+fn migrate_schema_v2(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute_batch(
+      r#"
+      CREATE TABLE change_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        todo_id TEXT NOT NULL,
+        op TEXT NOT NULL,
+        at TEXT NOT NULL
+      );
+
+      CREATE INDEX idx_change_log_at ON change_log(at);
+
+      CREATE TRIGGER change_log_todo_insert AFTER INSERT ON todos BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (new.id, 'insert', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_todo_update AFTER UPDATE ON todos BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (
+            new.id,
+            CASE WHEN old.deleted_at IS NULL AND new.deleted_at IS NOT NULL THEN 'delete' ELSE 'update' END,
+            strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+          );
+      END;
+
+      CREATE TRIGGER change_log_todo_delete AFTER DELETE ON todos BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (old.id, 'delete', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_subtask_insert AFTER INSERT ON subtasks BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (new.todo_id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_subtask_update AFTER UPDATE ON subtasks BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (new.todo_id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_subtask_delete AFTER DELETE ON subtasks
+      WHEN EXISTS (SELECT 1 FROM todos WHERE id = old.todo_id) BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (old.todo_id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_tag_insert AFTER INSERT ON todo_tags BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (new.todo_id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+
+      CREATE TRIGGER change_log_tag_delete AFTER DELETE ON todo_tags
+      WHEN EXISTS (SELECT 1 FROM todos WHERE id = old.todo_id) BEGIN
+        INSERT INTO change_log (todo_id, op, at)
+          VALUES (old.todo_id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+      END;
+    "#,
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// The partial unique index allows at most one running timer per todo.
+fn migrate_schema_v3(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute_batch(
+      r#"
+      ALTER TABLE todos ADD COLUMN estimate_minutes INTEGER;
+
+      CREATE TABLE time_entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        todo_id TEXT NOT NULL REFERENCES todos(id) ON DELETE CASCADE,
+        started_at TEXT NOT NULL,
+        stopped_at TEXT
+      );
+
+      CREATE INDEX idx_time_entries_todo ON time_entries(todo_id, started_at);
+      CREATE UNIQUE INDEX idx_time_entries_running ON time_entries(todo_id) WHERE stopped_at IS NULL;
+    "#,
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Existing long notes start collapsed, as new ones do. SQLite's `length`
+// counts characters for text, matching `default_note_collapsed`.
+fn migrate_schema_v4(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute("ALTER TABLE todos ADD COLUMN note_collapsed INTEGER NOT NULL DEFAULT 0", [])
+    .map_err(AppError::from)?;
+  conn
+    .execute(
+      "UPDATE todos SET note_collapsed = 1 WHERE length(note) > ?1",
+      params![NOTE_COLLAPSE_THRESHOLD_CHARS as i64],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Saved filters keep their `ListOptions` as JSON so they replay through the
+// same query as `list_todos`.
+fn migrate_schema_v5(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute_batch(
+      r#"
+      CREATE TABLE saved_filters (
+        name TEXT PRIMARY KEY,
+        params TEXT NOT NULL,
+        created_at TEXT NOT NULL
+      );
+    "#,
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn migrate_schema_v6(conn: &Connection) -> CommandResult<()> {
+  conn
+    .execute("CREATE INDEX idx_change_log_todo ON change_log(todo_id, id)", [])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn ensure_search_index(conn: &Connection) -> CommandResult<()> {
+  let exists: bool = conn
+    .query_row(
+      "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'todos_fts')",
+      [],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+  if exists {
+    return Ok(());
+  }
+
+  // Builds without FTS5 fall back to LIKE scans in `search_todos`.
+  if conn
+    .execute_batch("CREATE VIRTUAL TABLE todos_fts USING fts5(todo_id UNINDEXED, title, note, tokenize = 'trigram');")
+    .is_err()
+  {
+    return Ok(());
+  }
+
+  conn
+    .execute_batch(
+      r#"
+      CREATE TRIGGER IF NOT EXISTS todos_fts_after_insert AFTER INSERT ON todos BEGIN
+        INSERT INTO todos_fts (todo_id, title, note) VALUES (new.id, new.title, new.note);
+      END;
+
+      CREATE TRIGGER IF NOT EXISTS todos_fts_after_delete AFTER DELETE ON todos BEGIN
+        DELETE FROM todos_fts WHERE todo_id = old.id;
+      END;
+
+      CREATE TRIGGER IF NOT EXISTS todos_fts_after_update AFTER UPDATE OF id, title, note ON todos BEGIN
+        DELETE FROM todos_fts WHERE todo_id = old.id;
+        INSERT INTO todos_fts (todo_id, title, note) VALUES (new.id, new.title, new.note);
+      END;
+
+      INSERT INTO todos_fts (todo_id, title, note) SELECT id, title, note FROM todos;
+    "#,
+    )
+    .map_err(AppError::from)
+}
+
+// The `scheduled_load` key for a due date: plain days as stored, timestamps
+// (e.g. from snoozing) on their local calendar day, matching `parse_due_day`.
+fn scheduled_day_sql(column: &str) -> String {
+  format!("CASE WHEN length({column}) > 10 THEN coalesce(date({column}, 'localtime'), {column}) ELSE {column} END")
+}
+
+fn ensure_scheduled_load(conn: &Connection) -> CommandResult<()> {
+  let new_day = scheduled_day_sql("new.due_date");
+  let old_day = scheduled_day_sql("old.due_date");
+  // Triggers keep the per-day counts of open dated todos current; the startup
+  // rebuild below heals any drift from rows written before they existed, or
+  // keyed under a different local timezone.
+  conn
+    .execute_batch(&format!(
+      r#"
+      CREATE TABLE IF NOT EXISTS scheduled_load (
+        day TEXT PRIMARY KEY,
+        open_count INTEGER NOT NULL DEFAULT 0
+      );
+
+      DROP TRIGGER IF EXISTS scheduled_load_after_insert;
+      DROP TRIGGER IF EXISTS scheduled_load_after_delete;
+      DROP TRIGGER IF EXISTS scheduled_load_after_update;
+
+      CREATE TRIGGER scheduled_load_after_insert AFTER INSERT ON todos
+      WHEN new.completed = 0 AND new.due_date IS NOT NULL AND new.deleted_at IS NULL AND new.archived = 0 BEGIN
+        INSERT OR IGNORE INTO scheduled_load (day, open_count) VALUES ({new_day}, 0);
+        UPDATE scheduled_load SET open_count = open_count + 1 WHERE day = {new_day};
+      END;
+
+      CREATE TRIGGER scheduled_load_after_delete AFTER DELETE ON todos
+      WHEN old.completed = 0 AND old.due_date IS NOT NULL AND old.deleted_at IS NULL AND old.archived = 0 BEGIN
+        UPDATE scheduled_load SET open_count = open_count - 1 WHERE day = {old_day};
+        DELETE FROM scheduled_load WHERE day = {old_day} AND open_count <= 0;
+      END;
+
+      CREATE TRIGGER scheduled_load_after_update AFTER UPDATE OF completed, due_date, deleted_at, archived ON todos BEGIN
+        UPDATE scheduled_load SET open_count = open_count - 1
+          WHERE day = {old_day} AND old.completed = 0 AND old.deleted_at IS NULL AND old.archived = 0;
+        DELETE FROM scheduled_load WHERE day = {old_day} AND open_count <= 0;
+        INSERT OR IGNORE INTO scheduled_load (day, open_count)
+          SELECT {new_day}, 0 WHERE new.completed = 0 AND new.due_date IS NOT NULL AND new.deleted_at IS NULL AND new.archived = 0;
+        UPDATE scheduled_load SET open_count = open_count + 1
+          WHERE day = {new_day} AND new.completed = 0 AND new.deleted_at IS NULL AND new.archived = 0;
+      END;
+    "#
+    ))
+    .map_err(AppError::from)?;
+
+  rebuild_scheduled_load(conn)?;
+  Ok(())
+}
+
+fn rebuild_scheduled_load(conn: &Connection) -> CommandResult<usize> {
+  conn
+    .execute("DELETE FROM scheduled_load", [])
+    .map_err(AppError::from)?;
+
+  let day = scheduled_day_sql("due_date");
+  conn
+    .execute(
+      &format!(
+        "INSERT INTO scheduled_load (day, open_count)
+         SELECT {day}, COUNT(*) FROM todos
+         WHERE completed = 0 AND due_date IS NOT NULL AND deleted_at IS NULL AND archived = 0
+         GROUP BY 1"
+      ),
+      [],
+    )
+    .map_err(AppError::from)
+}
+
+fn insert_todo(conn: &Connection, todo: &Todo) -> CommandResult<()> {
+  conn
+    .execute(
+      "INSERT INTO todos
+       (id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, reminder_enabled, last_reminded_on, sort_order, created_at, updated_at, completed_at, series_id, recurrence_until, note_format, rolled_at, priority, energy, deleted_at, recurrence_max_count, recurrence_completed_count, notified_at, remind_after, context, snooze_count, color, archived, pinned, reminder_offset_minutes, estimate_minutes, note_collapsed)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32)",
+      params![
+        &todo.id,
+        &todo.title,
+        &todo.recurrence_tag,
+        &todo.recurrence_checked_at,
+        &todo.note,
+        to_db_bool(todo.completed),
+        &todo.due_date,
+        to_db_bool(todo.reminder_enabled),
+        &todo.last_reminded_on,
+        todo.sort_order,
+        &todo.created_at,
+        &todo.updated_at,
+        &todo.completed_at,
+        &todo.series_id,
+        &todo.recurrence_until,
+        &todo.note_format,
+        &todo.rolled_at,
+        &todo.priority,
+        &todo.energy,
+        &todo.deleted_at,
+        &todo.recurrence_max_count,
+        &todo.recurrence_completed_count,
+        &todo.notified_at,
+        &todo.remind_after,
+        &todo.context,
+        &todo.snooze_count,
+        &todo.color,
+        to_db_bool(todo.archived),
+        to_db_bool(todo.pinned),
+        &todo.reminder_offset_minutes,
+        &todo.estimate_minutes,
+        to_db_bool(todo.note_collapsed),
+      ],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn update_todo_row(conn: &Connection, todo: &Todo) -> CommandResult<()> {
+  conn
+    .execute(
+      "UPDATE todos
+       SET title = ?2, recurrence_tag = ?3, recurrence_checked_at = ?4, note = ?5, completed = ?6, due_date = ?7, reminder_enabled = ?8, last_reminded_on = ?9, created_at = ?10, updated_at = ?11, completed_at = ?12, series_id = ?13, recurrence_until = ?14, note_format = ?15, rolled_at = ?16, priority = ?17, energy = ?18, deleted_at = ?19, recurrence_max_count = ?20, recurrence_completed_count = ?21, notified_at = ?22, remind_after = ?23, context = ?24, snooze_count = ?25, color = ?26, archived = ?27, pinned = ?28, reminder_offset_minutes = ?29, estimate_minutes = ?30, note_collapsed = ?31
+       WHERE id = ?1",
+      params![
+        &todo.id,
+        &todo.title,
+        &todo.recurrence_tag,
+        &todo.recurrence_checked_at,
+        &todo.note,
+        to_db_bool(todo.completed),
+        &todo.due_date,
+        to_db_bool(todo.reminder_enabled),
+        &todo.last_reminded_on,
+        &todo.created_at,
+        &todo.updated_at,
+        &todo.completed_at,
+        &todo.series_id,
+        &todo.recurrence_until,
+        &todo.note_format,
+        &todo.rolled_at,
+        &todo.priority,
+        &todo.energy,
+        &todo.deleted_at,
+        &todo.recurrence_max_count,
+        &todo.recurrence_completed_count,
+        &todo.notified_at,
+        &todo.remind_after,
+        &todo.context,
+        &todo.snooze_count,
+        &todo.color,
+        to_db_bool(todo.archived),
+        to_db_bool(todo.pinned),
+        &todo.reminder_offset_minutes,
+        &todo.estimate_minutes,
+        to_db_bool(todo.note_collapsed),
+      ],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn db_conn(state: &AppState) -> CommandResult<DbConnection> {
+  let database = state.read_db();
+  if database.locked {
+    return Err(AppError::Database("The database is encrypted; unlock it with its passphrase first".to_string()));
+  }
+
+  database
+    .pool
+    .get()
+    .map_err(|err| AppError::Lock(format!("Failed to acquire database connection: {err}")))
+}
+
+fn get_todo_by_id(conn: &Connection, id: &str, include_trashed: bool) -> CommandResult<Option<Todo>> {
+  let trash_filter = if include_trashed { "" } else { " AND deleted_at IS NULL" };
+
+  conn
+    .query_row(
+      &format!("SELECT {TODO_COLUMNS} FROM todos WHERE id = ?1{trash_filter}"),
+      params![id],
+      map_todo_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn detect_clock_skew(conn: &Connection) -> CommandResult<ClockSkew> {
+  let mut statement = conn
+    .prepare("SELECT updated_at FROM todos")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut latest: Option<DateTime<Utc>> = None;
+  for row in rows {
+    let raw = row.map_err(AppError::from)?;
+    let Ok(parsed) = DateTime::parse_from_rfc3339(&raw) else {
+      continue;
+    };
+
+    let parsed = parsed.with_timezone(&Utc);
+    if latest.is_none_or(|current| parsed > current) {
+      latest = Some(parsed);
+    }
+  }
+
+  let skew_seconds = latest
+    .map(|stored| stored.signed_duration_since(Utc::now()).num_seconds().max(0))
+    .unwrap_or(0);
+
+  Ok(ClockSkew {
+    latest_stored_at: latest.map(|stored| stored.to_rfc3339()),
+    skew_seconds,
+    exceeds_threshold: skew_seconds > CLOCK_SKEW_THRESHOLD_SECONDS,
+  })
+}
+
+fn set_meta(conn: &Connection, key: &str, value: &str) -> CommandResult<()> {
+  conn
+    .execute(
+      "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
+       ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+      params![key, value],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn get_meta(conn: &Connection, key: &str) -> CommandResult<Option<String>> {
+  conn
+    .query_row("SELECT value FROM app_meta WHERE key = ?1", params![key], |row| {
+      row.get(0)
+    })
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn delete_meta(conn: &Connection, key: &str) -> CommandResult<()> {
+  conn
+    .execute("DELETE FROM app_meta WHERE key = ?1", params![key])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn get_window_prefs_from_conn(conn: &Connection) -> CommandResult<WindowPrefs> {
+  let raw = get_meta(conn, WINDOW_PREFS_KEY)?;
+
+  match raw {
+    Some(value) => match serde_json::from_str::<WindowPrefs>(&value) {
+      Ok(parsed) => Ok(normalize_window_prefs(parsed)),
+      Err(_) => {
+        let legacy = match serde_json::from_str::<LegacyWindowPrefs>(&value) {
+          Ok(legacy) => legacy,
+          Err(error) => {
+            eprintln!("Discarding corrupted window prefs: {error}");
+            let defaults = WindowPrefs::default();
+            save_window_prefs_to_conn(conn, &defaults)?;
+            return Ok(defaults);
+          }
+        };
+        let size_class = infer_size_class_from_dimensions(legacy.width, legacy.height);
+        Ok(normalize_window_prefs(WindowPrefs {
+          x: legacy.x,
+          y: legacy.y,
+          width: legacy.width,
+          height: legacy.height,
+          mode: legacy.mode,
+          size_class,
+          always_on_top: legacy.always_on_top,
+          aspect_ratio: None,
+          start_minimized: false,
+          mini_size: default_mini_size(),
+          expanded_size: default_expanded_size(),
+          dock_edge: None,
+          always_on_top_mini: None,
+          always_on_top_expanded: None,
+          opacity: default_window_opacity(),
+          ignore_cursor_events: false,
+        }))
+      }
+    },
+    None => Ok(WindowPrefs::default()),
+  }
+}
+
+fn save_window_prefs_to_conn(conn: &Connection, prefs: &WindowPrefs) -> CommandResult<()> {
+  let value = serde_json::to_string(prefs).map_err(AppError::from)?;
+  set_meta(conn, WINDOW_PREFS_KEY, &value)
+}
+
+fn get_ui_prefs_from_conn(conn: &Connection) -> CommandResult<UiPrefs> {
+  let raw = get_meta(conn, UI_PREFS_KEY)?;
+
+  match raw {
+    Some(value) => match serde_json::from_str::<UiPrefs>(&value) {
+      Ok(parsed) => Ok(parsed),
+      Err(error) => {
+        eprintln!("Discarding corrupted UI prefs: {error}");
+        let defaults = UiPrefs::default();
+        save_ui_prefs_to_conn(conn, &defaults)?;
+        Ok(defaults)
+      }
+    },
+    None => Ok(UiPrefs::default()),
+  }
+}
+
+fn save_ui_prefs_to_conn(conn: &Connection, prefs: &UiPrefs) -> CommandResult<()> {
+  let value = serde_json::to_string(prefs).map_err(AppError::from)?;
+  set_meta(conn, UI_PREFS_KEY, &value)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WindowRect {
+  x: f64,
+  y: f64,
+  width: f64,
+  height: f64,
+}
+
+impl WindowRect {
+  fn intersects(&self, other: &WindowRect) -> bool {
+    self.x < other.x + other.width
+      && other.x < self.x + self.width
+      && self.y < other.y + other.height
+      && other.y < self.y + self.height
+  }
+}
+
+fn logical_work_area(monitor: &Monitor) -> WindowRect {
+  let area = monitor.work_area();
+  let scale = monitor.scale_factor();
+
+  WindowRect {
+    x: f64::from(area.position.x) / scale,
+    y: f64::from(area.position.y) / scale,
+    width: f64::from(area.size.width) / scale,
+    height: f64::from(area.size.height) / scale,
+  }
+}
+
+// Keeps a saved rect that still overlaps some monitor; otherwise (e.g. its
+// monitor was unplugged) centers it on the primary monitor, shrinking it to
+// fit if needed.
+fn clamp_to_visible_monitors(window: &WebviewWindow, saved: WindowRect) -> WindowRect {
+  let work_areas: Vec<WindowRect> = window
+    .available_monitors()
+    .unwrap_or_default()
+    .iter()
+    .map(logical_work_area)
+    .collect();
+
+  if work_areas.is_empty() || work_areas.iter().any(|area| area.intersects(&saved)) {
+    return saved;
+  }
+
+  let Some(target) = window
+    .primary_monitor()
+    .ok()
+    .flatten()
+    .map(|monitor| logical_work_area(&monitor))
+    .or_else(|| work_areas.first().copied())
+  else {
+    return saved;
+  };
+
+  centered_rect(target, saved.width, saved.height)
+}
+
+fn centered_rect(work_area: WindowRect, width: f64, height: f64) -> WindowRect {
+  let width = width.min(work_area.width);
+  let height = height.min(work_area.height);
+
+  WindowRect {
+    x: work_area.x + (work_area.width - width) / 2.0,
+    y: work_area.y + (work_area.height - height) / 2.0,
+    width,
+    height,
+  }
+}
+
+fn current_window_rect(window: &WebviewWindow) -> Option<WindowRect> {
+  let scale = window.scale_factor().ok()?;
+  let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+  let size = window.outer_size().ok()?.to_logical::<f64>(scale);
+
+  Some(WindowRect {
+    x: position.x,
+    y: position.y,
+    width: size.width,
+    height: size.height,
+  })
+}
+
+// Unknown geometry or no reported monitors count as on screen, so the UI
+// never nags about a window it can't actually place.
+fn window_on_screen(window: &WebviewWindow) -> bool {
+  let Some(rect) = current_window_rect(window) else {
+    return true;
+  };
+  let work_areas: Vec<WindowRect> = window
+    .available_monitors()
+    .unwrap_or_default()
+    .iter()
+    .map(logical_work_area)
+    .collect();
+
+  work_areas.is_empty() || work_areas.iter().any(|area| area.intersects(&rect))
+}
+
+// Spans the full length of the chosen edge and keeps the saved size across it,
+// shrunk to fit the work area.
+fn docked_rect(work_area: WindowRect, width: f64, height: f64, edge: DockEdge) -> WindowRect {
+  let width = width.min(work_area.width);
+  let height = height.min(work_area.height);
+
+  match edge {
+    DockEdge::Left | DockEdge::Right => WindowRect {
+      x: match edge {
+        DockEdge::Left => work_area.x,
+        _ => work_area.x + work_area.width - width,
+      },
+      y: work_area.y,
+      width,
+      height: work_area.height,
+    },
+    DockEdge::Top | DockEdge::Bottom => WindowRect {
+      x: work_area.x,
+      y: match edge {
+        DockEdge::Top => work_area.y,
+        _ => work_area.y + work_area.height - height,
+      },
+      width: work_area.width,
+      height,
+    },
+  }
+}
+
+fn apply_window_prefs(window: &WebviewWindow, prefs: &WindowPrefs) -> CommandResult<()> {
+  let saved = WindowRect {
+    x: prefs.x,
+    y: prefs.y,
+    width: prefs.width,
+    height: prefs.height,
+  };
+  let monitor = window
+    .current_monitor()
+    .ok()
+    .flatten()
+    .or_else(|| window.primary_monitor().ok().flatten());
+  let rect = match (prefs.dock_edge, monitor) {
+    (Some(edge), Some(monitor)) => docked_rect(logical_work_area(&monitor), prefs.width, prefs.height, edge),
+    _ => clamp_to_visible_monitors(window, saved),
+  };
+
+  window
+    .set_size(Size::Logical(LogicalSize::new(rect.width, rect.height)))
+    .map_err(AppError::from)?;
+
+  window
+    .set_position(Position::Logical(LogicalPosition::new(rect.x, rect.y)))
+    .map_err(AppError::from)?;
+
+  window
+    .set_always_on_top(prefs.always_on_top)
+    .map_err(AppError::from)?;
+
+  window
+    .set_ignore_cursor_events(prefs.ignore_cursor_events)
+    .map_err(AppError::from)?;
+
+  // Fully opaque is the native default; skipping it keeps launch working on
+  // platforms without opacity support.
+  if prefs.opacity < 1.0 {
+    set_native_window_opacity(window, prefs.opacity)?;
+  }
+
+  Ok(())
+}
+
+// Tauri has no opacity setter, so Windows goes through a layered window.
+#[cfg(target_os = "windows")]
+fn set_native_window_opacity(window: &WebviewWindow, opacity: f64) -> CommandResult<()> {
+  use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA, WS_EX_LAYERED,
+  };
+
+  let hwnd = window.hwnd().map_err(AppError::from)?.0;
+  let alpha = (opacity * 255.0).round() as u8;
+
+  // SAFETY: `hwnd` is the live handle of this process's own main window.
+  let applied = unsafe {
+    let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED as isize);
+    SetLayeredWindowAttributes(hwnd, 0, alpha, LWA_ALPHA)
+  };
+  if applied == 0 {
+    return Err(AppError::Window(std::io::Error::last_os_error().to_string()));
+  }
+
+  Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_native_window_opacity(_window: &WebviewWindow, _opacity: f64) -> CommandResult<()> {
+  Err(AppError::Window("Window opacity is only supported on Windows".to_string()))
+}
+
+fn save_window_position(app: &AppHandle, x: f64, y: f64) -> CommandResult<()> {
+  match app.try_state::<AppState>() {
+    Some(state) => save_window_position_in(&state, x, y),
+    None => Ok(()),
+  }
+}
+
+// No-op while `set_window_persistence(false)` is in effect.
+fn save_window_position_in(state: &AppState, x: f64, y: f64) -> CommandResult<()> {
+  if !state.window_persistence_enabled.load(Ordering::SeqCst) {
+    return Ok(());
+  }
+
+  let conn = db_conn(state)?;
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  // Docked geometry is derived from the monitor, so it is never saved.
+  if prefs.dock_edge.is_some() {
+    return Ok(());
+  }
+  prefs.x = x;
+  prefs.y = y;
+  save_window_prefs_to_conn(&conn, &prefs)
+}
+
+fn save_window_size(app: &AppHandle, width: f64, height: f64) -> CommandResult<()> {
+  match app.try_state::<AppState>() {
+    Some(state) => save_window_size_in(&state, width, height),
+    None => Ok(()),
+  }
+}
+
+fn save_window_size_in(state: &AppState, width: f64, height: f64) -> CommandResult<()> {
+  if !state.window_persistence_enabled.load(Ordering::SeqCst) {
+    return Ok(());
+  }
+
+  let conn = db_conn(state)?;
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  if prefs.dock_edge.is_some() {
+    return Ok(());
+  }
+  let inferred = infer_size_class_from_dimensions(width, height);
+  prefs.width = width;
+  prefs.height = height;
+  prefs.size_class = inferred;
+  prefs.mode = mode_from_size_class(&prefs.size_class);
+  match prefs.size_class {
+    WindowSizeClass::Mini => prefs.mini_size = PanelSize { width, height },
+    WindowSizeClass::Wide => prefs.expanded_size = PanelSize { width, height },
+    WindowSizeClass::Standard => {}
+  }
+  save_window_prefs_to_conn(&conn, &prefs)
+}
+
+fn window_aspect_ratio(app: &AppHandle) -> Option<f64> {
+  let state = app.try_state::<AppState>()?;
+  let conn = db_conn(&state).ok()?;
+  get_window_prefs_from_conn(&conn).ok()?.aspect_ratio
+}
+
+fn attach_window_persistence(window: WebviewWindow, app: AppHandle) {
+  let handle = window.clone();
+  window.on_window_event(move |event| match event {
+    WindowEvent::Moved(position) => {
+      let _ = save_window_position(&app, position.x as f64, position.y as f64);
+      emit_if_offscreen(&app, &handle);
+    }
+    WindowEvent::Resized(size) => {
+      let (mut width, mut height) = (size.width as f64, size.height as f64);
+
+      if let Some(ratio) = window_aspect_ratio(&app) {
+        let (corrected_width, corrected_height) = aspect_corrected_size(width, height, ratio);
+        if (corrected_height - height).abs() >= 1.0 {
+          // The follow-up Resized event already matches the ratio, so this does not loop.
+          let _ = handle.set_size(Size::Physical(PhysicalSize::new(
+            corrected_width as u32,
+            corrected_height as u32,
+          )));
+        }
+        width = corrected_width;
+        height = corrected_height;
+      }
+
+      let _ = save_window_size(&app, width, height);
+      emit_if_offscreen(&app, &handle);
+    }
+    WindowEvent::Focused(focused) => {
+      if let Some(state) = app.try_state::<AppState>() {
+        state.window_focused.store(*focused, Ordering::SeqCst);
+        let _ = apply_focus_opacity(&state, &handle, false);
+      }
+    }
+    _ => {}
+  });
+}
+
+// Lets the UI offer a "recenter" action, e.g. after a resolution change.
+fn emit_if_offscreen(app: &AppHandle, window: &WebviewWindow) {
+  if !window_on_screen(window) {
+    let _ = app.emit("window-offscreen", ());
+  }
+}
+
+// The target level is picked from the latest stored focus flag rather than the
+// event that triggered the call, so a blur handled after a newer focus still
+// settles on the focused opacity instead of leaving the panel stuck dimmed.
+// With dimming off nothing is touched unless `force` asks to restore the level.
+fn apply_focus_opacity(state: &AppState, window: &WebviewWindow, force: bool) -> CommandResult<()> {
+  let conn = db_conn(state)?;
+  let ui_prefs = get_ui_prefs_from_conn(&conn)?;
+  if !ui_prefs.dim_on_blur && !force {
+    return Ok(());
+  }
+
+  let focused_opacity = get_window_prefs_from_conn(&conn)?.opacity;
+  let opacity = if !ui_prefs.dim_on_blur || state.window_focused.load(Ordering::SeqCst) {
+    focused_opacity
+  } else {
+    ui_prefs.blurred_opacity.clamp(MIN_WINDOW_OPACITY, focused_opacity)
+  };
+
+  set_native_window_opacity(window, opacity)
+}
+
+fn push_undo(state: &AppState, entry: UndoEntry) -> CommandResult<()> {
+  let mut stack = state.lock_undo_stack();
+  stack.push(entry);
+  if stack.len() > UNDO_LIMIT {
+    stack.remove(0);
+  }
+
+  Ok(())
+}
+
+// Broadcast to every window so views opened side by side refetch after a write.
+// Only call this once the change has been committed.
+fn emit_todos_changed(app: &AppHandle, kind: &str, id: Option<&str>) {
+  let _ = app.emit("todos-changed", TodosChanged { kind, id });
+}
+
+// Called with the number of rows handled so far before each row, so the loop's
+// `continue`s need no extra bookkeeping. The 100% event is sent by the caller
+// after the commit, and only then.
+fn emit_migration_progress(app: &AppHandle, done: usize, total: usize) {
+  if done > 0 && done.is_multiple_of(MIGRATION_PROGRESS_INTERVAL) && done < total {
+    let _ = app.emit("migration-progress", MigrationProgress { done, total });
+  }
+}
+
+fn emit_migration_complete(app: &AppHandle, total: usize) {
+  let _ = app.emit("migration-progress", MigrationProgress { done: total, total });
+}
+
+fn show_main_window(app: &AppHandle) {
+  if let Some(window) = app.get_webview_window("main") {
+    let _ = window.show();
+    let _ = window.set_focus();
+  }
+}
+
+// A click-through panel can't be clicked, so the tray is the way back: both
+// "Show" and the dedicated item turn it off.
+fn disable_click_through(app: &AppHandle) {
+  let state = app.state::<AppState>();
+  if let Err(err) = set_click_through_inner(&state, app, false) {
+    eprintln!("Failed to disable click-through: {}", err.message());
+  }
+}
+
+fn build_tray(app: &App) -> tauri::Result<()> {
+  let show = MenuItem::with_id(app, "show", "Show Simple Todo Note", true, None::<&str>)?;
+  let click_through = MenuItem::with_id(app, "disable_click_through", "Disable Click-Through", true, None::<&str>)?;
+  let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+  let menu = Menu::with_items(app, &[&show, &click_through, &quit])?;
+
+  let mut builder = TrayIconBuilder::new()
+    .tooltip("Simple Todo Note")
+    .menu(&menu)
+    .on_menu_event(|app, event| match event.id().as_ref() {
+      "show" => {
+        disable_click_through(app);
+        show_main_window(app);
+      }
+      "disable_click_through" => disable_click_through(app),
+      "quit" => app.exit(0),
+      _ => {}
+    });
+
+  if let Some(icon) = app.default_window_icon() {
+    builder = builder.icon(icon.clone());
+  }
+
+  builder.build(app)?;
+  Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn enable_autostart() -> CommandResult<()> {
+  use winreg::enums::HKEY_CURRENT_USER;
+  use winreg::RegKey;
+
+  let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+  let (run_key, _) = hkcu
+    .create_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Run")
+    .map_err(AppError::from)?;
+
+  // Quoted so paths with spaces are not split into arguments.
+  let current_exe = std::env::current_exe().map_err(AppError::from)?;
+  let command = format!("\"{}\"", current_exe.display());
+
+  run_key
+    .set_value(AUTOSTART_NAME, &command)
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn disable_autostart() -> CommandResult<()> {
+  use winreg::enums::{HKEY_CURRENT_USER, KEY_SET_VALUE};
+  use winreg::RegKey;
+
+  let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+  let run_key = hkcu
+    .open_subkey_with_flags("Software\\Microsoft\\Windows\\CurrentVersion\\Run", KEY_SET_VALUE)
+    .map_err(AppError::from)?;
+
+  match run_key.delete_value(AUTOSTART_NAME) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(AppError::from(err)),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(target_os = "windows")]
+fn autostart_enabled() -> bool {
+  use winreg::enums::HKEY_CURRENT_USER;
+  use winreg::RegKey;
+
+  RegKey::predef(HKEY_CURRENT_USER)
+    .open_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Run")
+    .and_then(|run_key| run_key.get_value::<String, _>(AUTOSTART_NAME))
+    .is_ok()
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn home_dir() -> CommandResult<std::path::PathBuf> {
+  std::env::var_os("HOME")
+    .filter(|value| !value.is_empty())
+    .map(std::path::PathBuf::from)
+    .ok_or_else(|| AppError::Io("HOME is not set".to_string()))
+}
+
+#[cfg(target_os = "macos")]
+fn autostart_entry_path() -> CommandResult<std::path::PathBuf> {
+  Ok(home_dir()?.join("Library/LaunchAgents").join(format!("{AUTOSTART_LABEL}.plist")))
+}
+
+// The executable is its own <string> in ProgramArguments, so spaces need no
+// quoting; only XML escaping applies.
+#[cfg(target_os = "macos")]
+fn autostart_entry_contents(exe: &std::path::Path) -> String {
+  format!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{AUTOSTART_LABEL}</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{}</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+</dict>
+</plist>
+"#,
+    xml_attr(&exe.display().to_string())
+  )
+}
+
+#[cfg(target_os = "linux")]
+fn autostart_entry_path() -> CommandResult<std::path::PathBuf> {
+  let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|value| !value.is_empty()) {
+    Some(value) => std::path::PathBuf::from(value),
+    None => home_dir()?.join(".config"),
+  };
+  Ok(config_dir.join("autostart").join(format!("{AUTOSTART_NAME}.desktop")))
+}
+
+// Exec= follows the desktop entry quoting rules: wrap in double quotes,
+// backslash-escape the reserved characters, and double any field-code `%`.
+#[cfg(target_os = "linux")]
+fn autostart_entry_contents(exe: &std::path::Path) -> String {
+  let mut quoted = String::new();
+  for ch in exe.display().to_string().chars() {
+    match ch {
+      '"' | '`' | '$' | '\\' => {
+        quoted.push('\\');
+        quoted.push(ch);
+      }
+      '%' => quoted.push_str("%%"),
+      ch => quoted.push(ch),
+    }
+  }
+
+  format!(
+    "[Desktop Entry]\nType=Application\nName=Simple Todo Note\nExec=\"{quoted}\"\nX-GNOME-Autostart-enabled=true\nTerminal=false\n"
+  )
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn enable_autostart() -> CommandResult<()> {
+  let path = autostart_entry_path()?;
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent).map_err(AppError::from)?;
+  }
+
+  let current_exe = std::env::current_exe().map_err(AppError::from)?;
+  std::fs::write(&path, autostart_entry_contents(&current_exe)).map_err(AppError::from)
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn disable_autostart() -> CommandResult<()> {
+  match std::fs::remove_file(autostart_entry_path()?) {
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(AppError::from(err)),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn autostart_enabled() -> bool {
+  autostart_entry_path().is_ok_and(|path| path.exists())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn enable_autostart() -> CommandResult<()> {
+  Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn disable_autostart() -> CommandResult<()> {
+  Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn autostart_enabled() -> bool {
+  false
+}
+
+#[tauri::command]
+fn set_autostart(state: State<'_, AppState>, enabled: bool) -> CommandResult<bool> {
+  let conn = db_conn(&state)?;
+
+  if enabled {
+    enable_autostart()?;
+  } else {
+    disable_autostart()?;
+  }
+
+  // Remembered so startup stops re-registering once the user opts out.
+  set_meta(&conn, AUTOSTART_DISABLED_KEY, if enabled { "false" } else { "true" })?;
+
+  Ok(autostart_enabled())
+}
+
+#[tauri::command]
+fn get_autostart_enabled() -> bool {
+  autostart_enabled()
+}
+
+// Validated (limit, offset); a limit of -1 means "no limit", matching SQLite's LIMIT semantics.
+fn page_bounds(options: &ListOptions) -> CommandResult<Option<(i64, i64)>> {
+  if options.limit.is_none() && options.offset.is_none() {
+    return Ok(None);
+  }
+
+  let limit = options.limit.unwrap_or(-1);
+  let offset = options.offset.unwrap_or(0);
+  if options.limit.is_some_and(|limit| limit < 0) || offset < 0 {
+    return Err(AppError::Validation("Limit and offset must not be negative".to_string()));
+  }
+
+  Ok(Some((limit, offset)))
+}
+
+// Builds the `list_todos` query and its parameters. Shared by `list_todos` and
+// `explain_list_query` so the plan always matches. Filters are only added when
+// set so the planner can use `idx_todos_completed_sort`. `Hidden` placement
+// only applies when the caller hasn't asked for a specific completion state;
+// `count_hidden_completed` counts the rows it leaves out.
+fn list_todos_query(
+  sort_by_priority: bool,
+  placement: CompletedPlacement,
+  active_context: Option<&str>,
+  options: &ListOptions,
+) -> CommandResult<(String, Vec<Value>)> {
+  let priority_order = if sort_by_priority {
+    "CASE priority WHEN 'high' THEN 0 WHEN 'normal' THEN 1 ELSE 2 END ASC, "
+  } else {
+    ""
+  };
+  let completed_order = if placement == CompletedPlacement::Bottom {
+    "completed ASC, "
+  } else {
+    ""
+  };
+
+  let (filters, mut values) = list_todos_filters(placement, active_context, options)?;
+
+  let mut page = String::new();
+  if let Some((limit, offset)) = page_bounds(options)? {
+    values.push(Value::Integer(limit));
+    values.push(Value::Integer(offset));
+    page = format!(" LIMIT ?{} OFFSET ?{}", values.len() - 1, values.len());
+  }
+
+  let sql = format!(
+    "SELECT {TODO_COLUMNS},
+            COALESCE(counts.total, 0) AS subtask_count,
+            COALESCE(counts.done, 0) AS completed_subtask_count
+     FROM todos
+     LEFT JOIN (
+       SELECT todo_id, COUNT(*) AS total, SUM(completed) AS done FROM subtasks GROUP BY todo_id
+     ) AS counts ON counts.todo_id = todos.id
+     WHERE deleted_at IS NULL AND archived = 0{filters}
+     ORDER BY {completed_order}pinned DESC, {priority_order}sort_order ASC, created_at DESC{page}"
+  );
+
+  Ok((sql, values))
+}
+
+// The `AND ...` clauses `list_todos_query` adds after its fixed
+// `deleted_at IS NULL AND archived = 0`, with their numbered parameters.
+fn list_todos_filters(
+  placement: CompletedPlacement,
+  active_context: Option<&str>,
+  options: &ListOptions,
+) -> CommandResult<(String, Vec<Value>)> {
+  let mut filters = String::new();
+  let mut values = Vec::new();
+
+  match options.completed {
+    Some(completed) => {
+      values.push(Value::Integer(to_db_bool(completed)));
+      filters.push_str(&format!(" AND completed = ?{}", values.len()));
+    }
+    None if placement == CompletedPlacement::Hidden => filters.push_str(" AND completed = 0"),
+    None => {}
+  }
+
+  if let Some(created_after) = &options.created_after {
+    let cutoff = DateTime::parse_from_rfc3339(created_after.trim())
+      .map_err(|_| AppError::Validation(format!("Invalid createdAfter timestamp: {created_after}")))?
+      .with_timezone(&Utc)
+      .to_rfc3339();
+    values.push(Value::Text(cutoff));
+    filters.push_str(&format!(" AND created_at >= ?{}", values.len()));
+  }
+
+  if let Some(context) = active_context {
+    filters.push_str(&context_tag_clause(context, &mut values));
+  }
+
+  Ok((filters, values))
+}
+
+// Work mode's filter: todos tagged with the active context. Pushes the tag
+// onto `values` and numbers its placeholder to match.
+fn context_tag_clause(context: &str, values: &mut Vec<Value>) -> String {
+  values.push(Value::Text(context.to_string()));
+  format!(
+    " AND EXISTS (SELECT 1 FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id
+                  WHERE todo_tags.todo_id = todos.id AND tags.name = ?{})",
+    values.len()
+  )
+}
+
+// `context_tag_clause` for whatever context is active, or nothing.
+fn active_context_filter(conn: &Connection, values: &mut Vec<Value>) -> CommandResult<String> {
+  Ok(
+    get_meta(conn, ACTIVE_CONTEXT_KEY)?
+      .map(|context| context_tag_clause(&context, values))
+      .unwrap_or_default(),
+  )
+}
+
+// How many completed todos `list_todos` left out under `Hidden` placement for
+// the same options and active context; 0 for the other placements, or when
+// the options already pick a completion state.
+#[tauri::command]
+fn count_hidden_completed(state: State<'_, AppState>, options: Option<ListOptions>) -> CommandResult<i64> {
+  let conn = db_conn(&state)?;
+
+  let placement = get_ui_prefs_from_conn(&conn)?.completed_placement;
+  count_hidden_completed_in(&conn, placement, &options.unwrap_or_default())
+}
+
+fn count_hidden_completed_in(conn: &Connection, placement: CompletedPlacement, options: &ListOptions) -> CommandResult<i64> {
+  if placement != CompletedPlacement::Hidden || options.completed.is_some() {
+    return Ok(0);
+  }
+
+  let completed_only = ListOptions {
+    completed: Some(true),
+    ..options.clone()
+  };
+  let active_context = get_meta(conn, ACTIVE_CONTEXT_KEY)?;
+  let (filters, values) = list_todos_filters(placement, active_context.as_deref(), &completed_only)?;
+
+  conn
+    .query_row(
+      &format!("SELECT COUNT(*) FROM todos WHERE deleted_at IS NULL AND archived = 0{filters}"),
+      params_from_iter(values.iter()),
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn list_todos(
+  state: State<'_, AppState>,
+  sort_by_priority: Option<bool>,
+  options: Option<ListOptions>,
+  sort_mode: Option<SortMode>,
+) -> CommandResult<Vec<ListedTodo>> {
+  let conn = db_conn(&state)?;
+
+  // An explicit mode becomes the remembered one; otherwise reuse the last choice.
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  if let Some(mode) = sort_mode.filter(|mode| *mode != prefs.sort_mode) {
+    prefs.sort_mode = mode;
+    save_ui_prefs_to_conn(&conn, &prefs)?;
+  }
+
+  list_todos_from_conn(
+    &conn,
+    sort_by_priority.unwrap_or(false),
+    &options.unwrap_or_default(),
+    prefs.sort_mode,
+    prefs.completed_placement,
+  )
+}
+
+// Completed todos sink in every computed mode; pins still lead within each
+// half, and ties keep their manual order since the sort is stable.
+fn sort_listed_todos(todos: &mut [ListedTodo], mode: SortMode) {
+  let due_key = |todo: &Todo| todo.due_date.as_deref().and_then(due_instant);
+  let priority_rank = |todo: &Todo| match todo.priority {
+    TodoPriority::High => 0,
+    TodoPriority::Normal => 1,
+    TodoPriority::Low => 2,
+  };
+  // Undated todos go after dated ones.
+  let by_due = |a: &Todo, b: &Todo| {
+    let (a, b) = (due_key(a), due_key(b));
+    a.is_none().cmp(&b.is_none()).then_with(|| a.cmp(&b))
+  };
+  // Likewise, unestimated todos go after estimated ones.
+  let by_estimate = |a: &Todo, b: &Todo| {
+    let (a, b) = (a.estimate_minutes, b.estimate_minutes);
+    a.is_none().cmp(&b.is_none()).then_with(|| a.cmp(&b))
+  };
+
+  todos.sort_by(|a, b| {
+    let (a, b) = (&a.todo, &b.todo);
+    a.completed
+      .cmp(&b.completed)
+      .then_with(|| b.pinned.cmp(&a.pinned))
+      .then_with(|| match mode {
+        SortMode::Manual => std::cmp::Ordering::Equal,
+        SortMode::DueDateAsc => by_due(a, b),
+        SortMode::PriorityThenDue => priority_rank(a).cmp(&priority_rank(b)).then_with(|| by_due(a, b)),
+        SortMode::CreatedDesc => {
+          parse_iso_to_local_datetime(&b.created_at).cmp(&parse_iso_to_local_datetime(&a.created_at))
+        }
+        SortMode::PriorityDesc => priority_rank(a).cmp(&priority_rank(b)),
+        SortMode::EstimateAsc => by_estimate(a, b),
+      })
+  });
+}
+
+fn list_todos_from_conn(
+  conn: &Connection,
+  sort_by_priority: bool,
+  options: &ListOptions,
+  sort_mode: SortMode,
+  placement: CompletedPlacement,
+) -> CommandResult<Vec<ListedTodo>> {
+  // Computed modes must see every matching row before paging.
+  let page = page_bounds(options)?;
+  let unpaged;
+  let query_options = if sort_mode == SortMode::Manual {
+    options
+  } else {
+    unpaged = ListOptions {
+      limit: None,
+      offset: None,
+      ..options.clone()
+    };
+    &unpaged
+  };
+
+  let active_context = get_meta(conn, ACTIVE_CONTEXT_KEY)?;
+  let (sql, values) = list_todos_query(sort_by_priority, placement, active_context.as_deref(), query_options)?;
+  let mut statement = conn.prepare(&sql).map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), |row| {
+      Ok(ListedTodo {
+        todo: map_todo_row(row)?,
+        subtask_count: row.get("subtask_count")?,
+        completed_subtask_count: row.get("completed_subtask_count")?,
+        tags: Vec::new(),
+      })
+    })
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  if sort_mode != SortMode::Manual {
+    sort_listed_todos(&mut todos, sort_mode);
+    if let Some((limit, offset)) = page {
+      let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+      todos = todos.into_iter().skip(offset as usize).take(limit).collect();
+    }
+  }
+
+  let mut tags_by_todo = load_tags_by_todo(conn)?;
+  for listed in &mut todos {
+    listed.tags = tags_by_todo.remove(&listed.todo.id).unwrap_or_default();
+  }
+
+  Ok(todos)
+}
+
+// A missing (or trashed) id comes back as null so a window can drop a todo
+// that was deleted elsewhere without treating it as a failure.
+#[tauri::command]
+fn get_todo(state: State<'_, AppState>, id: String) -> CommandResult<Option<Todo>> {
+  let conn = db_conn(&state)?;
+
+  get_todo_by_id(&conn, &id, false)
+}
+
+#[tauri::command]
+fn list_todos_with_order(state: State<'_, AppState>) -> CommandResult<Vec<TodoWithOrder>> {
+  let conn = db_conn(&state)?;
+
+  Ok(
+    visible_todos_in_order(&conn)?
+      .into_iter()
+      .map(|todo| TodoWithOrder {
+        sort_order: todo.sort_order,
+        todo,
+      })
+      .collect(),
+  )
+}
+
+// Undated todos always sort last; ties keep the manual sort order.
+#[tauri::command]
+fn list_todos_by_due_date(state: State<'_, AppState>, ascending: bool, completed_last: bool) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  list_todos_by_due_date_in(&conn, ascending, completed_last)
+}
+
+fn list_todos_by_due_date_in(conn: &Connection, ascending: bool, completed_last: bool) -> CommandResult<Vec<Todo>> {
+  let mut values = Vec::new();
+  let context_filter = active_context_filter(conn, &mut values)?;
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0{context_filter} ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut keyed = Vec::new();
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    let due = todo.due_date.as_deref().and_then(due_instant);
+    keyed.push((due, todo));
+  }
+
+  keyed.sort_by(|(a_due, a), (b_due, b)| {
+    let completed_order = if completed_last {
+      a.completed.cmp(&b.completed)
+    } else {
+      std::cmp::Ordering::Equal
+    };
+    let due_order = match (a_due, b_due) {
+      (Some(a_due), Some(b_due)) if ascending => a_due.cmp(b_due),
+      (Some(a_due), Some(b_due)) => b_due.cmp(a_due),
+      (Some(_), None) => std::cmp::Ordering::Less,
+      (None, Some(_)) => std::cmp::Ordering::Greater,
+      (None, None) => std::cmp::Ordering::Equal,
+    };
+    completed_order.then(due_order)
+  });
+
+  Ok(keyed.into_iter().map(|(_, todo)| todo).collect())
+}
+
+#[tauri::command]
+fn list_overdue_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  open_overdue_todos(&conn, &Local)
+}
+
+// Open todos due before today in `tz`, oldest first. Unparseable due dates
+// never count as overdue.
+fn open_overdue_todos<Tz: TimeZone>(conn: &Connection, tz: &Tz) -> CommandResult<Vec<Todo>> {
+  let today = Utc::now().with_timezone(tz).date_naive();
+  let mut overdue: Vec<(NaiveDate, Todo)> = list_open_dated_todos(conn)?
+    .into_iter()
+    .filter_map(|todo| {
+      let due_day = parse_due_day(todo.due_date.as_deref()?, tz)?;
+      (due_day < today).then_some((due_day, todo))
+    })
+    .collect();
+  overdue.sort_by_key(|(due_day, _)| *due_day);
+
+  Ok(overdue.into_iter().map(|(_, todo)| todo).collect())
+}
+
+// The week containing today, starting on the configured first day. Overdue
+// todos from earlier in the week are included, ordered by due day.
+#[tauri::command]
+fn list_todos_due_this_week(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let first_day = get_ui_prefs_from_conn(&conn)?.first_day_of_week;
+  let start = week_start(local_today_naive(), first_day);
+  let end = start + Duration::days(6);
+  let mut due: Vec<(NaiveDate, Todo)> = list_open_dated_todos(&conn)?
+    .into_iter()
+    .filter_map(|todo| {
+      let due_day = parse_due_day(todo.due_date.as_deref()?, &Local)?;
+      (start <= due_day && due_day <= end).then_some((due_day, todo))
+    })
+    .collect();
+  due.sort_by_key(|(due_day, _)| *due_day);
+
+  Ok(due.into_iter().map(|(_, todo)| todo).collect())
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum CountFilter {
+  All,
+  Incomplete,
+  Completed,
+  Overdue,
+  DueToday,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum BulkOperation {
+  Delete,
+  Complete,
+  Archive,
+}
+
+// Badge counts without serializing rows. Due dates are free-form text, so the
+// date-based filters only pull `due_date` and classify it with `parse_due_day`
+// exactly like `list_overdue_todos` and `list_due_today_todos` do.
+#[tauri::command]
+fn count_todos(state: State<'_, AppState>, filter: CountFilter) -> CommandResult<i64> {
+  let conn = db_conn(&state)?;
+
+  count_todos_in(&conn, filter, local_today_naive())
+}
+
+fn count_todos_in(conn: &Connection, filter: CountFilter, today: NaiveDate) -> CommandResult<i64> {
+  match filter {
+    CountFilter::All => count_listed_todos(conn, ""),
+    CountFilter::Incomplete => count_listed_todos(conn, " AND completed = 0"),
+    CountFilter::Completed => count_listed_todos(conn, " AND completed = 1"),
+    CountFilter::Overdue => count_open_by_due_day(conn, |due_day| due_day < today),
+    CountFilter::DueToday => count_open_by_due_day(conn, |due_day| due_day == today),
+  }
+}
+
+fn count_listed_todos(conn: &Connection, extra_filter: &str) -> CommandResult<i64> {
+  let mut values = Vec::new();
+  let context_filter = active_context_filter(conn, &mut values)?;
+  conn
+    .query_row(
+      &format!("SELECT COUNT(*) FROM todos WHERE deleted_at IS NULL AND archived = 0{extra_filter}{context_filter}"),
+      params_from_iter(values.iter()),
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)
+}
+
+fn count_open_by_due_day(conn: &Connection, matches: impl Fn(NaiveDate) -> bool) -> CommandResult<i64> {
+  let mut values = Vec::new();
+  let context_filter = active_context_filter(conn, &mut values)?;
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT due_date FROM todos
+       WHERE completed = 0 AND deleted_at IS NULL AND archived = 0 AND due_date IS NOT NULL{context_filter}"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut count = 0;
+  for row in rows {
+    let due_date = row.map_err(AppError::from)?;
+    if parse_due_day(&due_date, &Local).is_some_and(&matches) {
+      count += 1;
+    }
+  }
+
+  Ok(count)
+}
+
+// `tz_offset_minutes` is minutes east of UTC, i.e. the negation of JavaScript's
+// `Date#getTimezoneOffset()`.
+#[tauri::command]
+fn list_due_today_todos(state: State<'_, AppState>, tz_offset_minutes: i32) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let offset = FixedOffset::east_opt(tz_offset_minutes.saturating_mul(60))
+    .ok_or_else(|| AppError::Validation(format!("Invalid timezone offset: {tz_offset_minutes} minutes")))?;
+  let today = Utc::now().with_timezone(&offset).date_naive();
+
+  Ok(
+    list_open_dated_todos(&conn)?
+      .into_iter()
+      .filter(|todo| {
+        todo
+          .due_date
+          .as_deref()
+          .and_then(|value| parse_due_day(value, &offset))
+          .is_some_and(|due_day| due_day == today)
+      })
+      .collect(),
+  )
+}
+
+// Open, undated todos untouched for more than `inactive_days`, oldest first.
+// Rows whose `updated_at` doesn't parse are left out rather than guessed at.
+#[tauri::command]
+fn list_stale_todos(state: State<'_, AppState>, inactive_days: i64) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let cutoff = Duration::try_days(inactive_days)
+    .filter(|_| inactive_days >= 0)
+    .and_then(|age| Utc::now().checked_sub_signed(age))
+    .ok_or_else(|| AppError::Validation(format!("Invalid inactivity in days: {inactive_days}")))?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE deleted_at IS NULL AND archived = 0 AND completed = 0 AND due_date IS NULL"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut stale = Vec::new();
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    if let Ok(updated_at) = DateTime::parse_from_rfc3339(&todo.updated_at) {
+      if updated_at < cutoff {
+        stale.push((updated_at, todo));
+      }
+    }
+  }
+  stale.sort_by(|left, right| left.0.cmp(&right.0).then_with(|| left.1.id.cmp(&right.1.id)));
+
+  Ok(stale.into_iter().map(|(_, todo)| todo).collect())
+}
+
+// Todos have no lists yet, so "uncategorized" means open, unarchived, undated,
+// untagged and carrying neither a recurrence nor an energy level. Work mode
+// only shows todos tagged with its context, so it leaves the inbox empty.
+#[tauri::command]
+fn list_inbox_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  list_inbox_todos_in(&conn)
+}
+
+fn list_inbox_todos_in(conn: &Connection) -> CommandResult<Vec<Todo>> {
+  let mut values = vec![Value::Text(RECURRENCE_NONE.to_string())];
+  let context_filter = active_context_filter(conn, &mut values)?;
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE deleted_at IS NULL
+         AND archived = 0
+         AND completed = 0
+         AND due_date IS NULL
+         AND recurrence_tag = ?1
+         AND energy IS NULL
+         AND id NOT IN (SELECT todo_id FROM todo_tags){context_filter}
+       ORDER BY created_at ASC, id ASC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+// Passing "none" (or any unrecognized level) lists todos without an energy tag.
+#[tauri::command]
+fn list_todos_by_energy(state: State<'_, AppState>, level: String) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  list_todos_by_energy_in(&conn, level)
+}
+
+fn list_todos_by_energy_in(conn: &Connection, level: String) -> CommandResult<Vec<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0 AND energy IS ?1 ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![normalize_energy(Some(level))], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+#[tauri::command]
+fn set_context(state: State<'_, AppState>, app: AppHandle, id: String, context: String) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  target.context = normalize_context(Some(context));
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET context = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, &target.context, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+// Pinning only flips the flag; `sort_order` is untouched so an unpinned todo
+// drops back to where it was.
+#[tauri::command]
+fn set_pinned(state: State<'_, AppState>, app: AppHandle, id: String, pinned: bool) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  target.pinned = pinned;
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET pinned = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, to_db_bool(pinned), &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  emit_todos_changed(&app, "updated", Some(&target.id));
+  Ok(target)
+}
+
+// Collapsing is view state, not an edit, so `updated_at` is left alone.
+#[tauri::command]
+fn set_note_collapsed(state: State<'_, AppState>, app: AppHandle, id: String, collapsed: bool) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  target.note_collapsed = collapsed;
+
+  tx
+    .execute(
+      "UPDATE todos SET note_collapsed = ?2 WHERE id = ?1",
+      params![&target.id, to_db_bool(collapsed)],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  emit_todos_changed(&app, "updated", Some(&target.id));
+  Ok(target)
+}
+
+// The focused todo is a single id in `app_meta`; `None` clears it.
+#[tauri::command]
+fn set_focus_todo(state: State<'_, AppState>, id: Option<String>) -> CommandResult<Option<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let Some(id) = id else {
+    delete_meta(&conn, FOCUS_TODO_KEY)?;
+    return Ok(None);
+  };
+  let target = get_todo_by_id(&conn, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  set_meta(&conn, FOCUS_TODO_KEY, &target.id)?;
+
+  Ok(Some(target))
+}
+
+// Forgets the focus once its todo has been deleted or trashed.
+#[tauri::command]
+fn get_focus_todo(state: State<'_, AppState>) -> CommandResult<Option<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let Some(id) = get_meta(&conn, FOCUS_TODO_KEY)? else {
+    return Ok(None);
+  };
+  let target = get_todo_by_id(&conn, &id, false)?;
+  if target.is_none() {
+    delete_meta(&conn, FOCUS_TODO_KEY)?;
+  }
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn list_contexts(state: State<'_, AppState>) -> CommandResult<Vec<String>> {
+  let conn = db_conn(&state)?;
+
+  list_contexts_in(&conn)
+}
+
+fn list_contexts_in(conn: &Connection) -> CommandResult<Vec<String>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT DISTINCT context FROM todos
+       WHERE deleted_at IS NULL AND context IS NOT NULL
+       ORDER BY context COLLATE NOCASE ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut contexts = Vec::new();
+  for row in rows {
+    contexts.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(contexts)
+}
+
+// Work mode: while a context is active, only todos tagged with it show up in
+// `list_todos` (and the HTTP snapshot), `count_todos`, `count_hidden_completed`,
+// `list_todos_by_due_date`, `search_todos` and `list_inbox_todos`. The context
+// is a tag name, normalized like `set_todo_tags`; `None` or a blank one clears
+// it.
+#[tauri::command]
+fn set_active_context(state: State<'_, AppState>, app: AppHandle, context: Option<String>) -> CommandResult<Option<String>> {
+  let conn = db_conn(&state)?;
+
+  let context = normalize_tags(context.into_iter().collect()).pop();
+  match &context {
+    Some(context) => set_meta(&conn, ACTIVE_CONTEXT_KEY, context)?,
+    None => delete_meta(&conn, ACTIVE_CONTEXT_KEY)?,
+  }
+  emit_todos_changed(&app, "updated", None);
+
+  Ok(context)
+}
+
+#[tauri::command]
+fn get_active_context(state: State<'_, AppState>) -> CommandResult<Option<String>> {
+  let conn = db_conn(&state)?;
+
+  get_meta(&conn, ACTIVE_CONTEXT_KEY)
+}
+
+// A blank context lists todos that have none.
+#[tauri::command]
+fn list_todos_by_context(state: State<'_, AppState>, context: String) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  list_todos_by_context_in(&conn, context)
+}
+
+fn list_todos_by_context_in(conn: &Connection, context: String) -> CommandResult<Vec<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0 AND context IS ?1 ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![normalize_context(Some(context))], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+// An empty tag list means "no filter" and returns every todo.
+#[tauri::command]
+fn list_todos_by_recurrence_tags(state: State<'_, AppState>, tags: Vec<String>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  list_todos_by_recurrence_tags_in(&conn, tags)
+}
+
+fn list_todos_by_recurrence_tags_in(conn: &Connection, tags: Vec<String>) -> CommandResult<Vec<Todo>> {
+  let mut normalized: Vec<String> = tags
+    .into_iter()
+    .map(|tag| normalize_recurrence_tag(Some(tag)))
+    .collect();
+  normalized.sort();
+  normalized.dedup();
+
+  let filter = if normalized.is_empty() {
+    String::new()
+  } else {
+    let placeholders = vec!["?"; normalized.len()].join(", ");
+    format!("AND recurrence_tag IN ({placeholders})")
+  };
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL {filter} ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(normalized.iter()), map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+#[tauri::command]
+fn retag_recurrence(state: State<'_, AppState>, app: AppHandle, from: String, to: String) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  // Unknown tags normalize to "none", so only an exact match counts as valid here.
+  let to_tag = normalize_recurrence_tag(Some(to.clone()));
+  if to_tag != to.trim() {
+    return Err(AppError::Validation(format!("Unsupported recurrence: {to}")));
+  }
+  let from_tag = normalize_recurrence_tag(Some(from));
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let changed = tx
+    .execute(
+      "UPDATE todos SET recurrence_tag = ?2, updated_at = ?3 WHERE recurrence_tag = ?1 AND recurrence_tag <> ?2",
+      params![from_tag, to_tag, now_iso()],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  if changed > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(changed)
+}
+
+// Keyed by recurrence tag, with an entry (possibly empty) for every tag in
+// `RECURRENCE_TAGS`, so the frontend sees a stable shape.
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct GroupedTodos(BTreeMap<String, Vec<Todo>>);
+
+#[tauri::command]
+fn list_todos_grouped_by_recurrence(state: State<'_, AppState>) -> CommandResult<GroupedTodos> {
+  let conn = db_conn(&state)?;
+
+  let mut groups: BTreeMap<String, Vec<Todo>> = RECURRENCE_TAGS
+    .iter()
+    .map(|tag| (tag.to_string(), Vec::new()))
+    .collect();
+
+  for todo in visible_todos_in_order(&conn)? {
+    let tag = normalize_recurrence_tag(Some(todo.recurrence_tag.clone()));
+    groups.entry(tag).or_default().push(todo);
+  }
+
+  Ok(GroupedTodos(groups))
+}
+
+// Literal replacement that matches `find` ignoring case, char by char, so
+// multi-byte letters are compared without shifting byte offsets.
+fn replace_ignoring_case(haystack: &str, find: &str, replace: &str) -> String {
+  let mut output = String::with_capacity(haystack.len());
+  let mut rest = haystack;
+
+  while let Some(first) = rest.chars().next() {
+    let mut matched = 0;
+    let mut needle = find.chars();
+    for (offset, ch) in rest.char_indices() {
+      match needle.next() {
+        Some(expected) if ch.to_lowercase().eq(expected.to_lowercase()) => matched = offset + ch.len_utf8(),
+        Some(_) => {
+          matched = 0;
+          break;
+        }
+        None => break,
+      }
+    }
+    if needle.next().is_some() {
+      matched = 0;
+    }
+
+    if matched > 0 {
+      output.push_str(replace);
+      rest = &rest[matched..];
+    } else {
+      output.push(first);
+      rest = &rest[first.len_utf8()..];
+    }
+  }
+
+  output
+}
+
+// Trashed todos are left alone; only notes that actually change get a new
+// `updated_at`.
+#[tauri::command]
+fn replace_in_notes(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  find: String,
+  replace: String,
+  case_sensitive: bool,
+) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  if find.is_empty() {
+    return Err(AppError::Validation("Search text cannot be empty".to_string()));
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let notes: Vec<(String, String)> = {
+    let mut statement = tx
+      .prepare("SELECT id, note FROM todos WHERE deleted_at IS NULL AND note <> ''")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(AppError::from)?;
+
+    let mut notes = Vec::new();
+    for row in rows {
+      notes.push(row.map_err(AppError::from)?);
+    }
+    notes
+  };
+
+  let now = now_iso();
+  let mut changed = 0;
+  for (id, note) in notes {
+    let updated = if case_sensitive {
+      note.replace(&find, &replace)
+    } else {
+      replace_ignoring_case(&note, &find, &replace)
+    };
+    if updated == note {
+      continue;
+    }
+
+    tx
+      .execute(
+        "UPDATE todos SET note = ?2, updated_at = ?3 WHERE id = ?1",
+        params![&id, &updated, &now],
+      )
+      .map_err(AppError::from)?;
+    changed += 1;
+  }
+
+  tx.commit().map_err(AppError::from)?;
+  if changed > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(changed)
+}
+
+#[tauri::command]
+fn search_todos(state: State<'_, AppState>, query: String) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  search_todos_in(&conn, &query)
+}
+
+fn search_todos_in(conn: &Connection, query: &str) -> CommandResult<Vec<Todo>> {
+  let trimmed = query.trim();
+  if trimmed.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let has_index: bool = conn
+    .query_row(
+      "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'todos_fts')",
+      [],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+
+  // The trigram tokenizer cannot match terms shorter than three characters.
+  let use_index = has_index && trimmed.chars().count() >= 3;
+  let (filter, pattern) = if use_index {
+    (
+      "id IN (SELECT todo_id FROM todos_fts WHERE todos_fts MATCH ?1)",
+      format!("\"{}\"", trimmed.replace('"', "\"\"")),
+    )
+  } else {
+    let escaped = trimmed
+      .replace('\\', "\\\\")
+      .replace('%', "\\%")
+      .replace('_', "\\_");
+    (
+      "(title LIKE ?1 ESCAPE '\\' OR note LIKE ?1 ESCAPE '\\')",
+      format!("%{escaped}%"),
+    )
+  };
+
+  let mut values = vec![Value::Text(pattern), Value::Text(trimmed.to_string())];
+  let context_filter = active_context_filter(conn, &mut values)?;
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE deleted_at IS NULL AND archived = 0 AND {filter}{context_filter}
+       ORDER BY CASE WHEN instr(lower(title), lower(?2)) > 0 THEN 0 ELSE 1 END, sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+// Groups open todos whose titles score at least `threshold`; a todo similar
+// to any member of a group joins that group.
+#[tauri::command]
+fn find_similar_titles(state: State<'_, AppState>, threshold: f64) -> CommandResult<Vec<Vec<Todo>>> {
+  let conn = db_conn(&state)?;
+
+  find_similar_titles_in(&conn, threshold)
+}
+
+fn find_similar_titles_in(conn: &Connection, threshold: f64) -> CommandResult<Vec<Vec<Todo>>> {
+  if !(threshold > 0.0 && threshold <= 1.0) {
+    return Err(AppError::Validation(format!("Similarity threshold must be in (0, 1]: {threshold}")));
+  }
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE completed = 0 AND deleted_at IS NULL AND archived = 0 ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  let trigrams: Vec<_> = todos.iter().map(|todo| title_trigrams(&todo.title)).collect();
+  let mut parent: Vec<usize> = (0..todos.len()).collect();
+
+  for i in 0..todos.len() {
+    for j in (i + 1)..todos.len() {
+      if title_similarity(&trigrams[i], &trigrams[j]) >= threshold {
+        let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, j));
+        parent[b.max(a)] = a.min(b);
+      }
+    }
+  }
+
+  let mut groups: Vec<Vec<Todo>> = Vec::new();
+  let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+  for (index, todo) in todos.into_iter().enumerate() {
+    let group_root = find_root(&mut parent, index);
+    let slot = *group_of_root.entry(group_root).or_insert_with(|| {
+      groups.push(Vec::new());
+      groups.len() - 1
+    });
+    groups[slot].push(todo);
+  }
+  groups.retain(|group| group.len() > 1);
+
+  Ok(groups)
+}
+
+#[tauri::command]
+fn list_todos_with_links(state: State<'_, AppState>) -> CommandResult<Vec<TodoLinks>> {
+  let conn = db_conn(&state)?;
+
+  todos_with_links_in(&conn)
+}
+
+fn todos_with_links_in(conn: &Connection) -> CommandResult<Vec<TodoLinks>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0 AND note LIKE '%http%' ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut output = Vec::new();
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    let urls = extract_urls(&todo.note);
+    if !urls.is_empty() {
+      output.push(TodoLinks { todo, urls });
+    }
+  }
+
+  Ok(output)
+}
+
+fn check_length(field: &str, value: &str, max_chars: usize) -> CommandResult<()> {
+  if value.chars().count() > max_chars {
+    return Err(AppError::Validation(format!(
+      "{field} cannot be longer than {max_chars} characters"
+    )));
+  }
+
+  Ok(())
+}
+
+fn default_note_collapsed(note: &str) -> bool {
+  note.chars().count() > NOTE_COLLAPSE_THRESHOLD_CHARS
+}
+
+// Trims and collapses internal whitespace runs (including newlines) to single
+// spaces, so "buy   milk" and "buy milk" are the same title.
+fn normalize_title(title: &str) -> String {
+  title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Case-insensitive on the normalized title; completed, archived and trashed
+// todos don't count. Compared in Rust because SQLite's NOCASE only folds ASCII.
+fn find_open_todo_with_title(conn: &Connection, title: &str) -> CommandResult<Option<String>> {
+  let wanted = normalize_title(title).to_lowercase();
+
+  let mut statement = conn
+    .prepare(
+      "SELECT id, title FROM todos
+       WHERE completed = 0 AND archived = 0 AND deleted_at IS NULL
+       ORDER BY sort_order ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+    .map_err(AppError::from)?;
+
+  for row in rows {
+    let (id, existing) = row.map_err(AppError::from)?;
+    if normalize_title(&existing).to_lowercase() == wanted {
+      return Ok(Some(id));
+    }
+  }
+
+  Ok(None)
+}
+
+// Validates `input` and builds the todo that goes above everything else.
+fn new_todo_from_input(conn: &Connection, input: CreateTodoInput) -> CommandResult<Todo> {
+  let title = normalize_title(&input.title);
+  if title.is_empty() {
+    return Err(AppError::Validation("Title cannot be empty".to_string()));
+  }
+  check_length("Title", &title, MAX_TITLE_CHARS)?;
+  if let Some(note) = &input.note {
+    check_length("Note", note, MAX_NOTE_CHARS)?;
+  }
+
+  if input.reject_duplicates {
+    if let Some(existing_id) = find_open_todo_with_title(conn, &title)? {
+      return Err(AppError::Duplicate {
+        message: format!("An open todo titled \"{title}\" already exists"),
+        existing_id,
+      });
+    }
+  }
+
+  let sort_order: i64 = conn
+    .query_row(
+      "SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos",
+      [],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let note = input.note.unwrap_or_default();
+  let note_collapsed = input.note_collapsed.unwrap_or_else(|| default_note_collapsed(&note));
+  let todo = Todo {
+    id: Uuid::new_v4().to_string(),
+    title,
+    recurrence_tag: normalize_recurrence_tag(input.recurrence_tag),
+    recurrence_checked_at: None,
+    note,
+    completed: false,
+    due_date: normalize_date(input.due_date)?,
+    created_at: now.clone(),
+    updated_at: now,
+    reminder_enabled: true,
+    last_reminded_on: None,
+    sort_order,
+    completed_at: None,
+    series_id: None,
+    recurrence_until: None,
+    note_format: normalize_note_format(input.note_format),
+    rolled_at: None,
+    priority: input.priority.unwrap_or_default(),
+    energy: normalize_energy(input.energy),
+    deleted_at: None,
+    recurrence_max_count: None,
+    recurrence_completed_count: 0,
+    notified_at: None,
+    remind_after: None,
+    context: normalize_context(input.context),
+    snooze_count: 0,
+    color: normalize_color(input.color)?,
+    archived: false,
+    pinned: false,
+    reminder_offset_minutes: normalize_reminder_offset(input.reminder_offset_minutes)?,
+    estimate_minutes: normalize_estimate_minutes(input.estimate_minutes)?,
+    note_collapsed,
+  };
+
+  Ok(todo)
+}
+
+#[tauri::command]
+fn create_todo(state: State<'_, AppState>, app: AppHandle, input: CreateTodoInput) -> CommandResult<Todo> {
+  let conn = db_conn(&state)?;
+
+  let todo = new_todo_from_input(&conn, input)?;
+  insert_todo(&conn, &todo)?;
+  emit_todos_changed(&app, "created", Some(&todo.id));
+
+  Ok(todo)
+}
+
+// `create_todo` plus where the new row landed in the default `list_todos`
+// ordering (the remembered sort mode, no filters), for insert animations.
+// The position is read in the inserting transaction, so no concurrent write
+// can shift it. `None` means the list hides the todo, e.g. under another
+// active context.
+#[tauri::command]
+fn create_todo_with_position(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  input: CreateTodoInput,
+) -> CommandResult<CreatedTodo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let todo = new_todo_from_input(&tx, input)?;
+  insert_todo(&tx, &todo)?;
+
+  let prefs = get_ui_prefs_from_conn(&tx)?;
+  let listed = list_todos_from_conn(
+    &tx,
+    false,
+    &ListOptions::default(),
+    prefs.sort_mode,
+    prefs.completed_placement,
+  )?;
+  let inserted_index = listed.iter().position(|entry| entry.todo.id == todo.id);
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "created", Some(&todo.id));
+
+  Ok(CreatedTodo { todo, inserted_index })
+}
+
+// The copy starts open and outside any recurring series, and is slotted in
+// directly after the original.
+#[tauri::command]
+fn duplicate_todo(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let original = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+
+  let neighbors = |conn: &Connection| -> CommandResult<(Option<i64>, Option<i64>)> {
+    let after = sort_order_of(conn, &original.id)?;
+    let before = conn
+      .query_row(
+        "SELECT MIN(sort_order) FROM todos WHERE sort_order > ?1",
+        params![after],
+        |row| row.get(0),
+      )
+      .map_err(AppError::from)?;
+    Ok((Some(after), before))
+  };
+
+  let (after, before) = neighbors(&tx)?;
+  let sort_order = match sort_order_between(after, before) {
+    Some(value) => value,
+    None => {
+      rebalance_sort_orders_in(&tx)?;
+      let (after, before) = neighbors(&tx)?;
+      sort_order_between(after, before)
+        .ok_or_else(|| AppError::Validation("No room to place the copy after the original".to_string()))?
+    }
+  };
+
+  let now = now_iso();
+  let todo = Todo {
+    id: Uuid::new_v4().to_string(),
+    title: format!("{} (copy)", original.title),
+    recurrence_tag: original.recurrence_tag.clone(),
+    recurrence_checked_at: None,
+    note: original.note.clone(),
+    completed: false,
+    due_date: original.due_date.clone(),
+    created_at: now.clone(),
+    updated_at: now,
+    reminder_enabled: original.reminder_enabled,
+    last_reminded_on: None,
+    sort_order,
+    completed_at: None,
+    series_id: None,
+    recurrence_until: None,
+    note_format: original.note_format.clone(),
+    rolled_at: None,
+    priority: original.priority,
+    energy: original.energy.clone(),
+    deleted_at: None,
+    recurrence_max_count: None,
+    recurrence_completed_count: 0,
+    notified_at: None,
+    remind_after: None,
+    context: original.context.clone(),
+    snooze_count: 0,
+    color: original.color.clone(),
+    archived: false,
+    pinned: false,
+    reminder_offset_minutes: original.reminder_offset_minutes,
+    estimate_minutes: original.estimate_minutes,
+    note_collapsed: original.note_collapsed,
+  };
+
+  insert_todo(&tx, &todo)?;
+  tx
+    .execute(
+      "INSERT INTO todo_tags (todo_id, tag_id) SELECT ?1, tag_id FROM todo_tags WHERE todo_id = ?2",
+      params![&todo.id, &original.id],
+    )
+    .map_err(AppError::from)?;
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "created", Some(&todo.id));
+
+  Ok(todo)
+}
+
+const MERGED_NOTE_SEPARATOR: &str = "\n\n---\n\n";
+
+// Folds `merge_id` into `keep_id`: notes are joined, tags unioned, subtasks
+// (appended after the kept ones) and attachments moved over, and the merged
+// todo goes to the trash.
+#[tauri::command]
+fn merge_todos(state: State<'_, AppState>, app: AppHandle, keep_id: String, merge_id: String) -> CommandResult<Todo> {
+  if keep_id == merge_id {
+    return Err(AppError::Validation("Cannot merge a todo into itself".to_string()));
+  }
+
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut kept = get_todo_by_id(&tx, &keep_id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {keep_id}")))?;
+  let merged = get_todo_by_id(&tx, &merge_id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {merge_id}")))?;
+  let now = now_iso();
+
+  if kept.note.trim().is_empty() {
+    kept.note = merged.note.clone();
+  } else if !merged.note.trim().is_empty() {
+    kept.note = format!("{}{MERGED_NOTE_SEPARATOR}{}", kept.note, merged.note);
+  }
+  if parse_iso_to_local_datetime(&merged.created_at) < parse_iso_to_local_datetime(&kept.created_at) {
+    kept.created_at = merged.created_at.clone();
+  }
+  kept.updated_at = now.clone();
+  update_todo_row(&tx, &kept)?;
+
+  absorb_todo_children(&tx, &keep_id, &merge_id, &now)?;
+  tx
+    .execute(
+      "UPDATE todos SET deleted_at = ?2, updated_at = ?2 WHERE id = ?1",
+      params![&merge_id, &now],
+    )
+    .map_err(AppError::from)?;
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&keep_id));
+  emit_todos_changed(&app, "deleted", Some(&merge_id));
+
+  Ok(kept)
+}
+
+// Moves tags, subtasks and attachments from `merge_id` onto `keep_id`;
+// subtasks land after the kept todo's own.
+fn absorb_todo_children(conn: &Connection, keep_id: &str, merge_id: &str, now: &str) -> CommandResult<()> {
+  conn
+    .execute(
+      "INSERT OR IGNORE INTO todo_tags (todo_id, tag_id) SELECT ?1, tag_id FROM todo_tags WHERE todo_id = ?2",
+      params![keep_id, merge_id],
+    )
+    .map_err(AppError::from)?;
+  let subtask_offset: i64 = conn
+    .query_row(
+      "SELECT COALESCE(MAX(sort_order), -1) + 1 FROM subtasks WHERE todo_id = ?1",
+      params![keep_id],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+  conn
+    .execute(
+      "UPDATE subtasks SET todo_id = ?1, sort_order = sort_order + ?3, updated_at = ?4 WHERE todo_id = ?2",
+      params![keep_id, merge_id, subtask_offset, now],
+    )
+    .map_err(AppError::from)?;
+  conn
+    .execute(
+      "UPDATE todo_attachments SET todo_id = ?1 WHERE todo_id = ?2",
+      params![keep_id, merge_id],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Keeps the earliest-created todo of each duplicate group. Titles are grouped
+// after `normalize_title`, so stray whitespace from an import doesn't hide a
+// duplicate. Duplicates' tags, subtasks and attachments move to the survivor,
+// and any note that differs from the survivor's is appended to it.
+#[tauri::command]
+fn deduplicate_todos(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  by: DedupeKey,
+  hard_delete: Option<bool>,
+) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let removed = deduplicate_todos_in(&tx, by, hard_delete.unwrap_or(false))?;
+  tx.commit().map_err(AppError::from)?;
+
+  if removed > 0 {
+    emit_todos_changed(&app, "deleted", None);
+  }
+
+  Ok(removed)
+}
+
+fn deduplicate_todos_in(conn: &Connection, by: DedupeKey, hard_delete: bool) -> CommandResult<usize> {
+  let todos = {
+    let mut statement = conn
+      .prepare(&format!(
+        "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL ORDER BY created_at ASC, rowid ASC"
+      ))
+      .map_err(AppError::from)?;
+    let rows = statement.query_map([], map_todo_row).map_err(AppError::from)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)?
+  };
+
+  let mut survivors: HashMap<(String, Option<String>), Todo> = HashMap::new();
+  let mut touched_ids = HashSet::new();
+  let mut removed_ids = Vec::new();
+  let now = now_iso();
+  for todo in todos {
+    let key = match by {
+      DedupeKey::TitleOnly => (normalize_title(&todo.title), None),
+      DedupeKey::TitleAndDueDate => (normalize_title(&todo.title), todo.due_date.clone()),
+    };
+    let Some(kept) = survivors.get_mut(&key) else {
+      survivors.insert(key, todo);
+      continue;
+    };
+
+    if kept.note.trim().is_empty() {
+      kept.note = todo.note.clone();
+    } else if !todo.note.trim().is_empty() && todo.note != kept.note {
+      kept.note = format!("{}{MERGED_NOTE_SEPARATOR}{}", kept.note, todo.note);
+    }
+    kept.updated_at = now.clone();
+    absorb_todo_children(conn, &kept.id, &todo.id, &now)?;
+    touched_ids.insert(kept.id.clone());
+    removed_ids.push(todo.id);
+  }
+
+  if removed_ids.is_empty() {
+    return Ok(0);
+  }
+
+  for kept in survivors.values().filter(|kept| touched_ids.contains(&kept.id)) {
+    update_todo_row(conn, kept)?;
+  }
+  for id in &removed_ids {
+    if hard_delete {
+      conn.execute("DELETE FROM todos WHERE id = ?1", params![id]).map_err(AppError::from)?;
+    } else {
+      conn
+        .execute(
+          "UPDATE todos SET deleted_at = ?2, updated_at = ?2 WHERE id = ?1",
+          params![id, &now],
+        )
+        .map_err(AppError::from)?;
+    }
+  }
+
+  Ok(removed_ids.len())
+}
+
+#[tauri::command]
+fn update_todo(state: State<'_, AppState>, app: AppHandle, input: UpdateTodoInput) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let updated = update_todo_in(&tx, input)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&updated.id));
+
+  Ok(updated)
+}
+
+/// Applies `input` to the stored row. Callers run this inside their write
+/// transaction so the read and the write see the same row.
+fn update_todo_in(conn: &Connection, input: UpdateTodoInput) -> CommandResult<Todo> {
+  let existing = get_todo_by_id(conn, &input.id, false)?
+    .ok_or_else(|| AppError::NotFound(format!("Todo not found: {}", input.id)))?;
+
+  let mut updated = existing;
+  let mut completion_changed = false;
+
+  if let Some(title) = input.title {
+    let title = normalize_title(&title);
+    if title.is_empty() {
+      return Err(AppError::Validation("Title cannot be empty".to_string()));
+    }
+    check_length("Title", &title, MAX_TITLE_CHARS)?;
+    updated.title = title;
+  }
+
+  if let Some(recurrence_tag) = input.recurrence_tag {
+    updated.recurrence_tag = normalize_recurrence_tag(Some(recurrence_tag));
+  }
+
+  if let Some(note) = input.note {
+    check_length("Note", &note, MAX_NOTE_CHARS)?;
+    updated.note = note;
+  }
+
+  if let Some(completed) = input.completed {
+    if completed != updated.completed {
+      updated.completed_at = if completed { Some(now_iso()) } else { None };
+      updated.completed = completed;
+      count_recurring_completion(&mut updated);
+      completion_changed = true;
+    }
+  }
+
+  if let Some(due_date) = input.due_date {
+    let due_date = normalize_date(due_date)?;
+    if due_date != updated.due_date {
+      updated.notified_at = None;
+    }
+    updated.due_date = due_date;
+  }
+
+  if let Some(reminder_enabled) = input.reminder_enabled {
+    updated.reminder_enabled = reminder_enabled;
+  }
+
+  if let Some(reminder_offset_minutes) = input.reminder_offset_minutes {
+    let reminder_offset_minutes = normalize_reminder_offset(reminder_offset_minutes)?;
+    if reminder_offset_minutes != updated.reminder_offset_minutes {
+      updated.notified_at = None;
+    }
+    updated.reminder_offset_minutes = reminder_offset_minutes;
+  }
+
+  if let Some(estimate_minutes) = input.estimate_minutes {
+    updated.estimate_minutes = normalize_estimate_minutes(estimate_minutes)?;
+  }
+
+  if let Some(note_collapsed) = input.note_collapsed {
+    updated.note_collapsed = note_collapsed;
+  }
+
+  if let Some(note_format) = input.note_format {
+    updated.note_format = normalize_note_format(Some(note_format));
+  }
+
+  if let Some(priority) = input.priority {
+    updated.priority = priority;
+  }
+
+  if let Some(energy) = input.energy {
+    updated.energy = normalize_energy(Some(energy));
+  }
+
+  if let Some(context) = input.context {
+    updated.context = normalize_context(Some(context));
+  }
+
+  if let Some(color) = input.color {
+    updated.color = normalize_color(Some(color))?;
+  }
+
+  updated.updated_at = now_iso();
+
+  update_todo_row(conn, &updated)?;
+  if completion_changed {
+    record_completion_change(conn, &updated)?;
+  }
+
+  Ok(updated)
+}
+
+#[tauri::command]
+fn set_energy(state: State<'_, AppState>, app: AppHandle, id: String, energy: String) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  target.energy = normalize_energy(Some(energy));
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET energy = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, &target.energy, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+// Minute and hour offsets give an exact RFC3339 instant; everything else a
+// plain day, both read in `offset`. "next_week" is the start of next week.
+fn resolve_relative_due(
+  spec: RelativeDueSpec,
+  now: DateTime<FixedOffset>,
+  first_day: FirstDayOfWeek,
+) -> CommandResult<String> {
+  let today = now.date_naive();
+  let invalid = || AppError::Validation(format!("Invalid relative due date: {spec:?}"));
+  let day = match spec {
+    RelativeDueSpec::Offset { unit: RelativeDueUnit::Minutes, amount } => {
+      let later = Duration::try_minutes(amount).and_then(|step| now.checked_add_signed(step));
+      return later.map(|instant| instant.to_rfc3339()).ok_or_else(invalid);
+    }
+    RelativeDueSpec::Offset { unit: RelativeDueUnit::Hours, amount } => {
+      let later = Duration::try_hours(amount).and_then(|step| now.checked_add_signed(step));
+      return later.map(|instant| instant.to_rfc3339()).ok_or_else(invalid);
+    }
+    RelativeDueSpec::Offset { unit: RelativeDueUnit::Days, amount } => {
+      Duration::try_days(amount).and_then(|step| today.checked_add_signed(step))
+    }
+    RelativeDueSpec::Offset { unit: RelativeDueUnit::Weeks, amount } => {
+      Duration::try_weeks(amount).and_then(|step| today.checked_add_signed(step))
+    }
+    RelativeDueSpec::Offset { unit: RelativeDueUnit::Months, amount } => {
+      let months = u32::try_from(amount.unsigned_abs()).ok().map(Months::new);
+      months.and_then(|months| {
+        if amount < 0 {
+          today.checked_sub_months(months)
+        } else {
+          today.checked_add_months(months)
+        }
+      })
+    }
+    RelativeDueSpec::Keyword(RelativeDueKeyword::Today) => Some(today),
+    RelativeDueSpec::Keyword(RelativeDueKeyword::Tomorrow) => today.succ_opt(),
+    RelativeDueSpec::Keyword(RelativeDueKeyword::NextWeek) => {
+      week_start(today, first_day).checked_add_signed(Duration::days(7))
+    }
+  };
+
+  day.map(|day| day.format("%Y-%m-%d").to_string()).ok_or_else(invalid)
+}
+
+#[tauri::command]
+fn set_due_relative(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  id: String,
+  spec: RelativeDueSpec,
+  tz_offset_minutes: i32,
+) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let offset = FixedOffset::east_opt(tz_offset_minutes.saturating_mul(60))
+    .ok_or_else(|| AppError::Validation(format!("Invalid timezone offset: {tz_offset_minutes} minutes")))?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let first_day = get_ui_prefs_from_conn(&tx)?.first_day_of_week;
+  let due_date = resolve_relative_due(spec, Utc::now().with_timezone(&offset), first_day)?;
+
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if target.due_date.as_deref() != Some(due_date.as_str()) {
+    target.notified_at = None;
+  }
+  target.due_date = Some(due_date);
+  target.updated_at = now_iso();
+  update_todo_row(&tx, &target)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn render_note_html(state: State<'_, AppState>, id: String) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let todo = get_todo_by_id(&conn, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if todo.note_format != NOTE_FORMAT_MARKDOWN {
+    return Err(AppError::Validation("Note is not formatted as Markdown".to_string()));
+  }
+
+  Ok(render_markdown_html(&todo.note))
+}
+
+#[tauri::command]
+fn toggle_todo(state: State<'_, AppState>, app: AppHandle, id: String, cascade: Option<bool>) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  let snapshot = target.clone();
+  target.completed = !target.completed;
+  target.updated_at = now_iso();
+  target.completed_at = if target.completed {
+    Some(target.updated_at.clone())
+  } else {
+    None
+  };
+  count_recurring_completion(&mut target);
+
+  tx
+    .execute(
+      "UPDATE todos SET completed = ?2, updated_at = ?3, completed_at = ?4, recurrence_completed_count = ?5 WHERE id = ?1",
+      params![
+        &target.id,
+        to_db_bool(target.completed),
+        &target.updated_at,
+        &target.completed_at,
+        target.recurrence_completed_count,
+      ],
+    )
+    .map_err(AppError::from)?;
+  record_completion_change(&tx, &target)?;
+  // Reopening never touches subtasks; only completion cascades.
+  if target.completed && cascade.unwrap_or(false) {
+    set_subtasks_completed(&tx, &target.id, true, &target.updated_at)?;
+  }
+  tx.commit().map_err(AppError::from)?;
+  push_undo(&state, UndoEntry::Toggle(Box::new(snapshot)))?;
+  emit_todos_changed(&app, "toggled", Some(&target.id));
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn set_recurrence_check(state: State<'_, AppState>, id: String, checked: bool) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+
+  if target.recurrence_tag == RECURRENCE_NONE {
+    return Err(AppError::Validation("Recurrence check is only available for recurring tasks".to_string()));
+  }
+
+  target.recurrence_checked_at = if checked { Some(now_iso()) } else { None };
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET recurrence_checked_at = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, &target.recurrence_checked_at, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+
+  if target.recurrence_tag == RECURRENCE_DAILY {
+    let event_day = local_day_key();
+
+    if checked {
+      tx
+        .execute(
+          "INSERT INTO daily_completion_events (todo_id, event_day, created_at)
+           VALUES (?1, ?2, ?3)
+           ON CONFLICT(todo_id, event_day) DO NOTHING",
+          params![&target.id, &event_day, &target.updated_at],
+        )
+        .map_err(AppError::from)?;
+    } else {
+      tx
+        .execute(
+          "DELETE FROM daily_completion_events
+           WHERE todo_id = ?1 AND event_day = ?2",
+          params![&target.id, &event_day],
+        )
+        .map_err(AppError::from)?;
+    }
+  }
+
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(target)
+}
+
+// A line may open with one recurrence directive, e.g. `#daily Water plants` or
+// `#bi-weekly Pay rent`. Only a leading token naming a known recurrence counts;
+// any other `#word` stays part of the title. Returns `None` for lines with no
+// title left.
+fn parse_bulk_line(line: &str) -> Option<(String, String)> {
+  let trimmed = line.trim();
+  let (recurrence_tag, title) = match trimmed.strip_prefix('#').and_then(|rest| {
+    let (directive, title) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    RECURRENCE_TAGS.contains(&directive).then_some((directive, title))
+  }) {
+    Some((directive, title)) => (directive.to_string(), title.trim()),
+    None => (RECURRENCE_NONE.to_string(), trimmed),
+  };
+
+  (!title.is_empty()).then(|| (title.to_string(), recurrence_tag))
+}
+
+// Brain-dump capture: the new todos go above everything else, in input order.
+#[tauri::command]
+fn create_todos_bulk(state: State<'_, AppState>, app: AppHandle, lines: Vec<String>) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let parsed: Vec<(String, String)> = lines.iter().filter_map(|line| parse_bulk_line(line)).collect();
+  if parsed.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let min_sort: i64 = tx
+    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let count = parsed.len();
+  let mut created = Vec::with_capacity(count);
+
+  for (index, (title, recurrence_tag)) in parsed.into_iter().enumerate() {
+    let todo = Todo {
+      id: Uuid::new_v4().to_string(),
+      title,
+      recurrence_tag,
+      recurrence_checked_at: None,
+      note: String::new(),
+      completed: false,
+      due_date: None,
+      created_at: now.clone(),
+      updated_at: now.clone(),
+      reminder_enabled: true,
+      last_reminded_on: None,
+      sort_order: min_sort - (count - index) as i64,
+      completed_at: None,
+      series_id: None,
+      recurrence_until: None,
+      note_format: NOTE_FORMAT_PLAIN.to_string(),
+      rolled_at: None,
+      priority: TodoPriority::Normal,
+      energy: None,
+      deleted_at: None,
+      recurrence_max_count: None,
+      recurrence_completed_count: 0,
+      notified_at: None,
+      remind_after: None,
+      context: None,
+      snooze_count: 0,
+      color: None,
+      archived: false,
+      pinned: false,
+      reminder_offset_minutes: None,
+      estimate_minutes: None,
+      note_collapsed: false,
+    };
+
+    insert_todo(&tx, &todo)?;
+    created.push(todo);
+  }
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "created", None);
+
+  Ok(created)
+}
+
+#[tauri::command]
+fn create_routine(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  titles: Vec<String>,
+  recurrence_tag: String,
+  start_due: Option<String>,
+) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let created = create_routine_in(&tx, titles, &recurrence_tag, start_due)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "created", None);
+
+  Ok(created)
+}
+
+fn create_routine_in(
+  conn: &Connection,
+  titles: Vec<String>,
+  recurrence_tag: &str,
+  start_due: Option<String>,
+) -> CommandResult<Vec<Todo>> {
+  let recurrence_tag = recurrence_tag.trim().to_string();
+  if recurrence_tag == RECURRENCE_NONE || !RECURRENCE_TAGS.contains(&recurrence_tag.as_str()) {
+    return Err(AppError::Validation(format!("Invalid routine recurrence: {recurrence_tag}")));
+  }
+
+  let due_date = match normalize_date(start_due)? {
+    Some(value) => Some(parse_day_key(&value, "start due")?.format("%Y-%m-%d").to_string()),
+    None => None,
+  };
+
+  let titles: Vec<String> = titles.iter().map(|title| title.trim().to_string()).collect();
+  if titles.is_empty() || titles.iter().any(String::is_empty) {
+    return Err(AppError::Validation("Routine titles cannot be empty".to_string()));
+  }
+
+  let min_sort: i64 = conn
+    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let count = titles.len();
+  let mut created = Vec::with_capacity(count);
+
+  // Every routine item shares the start date; listed in the order given.
+  for (index, title) in titles.into_iter().enumerate() {
+    let todo = Todo {
+      id: Uuid::new_v4().to_string(),
+      title,
+      recurrence_tag: recurrence_tag.clone(),
+      recurrence_checked_at: None,
+      note: String::new(),
+      completed: false,
+      due_date: due_date.clone(),
+      created_at: now.clone(),
+      updated_at: now.clone(),
+      reminder_enabled: true,
+      last_reminded_on: None,
+      sort_order: min_sort - (count - index) as i64,
+      completed_at: None,
+      series_id: None,
+      recurrence_until: None,
+      note_format: NOTE_FORMAT_PLAIN.to_string(),
+      rolled_at: None,
+      priority: TodoPriority::Normal,
+      energy: None,
+      deleted_at: None,
+      recurrence_max_count: None,
+      recurrence_completed_count: 0,
+      notified_at: None,
+      remind_after: None,
+      context: None,
+      snooze_count: 0,
+      color: None,
+      archived: false,
+      pinned: false,
+      reminder_offset_minutes: None,
+      estimate_minutes: None,
+      note_collapsed: false,
+    };
+
+    insert_todo(conn, &todo)?;
+    created.push(todo);
+  }
+
+  Ok(created)
+}
+
+#[tauri::command]
+fn generate_occurrences(state: State<'_, AppState>, app: AppHandle, id: String, count: u16) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let created = generate_occurrences_in(&tx, &id, count)?;
+  tx.commit().map_err(AppError::from)?;
+  if !created.is_empty() {
+    emit_todos_changed(&app, "created", None);
+  }
+
+  Ok(created)
+}
+
+// Occurrences continue from the latest due date already in the series, so
+// generating twice never repeats a date. Timestamped due dates (e.g. after a
+// snooze) count by their local day.
+fn generate_occurrences_in(conn: &Connection, id: &str, count: u16) -> CommandResult<Vec<Todo>> {
+  let source = get_todo_by_id(conn, id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if source.recurrence_tag == RECURRENCE_NONE {
+    return Err(AppError::Validation("Occurrences can only be generated for recurring tasks".to_string()));
+  }
+
+  let Some(source_due) = source.due_date.as_deref().and_then(|value| parse_due_day(value, &Local)) else {
+    return Err(AppError::Validation("Occurrences require a due date".to_string()));
+  };
+
+  let count = count.clamp(1, 100) as usize;
+  let until = source
+    .recurrence_until
+    .as_deref()
+    .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok());
+  let series_id = source.series_id.clone().unwrap_or_else(|| source.id.clone());
+
+  conn
+    .execute(
+      "UPDATE todos SET series_id = ?2 WHERE id = ?1 AND series_id IS NULL",
+      params![&source.id, &series_id],
+    )
+    .map_err(AppError::from)?;
+
+  let mut latest_due = source_due;
+  {
+    let mut statement = conn
+      .prepare("SELECT due_date FROM todos WHERE series_id = ?1 AND due_date IS NOT NULL")
+      .map_err(AppError::from)?;
+    let rows = statement
+      .query_map(params![&series_id], |row| row.get::<_, String>(0))
+      .map_err(AppError::from)?;
+
+    for row in rows {
+      let raw = row.map_err(AppError::from)?;
+      if let Some(day) = parse_due_day(&raw, &Local) {
+        latest_due = latest_due.max(day);
+      }
+    }
+  }
+
+  let min_sort: i64 = conn
+    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let mut created = Vec::with_capacity(count);
+  let mut next_due = latest_due;
+
+  for index in 0..count {
+    let Some(due_day) = next_due_date(&source.recurrence_tag, next_due) else {
+      break;
+    };
+    if until.is_some_and(|until| due_day > until) {
+      break;
+    }
+    next_due = due_day;
+
+    let todo = Todo {
+      id: Uuid::new_v4().to_string(),
+      title: source.title.clone(),
+      recurrence_tag: source.recurrence_tag.clone(),
+      recurrence_checked_at: None,
+      note: source.note.clone(),
+      completed: false,
+      due_date: Some(due_day.format("%Y-%m-%d").to_string()),
+      created_at: now.clone(),
+      updated_at: now.clone(),
+      reminder_enabled: source.reminder_enabled,
+      last_reminded_on: None,
+      sort_order: min_sort - (count - index) as i64,
+      completed_at: None,
+      series_id: Some(series_id.clone()),
+      recurrence_until: source.recurrence_until.clone(),
+      note_format: source.note_format.clone(),
+      rolled_at: None,
+      priority: source.priority,
+      energy: source.energy.clone(),
+      deleted_at: None,
+      recurrence_max_count: source.recurrence_max_count,
+      recurrence_completed_count: source.recurrence_completed_count,
+      notified_at: None,
+      remind_after: None,
+      context: source.context.clone(),
+      snooze_count: 0,
+      color: source.color.clone(),
+      archived: false,
+      pinned: false,
+      reminder_offset_minutes: source.reminder_offset_minutes,
+      estimate_minutes: source.estimate_minutes,
+      note_collapsed: source.note_collapsed,
+    };
+
+    insert_todo(conn, &todo)?;
+    created.push(todo);
+  }
+
+  Ok(created)
+}
+
+#[tauri::command]
+fn collapse_series(state: State<'_, AppState>, app: AppHandle, series_id: String) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let removed = collapse_series_in(&tx, &series_id)?;
+  tx.commit().map_err(AppError::from)?;
+  if removed > 0 {
+    emit_todos_changed(&app, "deleted", None);
+  }
+
+  Ok(removed)
+}
+
+// Keeps the earliest open member and every completed one; the other open
+// members go to the trash like any deleted todo.
+fn collapse_series_in(conn: &Connection, series_id: &str) -> CommandResult<usize> {
+  let earliest_open: Option<String> = conn
+    .query_row(
+      "SELECT id FROM todos
+       WHERE series_id = ?1 AND completed = 0 AND deleted_at IS NULL
+       ORDER BY due_date IS NULL, due_date ASC, sort_order ASC
+       LIMIT 1",
+      params![series_id],
+      |row| row.get(0),
+    )
+    .optional()
+    .map_err(AppError::from)?;
+
+  let Some(keep_id) = earliest_open else {
+    return Ok(0);
+  };
+
+  conn
+    .execute(
+      "UPDATE todos SET deleted_at = ?3, updated_at = ?3
+       WHERE series_id = ?1 AND completed = 0 AND deleted_at IS NULL AND id <> ?2",
+      params![series_id, &keep_id, now_iso()],
+    )
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn set_recurrence_until_bulk(
+  state: State<'_, AppState>,
+  ids: Vec<String>,
+  until: String,
+) -> CommandResult<RecurrenceUntilReport> {
+  let mut conn = db_conn(&state)?;
+
+  let until_day = parse_day_key(&until, "until")?;
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let report = set_recurrence_until_bulk_in(&tx, ids, until_day)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(report)
+}
+
+fn set_recurrence_until_bulk_in(
+  conn: &Connection,
+  ids: Vec<String>,
+  until_day: NaiveDate,
+) -> CommandResult<RecurrenceUntilReport> {
+  let until_key = until_day.format("%Y-%m-%d").to_string();
+  let now = now_iso();
+  let mut report = RecurrenceUntilReport::default();
+
+  for id in ids {
+    let Some(todo) = get_todo_by_id(conn, &id, false)? else {
+      report.skipped_ids.push(id);
+      continue;
+    };
+
+    let due_after_until = todo
+      .due_date
+      .as_deref()
+      .and_then(|value| parse_due_day(value, &Local))
+      .is_some_and(|due_day| due_day > until_day);
+    if due_after_until {
+      report.skipped_ids.push(id);
+      continue;
+    }
+
+    conn
+      .execute(
+        "UPDATE todos SET recurrence_until = ?2, updated_at = ?3 WHERE id = ?1",
+        params![&id, &until_key, &now],
+      )
+      .map_err(AppError::from)?;
+    report.updated += 1;
+  }
+
+  Ok(report)
+}
+
+#[tauri::command]
+fn set_recurrence_max_count(state: State<'_, AppState>, id: String, max_count: Option<i64>) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  if max_count.is_some_and(|value| value < 1) {
+    return Err(AppError::Validation("Recurrence max count must be at least 1".to_string()));
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if target.recurrence_tag == RECURRENCE_NONE {
+    return Err(AppError::Validation("Recurrence max count is only available for recurring tasks".to_string()));
+  }
+
+  target.recurrence_max_count = max_count;
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET recurrence_max_count = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, target.recurrence_max_count, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(target)
+}
+
+// Stops a series once the next occurrence would fall after `recurrence_until`
+// or the completion cap has been reached.
+fn recurrence_continues(original: &Todo, next_due: NaiveDate) -> bool {
+  let past_until = original
+    .recurrence_until
+    .as_deref()
+    .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+    .is_some_and(|until| next_due > until);
+  let reached_max_count = original
+    .recurrence_max_count
+    .is_some_and(|max_count| original.recurrence_completed_count >= max_count);
+
+  !(past_until || reached_max_count)
+}
+
+fn next_occurrence(original: &Todo, next_due: NaiveDate, sort_order: i64, now: &str) -> Todo {
+  Todo {
+    id: Uuid::new_v4().to_string(),
+    title: original.title.clone(),
+    recurrence_tag: original.recurrence_tag.clone(),
+    recurrence_checked_at: None,
+    note: original.note.clone(),
+    completed: false,
+    due_date: Some(next_due.format("%Y-%m-%d").to_string()),
+    created_at: now.to_string(),
+    updated_at: now.to_string(),
+    reminder_enabled: original.reminder_enabled,
+    last_reminded_on: None,
+    sort_order,
+    completed_at: None,
+    series_id: original.series_id.clone(),
+    recurrence_until: original.recurrence_until.clone(),
+    note_format: original.note_format.clone(),
+    rolled_at: None,
+    priority: original.priority,
+    energy: original.energy.clone(),
+    deleted_at: None,
+    recurrence_max_count: original.recurrence_max_count,
+    recurrence_completed_count: original.recurrence_completed_count,
+    notified_at: None,
+    remind_after: None,
+    context: original.context.clone(),
+    snooze_count: 0,
+    color: original.color.clone(),
+    archived: false,
+    pinned: original.pinned,
+    reminder_offset_minutes: original.reminder_offset_minutes,
+    estimate_minutes: original.estimate_minutes,
+    note_collapsed: original.note_collapsed,
+  }
+}
+
+// Completes the todo and, for a recurring one, inserts its next occurrence in
+// the same transaction, returning that occurrence. The original is marked
+// rolled so `roll_recurring_todos` won't spawn a second copy. Non-recurring
+// todos (and series that have ended) return the completed todo itself.
+#[tauri::command]
+fn complete_and_recur(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  let now = now_iso();
+
+  if !target.completed {
+    target.completed = true;
+    target.completed_at = Some(now.clone());
+    target.updated_at = now.clone();
+    count_recurring_completion(&mut target);
+
+    tx
+      .execute(
+        "UPDATE todos SET completed = 1, updated_at = ?2, completed_at = ?3, recurrence_completed_count = ?4 WHERE id = ?1",
+        params![&target.id, &target.updated_at, &target.completed_at, target.recurrence_completed_count],
+      )
+      .map_err(AppError::from)?;
+    record_completion_change(&tx, &target)?;
+  }
+
+  let today = local_today_naive();
+  let base_day = target
+    .due_date
+    .as_deref()
+    .and_then(|value| parse_due_day(value, &Local))
+    .unwrap_or(today);
+  let anchor = get_ui_prefs_from_conn(&tx)?.recurrence_anchor;
+  let next = match next_anchored_due_date(&target.recurrence_tag, base_day, today, anchor) {
+    Some(next_due) if target.rolled_at.is_none() => {
+      target.rolled_at = Some(now.clone());
+      tx
+        .execute("UPDATE todos SET rolled_at = ?2 WHERE id = ?1", params![&target.id, &now])
+        .map_err(AppError::from)?;
+
+      if recurrence_continues(&target, next_due) {
+        let sort_order: i64 = tx
+          .query_row("SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos", [], |row| row.get(0))
+          .map_err(AppError::from)?;
+        let todo = next_occurrence(&target, next_due, sort_order, &now);
+        insert_todo(&tx, &todo)?;
+        Some(todo)
+      } else {
+        None
+      }
+    }
+    _ => None,
+  };
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "toggled", Some(&target.id));
+
+  match next {
+    Some(todo) => {
+      emit_todos_changed(&app, "created", Some(&todo.id));
+      Ok(todo)
+    }
+    None => Ok(target),
+  }
+}
+
+// Reopens a recurring todo on its next due date. Unlike toggling it back, the
+// completion history and count are left alone, so streaks keep the earlier
+// completion.
+#[tauri::command]
+fn reset_recurring(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if target.recurrence_tag == RECURRENCE_NONE {
+    return Err(AppError::Validation("Only recurring todos can be reset".to_string()));
+  }
+
+  let today = local_today_naive();
+  let base_day = target
+    .due_date
+    .as_deref()
+    .and_then(|value| parse_due_day(value, &Local))
+    .unwrap_or(today);
+  let anchor = get_ui_prefs_from_conn(&tx)?.recurrence_anchor;
+  if let Some(next_due) = next_anchored_due_date(&target.recurrence_tag, base_day, today, anchor) {
+    target.due_date = Some(next_due.format("%Y-%m-%d").to_string());
+    target.notified_at = None;
+  }
+  target.completed = false;
+  target.completed_at = None;
+  target.updated_at = now_iso();
+  update_todo_row(&tx, &target)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+// Moves every open, unarchived todo tagged `from_tag` onto the `to_tag`
+// cadence. Each due day stays the anchor of the new grid, so a daily todo due
+// on a Tuesday becomes a Tuesday weekly; a date already in the past is advanced
+// to the first slot on or after today in `tz_offset_minutes`. Rescheduled dates
+// are written as plain days. Todos without a due date (or with one that doesn't
+// parse) are only retagged and keep their due date as is. Returns how many
+// todos had their tag or due date changed.
+#[tauri::command]
+fn reschedule_recurrence_group(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  from_tag: String,
+  to_tag: String,
+  tz_offset_minutes: i32,
+) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let offset = FixedOffset::east_opt(tz_offset_minutes.saturating_mul(60))
+    .ok_or_else(|| AppError::Validation(format!("Invalid timezone offset: {tz_offset_minutes} minutes")))?;
+  let today = Utc::now().with_timezone(&offset).date_naive();
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let changed = reschedule_recurrence_group_in(&tx, &from_tag, &to_tag, offset, today)?;
+  tx.commit().map_err(AppError::from)?;
+  if changed > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(changed)
+}
+
+fn reschedule_recurrence_group_in(
+  conn: &Connection,
+  from_tag: &str,
+  to_tag: &str,
+  offset: FixedOffset,
+  today: NaiveDate,
+) -> CommandResult<usize> {
+  let from_tag = from_tag.trim();
+  if from_tag == RECURRENCE_NONE || !RECURRENCE_TAGS.contains(&from_tag) {
+    return Err(AppError::Validation(format!("Unsupported source recurrence: {from_tag}")));
+  }
+  let to_tag = to_tag.trim();
+  if to_tag == RECURRENCE_NONE || !RECURRENCE_TAGS.contains(&to_tag) {
+    return Err(AppError::Validation(format!("Unsupported target recurrence: {to_tag}")));
+  }
+
+  let targets = {
+    let mut statement = conn
+      .prepare(&format!(
+        "SELECT {TODO_COLUMNS} FROM todos
+         WHERE recurrence_tag = ?1 AND completed = 0 AND archived = 0 AND deleted_at IS NULL"
+      ))
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map(params![from_tag], map_todo_row)
+      .map_err(AppError::from)?;
+
+    let mut targets = Vec::new();
+    for row in rows {
+      targets.push(row.map_err(AppError::from)?);
+    }
+    targets
+  };
+
+  let now = now_iso();
+  let mut changed = 0;
+  for mut target in targets {
+    let mut touched = target.recurrence_tag != to_tag;
+    target.recurrence_tag = to_tag.to_string();
+    if let Some(due_day) = target.due_date.as_deref().and_then(|value| parse_due_day(value, &offset)) {
+      let mut steps = 0;
+      let next_due = loop {
+        match nth_due_date(to_tag, due_day, steps) {
+          Some(next) if next < today => steps += 1,
+          next => break next,
+        }
+      };
+      let next_due = next_due.map(|day| day.format("%Y-%m-%d").to_string());
+      if next_due.is_some() && next_due != target.due_date {
+        target.due_date = next_due;
+        target.notified_at = None;
+        touched = true;
+      }
+    }
+    if !touched {
+      continue;
+    }
+    target.updated_at = now.clone();
+    update_todo_row(conn, &target)?;
+    changed += 1;
+  }
+
+  Ok(changed)
+}
+
+#[tauri::command]
+fn roll_recurring_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let created = roll_recurring_todos_in(&tx, local_today_naive())?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(created)
+}
+
+fn roll_recurring_todos_in(conn: &Connection, today: NaiveDate) -> CommandResult<Vec<Todo>> {
+  let candidates = {
+    let mut statement = conn
+      .prepare(&format!(
+        "SELECT {TODO_COLUMNS} FROM todos
+         WHERE completed = 1 AND recurrence_tag <> ?1 AND rolled_at IS NULL AND due_date IS NOT NULL AND deleted_at IS NULL
+         ORDER BY sort_order DESC"
+      ))
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map(params![RECURRENCE_NONE], map_todo_row)
+      .map_err(AppError::from)?;
+
+    let mut candidates = Vec::new();
+    for row in rows {
+      candidates.push(row.map_err(AppError::from)?);
+    }
+    candidates
+  };
+
+  let now = now_iso();
+  let anchor = get_ui_prefs_from_conn(conn)?.recurrence_anchor;
+  let mut created = Vec::new();
+
+  for original in candidates {
+    let Some(due_day) = original
+      .due_date
+      .as_deref()
+      .and_then(|value| parse_due_day(value, &Local))
+    else {
+      continue;
+    };
+    if due_day >= today {
+      continue;
+    }
+
+    conn
+      .execute(
+        "UPDATE todos SET rolled_at = ?2 WHERE id = ?1",
+        params![&original.id, &now],
+      )
+      .map_err(AppError::from)?;
+
+    let completed_on = original
+      .completed_at
+      .as_deref()
+      .and_then(|value| parse_due_day(value, &Local))
+      .unwrap_or(today);
+    let Some(next_due) = next_anchored_due_date(&original.recurrence_tag, due_day, completed_on, anchor) else {
+      continue;
+    };
+    if !recurrence_continues(&original, next_due) {
+      continue;
+    }
+
+    let sort_order: i64 = conn
+      .query_row("SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos", [], |row| row.get(0))
+      .map_err(AppError::from)?;
+
+    let todo = next_occurrence(&original, next_due, sort_order, &now);
+    insert_todo(conn, &todo)?;
+    created.push(todo);
+  }
+
+  Ok(created)
+}
+
+#[tauri::command]
+fn get_daily_completion_heatmap(
+  state: State<'_, AppState>,
+  days: u16,
+) -> CommandResult<Vec<DailyCompletionHeatmapDay>> {
+  let conn = db_conn(&state)?;
+
+  let clamped_days = days.clamp(1, 365) as i64;
+  let end_day = Local::now().date_naive();
+  let start_day = end_day - Duration::days(clamped_days - 1);
+  let start_key = start_day.format("%Y-%m-%d").to_string();
+  let end_key = end_day.format("%Y-%m-%d").to_string();
+
+  let mut statement = conn
+    .prepare(
+      "SELECT event_day, COUNT(*) AS completion_count
+       FROM daily_completion_events
+       WHERE event_day >= ?1 AND event_day <= ?2
+       GROUP BY event_day
+       ORDER BY event_day ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![start_key, end_key], |row| {
+      let count: i64 = row.get(1)?;
+
+      Ok(DailyCompletionHeatmapDay {
+        date: row.get(0)?,
+        count: if count < 0 { 0 } else { count as u32 },
+      })
+    })
+    .map_err(AppError::from)?;
+
+  let mut output = Vec::new();
+  for row in rows {
+    output.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(output)
+}
+
+#[tauri::command]
+fn recompute_scheduled_load(state: State<'_, AppState>) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let days = rebuild_scheduled_load(&tx)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(days)
+}
+
+#[tauri::command]
+fn get_scheduled_load(state: State<'_, AppState>, start: String, end: String) -> CommandResult<Vec<ScheduledLoadDay>> {
+  let conn = db_conn(&state)?;
+
+  let start_day = parse_day_key(&start, "start")?;
+  let end_day = parse_day_key(&end, "end")?;
+  if start_day > end_day {
+    return Err(AppError::Validation("Start date must not be after end date".to_string()));
+  }
+
+  get_scheduled_load_in(&conn, start_day, end_day)
+}
+
+fn get_scheduled_load_in(conn: &Connection, start_day: NaiveDate, end_day: NaiveDate) -> CommandResult<Vec<ScheduledLoadDay>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT day, open_count FROM scheduled_load
+       WHERE day >= ?1 AND day <= ?2 AND open_count > 0
+       ORDER BY day ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(
+      params![
+        start_day.format("%Y-%m-%d").to_string(),
+        end_day.format("%Y-%m-%d").to_string()
+      ],
+      |row| {
+        let count: i64 = row.get(1)?;
+
+        Ok(ScheduledLoadDay {
+          date: row.get(0)?,
+          count: if count < 0 { 0 } else { count as u32 },
+        })
+      },
+    )
+    .map_err(AppError::from)?;
+
+  let mut output = Vec::new();
+  for row in rows {
+    output.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(output)
+}
+
+// Counts the same days as `get_scheduled_load`, but reads `due_date` itself
+// instead of the trigger-maintained table.
+#[tauri::command]
+fn due_date_histogram(state: State<'_, AppState>, start: String, end: String) -> CommandResult<Vec<ScheduledLoadDay>> {
+  let conn = db_conn(&state)?;
+
+  let start_day = parse_day_key(&start, "start")?;
+  let end_day = parse_day_key(&end, "end")?;
+  if start_day > end_day {
+    return Err(AppError::Validation("Start date must not be after end date".to_string()));
+  }
+
+  let mut statement = conn
+    .prepare(
+      "SELECT due_date FROM todos
+       WHERE completed = 0 AND deleted_at IS NULL AND archived = 0 AND due_date IS NOT NULL",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut counts: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+  for row in rows {
+    let due_date = row.map_err(AppError::from)?;
+    if let Some(day) = parse_due_day(&due_date, &Local).filter(|day| (start_day..=end_day).contains(day)) {
+      *counts.entry(day).or_default() += 1;
+    }
+  }
+
+  Ok(
+    counts
+      .into_iter()
+      .map(|(day, count)| ScheduledLoadDay {
+        date: day.format("%Y-%m-%d").to_string(),
+        count,
+      })
+      .collect(),
+  )
+}
+
+#[tauri::command]
+fn completions_by_hour(state: State<'_, AppState>, start: String, end: String) -> CommandResult<Vec<u32>> {
+  let conn = db_conn(&state)?;
+
+  let start_day = parse_day_key(&start, "start")?;
+  let end_day = parse_day_key(&end, "end")?;
+  if start_day > end_day {
+    return Err(AppError::Validation("Start date must not be after end date".to_string()));
+  }
+
+  completions_by_hour_in(&conn, &Local, start_day, end_day)
+}
+
+// Completions are stored in UTC; both the day range and the hour buckets are
+// read in `tz`.
+fn completions_by_hour_in<Tz: TimeZone>(conn: &Connection, tz: &Tz, start_day: NaiveDate, end_day: NaiveDate) -> CommandResult<Vec<u32>> {
+  let mut statement = conn
+    .prepare("SELECT completed_at FROM todos WHERE completed = 1 AND completed_at IS NOT NULL AND deleted_at IS NULL")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut completions = Vec::new();
+  for row in rows {
+    let raw = row.map_err(AppError::from)?;
+    if let Ok(completed_at) = DateTime::parse_from_rfc3339(&raw) {
+      completions.push(completed_at.with_timezone(&Utc));
+    }
+  }
+
+  Ok(bucket_by_local_hour(&completions, tz, start_day, end_day))
+}
+
+// Bounds are local calendar days, both inclusive. `CompletedDuring` reads the
+// completion history, so a todo completed and later reopened still counts;
+// todos completed several times are ordered by their latest completion in range.
+#[tauri::command]
+fn list_todos_in_range(state: State<'_, AppState>, field: DateField, start: String, end: String) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let start_day = parse_day_key(&start, "start")?;
+  let end_day = parse_day_key(&end, "end")?;
+  if start_day > end_day {
+    return Err(AppError::Validation("Start date must not be after end date".to_string()));
+  }
+  let in_range = |raw: &str| {
+    parse_iso_to_local_datetime(raw).filter(|at| (start_day..=end_day).contains(&at.date_naive()))
+  };
+
+  let mut matched: Vec<(DateTime<Local>, Todo)> = Vec::new();
+  if let DateField::CompletedDuring = field {
+    let mut statement = conn
+      .prepare("SELECT todo_id, completed_at FROM todo_completions")
+      .map_err(AppError::from)?;
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(AppError::from)?;
+
+    let mut latest: HashMap<String, DateTime<Local>> = HashMap::new();
+    for row in rows {
+      let (todo_id, raw) = row.map_err(AppError::from)?;
+      if let Some(completed_at) = in_range(&raw) {
+        let entry = latest.entry(todo_id).or_insert(completed_at);
+        *entry = (*entry).max(completed_at);
+      }
+    }
+    for (todo_id, completed_at) in latest {
+      if let Some(todo) = get_todo_by_id(&conn, &todo_id, false)? {
+        matched.push((completed_at, todo));
+      }
+    }
+  } else {
+    let mut statement = conn
+      .prepare(&format!("SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL"))
+      .map_err(AppError::from)?;
+    let rows = statement.query_map([], map_todo_row).map_err(AppError::from)?;
+
+    for row in rows {
+      let todo = row.map_err(AppError::from)?;
+      let raw = match field {
+        DateField::Updated => &todo.updated_at,
+        _ => &todo.created_at,
+      };
+      if let Some(at) = in_range(raw) {
+        matched.push((at, todo));
+      }
+    }
+  }
+
+  matched.sort_by(|left, right| right.0.cmp(&left.0).then_with(|| left.1.id.cmp(&right.1.id)));
+  Ok(matched.into_iter().map(|(_, todo)| todo).collect())
+}
+
+#[tauri::command]
+fn get_rolling_completion_average(state: State<'_, AppState>, window_days: u16, now: String) -> CommandResult<f64> {
+  let conn = db_conn(&state)?;
+
+  let end_day = parse_now_to_local_date(&now)?;
+  get_rolling_completion_average_in(&conn, end_day, window_days)
+}
+
+fn get_rolling_completion_average_in(conn: &Connection, end_day: NaiveDate, window_days: u16) -> CommandResult<f64> {
+  let mut statement = conn
+    .prepare("SELECT completed_at FROM todos WHERE completed = 1 AND completed_at IS NOT NULL AND deleted_at IS NULL")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut counts: HashMap<NaiveDate, u32> = HashMap::new();
+  for row in rows {
+    let raw = row.map_err(AppError::from)?;
+    if let Some(completed_at) = parse_iso_to_local_datetime(&raw) {
+      *counts.entry(completed_at.date_naive()).or_default() += 1;
+    }
+  }
+
+  let day_counts: Vec<(NaiveDate, u32)> = counts.into_iter().collect();
+  Ok(rolling_daily_average(&day_counts, end_day, window_days.clamp(1, 365) as i64))
+}
+
+// Open todos, most avoided first. Age is measured from `now` rather than the
+// clock so the UI can score against a fixed instant.
+#[tauri::command]
+fn get_procrastination_index(state: State<'_, AppState>, now: String) -> CommandResult<Vec<ProcrastinationEntry>> {
+  let conn = db_conn(&state)?;
+
+  let now_at = parse_iso_to_local_datetime(&now)
+    .ok_or_else(|| AppError::Validation(format!("Invalid timestamp (expected RFC3339): {now}")))?;
+
+  get_procrastination_index_in(&conn, now_at)
+}
+
+fn get_procrastination_index_in(conn: &Connection, now_at: DateTime<Local>) -> CommandResult<Vec<ProcrastinationEntry>> {
+  let today = now_at.date_naive();
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE completed = 0 AND deleted_at IS NULL ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut entries = Vec::new();
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    let age_days = parse_iso_to_local_datetime(&todo.created_at)
+      .map(|created_at| (now_at - created_at).num_minutes() as f64 / (24.0 * 60.0))
+      .unwrap_or(0.0);
+    let days_overdue = todo
+      .due_date
+      .as_deref()
+      .and_then(|value| parse_due_day(value, &Local))
+      .map(|due_day| (today - due_day).num_days());
+
+    entries.push(ProcrastinationEntry {
+      score: procrastination_score(age_days, todo.snooze_count, days_overdue),
+      todo,
+    });
+  }
+
+  entries.sort_by(|a, b| b.score.total.total_cmp(&a.score.total));
+
+  Ok(entries)
+}
+
+// Plain due days are overdue from the next local day; timestamps from their
+// exact instant. `completions_per_day` covers the last 30 local days, oldest
+// first, with zero-filled gaps.
+#[tauri::command]
+fn get_stats(state: State<'_, AppState>) -> CommandResult<TodoStats> {
+  let conn = db_conn(&state)?;
+
+  let today = local_today_naive();
+  let (total, completed, overdue) = conn
+    .query_row(
+      "SELECT COUNT(*),
+              COALESCE(SUM(completed), 0),
+              COALESCE(SUM(CASE
+                WHEN completed = 1 OR due_date IS NULL THEN 0
+                WHEN length(due_date) = 10 THEN due_date < ?1
+                ELSE julianday(due_date) < julianday(?2)
+              END), 0)
+       FROM todos WHERE deleted_at IS NULL AND archived = 0",
+      params![today.format("%Y-%m-%d").to_string(), now_iso()],
+      |row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?, row.get::<_, u32>(2)?)),
+    )
+    .map_err(AppError::from)?;
+
+  let mut by_recurrence_tag = HashMap::new();
+  {
+    let mut statement = conn
+      .prepare(
+        "SELECT recurrence_tag, COUNT(*) FROM todos
+         WHERE deleted_at IS NULL AND archived = 0
+         GROUP BY recurrence_tag",
+      )
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)))
+      .map_err(AppError::from)?;
+
+    for row in rows {
+      let (tag, count) = row.map_err(AppError::from)?;
+      by_recurrence_tag.insert(tag, count);
+    }
+  }
+
+  let start_day = today - Duration::days(29);
+  let mut counts: HashMap<String, u32> = HashMap::new();
+  {
+    let mut statement = conn
+      .prepare(
+        "SELECT date(completed_at, 'localtime') AS day, COUNT(*)
+         FROM todo_completions
+         WHERE date(completed_at, 'localtime') >= ?1
+         GROUP BY day",
+      )
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map(params![start_day.format("%Y-%m-%d").to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?))
+      })
+      .map_err(AppError::from)?;
+
+    for row in rows {
+      let (day, count) = row.map_err(AppError::from)?;
+      counts.insert(day, count);
+    }
+  }
+
+  let completions_per_day = start_day
+    .iter_days()
+    .take(30)
+    .map(|day| {
+      let date = day.format("%Y-%m-%d").to_string();
+      let count = counts.get(&date).copied().unwrap_or(0);
+      DailyCompletionHeatmapDay { date, count }
+    })
+    .collect();
+
+  Ok(TodoStats {
+    total,
+    completed,
+    overdue,
+    by_recurrence_tag,
+    completions_per_day,
+  })
+}
+
+// Rolled occurrences of a recurring todo share a series, so the stats cover
+// the whole series rather than the single row.
+#[tauri::command]
+fn get_completion_stats(state: State<'_, AppState>, todo_id: String) -> CommandResult<CompletionStats> {
+  let conn = db_conn(&state)?;
+
+  let todo = get_todo_by_id(&conn, &todo_id, true)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {todo_id}")))?;
+
+  let mut statement = conn
+    .prepare(
+      "SELECT c.completed_at FROM todo_completions c
+       JOIN todos t ON t.id = c.todo_id
+       WHERE t.id = ?1 OR (?2 IS NOT NULL AND t.series_id = ?2)",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![&todo.id, &todo.series_id], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut total_completions = 0;
+  let mut days = Vec::new();
+  for row in rows {
+    let raw = row.map_err(AppError::from)?;
+    total_completions += 1;
+    if let Some(completed_at) = parse_iso_to_local_datetime(&raw) {
+      days.push(completed_at.date_naive());
+    }
+  }
+
+  let (current_streak, longest_streak) = completion_streaks(&days, local_today_naive());
+
+  Ok(CompletionStats {
+    total_completions,
+    current_streak,
+    longest_streak,
+  })
+}
+
+fn week_start(day: NaiveDate, first_day: FirstDayOfWeek) -> NaiveDate {
+  let offset = match first_day {
+    FirstDayOfWeek::Monday => day.weekday().num_days_from_monday(),
+    FirstDayOfWeek::Sunday => day.weekday().num_days_from_sunday(),
+  };
+  day - Duration::days(i64::from(offset))
+}
+
+#[tauri::command]
+fn get_usage_summary(state: State<'_, AppState>) -> CommandResult<UsageSummary> {
+  let conn = db_conn(&state)?;
+
+  let first_day = get_ui_prefs_from_conn(&conn)?.first_day_of_week;
+  get_usage_summary_in(&conn, local_today_naive(), first_day)
+}
+
+fn get_usage_summary_in(conn: &Connection, today: NaiveDate, first_day: FirstDayOfWeek) -> CommandResult<UsageSummary> {
+  let mut statement = conn
+    .prepare("SELECT created_at, completed_at FROM todos WHERE deleted_at IS NULL")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| {
+      let created_at: String = row.get(0)?;
+      let completed_at: Option<String> = row.get(1)?;
+      Ok((created_at, completed_at))
+    })
+    .map_err(AppError::from)?;
+
+  let week_start = week_start(today, first_day);
+  let mut activity_days = Vec::new();
+  let mut created_this_week = 0u32;
+  let mut completed_this_week = 0u32;
+
+  for row in rows {
+    let (created_at, completed_at) = row.map_err(AppError::from)?;
+
+    if let Some(created_day) = parse_iso_to_local_datetime(&created_at).map(|value| value.date_naive()) {
+      if created_day >= week_start && created_day <= today {
+        created_this_week += 1;
+      }
+      activity_days.push(created_day);
+    }
+
+    let completed_day = completed_at
+      .as_deref()
+      .and_then(parse_iso_to_local_datetime)
+      .map(|value| value.date_naive());
+    if let Some(completed_day) = completed_day {
+      if completed_day >= week_start && completed_day <= today {
+        completed_this_week += 1;
+      }
+      activity_days.push(completed_day);
+    }
+  }
+
+  let elapsed_days = today.signed_duration_since(week_start).num_days() + 1;
+
+  Ok(UsageSummary {
+    created_this_week,
+    completed_this_week,
+    average_completions_per_day: completed_this_week as f64 / elapsed_days as f64,
+    most_active_weekday: most_active_weekday(&activity_days).map(|weekday| weekday.to_string()),
+  })
+}
+
+#[tauri::command]
+fn get_frog(state: State<'_, AppState>) -> CommandResult<Option<Todo>> {
+  let conn = db_conn(&state)?;
+
+  get_frog_in(&conn)
+}
+
+// Archived todos are hidden and blocked ones are still waiting on something
+// else, so neither can be the thing to tackle first.
+fn get_frog_in(conn: &Connection) -> CommandResult<Option<Todo>> {
+  conn
+    .query_row(
+      &format!(
+        "SELECT {TODO_COLUMNS} FROM todos
+         WHERE completed = 0 AND deleted_at IS NULL AND archived = 0
+           AND NOT EXISTS (
+             SELECT 1 FROM todo_dependencies
+             JOIN todos AS blocker ON blocker.id = todo_dependencies.depends_on_id
+             WHERE todo_dependencies.todo_id = todos.id AND blocker.completed = 0 AND blocker.deleted_at IS NULL
+           )
+         ORDER BY created_at ASC, id ASC LIMIT 1"
+      ),
+      [],
+      map_todo_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn export_completion_history_csv(state: State<'_, AppState>, start: String, end: String) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let start_day = parse_day_key(&start, "start")?;
+  let end_day = parse_day_key(&end, "end")?;
+  completion_history_csv_in(&conn, start_day, end_day)
+}
+
+fn completion_history_csv_in(conn: &Connection, start_day: NaiveDate, end_day: NaiveDate) -> CommandResult<String> {
+  let start_key = start_day.format("%Y-%m-%d").to_string();
+  let end_key = end_day.format("%Y-%m-%d").to_string();
+
+  let mut statement = conn
+    .prepare(
+      "SELECT events.todo_id, COALESCE(todos.title, '(deleted todo)'), events.event_day
+       FROM daily_completion_events AS events
+       LEFT JOIN todos ON todos.id = events.todo_id
+       WHERE events.event_day >= ?1 AND events.event_day <= ?2
+       ORDER BY events.event_day ASC, events.todo_id ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![start_key, end_key], |row| {
+      let todo_id: String = row.get(0)?;
+      let title: String = row.get(1)?;
+      let completed_date: String = row.get(2)?;
+
+      Ok((todo_id, title, completed_date))
+    })
+    .map_err(AppError::from)?;
+
+  let mut output = String::from("todo_id,title,completed_date\n");
+  for row in rows {
+    let (todo_id, title, completed_date) = row.map_err(AppError::from)?;
+    output.push_str(&format!(
+      "{},{},{}\n",
+      csv_field(&todo_id),
+      csv_field(&title),
+      csv_field(&completed_date)
+    ));
+  }
+
+  Ok(output)
+}
+
+#[tauri::command]
+fn export_opml(state: State<'_, AppState>) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  export_opml_in(&conn)
+}
+
+fn export_opml_in(conn: &Connection) -> CommandResult<String> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n");
+  output.push_str(&format!(
+    "  <head>\n    <title>Simple Todo Note</title>\n    <dateCreated>{}</dateCreated>\n  </head>\n  <body>\n",
+    Utc::now().to_rfc2822()
+  ));
+
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    output.push_str(&format!(
+      "    <outline text=\"{}\" _note=\"{}\" _status=\"{}\"/>\n",
+      xml_attr(&todo.title),
+      xml_attr(&todo.note),
+      if todo.completed { "checked" } else { "unchecked" }
+    ));
+  }
+
+  output.push_str("  </body>\n</opml>\n");
+  Ok(output)
+}
+
+#[tauri::command]
+fn export_todos(state: State<'_, AppState>, format: ExportFormat) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL ORDER BY sort_order ASC, created_at DESC, id ASC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  match format {
+    ExportFormat::Json => {
+      let exported: Vec<ExportedTodo> = todos
+        .iter()
+        .map(|todo| ExportedTodo {
+          todo,
+          sort_order: todo.sort_order,
+        })
+        .collect();
+      serde_json::to_string_pretty(&exported).map_err(AppError::from)
+    }
+    ExportFormat::Markdown => {
+      let mut output = String::new();
+      for todo in &todos {
+        output.push_str(&markdown_checkbox_line(todo));
+        output.push('\n');
+
+        for line in todo.note.lines().filter(|line| !line.trim().is_empty()) {
+          output.push_str("  ");
+          output.push_str(line.trim_end());
+          output.push('\n');
+        }
+      }
+      Ok(output)
+    }
+  }
+}
+
+fn markdown_checkbox_line(todo: &Todo) -> String {
+  let mut line = format!("- [{}] {}", if todo.completed { "x" } else { " " }, todo.title.trim());
+  if let Some(due_date) = &todo.due_date {
+    line.push_str(&format!(" ({due_date})"));
+  }
+  line
+}
+
+// One todo for pasting elsewhere: a checkbox line with its subtasks nested
+// below, or the same compact JSON record `export_todos` writes per todo.
+#[tauri::command]
+fn export_todo(state: State<'_, AppState>, id: String, format: ExportFormat) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let todo = get_todo_by_id(&conn, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+
+  match format {
+    ExportFormat::Json => serde_json::to_string(&ExportedTodo {
+      todo: &todo,
+      sort_order: todo.sort_order,
+    })
+    .map_err(AppError::from),
+    ExportFormat::Markdown => {
+      let mut output = markdown_checkbox_line(&todo);
+      for subtask in subtasks_of(&conn, &todo.id)? {
+        output.push_str(&format!(
+          "\n  - [{}] {}",
+          if subtask.completed { "x" } else { " " },
+          subtask.title.trim()
+        ));
+      }
+      Ok(output)
+    }
+  }
+}
+
+// Accepts the JSON produced by `export_todos`. Any invalid record aborts the
+// whole import so the database is never left half-written.
+#[tauri::command]
+fn import_todos(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  json: String,
+  strategy: ImportStrategy,
+) -> CommandResult<ImportResult> {
+  let mut conn = db_conn(&state)?;
+
+  let records: Vec<ImportedTodo> = serde_json::from_str(&json).map_err(AppError::from)?;
+  let total = records.len();
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut result = ImportResult::default();
+
+  for (index, record) in records.into_iter().enumerate() {
+    emit_migration_progress(&app, index, total);
+    let incoming = record.todo;
+    let title = incoming.title.trim().to_string();
+    if title.is_empty() {
+      return Err(AppError::Validation(format!("Imported todo #{} has an empty title", index + 1)));
+    }
+    let due_date = normalize_date(incoming.due_date.clone())
+      .map_err(|_| AppError::Validation(format!("Imported todo #{} has an invalid due date", index + 1)))?;
+
+    let mut todo = Todo {
+      title,
+      recurrence_tag: normalize_recurrence_tag(Some(incoming.recurrence_tag.clone())),
+      due_date,
+      note_format: normalize_note_format(Some(incoming.note_format.clone())),
+      energy: normalize_energy(incoming.energy.clone()),
+      context: normalize_context(incoming.context.clone()),
+      color: normalize_color(incoming.color.clone()).unwrap_or_default(),
+      reminder_offset_minutes: normalize_reminder_offset(incoming.reminder_offset_minutes).unwrap_or_default(),
+      estimate_minutes: normalize_estimate_minutes(incoming.estimate_minutes).unwrap_or_default(),
+      last_reminded_on: None,
+      ..incoming
+    };
+
+    let existing = get_todo_by_id(&tx, &todo.id, true)?;
+    match (existing, strategy) {
+      (Some(_), ImportStrategy::Skip) => {
+        result.skipped += 1;
+        continue;
+      }
+      (Some(local), ImportStrategy::Overwrite) => {
+        todo.sort_order = record.sort_order.unwrap_or(local.sort_order);
+        todo.last_reminded_on = local.last_reminded_on;
+        update_todo_row(&tx, &todo)?;
+        tx
+          .execute(
+            "UPDATE todos SET sort_order = ?2 WHERE id = ?1",
+            params![&todo.id, todo.sort_order],
+          )
+          .map_err(AppError::from)?;
+        result.overwritten += 1;
+        continue;
+      }
+      (Some(_), ImportStrategy::Duplicate) => {
+        todo.id = Uuid::new_v4().to_string();
+      }
+      (None, _) => {}
+    }
+
+    todo.sort_order = match record.sort_order {
+      Some(sort_order) => sort_order,
+      None => tx
+        .query_row("SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos", [], |row| row.get(0))
+        .map_err(AppError::from)?,
+    };
+    insert_todo(&tx, &todo)?;
+    result.imported += 1;
+  }
+
+  tx.commit().map_err(AppError::from)?;
+  emit_migration_complete(&app, total);
+  if result.imported > 0 {
+    emit_todos_changed(&app, "created", None);
+  } else if result.overwritten > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(result)
+}
+
+struct MarkdownItem {
+  indent: usize,
+  title: String,
+  completed: bool,
+  note_lines: Vec<String>,
+  subtasks: Vec<(String, bool)>,
+}
+
+fn markdown_indent(line: &str) -> usize {
+  line
+    .chars()
+    .take_while(|ch| ch.is_whitespace())
+    .map(|ch| if ch == '\t' { 4 } else { 1 })
+    .sum()
+}
+
+// Matches `- [ ] title`, `* [x] title` and `+ [X] title`, returning the
+// checkbox state and the trimmed title.
+fn parse_markdown_checkbox(line: &str) -> Option<(bool, &str)> {
+  let rest = line.trim_start().strip_prefix(['-', '*', '+'])?;
+  let rest = rest.strip_prefix(char::is_whitespace)?.trim_start();
+  let (completed, rest) = if let Some(rest) = rest.strip_prefix("[ ]") {
+    (false, rest)
+  } else {
+    (true, rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]"))?)
+  };
+  if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+    return None;
+  }
+
+  Some((completed, rest.trim()))
+}
+
+// `export_todos` writes the due date as a trailing ` (YYYY-MM-DD)`; read it
+// back so a Markdown export round-trips. Parentheses that aren't a date stay
+// part of the title.
+fn split_markdown_due_date(title: &str) -> (String, Option<String>) {
+  if let Some((head, tail)) = title.strip_suffix(')').and_then(|rest| rest.rsplit_once(" (")) {
+    if let Ok(Some(due_date)) = normalize_date(Some(tail.to_string())) {
+      if !head.trim().is_empty() {
+        return (head.trim().to_string(), Some(due_date));
+      }
+    }
+  }
+
+  (title.to_string(), None)
+}
+
+// Checkbox lines become todos in document order, above the existing list.
+// Deeper-indented checkboxes become subtasks of the item above them and other
+// indented lines its note; anything else ends the current item and is ignored.
+#[tauri::command]
+fn import_markdown(state: State<'_, AppState>, app: AppHandle, text: String) -> CommandResult<ImportResult> {
+  let mut conn = db_conn(&state)?;
+
+  let mut result = ImportResult::default();
+  let mut items: Vec<MarkdownItem> = Vec::new();
+  let mut item_open = false;
+
+  for line in text.lines() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let indent = markdown_indent(line);
+    let nested = item_open && items.last().is_some_and(|item| indent > item.indent);
+
+    match parse_markdown_checkbox(line) {
+      Some((_, "")) => result.skipped += 1,
+      Some((completed, title)) if nested => {
+        if let Some(item) = items.last_mut() {
+          item.subtasks.push((title.to_string(), completed));
+        }
+      }
+      Some((completed, title)) => {
+        items.push(MarkdownItem {
+          indent,
+          title: title.to_string(),
+          completed,
+          note_lines: Vec::new(),
+          subtasks: Vec::new(),
+        });
+        item_open = true;
+      }
+      None if nested => {
+        if let Some(item) = items.last_mut() {
+          item.note_lines.push(line.trim().to_string());
+        }
+      }
+      None => item_open = false,
+    }
+  }
+
+  if items.is_empty() {
+    return Ok(result);
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let min_sort: i64 = tx
+    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let count = items.len();
+  for (index, item) in items.into_iter().enumerate() {
+    let (title, due_date) = split_markdown_due_date(&item.title);
+    let note = item.note_lines.join("\n");
+    let note_collapsed = default_note_collapsed(&note);
+    let todo = Todo {
+      id: Uuid::new_v4().to_string(),
+      title,
+      recurrence_tag: RECURRENCE_NONE.to_string(),
+      recurrence_checked_at: None,
+      note,
+      completed: item.completed,
+      due_date,
+      created_at: now.clone(),
+      updated_at: now.clone(),
+      reminder_enabled: true,
+      last_reminded_on: None,
+      sort_order: min_sort - (count - index) as i64,
+      completed_at: item.completed.then(|| now.clone()),
+      series_id: None,
+      recurrence_until: None,
+      note_format: NOTE_FORMAT_PLAIN.to_string(),
+      rolled_at: None,
+      priority: TodoPriority::Normal,
+      energy: None,
+      deleted_at: None,
+      recurrence_max_count: None,
+      recurrence_completed_count: 0,
+      notified_at: None,
+      remind_after: None,
+      context: None,
+      snooze_count: 0,
+      color: None,
+      archived: false,
+      pinned: false,
+      reminder_offset_minutes: None,
+      estimate_minutes: None,
+      note_collapsed,
+    };
+    insert_todo(&tx, &todo)?;
+
+    for (subtask_index, (title, completed)) in item.subtasks.into_iter().enumerate() {
+      tx
+        .execute(
+          "INSERT INTO subtasks (id, todo_id, title, completed, sort_order, created_at, updated_at)
+           VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+          params![
+            Uuid::new_v4().to_string(),
+            &todo.id,
+            title,
+            to_db_bool(completed),
+            subtask_index as i64,
+            &now,
+          ],
+        )
+        .map_err(AppError::from)?;
+    }
+
+    result.imported += 1;
+  }
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "created", None);
+
+  Ok(result)
+}
+
+#[tauri::command]
+fn recommend_next(state: State<'_, AppState>, now: String) -> CommandResult<Option<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let today = parse_now_to_local_date(&now)?;
+  recommend_next_in(&conn, today)
+}
+
+// Highest `urgency_score` among open, visible todos whose recurrence isn't
+// already checked off for this cycle; ties go to the smallest id.
+fn recommend_next_in(conn: &Connection, today: NaiveDate) -> CommandResult<Option<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE completed = 0 AND archived = 0 AND deleted_at IS NULL"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut best: Option<(i64, Todo)> = None;
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    if is_recurrence_cycle_checked_at(&todo.recurrence_tag, todo.recurrence_checked_at.as_deref()) {
+      continue;
+    }
+
+    let score = urgency_score(&todo, today);
+    let is_better = match &best {
+      Some((best_score, best_todo)) => score > *best_score || (score == *best_score && todo.id < best_todo.id),
+      None => true,
+    };
+
+    if is_better {
+      best = Some((score, todo));
+    }
+  }
+
+  Ok(best.map(|(_, todo)| todo))
+}
+
+#[tauri::command]
+fn consume_daily_due_reminders(state: State<'_, AppState>) -> CommandResult<Vec<DueReminder>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let reminders = consume_daily_due_reminders_in(&tx, local_today_naive(), Utc::now())?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(reminders)
+}
+
+// Due dates may be plain days or timestamps, so they're compared as local days
+// in Rust rather than as text in SQL.
+fn consume_daily_due_reminders_in(
+  conn: &Connection,
+  today: NaiveDate,
+  now: DateTime<Utc>,
+) -> CommandResult<Vec<DueReminder>> {
+  let today_key = today.format("%Y-%m-%d").to_string();
+
+  let mut statement = conn
+    .prepare(
+      "SELECT id, title, due_date, recurrence_tag, recurrence_checked_at
+       FROM todos
+       WHERE reminder_enabled = 1
+         AND completed = 0
+         AND deleted_at IS NULL
+         AND due_date IS NOT NULL
+         AND (last_reminded_on IS NULL OR last_reminded_on <> ?1)
+         AND (remind_after IS NULL OR remind_after <= ?2)
+       ORDER BY sort_order ASC, created_at DESC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![&today_key, now.to_rfc3339()], |row| {
+      let id: String = row.get(0)?;
+      let title: String = row.get(1)?;
+      let due_date: String = row.get(2)?;
+      let recurrence_tag: String = row.get(3)?;
+      let recurrence_checked_at: Option<String> = row.get(4)?;
+
+      Ok((id, title, due_date, recurrence_tag, recurrence_checked_at))
+    })
+    .map_err(AppError::from)?;
+
+  let mut reminders = Vec::new();
+  let mut reminder_ids = Vec::new();
+
+  for row in rows {
+    let (id, title, due_date, recurrence_tag, recurrence_checked_at) =
+      row.map_err(AppError::from)?;
+
+    if is_recurrence_cycle_checked_at(&recurrence_tag, recurrence_checked_at.as_deref()) {
+      continue;
+    }
+
+    let Some(due_day) = parse_due_day(&due_date, &Local) else {
+      continue;
+    };
+
+    let days_overdue = today.signed_duration_since(due_day).num_days();
+    if days_overdue < 0 {
+      continue;
+    }
+
+    reminder_ids.push(id.clone());
+    reminders.push(DueReminder {
+      id,
+      title,
+      due_date,
+      days_overdue,
+      recurrence_tag,
+    });
+  }
+
+  drop(statement);
+
+  for id in &reminder_ids {
+    conn
+      .execute(
+        "UPDATE todos SET last_reminded_on = ?2 WHERE id = ?1",
+        params![id, &today_key],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  // Most overdue first; the sort is stable, so ties keep list order.
+  reminders.sort_by_key(|reminder| std::cmp::Reverse(reminder.days_overdue));
+
+  Ok(reminders)
+}
+
+// Holds the todo out of due reminders for the configured grace period so a
+// dismissed reminder doesn't fire again straight away.
+#[tauri::command]
+fn mark_reminder_sent(state: State<'_, AppState>, id: String) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  mark_reminder_sent_in(&conn, &id, Utc::now())
+}
+
+fn mark_reminder_sent_in(conn: &Connection, id: &str, now: DateTime<Utc>) -> CommandResult<String> {
+  let prefs = get_ui_prefs_from_conn(conn)?;
+  let grace = Duration::minutes(i64::from(prefs.reminder_snooze_default_minutes.min(24 * 60)));
+  let remind_after = (now + grace).to_rfc3339();
+
+  let updated = conn
+    .execute(
+      "UPDATE todos SET remind_after = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+      params![id, &remind_after],
+    )
+    .map_err(AppError::from)?;
+  if updated == 0 {
+    return Err(AppError::NotFound(format!("Todo not found: {id}")));
+  }
+
+  Ok(remind_after)
+}
+
+fn load_tags_by_todo(conn: &Connection) -> CommandResult<HashMap<String, Vec<String>>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT todo_tags.todo_id, tags.name
+       FROM todo_tags
+       JOIN tags ON tags.id = todo_tags.tag_id
+       ORDER BY tags.name ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+    .map_err(AppError::from)?;
+
+  let mut tags_by_todo: HashMap<String, Vec<String>> = HashMap::new();
+  for row in rows {
+    let (todo_id, name) = row.map_err(AppError::from)?;
+    tags_by_todo.entry(todo_id).or_default().push(name);
+  }
+
+  Ok(tags_by_todo)
+}
+
+#[tauri::command]
+fn set_todo_tags(state: State<'_, AppState>, todo_id: String, tags: Vec<String>) -> CommandResult<Vec<String>> {
+  let mut conn = db_conn(&state)?;
+
+  let tags = normalize_tags(tags);
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  replace_todo_tags_in(&tx, &todo_id, &tags)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(tags)
+}
+
+fn replace_todo_tags_in(conn: &Connection, todo_id: &str, tags: &[String]) -> CommandResult<()> {
+  if get_todo_by_id(conn, todo_id, false)?.is_none() {
+    return Err(AppError::NotFound(format!("Todo not found: {todo_id}")));
+  }
+
+  conn
+    .execute("DELETE FROM todo_tags WHERE todo_id = ?1", params![todo_id])
+    .map_err(AppError::from)?;
+
+  for tag in tags {
+    conn
+      .execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])
+      .map_err(AppError::from)?;
+    conn
+      .execute(
+        "INSERT INTO todo_tags (todo_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+        params![todo_id, tag],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  // Drop labels no todo uses anymore so `list_tags` stays meaningful.
+  conn
+    .execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM todo_tags)", [])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+#[tauri::command]
+fn list_tags(state: State<'_, AppState>) -> CommandResult<Vec<String>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare("SELECT name FROM tags ORDER BY name ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut tags = Vec::new();
+  for row in rows {
+    tags.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(tags)
+}
+
+#[tauri::command]
+fn tag_completion_stats(state: State<'_, AppState>) -> CommandResult<Vec<TagCompletionStats>> {
+  let conn = db_conn(&state)?;
+
+  tag_completion_stats_in(&conn)
+}
+
+// Trashed todos don't count, so a tag whose todos are all in the trash drops
+// out. Best-finished tags come first, then the busier of two equal ratios.
+fn tag_completion_stats_in(conn: &Connection) -> CommandResult<Vec<TagCompletionStats>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT tags.name, COUNT(*) AS total, SUM(todos.completed) AS completed
+       FROM todo_tags
+       JOIN tags ON tags.id = todo_tags.tag_id
+       JOIN todos ON todos.id = todo_tags.todo_id
+       WHERE todos.deleted_at IS NULL
+       GROUP BY tags.id
+       ORDER BY CAST(completed AS REAL) / total DESC, total DESC, tags.name ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| {
+      let total: u32 = row.get(1)?;
+      let completed: u32 = row.get(2)?;
+      Ok(TagCompletionStats {
+        tag: row.get(0)?,
+        total,
+        completed,
+        ratio: f64::from(completed) / f64::from(total),
+      })
+    })
+    .map_err(AppError::from)?;
+
+  let mut stats = Vec::new();
+  for row in rows {
+    stats.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(stats)
+}
+
+#[tauri::command]
+fn list_todos_by_tag(state: State<'_, AppState>, tag: String) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE deleted_at IS NULL
+         AND archived = 0
+         AND id IN (
+           SELECT todo_tags.todo_id FROM todo_tags
+           JOIN tags ON tags.id = todo_tags.tag_id
+           WHERE tags.name = ?1
+         )
+       ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![tag.trim().to_lowercase()], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+fn map_subtask_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Subtask> {
+  Ok(Subtask {
+    id: row.get(0)?,
+    todo_id: row.get(1)?,
+    title: row.get(2)?,
+    completed: row.get::<_, i64>(3)? != 0,
+    sort_order: row.get(4)?,
+    created_at: row.get(5)?,
+    updated_at: row.get(6)?,
+  })
+}
+
+fn get_subtask_by_id(conn: &Connection, id: &str) -> CommandResult<Option<Subtask>> {
+  conn
+    .query_row(
+      "SELECT id, todo_id, title, completed, sort_order, created_at, updated_at FROM subtasks WHERE id = ?1",
+      params![id],
+      map_subtask_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn list_subtasks(state: State<'_, AppState>, todo_id: String) -> CommandResult<Vec<Subtask>> {
+  let conn = db_conn(&state)?;
+
+  subtasks_of(&conn, &todo_id)
+}
+
+fn subtasks_of(conn: &Connection, todo_id: &str) -> CommandResult<Vec<Subtask>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT id, todo_id, title, completed, sort_order, created_at, updated_at
+       FROM subtasks
+       WHERE todo_id = ?1
+       ORDER BY sort_order ASC, created_at ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![todo_id], map_subtask_row)
+    .map_err(AppError::from)?;
+
+  let mut subtasks = Vec::new();
+  for row in rows {
+    subtasks.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(subtasks)
+}
+
+#[tauri::command]
+fn add_subtask(state: State<'_, AppState>, todo_id: String, title: String) -> CommandResult<Subtask> {
+  let conn = db_conn(&state)?;
+
+  let trimmed_title = title.trim();
+  if trimmed_title.is_empty() {
+    return Err(AppError::Validation("Title cannot be empty".to_string()));
+  }
+
+  if get_todo_by_id(&conn, &todo_id, false)?.is_none() {
+    return Err(AppError::NotFound(format!("Todo not found: {todo_id}")));
+  }
+
+  let sort_order: i64 = conn
+    .query_row(
+      "SELECT COALESCE(MAX(sort_order), -1) + 1 FROM subtasks WHERE todo_id = ?1",
+      params![&todo_id],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+
+  let now = now_iso();
+  let subtask = Subtask {
+    id: Uuid::new_v4().to_string(),
+    todo_id,
+    title: trimmed_title.to_string(),
+    completed: false,
+    sort_order,
+    created_at: now.clone(),
+    updated_at: now,
+  };
+
+  conn
+    .execute(
+      "INSERT INTO subtasks (id, todo_id, title, completed, sort_order, created_at, updated_at)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+      params![
+        &subtask.id,
+        &subtask.todo_id,
+        &subtask.title,
+        to_db_bool(subtask.completed),
+        subtask.sort_order,
+        &subtask.created_at,
+        &subtask.updated_at,
+      ],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(subtask)
+}
+
+#[tauri::command]
+fn toggle_subtask(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Subtask> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_subtask_by_id(&tx, &id)?.ok_or_else(|| AppError::NotFound(format!("Subtask not found: {id}")))?;
+  target.completed = !target.completed;
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE subtasks SET completed = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, to_db_bool(target.completed), &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+
+  let parent_completed = target.completed
+    && get_ui_prefs_from_conn(&tx)?.auto_complete_parent
+    && complete_parent_if_subtasks_done(&tx, &target.todo_id)?;
+  tx.commit().map_err(AppError::from)?;
+
+  if parent_completed {
+    emit_todos_changed(&app, "toggled", Some(&target.todo_id));
+  }
+
+  Ok(target)
+}
+
+// With `auto_complete_parent` on, checking off the last open subtask completes
+// the parent as well. Unchecking a subtask leaves the parent as it is.
+fn complete_parent_if_subtasks_done(conn: &Connection, todo_id: &str) -> CommandResult<bool> {
+  let open_subtasks: i64 = conn
+    .query_row(
+      "SELECT COUNT(*) FROM subtasks WHERE todo_id = ?1 AND completed = 0",
+      params![todo_id],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+  if open_subtasks > 0 {
+    return Ok(false);
+  }
+
+  let Some(mut parent) = get_todo_by_id(conn, todo_id, false)? else {
+    return Ok(false);
+  };
+  if parent.completed {
+    return Ok(false);
+  }
+
+  parent.completed = true;
+  parent.updated_at = now_iso();
+  parent.completed_at = Some(parent.updated_at.clone());
+  count_recurring_completion(&mut parent);
+
+  conn
+    .execute(
+      "UPDATE todos SET completed = 1, updated_at = ?2, completed_at = ?3, recurrence_completed_count = ?4 WHERE id = ?1",
+      params![&parent.id, &parent.updated_at, &parent.completed_at, parent.recurrence_completed_count],
+    )
+    .map_err(AppError::from)?;
+  record_completion_change(conn, &parent)?;
+
+  Ok(true)
+}
+
+// A parent agrees with its subtasks when it is done exactly when all of them
+// are. Todos without subtasks always agree.
+fn subtask_state_disagrees(parent_completed: bool, subtask_count: u32, completed_subtask_count: u32) -> bool {
+  subtask_count > 0 && parent_completed != (completed_subtask_count >= subtask_count)
+}
+
+#[tauri::command]
+fn check_subtask_consistency(state: State<'_, AppState>) -> CommandResult<Vec<SubtaskInconsistency>> {
+  let conn = db_conn(&state)?;
+
+  check_subtask_consistency_in(&conn)
+}
+
+fn check_subtask_consistency_in(conn: &Connection) -> CommandResult<Vec<SubtaskInconsistency>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT todos.id, COUNT(*), COALESCE(SUM(subtasks.completed), 0)
+       FROM todos
+       JOIN subtasks ON subtasks.todo_id = todos.id
+       WHERE todos.deleted_at IS NULL
+       GROUP BY todos.id
+       ORDER BY todos.sort_order ASC, todos.created_at DESC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?, row.get::<_, u32>(2)?)))
+    .map_err(AppError::from)?;
+
+  let mut counts = Vec::new();
+  for row in rows {
+    counts.push(row.map_err(AppError::from)?);
+  }
+
+  let mut inconsistencies = Vec::new();
+  for (id, subtask_count, completed_subtask_count) in counts {
+    let Some(todo) = get_todo_by_id(conn, &id, false)? else {
+      continue;
+    };
+    if subtask_state_disagrees(todo.completed, subtask_count, completed_subtask_count) {
+      inconsistencies.push(SubtaskInconsistency {
+        todo,
+        subtask_count,
+        completed_subtask_count,
+      });
+    }
+  }
+
+  Ok(inconsistencies)
+}
+
+#[tauri::command]
+fn fix_subtask_consistency(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  policy: SubtaskConsistencyPolicy,
+) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let fixed = fix_subtask_consistency_in(&tx, policy)?;
+  tx.commit().map_err(AppError::from)?;
+
+  if fixed > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(fixed)
+}
+
+// `ParentWins` checks or unchecks every subtask to match the parent;
+// `SubtasksWin` completes or reopens the parent, logging it like a toggle.
+fn fix_subtask_consistency_in(conn: &Connection, policy: SubtaskConsistencyPolicy) -> CommandResult<usize> {
+  let inconsistencies = check_subtask_consistency_in(conn)?;
+  let now = now_iso();
+
+  for entry in &inconsistencies {
+    let mut parent = entry.todo.clone();
+    match policy {
+      SubtaskConsistencyPolicy::ParentWins => {
+        set_subtasks_completed(conn, &parent.id, parent.completed, &now)?;
+      }
+      SubtaskConsistencyPolicy::SubtasksWin => {
+        parent.completed = !parent.completed;
+        parent.updated_at = now.clone();
+        parent.completed_at = parent.completed.then(|| now.clone());
+        count_recurring_completion(&mut parent);
+
+        conn
+          .execute(
+            "UPDATE todos SET completed = ?2, updated_at = ?3, completed_at = ?4, recurrence_completed_count = ?5 WHERE id = ?1",
+            params![
+              &parent.id,
+              to_db_bool(parent.completed),
+              &parent.updated_at,
+              &parent.completed_at,
+              parent.recurrence_completed_count,
+            ],
+          )
+          .map_err(AppError::from)?;
+        record_completion_change(conn, &parent)?;
+      }
+    }
+  }
+
+  Ok(inconsistencies.len())
+}
+
+fn set_subtasks_completed(conn: &Connection, todo_id: &str, completed: bool, now: &str) -> CommandResult<usize> {
+  conn
+    .execute(
+      "UPDATE subtasks SET completed = ?2, updated_at = ?3 WHERE todo_id = ?1 AND completed <> ?2",
+      params![todo_id, to_db_bool(completed), now],
+    )
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn set_all_subtasks_completed(state: State<'_, AppState>, todo_id: String, completed: bool) -> CommandResult<Vec<Subtask>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  if get_todo_by_id(&tx, &todo_id, false)?.is_none() {
+    return Err(AppError::NotFound(format!("Todo not found: {todo_id}")));
+  }
+
+  set_subtasks_completed(&tx, &todo_id, completed, &now_iso())?;
+  let subtasks = subtasks_of(&tx, &todo_id)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(subtasks)
+}
+
+#[tauri::command]
+fn delete_subtask(state: State<'_, AppState>, id: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  conn
+    .execute("DELETE FROM subtasks WHERE id = ?1", params![id])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+fn count_attachments_in(conn: &Connection, todo_id: &str) -> CommandResult<u32> {
+  conn
+    .query_row(
+      "SELECT COUNT(*) FROM todo_attachments WHERE todo_id = ?1",
+      params![todo_id],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)
+}
+
+// Run inside the caller's write transaction so two adds can't both see room
+// under the cap. A `limit` of 0 means no cap.
+fn insert_attachment_within_limit(conn: &Connection, attachment: &Attachment, limit: u32) -> CommandResult<()> {
+  if get_todo_by_id(conn, &attachment.todo_id, false)?.is_none() {
+    return Err(AppError::NotFound(format!("Todo not found: {}", attachment.todo_id)));
+  }
+  if limit > 0 && count_attachments_in(conn, &attachment.todo_id)? >= limit {
+    return Err(AppError::LimitReached(format!("A todo can have at most {limit} attachments")));
+  }
+
+  conn
+    .execute(
+      "INSERT INTO todo_attachments (id, todo_id, path, label, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+      params![
+        &attachment.id,
+        &attachment.todo_id,
+        &attachment.path,
+        &attachment.label,
+        &attachment.created_at,
+      ],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Stores the canonical absolute path so the link survives the frontend's
+// working directory and symlinked spellings of the same file compare equal.
+#[tauri::command]
+fn add_attachment(
+  state: State<'_, AppState>,
+  todo_id: String,
+  path: String,
+  label: Option<String>,
+) -> CommandResult<Attachment> {
+  let mut conn = db_conn(&state)?;
+
+  let canonical = std::fs::canonicalize(path.trim())
+    .map_err(|_| AppError::Validation(format!("Attachment path does not exist: {path}")))?;
+  let canonical = canonical
+    .to_str()
+    .ok_or_else(|| AppError::Validation(format!("Attachment path is not valid UTF-8: {path}")))?
+    .to_string();
+
+  let attachment = Attachment {
+    id: Uuid::new_v4().to_string(),
+    todo_id,
+    path: canonical,
+    label: label.map(|value| value.trim().to_string()).filter(|value| !value.is_empty()),
+    created_at: now_iso(),
+  };
+
+  let limit = get_ui_prefs_from_conn(&conn)?.max_attachments_per_todo;
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  insert_attachment_within_limit(&tx, &attachment, limit)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(attachment)
+}
+
+#[tauri::command]
+fn count_attachments(state: State<'_, AppState>, todo_id: String) -> CommandResult<u32> {
+  let conn = db_conn(&state)?;
+
+  count_attachments_in(&conn, &todo_id)
+}
+
+#[tauri::command]
+fn list_attachments(state: State<'_, AppState>, todo_id: String) -> CommandResult<Vec<Attachment>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(
+      "SELECT id, todo_id, path, label, created_at
+       FROM todo_attachments
+       WHERE todo_id = ?1
+       ORDER BY created_at ASC",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![todo_id], |row| {
+      Ok(Attachment {
+        id: row.get(0)?,
+        todo_id: row.get(1)?,
+        path: row.get(2)?,
+        label: row.get(3)?,
+        created_at: row.get(4)?,
+      })
+    })
+    .map_err(AppError::from)?;
+
+  let mut attachments = Vec::new();
+  for row in rows {
+    attachments.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(attachments)
+}
+
+fn running_time_entry(conn: &Connection, todo_id: &str) -> CommandResult<Option<TimeEntry>> {
+  conn
+    .query_row(
+      "SELECT id, todo_id, started_at, stopped_at FROM time_entries WHERE todo_id = ?1 AND stopped_at IS NULL",
+      params![todo_id],
+      |row| {
+        Ok(TimeEntry {
+          id: row.get(0)?,
+          todo_id: row.get(1)?,
+          started_at: row.get(2)?,
+          stopped_at: row.get(3)?,
+        })
+      },
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+// Starting a timer that is already running is a no-op that returns the
+// running entry, so a double click can't open a second one.
+#[tauri::command]
+fn start_timer(state: State<'_, AppState>, id: String) -> CommandResult<TimeEntry> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  if get_todo_by_id(&tx, &id, false)?.is_none() {
+    return Err(AppError::NotFound(format!("Todo not found: {id}")));
+  }
+  if let Some(running) = running_time_entry(&tx, &id)? {
+    return Ok(running);
+  }
+
+  let started_at = now_iso();
+  tx
+    .execute(
+      "INSERT INTO time_entries (todo_id, started_at) VALUES (?1, ?2)",
+      params![&id, &started_at],
+    )
+    .map_err(AppError::from)?;
+  let entry = TimeEntry {
+    id: tx.last_insert_rowid(),
+    todo_id: id,
+    started_at,
+    stopped_at: None,
+  };
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(entry)
+}
+
+#[tauri::command]
+fn stop_timer(state: State<'_, AppState>, id: String) -> CommandResult<TimeEntry> {
+  let conn = db_conn(&state)?;
+
+  let mut entry = running_time_entry(&conn, &id)?
+    .ok_or_else(|| AppError::Validation(format!("No timer is running for todo: {id}")))?;
+  let stopped_at = now_iso();
+  conn
+    .execute(
+      "UPDATE time_entries SET stopped_at = ?2 WHERE id = ?1",
+      params![entry.id, &stopped_at],
+    )
+    .map_err(AppError::from)?;
+  entry.stopped_at = Some(stopped_at);
+
+  Ok(entry)
+}
+
+// Whole minutes across all entries; a running timer counts up to now.
+#[tauri::command]
+fn get_tracked_minutes(state: State<'_, AppState>, id: String) -> CommandResult<i64> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare("SELECT started_at, stopped_at FROM time_entries WHERE todo_id = ?1")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))
+    .map_err(AppError::from)?;
+
+  let now = Utc::now();
+  let mut tracked = Duration::zero();
+  for row in rows {
+    let (started_at, stopped_at) = row.map_err(AppError::from)?;
+    let Ok(started) = DateTime::parse_from_rfc3339(&started_at) else {
+      continue;
+    };
+    let stopped = match stopped_at {
+      Some(value) => match DateTime::parse_from_rfc3339(&value) {
+        Ok(parsed) => parsed.with_timezone(&Utc),
+        Err(_) => continue,
+      },
+      None => now,
+    };
+    tracked += (stopped - started.with_timezone(&Utc)).max(Duration::zero());
+  }
+
+  Ok(tracked.num_minutes())
+}
+
+#[tauri::command]
+fn remove_attachment(state: State<'_, AppState>, id: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  conn
+    .execute("DELETE FROM todo_attachments WHERE id = ?1", params![id])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Walks the existing edges from `start`; adding `todo_id -> depends_on_id`
+// closes a cycle exactly when `todo_id` is reachable from `depends_on_id`.
+fn dependency_reaches(conn: &Connection, start: &str, target: &str) -> CommandResult<bool> {
+  let mut edges: HashMap<String, Vec<String>> = HashMap::new();
+  {
+    let mut statement = conn
+      .prepare("SELECT todo_id, depends_on_id FROM todo_dependencies")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(AppError::from)?;
+
+    for row in rows {
+      let (todo_id, depends_on_id) = row.map_err(AppError::from)?;
+      edges.entry(todo_id).or_default().push(depends_on_id);
+    }
+  }
+
+  let mut visited = HashSet::new();
+  let mut pending = vec![start.to_string()];
+  while let Some(current) = pending.pop() {
+    if current == target {
+      return Ok(true);
+    }
+    if !visited.insert(current.clone()) {
+      continue;
+    }
+    if let Some(next) = edges.get(&current) {
+      pending.extend(next.iter().cloned());
+    }
+  }
+
+  Ok(false)
+}
+
+#[tauri::command]
+fn add_dependency(state: State<'_, AppState>, todo_id: String, depends_on_id: String) -> CommandResult<()> {
+  let mut conn = db_conn(&state)?;
+
+  if todo_id == depends_on_id {
+    return Err(AppError::Validation("A todo cannot depend on itself".to_string()));
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  for id in [&todo_id, &depends_on_id] {
+    if get_todo_by_id(&tx, id, false)?.is_none() {
+      return Err(AppError::NotFound(format!("Todo not found: {id}")));
+    }
+  }
+
+  if dependency_reaches(&tx, &depends_on_id, &todo_id)? {
+    return Err(AppError::Validation(format!(
+      "Dependency would create a cycle: {depends_on_id} already depends on {todo_id}"
+    )));
+  }
+
+  tx
+    .execute(
+      "INSERT OR IGNORE INTO todo_dependencies (todo_id, depends_on_id) VALUES (?1, ?2)",
+      params![&todo_id, &depends_on_id],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(())
+}
+
+#[tauri::command]
+fn remove_dependency(state: State<'_, AppState>, todo_id: String, depends_on_id: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  conn
+    .execute(
+      "DELETE FROM todo_dependencies WHERE todo_id = ?1 AND depends_on_id = ?2",
+      params![todo_id, depends_on_id],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+// Open todos waiting on at least one open dependency. Trashed dependencies no
+// longer block anything.
+#[tauri::command]
+fn list_blocked_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos
+       WHERE completed = 0 AND deleted_at IS NULL AND archived = 0
+         AND EXISTS (
+           SELECT 1 FROM todo_dependencies
+           JOIN todos AS blocker ON blocker.id = todo_dependencies.depends_on_id
+           WHERE todo_dependencies.todo_id = todos.id AND blocker.completed = 0 AND blocker.deleted_at IS NULL
+         )
+       ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+// Pushes the todo `minutes` past now (or past its current due time, if later)
+// and re-arms its due notification.
+#[tauri::command]
+fn snooze_todo(state: State<'_, AppState>, app: AppHandle, id: String, minutes: i64) -> CommandResult<Todo> {
+  let mut conn = db_conn(&state)?;
+
+  let snooze = Duration::try_minutes(minutes)
+    .filter(|_| minutes > 0)
+    .ok_or_else(|| AppError::Validation(format!("Invalid snooze length in minutes: {minutes}")))?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut target = get_todo_by_id(&tx, &id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  let now = Local::now();
+  let base = target
+    .due_date
+    .as_deref()
+    .and_then(due_instant)
+    .filter(|instant| *instant > now)
+    .unwrap_or(now);
+  let snoozed_until = base
+    .checked_add_signed(snooze)
+    .ok_or_else(|| AppError::Validation(format!("Invalid snooze length in minutes: {minutes}")))?;
+
+  target.due_date = Some(snoozed_until.to_rfc3339());
+  target.notified_at = None;
+  target.snooze_count += 1;
+  target.updated_at = now_iso();
+
+  tx
+    .execute(
+      "UPDATE todos SET due_date = ?2, notified_at = NULL, snooze_count = ?3, updated_at = ?4 WHERE id = ?1",
+      params![&target.id, &target.due_date, target.snooze_count, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+// Like `snooze_todo` for every overdue todo at once: each todo's own due date
+// moves `minutes` later. Plain days count from midnight in the caller's
+// timezone and become timestamps there.
+#[tauri::command]
+fn snooze_overdue(state: State<'_, AppState>, app: AppHandle, minutes: i64, tz_offset_minutes: i32) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let snooze = Duration::try_minutes(minutes)
+    .filter(|_| minutes > 0)
+    .ok_or_else(|| AppError::Validation(format!("Invalid snooze length in minutes: {minutes}")))?;
+  let offset = FixedOffset::east_opt(tz_offset_minutes.saturating_mul(60))
+    .ok_or_else(|| AppError::Validation(format!("Invalid timezone offset: {tz_offset_minutes} minutes")))?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let snoozed = snooze_overdue_in(&tx, snooze, &offset)?;
+  tx.commit().map_err(AppError::from)?;
+
+  if snoozed > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(snoozed)
+}
+
+fn snooze_overdue_in(conn: &Connection, snooze: Duration, offset: &FixedOffset) -> CommandResult<usize> {
+  let now = now_iso();
+  let mut snoozed = 0;
+  for todo in open_overdue_todos(conn, offset)? {
+    let Some(due_until) = todo
+      .due_date
+      .as_deref()
+      .and_then(|due_date| advance_due_date(due_date, snooze, offset))
+    else {
+      continue;
+    };
+    snoozed += conn
+      .execute(
+        "UPDATE todos SET due_date = ?2, notified_at = NULL, snooze_count = snooze_count + 1, updated_at = ?3 WHERE id = ?1",
+        params![&todo.id, &due_until, &now],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  Ok(snoozed)
+}
+
+fn advance_due_date(value: &str, by: Duration, offset: &FixedOffset) -> Option<String> {
+  let trimmed = value.trim();
+  let due = match NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+    Ok(day) => day.and_hms_opt(0, 0, 0)?.and_local_timezone(*offset).single()?,
+    Err(_) => DateTime::parse_from_rfc3339(trimmed).ok()?.with_timezone(offset),
+  };
+
+  due.checked_add_signed(by).map(|advanced| advanced.to_rfc3339())
+}
+
+// Soft-deleted todos keep their subtasks so a restore brings them back; the
+// subtasks go with the row once it is purged (ON DELETE CASCADE).
+// Moves a todo to the trash, stamping `updated_at` with the deletion time so
+// sync peers see the tombstone as the newest version. Returns 0 when the todo
+// is missing or already trashed.
+fn trash_todo_in(conn: &Connection, id: &str, now: &str) -> CommandResult<usize> {
+  conn
+    .execute(
+      "UPDATE todos SET deleted_at = ?2, updated_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+      params![id, now],
+    )
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn delete_todo(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<usize> {
+  let conn = db_conn(&state)?;
+
+  let snapshot = get_todo_by_id(&conn, &id, false)?;
+  let deleted = trash_todo_in(&conn, &id, &now_iso())?;
+  // Zero rows means the todo was already gone, e.g. removed from another window.
+  if deleted == 0 {
+    return Ok(0);
+  }
+  if let Some(snapshot) = snapshot {
+    push_undo(&state, UndoEntry::Delete(vec![snapshot]))?;
+  }
+  emit_todos_changed(&app, "deleted", Some(&id));
+
+  Ok(deleted)
+}
+
+#[tauri::command]
+fn bulk_set_completed(state: State<'_, AppState>, app: AppHandle, ids: Vec<String>, completed: bool) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let affected = bulk_set_completed_in(&tx, &ids, completed)?;
+  tx.commit().map_err(AppError::from)?;
+  if affected > 0 {
+    emit_todos_changed(&app, "toggled", None);
+  }
+
+  Ok(affected)
+}
+
+fn bulk_set_completed_in(conn: &Connection, ids: &[String], completed: bool) -> CommandResult<usize> {
+  let now = now_iso();
+  let mut affected = 0;
+
+  for id in ids {
+    let Some(mut target) = get_todo_by_id(conn, id, false)? else {
+      continue;
+    };
+    if target.completed == completed {
+      continue;
+    }
+
+    target.completed = completed;
+    target.updated_at = now.clone();
+    target.completed_at = if completed { Some(now.clone()) } else { None };
+    count_recurring_completion(&mut target);
+
+    conn
+      .execute(
+        "UPDATE todos SET completed = ?2, updated_at = ?3, completed_at = ?4, recurrence_completed_count = ?5 WHERE id = ?1",
+        params![
+          &target.id,
+          to_db_bool(target.completed),
+          &target.updated_at,
+          &target.completed_at,
+          target.recurrence_completed_count,
+        ],
+      )
+      .map_err(AppError::from)?;
+    record_completion_change(conn, &target)?;
+    affected += 1;
+  }
+
+  Ok(affected)
+}
+
+// Applies the same patch to every listed todo, normalized as in `update_todo`.
+// Ids that are missing or trashed are skipped.
+#[tauri::command]
+fn bulk_update(state: State<'_, AppState>, app: AppHandle, ids: Vec<String>, patch: BulkPatch) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let recurrence_tag = patch.recurrence_tag.map(|tag| normalize_recurrence_tag(Some(tag)));
+  let due_date = patch.due_date.map(normalize_date).transpose()?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let now = now_iso();
+  let mut updated = Vec::new();
+
+  for id in &ids {
+    let Some(mut target) = get_todo_by_id(&tx, id, false)? else {
+      continue;
+    };
+
+    if let Some(recurrence_tag) = &recurrence_tag {
+      target.recurrence_tag = recurrence_tag.clone();
+    }
+    if let Some(due_date) = &due_date {
+      if *due_date != target.due_date {
+        target.notified_at = None;
+      }
+      target.due_date = due_date.clone();
+    }
+    let completion_changed = patch.completed.is_some_and(|completed| completed != target.completed);
+    if completion_changed {
+      target.completed = !target.completed;
+      target.completed_at = if target.completed { Some(now.clone()) } else { None };
+      count_recurring_completion(&mut target);
+    }
+    target.updated_at = now.clone();
+
+    update_todo_row(&tx, &target)?;
+    if completion_changed {
+      record_completion_change(&tx, &target)?;
+    }
+    updated.push(target);
+  }
+
+  tx.commit().map_err(AppError::from)?;
+  if !updated.is_empty() {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(updated)
+}
+
+// Moves the todos to the trash, like `delete_todo`.
+#[tauri::command]
+fn bulk_delete(state: State<'_, AppState>, app: AppHandle, ids: Vec<String>) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let snapshots = bulk_delete_in(&tx, &ids, &now_iso())?;
+  tx.commit().map_err(AppError::from)?;
+  let affected = snapshots.len();
+  if affected > 0 {
+    push_undo(&state, UndoEntry::Delete(snapshots))?;
+    emit_todos_changed(&app, "deleted", None);
+  }
+
+  Ok(affected)
+}
+
+// Returns the todos as they were before being trashed.
+fn bulk_delete_in(conn: &Connection, ids: &[String], now: &str) -> CommandResult<Vec<Todo>> {
+  let mut snapshots = Vec::new();
+
+  for id in ids {
+    let Some(snapshot) = get_todo_by_id(conn, id, false)? else {
+      continue;
+    };
+    trash_todo_in(conn, id, now)?;
+    snapshots.push(snapshot);
+  }
+
+  Ok(snapshots)
+}
+
+// Todos matching `filter` among the listed ones that `operation` would change,
+// in list order. Nothing is written. `Complete` skips todos already done, like
+// `bulk_set_completed`; `Archive` only takes completed todos, like
+// `archive_all_completed`.
+#[tauri::command]
+fn preview_bulk(state: State<'_, AppState>, operation: BulkOperation, filter: CountFilter) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  preview_bulk_in(&conn, operation, filter, local_today_naive())
+}
+
+fn preview_bulk_in(
+  conn: &Connection,
+  operation: BulkOperation,
+  filter: CountFilter,
+  today: NaiveDate,
+) -> CommandResult<Vec<Todo>> {
+  Ok(
+    todos_matching_filter(conn, filter, today)?
+      .into_iter()
+      .filter(|todo| match operation {
+        BulkOperation::Delete => true,
+        BulkOperation::Complete => !todo.completed,
+        BulkOperation::Archive => todo.completed,
+      })
+      .collect(),
+  )
+}
+
+// The rows `count_todos` counts for `filter`, in list order.
+fn todos_matching_filter(conn: &Connection, filter: CountFilter, today: NaiveDate) -> CommandResult<Vec<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0 ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    let due_day = todo.due_date.as_deref().and_then(|value| parse_due_day(value, &Local));
+    let matches = match filter {
+      CountFilter::All => true,
+      CountFilter::Incomplete => !todo.completed,
+      CountFilter::Completed => todo.completed,
+      CountFilter::Overdue => !todo.completed && due_day.is_some_and(|due_day| due_day < today),
+      CountFilter::DueToday => !todo.completed && due_day == Some(today),
+    };
+    if matches {
+      todos.push(todo);
+    }
+  }
+
+  Ok(todos)
+}
+
+// Reverses the most recent delete or toggle. A deleted todo whose id is live
+// again (restored from the trash, imported or synced since) is left as is and
+// reported in `skipped_ids`; one purged in the meantime is re-inserted.
+#[tauri::command]
+fn undo_last(state: State<'_, AppState>, app: AppHandle) -> CommandResult<Option<UndoDescription>> {
+  let mut stack = state.lock_undo_stack();
+  let Some(entry) = stack.last() else {
+    return Ok(None);
+  };
+
+  let mut conn = db_conn(&state)?;
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let description = apply_undo_in(&tx, entry)?;
+  tx.commit().map_err(AppError::from)?;
+  stack.pop();
+  if !description.restored_ids.is_empty() {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(Some(description))
+}
+
+// Undoing a toggle restores the completion fields but stamps `updated_at` now,
+// since the row has changed again.
+fn apply_undo_in(conn: &Connection, entry: &UndoEntry) -> CommandResult<UndoDescription> {
+  let mut description = UndoDescription {
+    kind: "delete",
+    restored_ids: Vec::new(),
+    skipped_ids: Vec::new(),
+  };
+
+  match entry {
+    UndoEntry::Delete(snapshots) => {
+      for snapshot in snapshots {
+        match get_todo_by_id(conn, &snapshot.id, true)? {
+          Some(current) if current.deleted_at.is_none() => {
+            description.skipped_ids.push(snapshot.id.clone());
+            continue;
+          }
+          Some(_) => update_todo_row(conn, snapshot)?,
+          None => insert_todo(conn, snapshot)?,
+        }
+        description.restored_ids.push(snapshot.id.clone());
+      }
+    }
+    UndoEntry::Toggle(snapshot) => {
+      description.kind = "toggle";
+      match get_todo_by_id(conn, &snapshot.id, false)? {
+        Some(mut current) => {
+          current.completed = snapshot.completed;
+          current.completed_at = snapshot.completed_at.clone();
+          current.recurrence_completed_count = snapshot.recurrence_completed_count;
+          current.updated_at = now_iso();
+          update_todo_row(conn, &current)?;
+          record_completion_change(conn, &current)?;
+          description.restored_ids.push(current.id);
+        }
+        None => description.skipped_ids.push(snapshot.id.clone()),
+      }
+    }
+  }
+
+  Ok(description)
+}
+
+// Archiving only flags the row, so `sort_order` is untouched and an unarchived
+// todo returns to where it was.
+fn set_archived(conn: &Connection, id: &str, archived: bool) -> CommandResult<Todo> {
+  let mut target = get_todo_by_id(conn, id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  target.archived = archived;
+  target.updated_at = now_iso();
+
+  conn
+    .execute(
+      "UPDATE todos SET archived = ?2, updated_at = ?3 WHERE id = ?1",
+      params![&target.id, to_db_bool(archived), &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn archive_todo(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let conn = db_conn(&state)?;
+
+  let target = set_archived(&conn, &id, true)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn unarchive_todo(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let conn = db_conn(&state)?;
+
+  let target = set_archived(&conn, &id, false)?;
+  emit_todos_changed(&app, "updated", Some(&target.id));
+
+  Ok(target)
+}
+
+#[tauri::command]
+fn archive_all_completed(state: State<'_, AppState>, app: AppHandle) -> CommandResult<usize> {
+  let conn = db_conn(&state)?;
+
+  let archived = archive_all_completed_in(&conn)?;
+  if archived > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(archived)
+}
+
+fn archive_all_completed_in(conn: &Connection) -> CommandResult<usize> {
+  conn
+    .execute(
+      "UPDATE todos SET archived = 1, updated_at = ?1 WHERE completed = 1 AND archived = 0 AND deleted_at IS NULL",
+      params![now_iso()],
+    )
+    .map_err(AppError::from)
+}
+
+// A completed todo is archived once its completion is at least `days` old.
+// Todos without a readable `completed_at` are left alone.
+fn should_auto_archive(todo: &Todo, now: DateTime<Utc>, days: u32) -> bool {
+  if days == 0 || !todo.completed || todo.archived || todo.deleted_at.is_some() {
+    return false;
+  }
+
+  todo
+    .completed_at
+    .as_deref()
+    .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+    .is_some_and(|completed_at| now.signed_duration_since(completed_at) >= Duration::days(i64::from(days)))
+}
+
+fn auto_archive_in(conn: &Connection, days: u32, now: DateTime<Utc>) -> CommandResult<usize> {
+  let candidates = {
+    let mut statement = conn
+      .prepare(&format!(
+        "SELECT {TODO_COLUMNS} FROM todos WHERE completed = 1 AND archived = 0 AND deleted_at IS NULL"
+      ))
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], map_todo_row)
+      .map_err(AppError::from)?;
+
+    let mut candidates = Vec::new();
+    for row in rows {
+      candidates.push(row.map_err(AppError::from)?);
+    }
+    candidates
+  };
+
+  let updated_at = now.to_rfc3339();
+  let mut archived = 0;
+  for todo in candidates.iter().filter(|todo| should_auto_archive(todo, now, days)) {
+    conn
+      .execute(
+        "UPDATE todos SET archived = 1, updated_at = ?2 WHERE id = ?1",
+        params![&todo.id, &updated_at],
+      )
+      .map_err(AppError::from)?;
+    archived += 1;
+  }
+
+  Ok(archived)
+}
+
+// Runs the `auto_archive_days` sweep that otherwise happens at launch.
+#[tauri::command]
+fn auto_archive_now(state: State<'_, AppState>, app: AppHandle) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let days = get_ui_prefs_from_conn(&conn)?.auto_archive_days;
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let archived = auto_archive_in(&tx, days, Utc::now())?;
+  tx.commit().map_err(AppError::from)?;
+  if archived > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(archived)
+}
+
+#[tauri::command]
+fn list_archived_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE archived = 1 AND deleted_at IS NULL ORDER BY sort_order ASC, created_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+#[tauri::command]
+fn request_wipe_token(state: State<'_, AppState>) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let token = WipeToken {
+    token: Uuid::new_v4().to_string(),
+    expires_at: (Utc::now() + Duration::seconds(WIPE_TOKEN_TTL_SECONDS)).to_rfc3339(),
+  };
+  set_meta(&conn, WIPE_TOKEN_KEY, &serde_json::to_string(&token).map_err(AppError::from)?)?;
+
+  Ok(token.token)
+}
+
+// Deletes every todo (and with them subtasks, tags and history) and clears the
+// legacy-migration flag. Window and UI prefs are kept. The token from
+// `request_wipe_token` is single-use: it is consumed even when rejected.
+#[tauri::command]
+fn wipe_all_data(state: State<'_, AppState>, app: AppHandle, confirm_token: String) -> CommandResult<()> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let stored = get_meta(&tx, WIPE_TOKEN_KEY)?;
+  delete_meta(&tx, WIPE_TOKEN_KEY)?;
+
+  let valid = stored
+    .and_then(|raw| serde_json::from_str::<WipeToken>(&raw).ok())
+    .filter(|stored| stored.token == confirm_token.trim())
+    .and_then(|stored| DateTime::parse_from_rfc3339(&stored.expires_at).ok())
+    .is_some_and(|expires_at| Utc::now() < expires_at);
+  if !valid {
+    tx.commit().map_err(AppError::from)?;
+    return Err(AppError::Validation("Wipe confirmation token is missing, expired or does not match".to_string()));
+  }
+
+  tx
+    .execute_batch(
+      "DELETE FROM todos;
+       DELETE FROM tags;
+       DELETE FROM daily_completion_events;",
+    )
+    .map_err(AppError::from)?;
+  tx
+    .execute(
+      "DELETE FROM app_meta WHERE key LIKE ?1 || '%'",
+      params![MIGRATION_KEY_PREFIX],
+    )
+    .map_err(AppError::from)?;
+  tx.commit().map_err(AppError::from)?;
+
+  state.lock_undo_stack().clear();
+  emit_todos_changed(&app, "deleted", None);
+
+  Ok(())
+}
+
+#[tauri::command]
+fn list_trashed_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
+  let conn = db_conn(&state)?;
+
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(todos)
+}
+
+#[tauri::command]
+fn restore_todo(state: State<'_, AppState>, app: AppHandle, id: String) -> CommandResult<Todo> {
+  let conn = db_conn(&state)?;
+
+  let (target, restored) = restore_todo_in(&conn, &id)?;
+  if restored {
+    emit_todos_changed(&app, "updated", Some(&target.id));
+  }
+
+  Ok(target)
+}
+
+// Also reports whether the todo was actually in the trash.
+fn restore_todo_in(conn: &Connection, id: &str) -> CommandResult<(Todo, bool)> {
+  let mut target = get_todo_by_id(conn, id, true)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  if target.deleted_at.is_none() {
+    return Ok((target, false));
+  }
+
+  target.deleted_at = None;
+  target.updated_at = now_iso();
+
+  conn
+    .execute(
+      "UPDATE todos SET deleted_at = NULL, updated_at = ?2 WHERE id = ?1",
+      params![&target.id, &target.updated_at],
+    )
+    .map_err(AppError::from)?;
+
+  Ok((target, true))
+}
+
+// Undoes a burst of deletes: everything trashed at or after `since` (RFC3339)
+// comes back in one transaction.
+#[tauri::command]
+fn restore_trashed_since(state: State<'_, AppState>, app: AppHandle, since: String) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let since = DateTime::parse_from_rfc3339(since.trim())
+    .map_err(|_| AppError::Validation(format!("Invalid timestamp (expected RFC3339): {since}")))?
+    .with_timezone(&Utc);
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let restored = restore_trashed_since_in(&tx, since)?;
+  tx.commit().map_err(AppError::from)?;
+  if restored > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(restored)
+}
+
+// `deleted_at` is compared as a parsed instant rather than as text, since
+// imported rows may carry other offsets or precisions.
+fn restore_trashed_since_in(conn: &Connection, since: DateTime<Utc>) -> CommandResult<usize> {
+  let trashed = {
+    let mut statement = conn
+      .prepare("SELECT id, deleted_at FROM todos WHERE deleted_at IS NOT NULL")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(AppError::from)?;
+
+    let mut trashed = Vec::new();
+    for row in rows {
+      trashed.push(row.map_err(AppError::from)?);
+    }
+    trashed
+  };
+
+  let now = now_iso();
+  let mut restored = 0;
+  for (id, deleted_at) in trashed {
+    let in_window = DateTime::parse_from_rfc3339(&deleted_at).is_ok_and(|at| at >= since);
+    if !in_window {
+      continue;
+    }
+    conn
+      .execute(
+        "UPDATE todos SET deleted_at = NULL, updated_at = ?2 WHERE id = ?1",
+        params![id, &now],
+      )
+      .map_err(AppError::from)?;
+    restored += 1;
+  }
+
+  Ok(restored)
+}
+
+#[tauri::command]
+fn purge_todo(state: State<'_, AppState>, id: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  conn
+    .execute("DELETE FROM todos WHERE id = ?1", params![id])
+    .map_err(AppError::from)?;
+
+  Ok(())
+}
+
+#[tauri::command]
+fn purge_trash_older_than(state: State<'_, AppState>, days: i64) -> CommandResult<usize> {
+  let conn = db_conn(&state)?;
+
+  purge_trash_older_than_in(&conn, days)
+}
+
+// Like `restore_trashed_since_in`, `deleted_at` is parsed rather than compared
+// as text. Rows whose timestamp doesn't parse are kept.
+fn purge_trash_older_than_in(conn: &Connection, days: i64) -> CommandResult<usize> {
+  let cutoff = Duration::try_days(days)
+    .filter(|_| days >= 0)
+    .and_then(|age| Utc::now().checked_sub_signed(age))
+    .ok_or_else(|| AppError::Validation(format!("Invalid trash age in days: {days}")))?;
+
+  let trashed = {
+    let mut statement = conn
+      .prepare("SELECT id, deleted_at FROM todos WHERE deleted_at IS NOT NULL")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(AppError::from)?;
+
+    let mut trashed = Vec::new();
+    for row in rows {
+      trashed.push(row.map_err(AppError::from)?);
+    }
+    trashed
+  };
+
+  let mut purged = 0;
+  for (id, deleted_at) in trashed {
+    if DateTime::parse_from_rfc3339(&deleted_at).is_ok_and(|at| at < cutoff) {
+      purged += conn
+        .execute("DELETE FROM todos WHERE id = ?1", params![id])
+        .map_err(AppError::from)?;
+    }
+  }
+
+  Ok(purged)
+}
+
+// Matches the fixed-width UTC format the change log triggers write.
+fn change_log_timestamp(value: &str) -> CommandResult<String> {
+  DateTime::parse_from_rfc3339(value.trim())
+    .map(|parsed| parsed.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+    .map_err(|_| AppError::Validation(format!("Invalid timestamp: {value}")))
+}
+
+// Entries at or after `iso_timestamp`, oldest first. Inclusive so a cursor
+// taken from the last entry can't miss writes in the same millisecond;
+// consumers should refetch the listed todos rather than replay the ops.
+#[tauri::command]
+fn changes_since(state: State<'_, AppState>, iso_timestamp: String) -> CommandResult<Vec<ChangeEntry>> {
+  let conn = db_conn(&state)?;
+
+  let since = change_log_timestamp(&iso_timestamp)?;
+  let mut statement = conn
+    .prepare("SELECT id, todo_id, op, at FROM change_log WHERE at >= ?1 ORDER BY id ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![since], map_change_entry_row)
+    .map_err(AppError::from)?;
+
+  let mut entries = Vec::new();
+  for row in rows {
+    entries.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(entries)
+}
+
+// Every change log entry for one todo, oldest first. Entries outlive the todo,
+// so trashed and purged todos keep their history until `prune_change_log`.
+// The log records operations only, not before/after snapshots.
+#[tauri::command]
+fn get_todo_history(state: State<'_, AppState>, id: String) -> CommandResult<Vec<ChangeEntry>> {
+  let conn = db_conn(&state)?;
+
+  get_todo_history_in(&conn, &id)
+}
+
+fn get_todo_history_in(conn: &Connection, id: &str) -> CommandResult<Vec<ChangeEntry>> {
+  let mut statement = conn
+    .prepare("SELECT id, todo_id, op, at FROM change_log WHERE todo_id = ?1 ORDER BY id ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![id], map_change_entry_row)
+    .map_err(AppError::from)?;
+
+  let mut entries = Vec::new();
+  for row in rows {
+    entries.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(entries)
+}
+
+// SHA-256 over every live todo with its subtasks and tags, in id order, so any
+// edit a list view could show changes the hash. Archived todos are included;
+// trashed ones drop out.
+fn list_hash_in(conn: &Connection) -> CommandResult<String> {
+  let mut hasher = Sha256::new();
+
+  let mut statement = conn
+    .prepare(&format!("SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL ORDER BY id ASC"))
+    .map_err(AppError::from)?;
+  let rows = statement.query_map([], map_todo_row).map_err(AppError::from)?;
+  for row in rows {
+    let todo = row.map_err(AppError::from)?;
+    hasher.update(serde_json::to_vec(&todo).map_err(AppError::from)?);
+    hasher.update(b"\n");
+  }
+
+  let mut statement = conn
+    .prepare(
+      "SELECT subtasks.todo_id, subtasks.id, subtasks.title, subtasks.completed, subtasks.sort_order
+       FROM subtasks
+       JOIN todos ON todos.id = subtasks.todo_id
+       WHERE todos.deleted_at IS NULL
+       ORDER BY subtasks.todo_id ASC, subtasks.id ASC",
+    )
+    .map_err(AppError::from)?;
+  let rows = statement
+    .query_map([], |row| {
+      Ok(format!(
+        "{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}",
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, String>(2)?,
+        row.get::<_, i64>(3)?,
+        row.get::<_, i64>(4)?
+      ))
+    })
+    .map_err(AppError::from)?;
+  for row in rows {
+    hasher.update(row.map_err(AppError::from)?.as_bytes());
+    hasher.update(b"\n");
+  }
+
+  let mut statement = conn
+    .prepare(
+      "SELECT todo_tags.todo_id, tags.name
+       FROM todo_tags
+       JOIN tags ON tags.id = todo_tags.tag_id
+       JOIN todos ON todos.id = todo_tags.todo_id
+       WHERE todos.deleted_at IS NULL
+       ORDER BY todo_tags.todo_id ASC, tags.name ASC",
+    )
+    .map_err(AppError::from)?;
+  let rows = statement
+    .query_map([], |row| {
+      Ok(format!("{}\u{1f}{}", row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })
+    .map_err(AppError::from)?;
+  for row in rows {
+    hasher.update(row.map_err(AppError::from)?.as_bytes());
+    hasher.update(b"\n");
+  }
+
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Nothing but the current hash when it matches `known_hash`. Otherwise every
+// todo the change log touched at or after `since`: live ones in full, trashed
+// or purged ones as ids only. Entries removed by `prune_change_log` can't be
+// reported, so a cursor older than the last prune should reload the list.
+#[tauri::command]
+fn get_delta(state: State<'_, AppState>, since: String, known_hash: String) -> CommandResult<TodoDelta> {
+  let mut conn = db_conn(&state)?;
+
+  // One read transaction so the hash and the changes come from the same snapshot.
+  let tx = conn.transaction().map_err(AppError::from)?;
+  get_delta_in(&tx, &since, &known_hash)
+}
+
+fn get_delta_in(conn: &Connection, since: &str, known_hash: &str) -> CommandResult<TodoDelta> {
+  let since = change_log_timestamp(since)?;
+  let hash = list_hash_in(conn)?;
+  if hash == known_hash.trim() {
+    return Ok(TodoDelta {
+      hash,
+      cursor: since,
+      unchanged: true,
+      changed: Vec::new(),
+      deleted_ids: Vec::new(),
+    });
+  }
+
+  let mut statement = conn
+    .prepare(
+      "SELECT todo_id, MAX(at) FROM change_log
+       WHERE at >= ?1
+       GROUP BY todo_id
+       ORDER BY MIN(id) ASC",
+    )
+    .map_err(AppError::from)?;
+  let rows = statement
+    .query_map(params![since], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+    .map_err(AppError::from)?;
+
+  let mut cursor = since;
+  let mut changed = Vec::new();
+  let mut deleted_ids = Vec::new();
+  for row in rows {
+    let (todo_id, at) = row.map_err(AppError::from)?;
+    if at > cursor {
+      cursor = at;
+    }
+    match get_todo_by_id(conn, &todo_id, false)? {
+      Some(todo) => changed.push(todo),
+      None => deleted_ids.push(todo_id),
+    }
+  }
+
+  Ok(TodoDelta {
+    hash,
+    cursor,
+    unchanged: false,
+    changed,
+    deleted_ids,
+  })
+}
+
+fn map_change_entry_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ChangeEntry> {
+  Ok(ChangeEntry {
+    id: row.get(0)?,
+    todo_id: row.get(1)?,
+    op: row.get(2)?,
+    at: row.get(3)?,
+  })
+}
+
+#[tauri::command]
+fn prune_change_log(state: State<'_, AppState>, before: String) -> CommandResult<usize> {
+  let conn = db_conn(&state)?;
+
+  let cutoff = change_log_timestamp(&before)?;
+  conn
+    .execute("DELETE FROM change_log WHERE at < ?1", params![cutoff])
+    .map_err(AppError::from)
+}
+
+// Ranks live todos by how many update entries the change log holds for them.
+// Todos never edited since creation are left out, as are edits older than the
+// last `prune_change_log`.
+#[tauri::command]
+fn list_by_edit_frequency(state: State<'_, AppState>, limit: u32) -> CommandResult<Vec<EditFrequencyEntry>> {
+  let conn = db_conn(&state)?;
+
+  list_by_edit_frequency_in(&conn, limit)
+}
+
+fn list_by_edit_frequency_in(conn: &Connection, limit: u32) -> CommandResult<Vec<EditFrequencyEntry>> {
+  let mut statement = conn
+    .prepare(
+      "SELECT change_log.todo_id, COUNT(*) AS edits
+       FROM change_log
+       JOIN todos ON todos.id = change_log.todo_id
+       WHERE change_log.op = 'update' AND todos.deleted_at IS NULL
+       GROUP BY change_log.todo_id
+       ORDER BY edits DESC, todos.created_at ASC, todos.id ASC
+       LIMIT ?1",
+    )
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![limit], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)))
+    .map_err(AppError::from)?;
+
+  let mut counts = Vec::new();
+  for row in rows {
+    counts.push(row.map_err(AppError::from)?);
+  }
+
+  let mut entries = Vec::new();
+  for (id, edit_count) in counts {
+    if let Some(todo) = get_todo_by_id(conn, &id, false)? {
+      entries.push(EditFrequencyEntry { todo, edit_count });
+    }
+  }
+
+  Ok(entries)
+}
+
+// Respaces the visible todos by SORT_ORDER_GAP in the given order, so later
+// single moves can land between neighbors. Ordering is view state, so
+// `updated_at` is left alone.
+#[tauri::command]
+fn reorder_todos(state: State<'_, AppState>, app: AppHandle, ids: Vec<String>) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let todos = reorder_todos_in(&tx, &ids)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "reordered", None);
+  Ok(todos)
+}
+
+fn reorder_todos_in(conn: &Connection, ids: &[String]) -> CommandResult<Vec<Todo>> {
+  // A partial list would leave the missing rows with stale, colliding orders.
+  // Checked inside the transaction, so a mismatch means another window changed
+  // the list since the caller fetched it.
+  let existing: HashSet<String> = {
+    let mut statement = conn
+      .prepare("SELECT id FROM todos WHERE deleted_at IS NULL AND archived = 0")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| row.get::<_, String>(0))
+      .map_err(AppError::from)?;
+
+    let mut existing = HashSet::new();
+    for row in rows {
+      existing.insert(row.map_err(AppError::from)?);
+    }
+    existing
+  };
+  let incoming: HashSet<String> = ids.iter().cloned().collect();
+  if incoming.len() != ids.len() {
+    return Err(AppError::Validation("Reorder ids contain duplicates".to_string()));
+  }
+  if incoming != existing {
+    let mut missing: Vec<&String> = existing.difference(&incoming).collect();
+    let mut extra: Vec<&String> = incoming.difference(&existing).collect();
+    missing.sort();
+    extra.sort();
+    return Err(AppError::Conflict(format!(
+      "Reorder ids must match the current todos (missing: {missing:?}, extra: {extra:?})"
+    )));
+  }
+
+  for (index, id) in ids.iter().enumerate() {
+    conn
+      .execute(
+        "UPDATE todos SET sort_order = ?2 WHERE id = ?1",
+        params![id, (index as i64 + 1) * SORT_ORDER_GAP],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  visible_todos_in_order(conn)
+}
+
+// Ordering is view state, so neither todo's `updated_at` changes.
+#[tauri::command]
+fn swap_todo_order(state: State<'_, AppState>, app: AppHandle, a_id: String, b_id: String) -> CommandResult<()> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let a = get_todo_by_id(&tx, &a_id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {a_id}")))?;
+  let b = get_todo_by_id(&tx, &b_id, false)?.ok_or_else(|| AppError::NotFound(format!("Todo not found: {b_id}")))?;
+  if a.id == b.id {
+    return Ok(());
+  }
+
+  tx
+    .execute("UPDATE todos SET sort_order = ?2 WHERE id = ?1", params![&a.id, b.sort_order])
+    .map_err(AppError::from)?;
+  tx
+    .execute("UPDATE todos SET sort_order = ?2 WHERE id = ?1", params![&b.id, a.sort_order])
+    .map_err(AppError::from)?;
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "reordered", None);
+
+  Ok(())
+}
+
+fn visible_todos_in_order(conn: &Connection) -> CommandResult<Vec<Todo>> {
+  let mut statement = conn
+    .prepare(&format!(
+      "SELECT {TODO_COLUMNS} FROM todos WHERE deleted_at IS NULL AND archived = 0 ORDER BY sort_order ASC"
+    ))
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], map_todo_row)
+    .map_err(AppError::from)?;
+
+  let mut todos = Vec::new();
+  for row in rows {
+    todos.push(row.map_err(AppError::from)?);
+  }
+  Ok(todos)
+}
+
+// Moves one todo to a zero-based slot, reusing the sort orders already held by
+// the rows between its old and new position so nothing outside that range
+// changes. Like `move_todo`, this leaves `updated_at` alone.
+#[tauri::command]
+fn move_todo_to_index(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  id: String,
+  index: usize,
+) -> CommandResult<Vec<Todo>> {
+  let mut conn = db_conn(&state)?;
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let todos = move_todo_to_index_in(&tx, &id, index)?;
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "reordered", Some(&id));
+  Ok(todos)
+}
+
+fn move_todo_to_index_in(conn: &Connection, id: &str, index: usize) -> CommandResult<Vec<Todo>> {
+  let mut todos = visible_todos_in_order(conn)?;
+  let from = todos
+    .iter()
+    .position(|todo| todo.id == id)
+    .ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))?;
+  let to = index.min(todos.len() - 1);
+
+  if from != to {
+    let range = from.min(to)..=from.max(to);
+    let orders: Vec<i64> = todos[range.clone()].iter().map(|todo| todo.sort_order).collect();
+    let moved = todos.remove(from);
+    todos.insert(to, moved);
+
+    for (todo, sort_order) in todos[range].iter_mut().zip(orders) {
+      todo.sort_order = sort_order;
+      conn
+        .execute("UPDATE todos SET sort_order = ?2 WHERE id = ?1", params![&todo.id, sort_order])
+        .map_err(AppError::from)?;
+    }
+  }
+
+  Ok(todos)
+}
+
+fn sort_order_of(conn: &Connection, id: &str) -> CommandResult<i64> {
+  conn
+    .query_row("SELECT sort_order FROM todos WHERE id = ?1", params![id], |row| row.get(0))
+    .optional()
+    .map_err(AppError::from)?
+    .ok_or_else(|| AppError::NotFound(format!("Todo not found: {id}")))
+}
+
+// Picks a sort_order strictly between the neighbors, or None when they are
+// adjacent and the list needs rebalancing first.
+fn sort_order_between(after: Option<i64>, before: Option<i64>) -> Option<i64> {
+  match (after, before) {
+    (Some(low), Some(high)) if high - low >= 2 => Some(low + (high - low) / 2),
+    (Some(_), Some(_)) => None,
+    (Some(low), None) => low.checked_add(SORT_ORDER_GAP),
+    (None, Some(high)) => high.checked_sub(SORT_ORDER_GAP),
+    (None, None) => None,
+  }
+}
+
+// Respaces every row by SORT_ORDER_GAP in current display order. Like
+// `move_todo`, this leaves `updated_at` alone since nothing user-visible changed.
+fn rebalance_sort_orders_in(conn: &Connection) -> CommandResult<usize> {
+  let ids = {
+    let mut statement = conn
+      .prepare("SELECT id FROM todos ORDER BY sort_order ASC, created_at DESC, id ASC")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| row.get::<_, String>(0))
+      .map_err(AppError::from)?;
+
+    let mut ids = Vec::new();
+    for row in rows {
+      ids.push(row.map_err(AppError::from)?);
+    }
+    ids
+  };
+
+  for (index, id) in ids.iter().enumerate() {
+    conn
+      .execute(
+        "UPDATE todos SET sort_order = ?2 WHERE id = ?1",
+        params![id, (index as i64 + 1) * SORT_ORDER_GAP],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  Ok(ids.len())
+}
+
+// `after_id` is the todo that should sit directly above the moved one and
+// `before_id` the one directly below; either may be omitted at the list edges.
+// Only the moved row is written unless its neighbors have no room between them.
+#[tauri::command]
+fn move_todo(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  id: String,
+  before_id: Option<String>,
+  after_id: Option<String>,
+) -> CommandResult<()> {
+  let mut conn = db_conn(&state)?;
+
+  if before_id.is_none() && after_id.is_none() {
+    return Err(AppError::Validation("Move requires a neighboring todo".to_string()));
+  }
+  if before_id.as_deref() == Some(id.as_str()) || after_id.as_deref() == Some(id.as_str()) {
+    return Err(AppError::Validation("A todo cannot be moved next to itself".to_string()));
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  sort_order_of(&tx, &id)?;
+
+  let neighbors = |conn: &Connection| -> CommandResult<(Option<i64>, Option<i64>)> {
+    let after = after_id.as_deref().map(|value| sort_order_of(conn, value)).transpose()?;
+    let before = before_id.as_deref().map(|value| sort_order_of(conn, value)).transpose()?;
+    Ok((after, before))
+  };
+
+  let (after, before) = neighbors(&tx)?;
+  let sort_order = match sort_order_between(after, before) {
+    Some(value) => value,
+    None => {
+      rebalance_sort_orders_in(&tx)?;
+      let (after, before) = neighbors(&tx)?;
+      sort_order_between(after, before)
+        .ok_or_else(|| AppError::Validation("Neighboring todos are out of order".to_string()))?
+    }
+  };
+
+  tx
+    .execute(
+      "UPDATE todos SET sort_order = ?2 WHERE id = ?1",
+      params![&id, sort_order],
+    )
+    .map_err(AppError::from)?;
+
+  tx.commit().map_err(AppError::from)?;
+  emit_todos_changed(&app, "reordered", Some(&id));
+  Ok(())
+}
+
+#[tauri::command]
+fn rebalance_sort_orders(state: State<'_, AppState>) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let count = rebalance_sort_orders_in(&tx)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(count)
+}
+
+// Renumbers every row to 0..n in current display order, with `id` breaking the
+// ties that duplicate sort_orders leave, so the order no longer depends on the
+// `created_at` fallback. Only rows whose value changes are written, which makes
+// a second run a no-op; `updated_at` is left alone as in
+// `rebalance_sort_orders_in`.
+#[tauri::command]
+fn normalize_sort_orders(state: State<'_, AppState>) -> CommandResult<usize> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let rows = {
+    let mut statement = tx
+      .prepare("SELECT id, sort_order FROM todos ORDER BY sort_order ASC, created_at DESC, id ASC")
+      .map_err(AppError::from)?;
+
+    let rows = statement
+      .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+      .map_err(AppError::from)?;
+
+    let mut collected = Vec::new();
+    for row in rows {
+      collected.push(row.map_err(AppError::from)?);
+    }
+    collected
+  };
+
+  let mut renumbered = 0;
+  for (index, (id, sort_order)) in rows.iter().enumerate() {
+    let index = index as i64;
+    if *sort_order == index {
+      continue;
+    }
+    tx
+      .execute("UPDATE todos SET sort_order = ?2 WHERE id = ?1", params![id, index])
+      .map_err(AppError::from)?;
+    renumbered += 1;
+  }
+
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(renumbered)
+}
+
+// SHA-256 over the sorted ids, so the same payload maps to the same key in any
+// order. Items without an id fall back to their title.
+fn legacy_payload_fingerprint(payload: &[LegacyTodo]) -> String {
+  let mut keys: Vec<&str> = payload
+    .iter()
+    .map(|legacy| {
+      if legacy.id.trim().is_empty() {
+        legacy.title.trim()
+      } else {
+        legacy.id.trim()
+      }
+    })
+    .collect();
+  keys.sort_unstable();
+
+  format!("{:x}", Sha256::digest(keys.join("\n").as_bytes()))
+}
+
+#[tauri::command]
+fn migrate_legacy_todos_if_needed(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  payload: Vec<LegacyTodo>,
+) -> CommandResult<MigrationResult> {
+  let mut conn = db_conn(&state)?;
+
+  let migration_key = format!("{MIGRATION_KEY_PREFIX}{}", legacy_payload_fingerprint(&payload));
+  let total_received = payload.len();
+  let already_migrated = get_meta(&conn, &migration_key)?.as_deref() == Some("true");
+  if already_migrated {
+    emit_migration_complete(&app, total_received);
+    return Ok(MigrationResult {
+      migrated_count: 0,
+      already_migrated: true,
+      total_received,
+      skipped_empty_title: 0,
+      skipped_duplicate_id: 0,
+    });
+  }
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let mut migrated_count = 0usize;
+  let mut skipped_empty_title = 0usize;
+  let mut skipped_duplicate_id = 0usize;
+
+  let min_sort: i64 = tx
+    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  let mut next_sort = min_sort - payload.len() as i64;
+
+  for (index, legacy) in payload.into_iter().enumerate() {
+    emit_migration_progress(&app, index, total_received);
+    let trimmed_title = legacy.title.trim();
+    if trimmed_title.is_empty() {
+      skipped_empty_title += 1;
+      continue;
+    }
+
+    let id = if legacy.id.trim().is_empty() {
+      Uuid::new_v4().to_string()
+    } else {
+      legacy.id
+    };
+
+    let created_at = if legacy.created_at.trim().is_empty() {
+      now_iso()
+    } else {
+      legacy.created_at
+    };
+
+    let updated_at = if legacy.updated_at.trim().is_empty() {
+      created_at.clone()
+    } else {
+      legacy.updated_at
+    };
+
+    let completed_at = if legacy.completed {
+      Some(updated_at.clone())
+    } else {
+      None
+    };
+
+    let inserted = tx
+      .execute(
+        "INSERT OR IGNORE INTO todos
+         (id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, sort_order, created_at, updated_at, completed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+          id,
+          trimmed_title,
+          normalize_recurrence_tag(legacy.recurrence_tag),
+          Option::<String>::None,
+          legacy.note,
+          to_db_bool(legacy.completed),
+          normalize_date(legacy.due_date).ok().flatten(),
+          next_sort,
+          created_at,
+          updated_at,
+          completed_at,
+        ],
+      )
+      .map_err(AppError::from)?;
+
+    if inserted > 0 {
+      migrated_count += 1;
+      next_sort += 1;
+    } else {
+      skipped_duplicate_id += 1;
+    }
+  }
+
+  tx
+    .execute(
+      "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
+       ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+      params![&migration_key, "true"],
+    )
+    .map_err(AppError::from)?;
+
+  tx.commit().map_err(AppError::from)?;
+  emit_migration_complete(&app, total_received);
+
+  Ok(MigrationResult {
+    migrated_count,
+    already_migrated: false,
+    total_received,
+    skipped_empty_title,
+    skipped_duplicate_id,
+  })
+}
+
+#[tauri::command]
+fn get_sync_manifest(state: State<'_, AppState>) -> CommandResult<Vec<(String, String)>> {
+  let conn = db_conn(&state)?;
+
+  sync_manifest_in(&conn)
+}
+
+// Trashed rows stay in the manifest as tombstones; trashing stamps
+// `updated_at` with the deletion time.
+fn sync_manifest_in(conn: &Connection) -> CommandResult<Vec<(String, String)>> {
+  let mut statement = conn
+    .prepare("SELECT id, updated_at FROM todos ORDER BY id ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+    .map_err(AppError::from)?;
+
+  let mut manifest = Vec::new();
+  for row in rows {
+    manifest.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(manifest)
+}
+
+#[tauri::command]
+fn apply_remote_changes(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  changes: Vec<Todo>,
+  deletions: Vec<String>,
+) -> CommandResult<RemoteApplyReport> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let report = apply_remote_changes_in(&tx, changes, &deletions)?;
+  tx.commit().map_err(AppError::from)?;
+  if report.applied > 0 || report.deleted > 0 {
+    emit_todos_changed(&app, "updated", None);
+  }
+
+  Ok(report)
+}
+
+// Last writer wins on `updated_at`: an incoming todo only replaces a local one
+// that is strictly older. A bare id in `deletions` carries no timestamp to
+// compare, so it moves the todo to the trash rather than deleting it, and a
+// stale remote delete can still be restored; peers that know when a todo was
+// deleted should send it as a tombstone in `changes` instead.
+fn apply_remote_changes_in(conn: &Connection, changes: Vec<Todo>, deletions: &[String]) -> CommandResult<RemoteApplyReport> {
+  let mut report = RemoteApplyReport::default();
+
+  for incoming in changes {
+    let trimmed_title = incoming.title.trim();
+    let Ok(incoming_updated_at) = DateTime::parse_from_rfc3339(&incoming.updated_at) else {
+      report.skipped += 1;
+      continue;
+    };
+    let Ok(due_date) = normalize_date(incoming.due_date.clone()) else {
+      report.skipped += 1;
+      continue;
+    };
+    if trimmed_title.is_empty() {
+      report.skipped += 1;
+      continue;
+    }
+
+    let existing = get_todo_by_id(conn, &incoming.id, true)?;
+    if let Some(local) = &existing {
+      let local_is_newer = DateTime::parse_from_rfc3339(&local.updated_at)
+        .map(|local_updated_at| local_updated_at >= incoming_updated_at)
+        .unwrap_or(false);
+      if local_is_newer {
+        report.skipped += 1;
+        continue;
+      }
+    }
+
+    let mut merged = Todo {
+      title: trimmed_title.to_string(),
+      recurrence_tag: normalize_recurrence_tag(Some(incoming.recurrence_tag.clone())),
+      due_date,
+      note_format: normalize_note_format(Some(incoming.note_format.clone())),
+      rolled_at: None,
+      energy: normalize_energy(incoming.energy.clone()),
+      context: normalize_context(incoming.context.clone()),
+      color: normalize_color(incoming.color.clone()).unwrap_or_default(),
+      reminder_offset_minutes: normalize_reminder_offset(incoming.reminder_offset_minutes).unwrap_or_default(),
+      estimate_minutes: normalize_estimate_minutes(incoming.estimate_minutes).unwrap_or_default(),
+      ..incoming
+    };
+
+    match existing {
+      Some(local) => {
+        merged.sort_order = local.sort_order;
+        merged.last_reminded_on = local.last_reminded_on;
+        update_todo_row(conn, &merged)?;
+      }
+      None => {
+        merged.sort_order = conn
+          .query_row("SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos", [], |row| row.get(0))
+          .map_err(AppError::from)?;
+        merged.last_reminded_on = None;
+        insert_todo(conn, &merged)?;
+      }
+    }
+
+    report.applied += 1;
+  }
+
+  let now = now_iso();
+  for id in deletions {
+    report.deleted += trash_todo_in(conn, id, &now)?;
+  }
+
+  Ok(report)
+}
+
+#[tauri::command]
+fn check_referential_integrity(state: State<'_, AppState>) -> CommandResult<IntegrityReport> {
+  let conn = db_conn(&state)?;
+
+  referential_integrity_report(&conn)
+}
+
+fn referential_integrity_report(conn: &Connection) -> CommandResult<IntegrityReport> {
+  let mut report = IntegrityReport::default();
+
+  let mut statement = conn
+    .prepare("SELECT \"table\", COUNT(*) FROM pragma_foreign_key_check GROUP BY \"table\" ORDER BY \"table\"")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| {
+      Ok(DanglingRows {
+        table: row.get(0)?,
+        count: row.get(1)?,
+      })
+    })
+    .map_err(AppError::from)?;
+
+  for row in rows {
+    report.foreign_key_violations.push(row.map_err(AppError::from)?);
+  }
+
+  for (table, predicate) in ORPHAN_CHECKS {
+    let count: usize = conn
+      .query_row(&format!("SELECT COUNT(*) FROM {table} WHERE {predicate}"), [], |row| row.get(0))
+      .map_err(AppError::from)?;
+    if count > 0 {
+      report.orphans.push(DanglingRows {
+        table: table.to_string(),
+        count,
+      });
+    }
+  }
+
+  Ok(report)
+}
+
+// Adds SQLite's own page-level check and shared `sort_order`s to the
+// referential checks. With `auto_repair`, orphans are deleted and the list
+// respaced in one transaction; the report still describes what was found.
+// Page-level corruption is reported but never repaired.
+#[tauri::command]
+fn check_integrity(state: State<'_, AppState>, auto_repair: Option<bool>) -> CommandResult<IntegrityReport> {
+  let mut conn = db_conn(&state)?;
+
+  let mut report = referential_integrity_report(&conn)?;
+
+  let mut statement = conn.prepare("PRAGMA integrity_check").map_err(AppError::from)?;
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+  for row in rows {
+    let message = row.map_err(AppError::from)?;
+    if message != "ok" {
+      report.integrity_errors.push(message);
+    }
+  }
+  drop(statement);
+
+  report.duplicate_sort_orders = conn
+    .query_row(
+      "SELECT COALESCE(SUM(shared), 0) FROM (
+         SELECT COUNT(*) AS shared FROM todos GROUP BY sort_order HAVING COUNT(*) > 1
+       )",
+      [],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+
+  let needs_repair = !report.orphans.is_empty() || report.duplicate_sort_orders > 0;
+  if auto_repair.unwrap_or(false) && needs_repair {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+    prune_orphans_in(&tx)?;
+    if report.duplicate_sort_orders > 0 {
+      rebalance_sort_orders_in(&tx)?;
+    }
+    tx.commit().map_err(AppError::from)?;
+    report.repaired = true;
+  }
+
+  Ok(report)
+}
+
+#[tauri::command]
+fn prune_orphans(state: State<'_, AppState>) -> CommandResult<Vec<DanglingRows>> {
+  let mut conn = db_conn(&state)?;
+
+  let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(AppError::from)?;
+  let pruned = prune_orphans_in(&tx)?;
+  tx.commit().map_err(AppError::from)?;
+
+  Ok(pruned)
+}
+
+fn prune_orphans_in(conn: &Connection) -> CommandResult<Vec<DanglingRows>> {
+  let mut pruned = Vec::new();
+
+  for (table, predicate) in ORPHAN_CHECKS {
+    let count = conn
+      .execute(&format!("DELETE FROM {table} WHERE {predicate}"), [])
+      .map_err(AppError::from)?;
+    if count > 0 {
+      pruned.push(DanglingRows {
+        table: table.to_string(),
+        count,
+      });
+    }
+  }
+
+  Ok(pruned)
+}
+
+#[tauri::command]
+fn explain_list_query(
+  state: State<'_, AppState>,
+  sort_by_priority: Option<bool>,
+  options: Option<ListOptions>,
+) -> CommandResult<Vec<String>> {
+  let conn = db_conn(&state)?;
+
+  explain_list_query_in(&conn, sort_by_priority.unwrap_or(false), &options.unwrap_or_default())
+}
+
+fn explain_list_query_in(conn: &Connection, sort_by_priority: bool, options: &ListOptions) -> CommandResult<Vec<String>> {
+  let placement = get_ui_prefs_from_conn(conn)?.completed_placement;
+  let active_context = get_meta(conn, ACTIVE_CONTEXT_KEY)?;
+  let (sql, values) = list_todos_query(sort_by_priority, placement, active_context.as_deref(), options)?;
+  let mut statement = conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}")).map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params_from_iter(values.iter()), |row| row.get::<_, String>(3))
+    .map_err(AppError::from)?;
+
+  let mut plan = Vec::new();
+  for row in rows {
+    plan.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(plan)
+}
+
+// Saving under an existing name replaces its options but keeps its creation time.
+#[tauri::command]
+fn save_filter(state: State<'_, AppState>, name: String, options: ListOptions) -> CommandResult<SavedFilter> {
+  let conn = db_conn(&state)?;
+
+  save_filter_in(&conn, &name, options)
+}
+
+fn save_filter_in(conn: &Connection, name: &str, options: ListOptions) -> CommandResult<SavedFilter> {
+  let name = name.trim();
+  if name.is_empty() {
+    return Err(AppError::Validation("Filter name cannot be empty".to_string()));
+  }
+  page_bounds(&options)?;
+
+  let params = serde_json::to_string(&options).map_err(AppError::from)?;
+  let created_at: String = conn
+    .query_row(
+      "INSERT INTO saved_filters (name, params, created_at) VALUES (?1, ?2, ?3)
+       ON CONFLICT(name) DO UPDATE SET params = excluded.params
+       RETURNING created_at",
+      params![name, &params, now_iso()],
+      |row| row.get(0),
+    )
+    .map_err(AppError::from)?;
+
+  Ok(SavedFilter {
+    name: name.to_string(),
+    options,
+    created_at,
+  })
+}
+
+#[tauri::command]
+fn list_saved_filters(state: State<'_, AppState>) -> CommandResult<Vec<SavedFilter>> {
+  let conn = db_conn(&state)?;
+
+  list_saved_filters_in(&conn)
+}
+
+fn list_saved_filters_in(conn: &Connection) -> CommandResult<Vec<SavedFilter>> {
+  let mut statement = conn
+    .prepare("SELECT name, params, created_at FROM saved_filters ORDER BY name ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+    .map_err(AppError::from)?;
+
+  let mut filters = Vec::new();
+  for row in rows {
+    let (name, params, created_at) = row.map_err(AppError::from)?;
+    let options = parse_saved_filter_params(&name, &params)?;
+    filters.push(SavedFilter {
+      name,
+      options,
+      created_at,
+    });
+  }
+
+  Ok(filters)
+}
+
+#[tauri::command]
+fn delete_saved_filter(state: State<'_, AppState>, name: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  delete_saved_filter_in(&conn, &name)
+}
+
+fn delete_saved_filter_in(conn: &Connection, name: &str) -> CommandResult<()> {
+  let deleted = conn
+    .execute("DELETE FROM saved_filters WHERE name = ?1", params![name.trim()])
+    .map_err(AppError::from)?;
+  if deleted == 0 {
+    return Err(AppError::NotFound(format!("Saved filter not found: {name}")));
+  }
+
+  Ok(())
+}
+
+// Runs with the remembered sort mode and completed placement, exactly as
+// `list_todos` would without an explicit mode.
+#[tauri::command]
+fn run_saved_filter(state: State<'_, AppState>, name: String) -> CommandResult<Vec<ListedTodo>> {
+  let conn = db_conn(&state)?;
+
+  run_saved_filter_in(&conn, &name)
+}
+
+fn run_saved_filter_in(conn: &Connection, name: &str) -> CommandResult<Vec<ListedTodo>> {
+  let params: String = conn
+    .query_row(
+      "SELECT params FROM saved_filters WHERE name = ?1",
+      params![name.trim()],
+      |row| row.get(0),
+    )
+    .optional()
+    .map_err(AppError::from)?
+    .ok_or_else(|| AppError::NotFound(format!("Saved filter not found: {name}")))?;
+  let options = parse_saved_filter_params(name, &params)?;
+
+  let prefs = get_ui_prefs_from_conn(conn)?;
+  list_todos_from_conn(conn, false, &options, prefs.sort_mode, prefs.completed_placement)
+}
+
+fn parse_saved_filter_params(name: &str, params: &str) -> CommandResult<ListOptions> {
+  serde_json::from_str(params)
+    .map_err(|err| AppError::Validation(format!("Saved filter {name} has unreadable options: {err}")))
+}
+
+#[tauri::command]
+fn get_database_path(state: State<'_, AppState>) -> CommandResult<String> {
+  Ok(active_database_path(&state)?.display().to_string())
+}
+
+// The size covers the WAL and shared-memory siblings too, since recent writes
+// may not have been checkpointed into the main file yet. Sandbox mode has no
+// file, so it reports no path and zero bytes.
+#[tauri::command]
+fn get_storage_info(app: AppHandle, state: State<'_, AppState>) -> CommandResult<StorageInfo> {
+  let app_data_dir = app.path().app_data_dir().map_err(std::io::Error::other)?;
+  let path = state.read_db().path.clone();
+
+  let database_size_bytes = database_files_size(path.as_deref());
+
+  let conn = db_conn(&state)?;
+  let todo_count = conn
+    .query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))
+    .map_err(AppError::from)?;
+
+  Ok(StorageInfo {
+    app_data_dir: app_data_dir.display().to_string(),
+    database_path: path.map(|path| path.display().to_string()),
+    database_size_bytes,
+    todo_count,
+  })
+}
+
+fn database_files_size(path: Option<&std::path::Path>) -> u64 {
+  path.map_or(0, |path| {
+    ["", "-wal", "-shm"]
+      .iter()
+      .filter_map(|suffix| {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        std::fs::metadata(file).ok()
+      })
+      .map(|metadata| metadata.len())
+      .sum()
+  })
+}
+
+// VACUUM can't run inside a transaction and needs every other connection
+// idle, so this holds the handle's write lock: new commands block in
+// `db_conn` until it finishes. Connections already checked out keep working
+// and VACUUM waits for them via `busy_timeout`, so a long-running command
+// surfaces as a `database` error here instead of a deadlock. Nothing below may
+// call `db_conn` while the lock is held.
+//
+// In WAL mode the rewritten pages land in the -wal file first, so the size
+// only drops once `wal_checkpoint` truncates it.
+#[tauri::command]
+fn vacuum_database(state: State<'_, AppState>, wal_checkpoint: Option<bool>) -> CommandResult<VacuumReport> {
+  let database = state.write_db();
+  let size_before_bytes = database_files_size(database.path.as_deref());
+
+  let conn = database
+    .pool
+    .get()
+    .map_err(|err| AppError::Lock(format!("Failed to acquire database connection: {err}")))?;
+  conn.execute_batch("VACUUM").map_err(AppError::from)?;
+  // Cheap, and a good moment to refresh the planner's statistics.
+  conn.execute_batch("PRAGMA optimize").map_err(AppError::from)?;
+
+  let wal_checkpointed = if wal_checkpoint.unwrap_or(false) {
+    let busy: i64 = conn
+      .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+      .map_err(AppError::from)?;
+    busy == 0
+  } else {
+    false
+  };
+  drop(conn);
+
+  Ok(VacuumReport {
+    size_before_bytes,
+    size_after_bytes: database_files_size(database.path.as_deref()),
+    wal_checkpointed,
+  })
+}
+
+fn active_database_path(state: &AppState) -> CommandResult<std::path::PathBuf> {
+  state
+    .read_db()
+    .path
+    .clone()
+    .ok_or_else(|| AppError::Validation("Sandbox mode has no database file".to_string()))
+}
+
+fn same_file(a: &std::path::Path, b: &std::path::Path) -> bool {
+  match (a.canonicalize(), b.canonicalize()) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => a == b,
+  }
+}
+
+// Moves the live database to `new_path` by copying it with the online backup
+// API, then swaps the pool. An existing database at the target is never
+// overwritten: the call fails unless `use_existing` is set, in which case the
+// app switches to that file as is. The override is kept in the default
+// database so the next launch can find it. Files are opened with the current
+// passphrase when they turn out to be encrypted; a target needing any other
+// key is rejected, as is moving away from an encrypted default database,
+// since startup can't read its override before it is unlocked.
+#[tauri::command]
+fn set_database_path(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  new_path: String,
+  use_existing: bool,
+) -> CommandResult<String> {
+  let current = active_database_path(&state)?;
+  let target = std::path::PathBuf::from(new_path.trim());
+  if target.as_os_str().is_empty() {
+    return Err(AppError::Validation("Database path cannot be empty".to_string()));
+  }
+  if same_file(&current, &target) {
+    return Ok(current.display().to_string());
+  }
+
+  let passphrase = database_passphrase(&state)?;
+  let default_path = database_path(&app)?;
+  let (default_conn, default_encrypted) = open_keyed_connection(&default_path, passphrase.as_deref())?;
+  if default_encrypted && !same_file(&default_path, &target) {
+    return Err(AppError::Validation(
+      "The default database is encrypted, so it can't point to another location".to_string(),
+    ));
+  }
+
+  let mut target_passphrase = None;
+  if target.exists() {
+    let (existing, encrypted) = open_keyed_connection(&target, passphrase.as_deref())?;
+    if encrypted {
+      target_passphrase = passphrase.clone();
+    }
+    let has_todos: bool = existing
+      .query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'todos')",
+        [],
+        |row| row.get(0),
+      )
+      .map_err(|_| AppError::Validation(format!("Not a todo database: {}", target.display())))?;
+    if !has_todos {
+      return Err(AppError::Validation(format!("Not a todo database: {}", target.display())));
+    }
+    if !use_existing {
+      return Err(AppError::Validation(format!(
+        "A database already exists at {}; pass useExisting to switch to it",
+        target.display()
+      )));
+    }
+  } else {
+    // The backup API can't copy between an encrypted and a plain file.
+    if passphrase.is_some() {
+      return Err(AppError::Validation("An encrypted database can't be moved to a new file".to_string()));
+    }
+    if let Some(parent) = target.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    db_conn(&state)?
+      .backup(DatabaseName::Main, &target, None)
+      .map_err(AppError::from)?;
+  }
+
+  let pool = open_file_pool(&target, target_passphrase.as_deref())?;
+  let conn = pool
+    .get()
+    .map_err(|err| AppError::Lock(format!("Failed to open the new database: {err}")))?;
+  ensure_schema(&conn)?;
+  drop(conn);
+
+  if same_file(&default_path, &target) {
+    delete_meta(&default_conn, DB_PATH_OVERRIDE_KEY)?;
+  } else {
+    set_meta(&default_conn, DB_PATH_OVERRIDE_KEY, &target.display().to_string())?;
+  }
+
+  *state.write_db() = Database {
+    pool,
+    path: Some(target.clone()),
+    passphrase: target_passphrase,
+    locked: false,
+  };
+  // Snapshots belong to the old file and may not match the new one.
+  state.lock_undo_stack().clear();
+  emit_todos_changed(&app, "updated", None);
+
+  Ok(target.display().to_string())
+}
+
+// Uses SQLite's online backup so the copy is consistent even while other
+// pooled connections keep writing.
+#[tauri::command]
+fn backup_database(state: State<'_, AppState>, dest_path: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  let dest = std::path::PathBuf::from(dest_path.trim());
+  if dest.as_os_str().is_empty() {
+    return Err(AppError::Validation("Backup destination cannot be empty".to_string()));
+  }
+
+  if !sandbox_enabled() && same_file(&active_database_path(&state)?, &dest) {
+    return Err(AppError::Validation("Backup destination must differ from the live database".to_string()));
+  }
+
+  conn
+    .backup(DatabaseName::Main, &dest, None)
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_database_locked(state: State<'_, AppState>) -> CommandResult<bool> {
+  Ok(state.read_db().locked)
+}
+
+// Supplies the key for a database that started locked. A wrong passphrase
+// leaves it locked and fails with a `database` error rather than SQLCipher's
+// "file is not a database". Window prefs stored in the file are applied once
+// it opens.
+#[tauri::command]
+fn unlock_database(state: State<'_, AppState>, app: AppHandle, passphrase: String) -> CommandResult<()> {
+  let mut database = state.write_db();
+  if !database.locked {
+    return Ok(());
+  }
+  let path = database
+    .path
+    .clone()
+    .ok_or_else(|| AppError::Validation("The sandbox database is never encrypted".to_string()))?;
+
+  let pool = open_file_pool(&path, Some(&passphrase))?;
+  let conn = pool
+    .get()
+    .map_err(|err| AppError::Lock(format!("Failed to open the database: {err}")))?;
+  if database_needs_key(&conn) {
+    return Err(AppError::Database("Incorrect database passphrase".to_string()));
+  }
+  ensure_schema(&conn)?;
+  let prefs = get_window_prefs_from_conn(&conn)?;
+  drop(conn);
+
+  *database = Database {
+    pool,
+    path: Some(path),
+    passphrase: Some(passphrase),
+    locked: false,
+  };
+  drop(database);
+
+  if let Some(window) = app.get_webview_window("main") {
+    let _ = apply_window_prefs(&window, &prefs);
+  }
+  emit_todos_changed(&app, "updated", None);
+
+  Ok(())
+}
+
+// Encrypts the live database, or changes the key of one that already is.
+// SQLCipher's `PRAGMA rekey` only works on an encrypted file, so a plain one
+// is exported into an encrypted copy that then replaces it. Like
+// `vacuum_database` this holds the handle's write lock throughout, and the
+// pool is rebuilt afterwards since open connections still carry the old key.
+// There is no way back to a plain file, and a lost passphrase loses the data.
+#[tauri::command]
+fn set_database_passphrase(app: AppHandle, state: State<'_, AppState>, passphrase: String) -> CommandResult<()> {
+  if passphrase.is_empty() {
+    return Err(AppError::Validation("Passphrase cannot be empty".to_string()));
+  }
+
+  let mut database = state.write_db();
+  if database.locked {
+    return Err(AppError::Database("The database is encrypted; unlock it with its passphrase first".to_string()));
+  }
+  let path = database
+    .path
+    .clone()
+    .ok_or_else(|| AppError::Validation("The sandbox database can't be encrypted".to_string()))?;
+
+  let conn = database
+    .pool
+    .get()
+    .map_err(|err| AppError::Lock(format!("Failed to acquire database connection: {err}")))?;
+  let staging = if database.passphrase.is_some() {
+    conn.pragma_update(None, "rekey", &passphrase).map_err(AppError::from)?;
+    None
+  } else {
+    let mut staging = path.clone().into_os_string();
+    staging.push(".encrypting");
+    let staging = std::path::PathBuf::from(staging);
+    let _ = std::fs::remove_file(&staging);
+
+    conn
+      .execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        params![staging.to_string_lossy(), &passphrase],
+      )
+      .map_err(AppError::from)?;
+    let exported = conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()));
+    conn.execute_batch("DETACH DATABASE encrypted").map_err(AppError::from)?;
+    if let Err(err) = exported {
+      let _ = std::fs::remove_file(&staging);
+      return Err(AppError::from(err));
+    }
+    Some(staging)
+  };
+  drop(conn);
+
+  // Dropping the old pool closes its connections, which checkpoints and
+  // removes the plain file's WAL before the encrypted copy takes its place.
+  // The new pool opens nothing until the swap is done.
+  let previous = std::mem::replace(
+    &mut *database,
+    Database {
+      pool: open_file_pool(&path, Some(&passphrase))?,
+      path: Some(path.clone()),
+      passphrase: Some(passphrase),
+      locked: false,
+    },
+  );
+  drop(previous);
+
+  if let Some(staging) = staging {
+    let mut wal = path.clone().into_os_string();
+    wal.push("-wal");
+    let replaced = if std::path::Path::new(&wal).exists() {
+      Err(AppError::Lock("The database is still in use; try again".to_string()))
+    } else {
+      std::fs::rename(&staging, &path).map_err(AppError::from)
+    };
+    if let Err(err) = replaced {
+      let _ = std::fs::remove_file(&staging);
+      *database = Database {
+        pool: open_file_pool(&path, None)?,
+        path: Some(path),
+        passphrase: None,
+        locked: false,
+      };
+      return Err(err);
+    }
+  }
+  drop(database);
+
+  emit_todos_changed(&app, "updated", None);
+
+  Ok(())
+}
+
+#[tauri::command]
+fn get_clock_skew(state: State<'_, AppState>) -> CommandResult<ClockSkew> {
+  let conn = db_conn(&state)?;
+
+  detect_clock_skew(&conn)
+}
+
+#[tauri::command]
+fn get_window_prefs(state: State<'_, AppState>) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  get_window_prefs_from_conn(&conn)
+}
+
+#[tauri::command]
+fn save_window_prefs(state: State<'_, AppState>, input: WindowPrefs) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  let normalized = normalize_window_prefs(input);
+  save_window_prefs_to_conn(&conn, &normalized)
+}
+
+// Drops only the stored window prefs; UI prefs and todos are left alone.
+#[tauri::command]
+fn reset_window_prefs_only(state: State<'_, AppState>, app: AppHandle) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let prefs = reset_window_prefs_in(&conn)?;
+  if let Some(window) = app.get_webview_window("main") {
+    apply_window_prefs(&window, &prefs)?;
+  }
+
+  Ok(prefs)
+}
+
+fn reset_window_prefs_in(conn: &Connection) -> CommandResult<WindowPrefs> {
+  delete_meta(conn, WINDOW_PREFS_KEY)?;
+
+  Ok(WindowPrefs::default())
+}
+
+#[tauri::command]
+fn get_ui_prefs(state: State<'_, AppState>) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  get_ui_prefs_from_conn(&conn)
+}
+
+#[tauri::command]
+fn audit_accessibility(state: State<'_, AppState>) -> CommandResult<AccessibilityAudit> {
+  let conn = db_conn(&state)?;
+
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  Ok(audit_accessibility_prefs(&prefs))
+}
+
+#[tauri::command]
+fn get_effective_reduce_motion(state: State<'_, AppState>) -> CommandResult<bool> {
+  let conn = db_conn(&state)?;
+
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  Ok(effective_reduce_motion(&prefs))
+}
+
+#[tauri::command]
+fn format_display_date(state: State<'_, AppState>, date: String) -> CommandResult<String> {
+  let conn = db_conn(&state)?;
+
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  let day = match NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+    Ok(day) => day,
+    Err(_) => parse_now_to_local_date(date.trim())?,
+  };
+
+  Ok(format_date_for_locale(day, &prefs.locale))
+}
+
+#[tauri::command]
+fn save_ui_prefs(state: State<'_, AppState>, app: AppHandle, mut input: UiPrefs) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  let stored = get_ui_prefs_from_conn(&conn)?;
+  let dimming_involved = stored.dim_on_blur || input.dim_on_blur;
+  // The HTTP server settings and the quick-capture shortcut only change through
+  // their own commands, which also apply them.
+  input.http_server_enabled = stored.http_server_enabled;
+  input.http_server_port = stored.http_server_port;
+  input.http_server_lan = stored.http_server_lan;
+  input.quick_capture_shortcut = stored.quick_capture_shortcut;
+  save_ui_prefs_to_conn(&conn, &input)?;
+  drop(conn);
+
+  // A new blurred level, or turning dimming off while blurred, applies at once.
+  if dimming_involved {
+    if let Some(window) = app.get_webview_window("main") {
+      let _ = apply_focus_opacity(&state, &window, true);
+    }
+  }
+
+  Ok(())
+}
+
+// Drops only the stored UI prefs; window prefs and todos are left alone.
+#[tauri::command]
+fn reset_ui_prefs(state: State<'_, AppState>) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  reset_ui_prefs_in(&conn)
+}
+
+fn reset_ui_prefs_in(conn: &Connection) -> CommandResult<UiPrefs> {
+  delete_meta(conn, UI_PREFS_KEY)?;
+
+  Ok(UiPrefs::default())
+}
+
+#[tauri::command]
+fn set_panel_mode(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  mode: PanelMode,
+) -> CommandResult<WindowPrefs> {
+  // Compatibility alias. New sizing uses explicit window size classes.
+  set_window_size_class_inner(state, app, size_class_for_panel_mode(&mode))
+}
+
+fn window_layout_key(name: &str) -> CommandResult<String> {
+  let trimmed = name.trim();
+  if trimmed.is_empty() {
+    return Err(AppError::Validation("Layout name cannot be empty".to_string()));
+  }
+
+  Ok(format!("{WINDOW_LAYOUT_KEY_PREFIX}{trimmed}"))
+}
+
+// Snapshots the current window prefs, which the persistence hooks keep in step
+// with the window. Saving under an existing name replaces that layout.
+#[tauri::command]
+fn save_window_layout(state: State<'_, AppState>, name: String) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let key = window_layout_key(&name)?;
+  let prefs = get_window_prefs_from_conn(&conn)?;
+  let value = serde_json::to_string(&prefs).map_err(AppError::from)?;
+  set_meta(&conn, &key, &value)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn list_window_layouts(state: State<'_, AppState>) -> CommandResult<Vec<String>> {
+  let conn = db_conn(&state)?;
+
+  // Compared with substr rather than LIKE, where `_` in the prefix is a wildcard.
+  let mut statement = conn
+    .prepare("SELECT substr(key, length(?1) + 1) FROM app_meta WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key ASC")
+    .map_err(AppError::from)?;
+
+  let rows = statement
+    .query_map(params![WINDOW_LAYOUT_KEY_PREFIX], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+
+  let mut names = Vec::new();
+  for row in rows {
+    names.push(row.map_err(AppError::from)?);
+  }
+
+  Ok(names)
+}
+
+// The layout becomes the current prefs, so `set_panel_mode` and the other
+// window commands carry on from it. Prefs are saved before the window moves,
+// like `dock_window`.
+#[tauri::command]
+fn apply_window_layout(state: State<'_, AppState>, app: AppHandle, name: String) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let key = window_layout_key(&name)?;
+  let raw = get_meta(&conn, &key)?.ok_or_else(|| AppError::NotFound(format!("Window layout not found: {}", name.trim())))?;
+  let prefs = normalize_window_prefs(serde_json::from_str::<WindowPrefs>(&raw).map_err(AppError::from)?);
+  save_window_prefs_to_conn(&conn, &prefs)?;
+  drop(conn);
+
+  if let Some(window) = app.get_webview_window("main") {
+    apply_window_prefs(&window, &prefs)?;
+  }
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn delete_window_layout(state: State<'_, AppState>, name: String) -> CommandResult<()> {
+  let conn = db_conn(&state)?;
+
+  delete_meta(&conn, &window_layout_key(&name)?)
+}
+
+#[tauri::command]
+fn is_window_on_screen(app: AppHandle) -> bool {
+  app.get_webview_window("main").is_none_or(|window| window_on_screen(&window))
+}
+
+// Keeps the current size (shrunk to fit) and undocks, since a docked window
+// is pinned to its edge. Prefs are saved first, as in `dock_window`.
+#[tauri::command]
+fn recenter_window(app: AppHandle, state: State<'_, AppState>) -> CommandResult<WindowPrefs> {
+  let Some(window) = app.get_webview_window("main") else {
+    return Err(AppError::Window("Main window is not available".to_string()));
+  };
+  let work_area = window
+    .primary_monitor()
+    .ok()
+    .flatten()
+    .or_else(|| window.available_monitors().ok()?.into_iter().next())
+    .map(|monitor| logical_work_area(&monitor))
+    .ok_or_else(|| AppError::Window("No monitor available to recenter on".to_string()))?;
+
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  let (width, height) = current_window_rect(&window)
+    .map_or((prefs.width, prefs.height), |rect| (rect.width, rect.height));
+  let rect = centered_rect(work_area, width, height);
+  prefs.x = rect.x;
+  prefs.y = rect.y;
+  prefs.width = rect.width;
+  prefs.height = rect.height;
+  prefs.dock_edge = None;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+  drop(conn);
+
+  apply_window_prefs(&window, &prefs)?;
+
+  Ok(prefs)
+}
+
+// `None` undocks and puts the window back where it was before docking. Prefs
+// are saved first so the move/resize events this triggers see the new state.
+#[tauri::command]
+fn dock_window(state: State<'_, AppState>, app: AppHandle, edge: Option<DockEdge>) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  prefs.dock_edge = edge;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+  drop(conn);
+
+  if let Some(window) = app.get_webview_window("main") {
+    apply_window_prefs(&window, &prefs)?;
+  }
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_panel_mode_size(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  mode: PanelMode,
+  width: f64,
+  height: f64,
+) -> CommandResult<WindowPrefs> {
+  if !(width.is_finite() && height.is_finite()) || width < MIN_PANEL_DIMENSION || height < MIN_PANEL_DIMENSION {
+    return Err(AppError::Validation(format!(
+      "Panel size must be at least {MIN_PANEL_DIMENSION}x{MIN_PANEL_DIMENSION}"
+    )));
+  }
+
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  let size = PanelSize { width, height };
+  match mode {
+    PanelMode::Mini => prefs.mini_size = size,
+    PanelMode::Expanded => prefs.expanded_size = size,
+  }
+
+  if prefs.size_class == size_class_for_panel_mode(&mode) {
+    if let Some(window) = app.get_webview_window("main") {
+      window
+        .set_size(Size::Logical(LogicalSize::new(width, height)))
+        .map_err(AppError::from)?;
+    }
+    prefs.width = width;
+    prefs.height = height;
+  }
+
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+fn set_window_size_class_inner(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  size_class: WindowSizeClass,
+) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  let (target_width, target_height) = dimensions_for_prefs(&prefs, &size_class);
+  let mode = mode_from_size_class(&size_class);
+  let fallback = prefs.always_on_top;
+  let always_on_top = always_on_top_flag(&mut prefs, &mode).unwrap_or(fallback);
+
+  if let Some(window) = app.get_webview_window("main") {
+    window
+      .set_size(Size::Logical(LogicalSize::new(target_width, target_height)))
+      .map_err(AppError::from)?;
+    window
+      .set_always_on_top(always_on_top)
+      .map_err(AppError::from)?;
+  }
+
+  prefs.size_class = size_class;
+  prefs.mode = mode;
+  prefs.always_on_top = always_on_top;
+  prefs.width = target_width;
+  prefs.height = target_height;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_window_size_class(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  size_class: WindowSizeClass,
+) -> CommandResult<WindowPrefs> {
+  set_window_size_class_inner(state, app, size_class)
+}
+
+#[tauri::command]
+fn set_always_on_top(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  enabled: bool,
+) -> CommandResult<WindowPrefs> {
+  if let Some(window) = app.get_webview_window("main") {
+    window
+      .set_always_on_top(enabled)
+      .map_err(AppError::from)?;
+  }
+
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  prefs.always_on_top = enabled;
+  let mode = prefs.mode.clone();
+  *always_on_top_flag(&mut prefs, &mode) = Some(enabled);
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+// Lets clicks pass through the panel to the windows behind it. The tray menu
+// can always turn this back off.
+#[tauri::command]
+fn set_click_through(state: State<'_, AppState>, app: AppHandle, enabled: bool) -> CommandResult<WindowPrefs> {
+  set_click_through_inner(&state, &app, enabled)
+}
+
+fn set_click_through_inner(state: &AppState, app: &AppHandle, enabled: bool) -> CommandResult<WindowPrefs> {
+  if let Some(window) = app.get_webview_window("main") {
+    window
+      .set_ignore_cursor_events(enabled)
+      .map_err(AppError::from)?;
+  }
+
+  let conn = db_conn(state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  prefs.ignore_cursor_events = enabled;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+// Clamped to [0.2, 1.0] so the panel can never become invisible.
+#[tauri::command]
+fn set_window_opacity(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  opacity: f64,
+) -> CommandResult<WindowPrefs> {
+  if !opacity.is_finite() {
+    return Err(AppError::Validation(format!("Invalid window opacity: {opacity}")));
+  }
+  let opacity = opacity.clamp(MIN_WINDOW_OPACITY, 1.0);
+
+  if let Some(window) = app.get_webview_window("main") {
+    set_native_window_opacity(&window, opacity)?;
+  }
+
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  prefs.opacity = opacity;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_aspect_ratio(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  ratio: Option<f64>,
+) -> CommandResult<WindowPrefs> {
+  if let Some(value) = ratio {
+    if !value.is_finite() || value <= 0.0 {
+      return Err(AppError::Validation("Aspect ratio must be a positive number".to_string()));
+    }
+  }
+
+  let prefs = {
+    let conn = db_conn(&state)?;
+
+    let mut prefs = get_window_prefs_from_conn(&conn)?;
+    prefs.aspect_ratio = ratio;
+    if let Some(value) = ratio {
+      let (width, height) = aspect_corrected_size(prefs.width, prefs.height, value);
+      prefs.width = width;
+      prefs.height = height;
+    }
+
+    save_window_prefs_to_conn(&conn, &prefs)?;
+    prefs
+  };
+
+  if ratio.is_some() {
+    if let Some(window) = app.get_webview_window("main") {
+      window
+        .set_size(Size::Logical(LogicalSize::new(prefs.width, prefs.height)))
+        .map_err(AppError::from)?;
+    }
+  }
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_start_minimized(state: State<'_, AppState>, enabled: bool) -> CommandResult<WindowPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_window_prefs_from_conn(&conn)?;
+  prefs.start_minimized = enabled;
+  save_window_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_window_persistence(state: State<'_, AppState>, app: AppHandle, enabled: bool) -> CommandResult<()> {
+  let was_enabled = state.window_persistence_enabled.swap(enabled, Ordering::SeqCst);
+  if !enabled || was_enabled {
+    return Ok(());
+  }
+
+  if let Some(window) = app.get_webview_window("main") {
+    let position = window.outer_position().map_err(AppError::from)?;
+    let size = window.inner_size().map_err(AppError::from)?;
+    save_window_position(&app, position.x as f64, position.y as f64)?;
+    save_window_size(&app, size.width as f64, size.height as f64)?;
+  }
+
+  Ok(())
+}
+
+// Fires one system notification per todo, then returns the interval (from
+// UiPrefs) to wait before the next pass. A todo with a reminder offset is
+// notified once, `reminder_offset_minutes` before it falls due, instead of at
+// the due time itself.
+fn notify_due_todos(app: &AppHandle) -> CommandResult<u32> {
+  let Some(state) = app.try_state::<AppState>() else {
+    return Ok(DEFAULT_DUE_CHECK_INTERVAL_MINUTES);
+  };
+  let conn = db_conn(&state)?;
+
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  let interval = prefs.due_check_interval_minutes.clamp(1, 24 * 60);
+  if !prefs.due_notifications_enabled {
+    return Ok(interval);
+  }
+
+  let now = Local::now();
+  let due: Vec<Todo> = list_open_dated_todos(&conn)?
+    .into_iter()
+    .filter(|todo| todo.reminder_enabled && todo.notified_at.is_none())
+    .filter(|todo| {
+      let lead = Duration::minutes(todo.reminder_offset_minutes.unwrap_or(0));
+      todo
+        .due_date
+        .as_deref()
+        .and_then(due_instant)
+        .is_some_and(|instant| instant - lead <= now)
+    })
+    .collect();
+
+  let notified_at = now_iso();
+  for todo in due {
+    let body = match todo.reminder_offset_minutes {
+      Some(minutes) if minutes > 0 => format!("This todo is due in {minutes} minutes."),
+      _ => "This todo is due.".to_string(),
+    };
+    let shown = app
+      .notification()
+      .builder()
+      .title(todo.title.clone())
+      .body(body)
+      .show();
+    if shown.is_err() {
+      continue;
+    }
+
+    conn
+      .execute(
+        "UPDATE todos SET notified_at = ?2 WHERE id = ?1",
+        params![&todo.id, &notified_at],
+      )
+      .map_err(AppError::from)?;
+  }
+
+  Ok(interval)
+}
+
+fn daily_summary_offset(prefs: &UiPrefs) -> Option<FixedOffset> {
+  match prefs.daily_summary_tz_offset_minutes {
+    Some(minutes) => FixedOffset::east_opt(minutes.saturating_mul(60)),
+    None => Some(Local::now().offset().fix()),
+  }
+}
+
+fn daily_summary_in(conn: &Connection, offset: &FixedOffset) -> CommandResult<DailySummary> {
+  let today = Utc::now().with_timezone(offset).date_naive();
+  let tomorrow = today + Duration::days(1);
+
+  let mut statement = conn
+    .prepare("SELECT completed_at FROM todo_completions")
+    .map_err(AppError::from)?;
+  let rows = statement
+    .query_map([], |row| row.get::<_, String>(0))
+    .map_err(AppError::from)?;
+  let mut completed_today = 0;
+  for row in rows {
+    let raw = row.map_err(AppError::from)?;
+    if DateTime::parse_from_rfc3339(&raw).is_ok_and(|at| at.with_timezone(offset).date_naive() == today) {
+      completed_today += 1;
+    }
+  }
+
+  let due_tomorrow = list_open_dated_todos(conn)?
+    .iter()
+    .filter(|todo| {
+      todo
+        .due_date
+        .as_deref()
+        .and_then(|value| parse_due_day(value, offset))
+        == Some(tomorrow)
+    })
+    .count();
+
+  Ok(DailySummary {
+    date: today.format("%Y-%m-%d").to_string(),
+    completed_today,
+    overdue: open_overdue_todos(conn, offset)?.len() as u32,
+    due_tomorrow: due_tomorrow as u32,
+  })
+}
+
+// Fires at most once per local day: the date is stored before notifying, so
+// a summary that fails to show isn't retried, and a restart after the
+// configured time doesn't repeat it.
+fn send_daily_summary_if_due(app: &AppHandle) -> CommandResult<()> {
+  let Some(state) = app.try_state::<AppState>() else {
+    return Ok(());
+  };
+  let conn = db_conn(&state)?;
+
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  if !prefs.daily_summary_enabled {
+    return Ok(());
+  }
+  let (Ok(at), Some(offset)) = (
+    NaiveTime::parse_from_str(prefs.daily_summary_time.trim(), "%H:%M"),
+    daily_summary_offset(&prefs),
+  ) else {
+    return Ok(());
+  };
+
+  let now = Utc::now().with_timezone(&offset);
+  let today = now.date_naive().format("%Y-%m-%d").to_string();
+  if now.time() < at || get_meta(&conn, DAILY_SUMMARY_LAST_KEY)?.as_deref() == Some(today.as_str()) {
+    return Ok(());
+  }
+  set_meta(&conn, DAILY_SUMMARY_LAST_KEY, &today)?;
+
+  let summary = daily_summary_in(&conn, &offset)?;
+  drop(conn);
+
+  let _ = app
+    .notification()
+    .builder()
+    .title("Daily summary")
+    .body(format!(
+      "Completed today: {}. Overdue: {}. Due tomorrow: {}.",
+      summary.completed_today, summary.overdue, summary.due_tomorrow
+    ))
+    .show();
+  let _ = app.emit("daily-summary", summary);
+
+  Ok(())
+}
+
+fn spawn_daily_summary_loop(app: AppHandle) {
+  std::thread::spawn(move || loop {
+    let _ = send_daily_summary_if_due(&app);
+    std::thread::sleep(std::time::Duration::from_secs(DAILY_SUMMARY_CHECK_SECONDS));
+  });
+}
+
+// The snapshot server is read-only: it answers `GET /todos` with the same JSON
+// as `list_todos` and nothing else. It listens on 127.0.0.1 unless the user
+// explicitly opts into `http_server_lan`, which binds every interface so other
+// devices on the network can read the list. See `authorize_http_request` for
+// what each mode asks of a request.
+fn bind_http_listener(prefs: &UiPrefs) -> std::io::Result<std::net::TcpListener> {
+  let host = if prefs.http_server_lan { "0.0.0.0" } else { "127.0.0.1" };
+  let listener = std::net::TcpListener::bind((host, prefs.http_server_port))?;
+  // Non-blocking so the loop can notice the stop flag between connections.
+  listener.set_nonblocking(true)?;
+  Ok(listener)
+}
+
+fn start_http_server(app: &AppHandle, state: &AppState, prefs: &UiPrefs) -> CommandResult<()> {
+  let listener = bind_http_listener(prefs).map_err(AppError::from)?;
+  let server = spawn_http_server(app, prefs, listener);
+
+  let mut running = state.lock_http_server();
+  *running = Some(server);
+
+  Ok(())
+}
+
+// Each connection gets its own thread so a slow client can't hold up the
+// rest; past `MAX_HTTP_CONNECTIONS` in flight new connections are dropped.
+fn spawn_http_server(app: &AppHandle, prefs: &UiPrefs, listener: std::net::TcpListener) -> HttpServer {
+  let port = prefs.http_server_port;
+  let token = prefs.http_server_lan.then(|| Uuid::new_v4().simple().to_string());
+  let stop = Arc::new(AtomicBool::new(false));
+  let in_flight = Arc::new(AtomicUsize::new(0));
+
+  let thread = {
+    let app = app.clone();
+    let stop = stop.clone();
+    let token = token.clone();
+    std::thread::spawn(move || {
+      while !stop.load(Ordering::SeqCst) {
+        match listener.accept() {
+          Ok((stream, _)) => {
+            if in_flight.fetch_add(1, Ordering::SeqCst) >= MAX_HTTP_CONNECTIONS {
+              in_flight.fetch_sub(1, Ordering::SeqCst);
+              continue;
+            }
+            let app = app.clone();
+            let token = token.clone();
+            let in_flight = in_flight.clone();
+            std::thread::spawn(move || {
+              let _ = serve_http_request(&app, stream, port, token.as_deref());
+              in_flight.fetch_sub(1, Ordering::SeqCst);
+            });
+          }
+          Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+          }
+          Err(_) => std::thread::sleep(std::time::Duration::from_millis(100)),
+        }
+      }
+    })
+  };
+
+  HttpServer { stop, thread, port, token }
+}
+
+fn stop_http_server(state: &AppState) -> CommandResult<()> {
+  let running = state.lock_http_server().take();
+
+  if let Some(server) = running {
+    server.stop.store(true, Ordering::SeqCst);
+    // The listener is dropped with the thread, freeing the port for a restart.
+    let _ = server.thread.join();
+  }
+
+  Ok(())
+}
+
+fn serve_http_request(
+  app: &AppHandle,
+  mut stream: std::net::TcpStream,
+  port: u16,
+  token: Option<&str>,
+) -> std::io::Result<()> {
+  use std::io::{BufRead, BufReader, Read, Write};
+
+  stream.set_nonblocking(false)?;
+  stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+
+  let mut request_line = String::new();
+  let mut host = None;
+  let mut authorization = None;
+  {
+    let mut reader = BufReader::new(&stream).take(8 * 1024);
+    reader.read_line(&mut request_line)?;
+    loop {
+      let mut line = String::new();
+      if reader.read_line(&mut line)? == 0 {
+        break;
+      }
+      let line = line.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if let Some((name, value)) = line.split_once(':') {
+        if name.eq_ignore_ascii_case("host") {
+          host = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("authorization") {
+          authorization = Some(value.trim().to_string());
+        }
+      }
+    }
+  }
+  let mut parts = request_line.split_whitespace();
+  let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+  let (status, body) = match authorize_http_request(host.as_deref(), authorization.as_deref(), port, token) {
+    Err(rejection) => rejection,
+    Ok(()) => match (method, path) {
+      ("GET", "/todos") => match http_todos_json(app) {
+        Ok(json) => ("200 OK", json),
+        Err(err) => ("500 Internal Server Error", serde_json::to_string(&err).unwrap_or_default()),
+      },
+      ("GET", _) => ("404 Not Found", r#"{"code":"notFound","message":"Not found"}"#.to_string()),
+      _ => (
+        "405 Method Not Allowed",
+        r#"{"code":"validation","message":"Read-only endpoint"}"#.to_string(),
+      ),
+    },
+  };
+
+  write!(
+    stream,
+    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  )?;
+  stream.flush()
+}
+
+// Loopback mode only answers requests addressed to `127.0.0.1:<port>` or
+// `localhost:<port>`, so a page that rebinds its own hostname to 127.0.0.1
+// can't read the list through the browser. LAN mode has to accept whatever
+// address other devices use, so it asks for `Authorization: Bearer <token>`
+// with the session token from `get_http_server_token` instead.
+fn authorize_http_request(
+  host: Option<&str>,
+  authorization: Option<&str>,
+  port: u16,
+  token: Option<&str>,
+) -> Result<(), (&'static str, String)> {
+  match token {
+    Some(token) => {
+      let presented = authorization.and_then(|value| value.strip_prefix("Bearer ")).map(str::trim);
+      if presented.is_some_and(|presented| tokens_match(presented, token)) {
+        Ok(())
+      } else {
+        Err(("401 Unauthorized", r#"{"code":"validation","message":"Missing or invalid token"}"#.to_string()))
+      }
+    }
+    None => {
+      let allowed = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+      if host.is_some_and(|host| allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))) {
+        Ok(())
+      } else {
+        Err(("403 Forbidden", r#"{"code":"validation","message":"Unexpected Host header"}"#.to_string()))
+      }
+    }
+  }
+}
+
+// Compares every byte so the time taken doesn't say how much of a guess was right.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+  presented.len() == expected.len()
+    && presented.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn http_todos_json(app: &AppHandle) -> CommandResult<String> {
+  let state = app.state::<AppState>();
+  let conn = db_conn(&state)?;
+  let prefs = get_ui_prefs_from_conn(&conn)?;
+  let todos = list_todos_from_conn(&conn, false, &ListOptions::default(), prefs.sort_mode, prefs.completed_placement)?;
+  serde_json::to_string(&todos).map_err(AppError::from)
+}
+
+// Binds the new listener before stopping the running server, so a port that's
+// taken by something else leaves the old server (and the stored prefs) as they
+// were. Only when the running server itself holds the port is it stopped first;
+// if the port still can't be bound after that, the server is left off and the
+// prefs record that.
+#[tauri::command]
+fn set_http_server(
+  state: State<'_, AppState>,
+  app: AppHandle,
+  enabled: bool,
+  port: Option<u16>,
+  lan: Option<bool>,
+) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  prefs.http_server_enabled = enabled;
+  if let Some(port) = port {
+    if port == 0 {
+      return Err(AppError::Validation("HTTP server port cannot be 0".to_string()));
+    }
+    prefs.http_server_port = port;
+  }
+  if let Some(lan) = lan {
+    prefs.http_server_lan = lan;
+  }
+  drop(conn);
+
+  if enabled {
+    let running_port = state.lock_http_server().as_ref().map(|server| server.port);
+    let listener = match bind_http_listener(&prefs) {
+      Ok(listener) => listener,
+      Err(err) if err.kind() == std::io::ErrorKind::AddrInUse && running_port == Some(prefs.http_server_port) => {
+        stop_http_server(&state)?;
+        match bind_http_listener(&prefs) {
+          Ok(listener) => listener,
+          Err(err) => {
+            let conn = db_conn(&state)?;
+            let mut stored = get_ui_prefs_from_conn(&conn)?;
+            stored.http_server_enabled = false;
+            save_ui_prefs_to_conn(&conn, &stored)?;
+            return Err(AppError::from(err));
+          }
+        }
+      }
+      Err(err) => return Err(AppError::from(err)),
+    };
+    stop_http_server(&state)?;
+    let server = spawn_http_server(&app, &prefs, listener);
+    *state.lock_http_server() = Some(server);
+  } else {
+    stop_http_server(&state)?;
+  }
+
+  let conn = db_conn(&state)?;
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+// The bearer token LAN clients must send; `None` while the server is off or
+// only listening on loopback.
+#[tauri::command]
+fn get_http_server_token(state: State<'_, AppState>) -> CommandResult<Option<String>> {
+  Ok(state.lock_http_server().as_ref().and_then(|server| server.token.clone()))
+}
+
+fn parse_quick_capture_shortcut(value: &str) -> CommandResult<Option<Shortcut>> {
+  let trimmed = value.trim();
+  if trimmed.is_empty() {
+    return Ok(None);
+  }
+
+  trimmed
+    .parse::<Shortcut>()
+    .map(Some)
+    .map_err(|err| AppError::Validation(format!("Invalid shortcut \"{trimmed}\": {err}")))
+}
+
+fn register_quick_capture_shortcut(app: &AppHandle, shortcut: Option<Shortcut>) -> CommandResult<()> {
+  if let Some(shortcut) = shortcut {
+    app
+      .global_shortcut()
+      .register(shortcut)
+      .map_err(|err| AppError::Window(err.to_string()))?;
+  }
+
+  Ok(())
+}
+
+// The capture window is created on first use and hidden rather than closed, so
+// later presses only have to show it. It also hides as soon as it loses focus.
+fn show_quick_capture_window(app: &AppHandle) {
+  if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+    let _ = window.show();
+    let _ = window.set_focus();
+    return;
+  }
+
+  let built = WebviewWindowBuilder::new(app, QUICK_CAPTURE_LABEL, WebviewUrl::App("index.html".into()))
+    .title("Quick Capture")
+    .inner_size(360.0, 56.0)
+    .resizable(false)
+    .decorations(false)
+    .always_on_top(true)
+    .skip_taskbar(true)
+    .center()
+    .focused(true)
+    .build();
+
+  match built {
+    Ok(window) => {
+      let handle = window.clone();
+      window.on_window_event(move |event| {
+        if let WindowEvent::Focused(false) = event {
+          let _ = handle.hide();
+        }
+      });
+    }
+    Err(err) => eprintln!("Failed to open quick capture: {err}"),
+  }
+}
+
+// Adds to the top of the list like `create_todo`, then puts the capture window
+// away.
+#[tauri::command]
+fn quick_capture(state: State<'_, AppState>, app: AppHandle, title: String) -> CommandResult<Todo> {
+  let todo = create_todo(
+    state,
+    app.clone(),
+    CreateTodoInput {
+      title,
+      ..Default::default()
+    },
+  )?;
+  dismiss_quick_capture(app)?;
+
+  Ok(todo)
+}
+
+#[tauri::command]
+fn dismiss_quick_capture(app: AppHandle) -> CommandResult<()> {
+  if let Some(window) = app.get_webview_window(QUICK_CAPTURE_LABEL) {
+    window.hide().map_err(AppError::from)?;
+  }
+
+  Ok(())
+}
+
+// The new binding is validated before the old one is released, and the old one
+// is restored if the new one can't be registered (e.g. another app owns it).
+#[tauri::command]
+fn set_quick_capture_shortcut(state: State<'_, AppState>, app: AppHandle, shortcut: String) -> CommandResult<UiPrefs> {
+  let next = parse_quick_capture_shortcut(&shortcut)?;
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  let previous = parse_quick_capture_shortcut(&prefs.quick_capture_shortcut).ok().flatten();
+  if let Some(previous) = previous {
+    let _ = app.global_shortcut().unregister(previous);
+  }
+  if let Err(err) = register_quick_capture_shortcut(&app, next) {
+    let _ = register_quick_capture_shortcut(&app, previous);
+    return Err(err);
+  }
+
+  prefs.quick_capture_shortcut = shortcut.trim().to_string();
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+// A blank time keeps the stored one.
+#[tauri::command]
+fn set_daily_summary(
+  state: State<'_, AppState>,
+  enabled: bool,
+  time: String,
+  tz_offset_minutes: Option<i32>,
+) -> CommandResult<UiPrefs> {
+  let time = time.trim();
+  if !time.is_empty() && NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+    return Err(AppError::Validation(format!("Invalid daily summary time (expected HH:MM): {time}")));
+  }
+  if let Some(minutes) = tz_offset_minutes {
+    FixedOffset::east_opt(minutes.saturating_mul(60))
+      .ok_or_else(|| AppError::Validation(format!("Invalid timezone offset: {minutes} minutes")))?;
+  }
+
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  prefs.daily_summary_enabled = enabled;
+  if !time.is_empty() {
+    prefs.daily_summary_time = time.to_string();
+  }
+  prefs.daily_summary_tz_offset_minutes = tz_offset_minutes;
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_max_attachments_per_todo(state: State<'_, AppState>, count: u32) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  prefs.max_attachments_per_todo = count;
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_auto_archive_days(state: State<'_, AppState>, days: u32) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  prefs.auto_archive_days = days;
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+#[tauri::command]
+fn set_trash_retention_days(state: State<'_, AppState>, days: u32) -> CommandResult<UiPrefs> {
+  let conn = db_conn(&state)?;
+
+  let mut prefs = get_ui_prefs_from_conn(&conn)?;
+  prefs.trash_retention_days = days;
+  save_ui_prefs_to_conn(&conn, &prefs)?;
+
+  Ok(prefs)
+}
+
+fn spawn_due_notification_loop(app: AppHandle) {
+  std::thread::spawn(move || loop {
+    let minutes = notify_due_todos(&app).unwrap_or(DEFAULT_DUE_CHECK_INTERVAL_MINUTES);
+    std::thread::sleep(std::time::Duration::from_secs(u64::from(minutes) * 60));
+  });
+}
+
+fn database_path(app: &AppHandle) -> std::io::Result<std::path::PathBuf> {
+  let app_data_dir = app.path().app_data_dir().map_err(std::io::Error::other)?;
+  Ok(app_data_dir.join("simple_todo_note.db"))
+}
+
+fn sandbox_enabled() -> bool {
+  std::env::var(SANDBOX_ENV).is_ok_and(|value| value.trim() == "1")
+}
+
+fn open_db_pool(app: &AppHandle, sandbox: bool) -> std::io::Result<DbPool> {
+  if sandbox {
+    return open_sandbox_pool();
+  }
+
+  let db_path = database_path(app)?;
+  if let Some(parent) = db_path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+
+  open_file_pool(&db_path, None)
+}
+
+// Sandbox mode swaps the file DB for a shared in-memory one. Every pooled
+// connection must see the same database, and it vanishes once the last one
+// closes, so the sandbox pool holds a single connection that never expires.
+fn open_sandbox_pool() -> std::io::Result<DbPool> {
+  r2d2::Pool::builder()
+    .max_size(1)
+    .idle_timeout(None)
+    .max_lifetime(None)
+    .build(SqliteConnectionManager::file(SANDBOX_DB_URI).with_init(init_connection))
+    .map_err(std::io::Error::other)
+}
+
+// Writers queue on the busy timeout rather than failing with SQLITE_BUSY.
+// Foreign keys are per-connection, and subtasks rely on them to cascade.
+fn init_connection(conn: &mut Connection) -> rusqlite::Result<()> {
+  conn.execute_batch("PRAGMA busy_timeout = 5000; PRAGMA foreign_keys = ON;")
+}
+
+// SQLCipher needs the key before anything reads the file, so it goes first.
+// Connections are opened lazily so a pool can be built before the file it
+// points at is swapped in (see `set_database_passphrase`).
+fn open_file_pool(path: &std::path::Path, passphrase: Option<&str>) -> std::io::Result<DbPool> {
+  let passphrase = passphrase.map(str::to_string);
+  let manager = SqliteConnectionManager::file(path).with_init(move |conn| {
+    if let Some(passphrase) = &passphrase {
+      conn.pragma_update(None, "key", passphrase)?;
+    }
+    init_connection(conn)
+  });
+
+  r2d2::Pool::builder()
+    .max_size(4)
+    .min_idle(Some(0))
+    .build(manager)
+    .map_err(std::io::Error::other)
+}
+
+// An encrypted file opened without (or with the wrong) key fails its first
+// read with SQLITE_NOTADB.
+fn database_needs_key(conn: &Connection) -> bool {
+  match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+    Err(rusqlite::Error::SqliteFailure(err, _)) => err.code == rusqlite::ErrorCode::NotADatabase,
+    _ => false,
+  }
+}
+
+// Opens `path` outside the pool, keyed with `passphrase` if the file turns out
+// to be encrypted. Returns whether the key was needed; a file `passphrase`
+// doesn't unlock is a validation error rather than a bare `NotADatabase`.
+fn open_keyed_connection(path: &std::path::Path, passphrase: Option<&str>) -> CommandResult<(Connection, bool)> {
+  let conn = Connection::open(path).map_err(AppError::from)?;
+  if !database_needs_key(&conn) {
+    return Ok((conn, false));
+  }
+
+  if let Some(passphrase) = passphrase {
+    let keyed = Connection::open(path).map_err(AppError::from)?;
+    keyed.pragma_update(None, "key", passphrase).map_err(AppError::from)?;
+    if !database_needs_key(&keyed) {
+      return Ok((keyed, true));
+    }
+  }
+
+  Err(AppError::Validation(format!(
+    "{} is not a database, or is encrypted with a different passphrase",
+    path.display()
+  )))
+}
+
+fn database_passphrase(state: &AppState) -> CommandResult<Option<String>> {
+  Ok(state.read_db().passphrase.clone())
+}
+
+// Follows a `set_database_path` override stored in the default database. A
+// missing or unopenable override falls back to the default file so the app
+// still starts. An encrypted override is kept as is; it starts locked.
+fn open_override_pool(conn: &Connection) -> Option<(DbPool, std::path::PathBuf)> {
+  let path = std::path::PathBuf::from(get_meta(conn, DB_PATH_OVERRIDE_KEY).ok()??);
+  if !path.exists() {
+    return None;
+  }
+
+  let pool = open_file_pool(&path, None).ok()?;
+  let conn = pool.get().ok()?;
+  if !database_needs_key(&conn) {
+    ensure_schema(&conn).ok()?;
+  }
+  drop(conn);
+  Some((pool, path))
+}
+
+fn seed_sandbox_todos(conn: &Connection) -> CommandResult<()> {
+  let today = local_today_naive();
+  let samples = [
+    ("Try dragging this todo to reorder", "", None, RECURRENCE_NONE),
+    ("Water the plants", "Every morning before work.", Some(0), RECURRENCE_DAILY),
+    ("Send the weekly report", "", Some(2), RECURRENCE_WEEKLY),
+    ("Renew library books", "Overdue on purpose to show reminders.", Some(-1), RECURRENCE_NONE),
+  ];
+
+  let now = now_iso();
+  for (index, (title, note, due_offset, recurrence_tag)) in samples.into_iter().enumerate() {
+    let todo = Todo {
+      id: Uuid::new_v4().to_string(),
+      title: title.to_string(),
+      recurrence_tag: recurrence_tag.to_string(),
+      recurrence_checked_at: None,
+      note: note.to_string(),
+      completed: false,
+      due_date: due_offset.map(|days| (today + Duration::days(days)).format("%Y-%m-%d").to_string()),
+      created_at: now.clone(),
+      updated_at: now.clone(),
+      reminder_enabled: true,
+      last_reminded_on: None,
+      sort_order: (index as i64 + 1) * SORT_ORDER_GAP,
+      completed_at: None,
+      series_id: None,
+      recurrence_until: None,
+      note_format: NOTE_FORMAT_PLAIN.to_string(),
+      rolled_at: None,
+      priority: TodoPriority::Normal,
+      energy: None,
+      deleted_at: None,
+      recurrence_max_count: None,
+      recurrence_completed_count: 0,
+      notified_at: None,
+      remind_after: None,
+      context: None,
+      snooze_count: 0,
+      color: None,
+      archived: false,
+      pinned: false,
+      reminder_offset_minutes: None,
+      estimate_minutes: None,
+      note_collapsed: false,
+    };
+    insert_todo(conn, &todo)?;
+  }
+
+  Ok(())
+}
+
+fn main() {
+  tauri::Builder::default()
+    .plugin(tauri_plugin_notification::init())
+    .plugin(
+      tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, _shortcut, event| {
+          if event.state() == ShortcutState::Pressed {
+            show_quick_capture_window(app);
+          }
+        })
+        .build(),
+    )
+    .setup(|app| {
+      let sandbox = sandbox_enabled();
+      let mut pool = open_db_pool(app.handle(), sandbox)?;
+      let mut db_path = if sandbox { None } else { Some(database_path(app.handle())?) };
+
+      let mut conn = pool.get().map_err(std::io::Error::other)?;
+      // An encrypted file can't be migrated or read for prefs until the
+      // frontend supplies its passphrase via `unlock_database`; until then
+      // the app runs on default prefs.
+      let mut locked = database_needs_key(&conn);
+      if !locked {
+        ensure_schema(&conn).map_err(std::io::Error::other)?;
+        if sandbox {
+          seed_sandbox_todos(&conn).map_err(std::io::Error::other)?;
+        } else if let Some((override_pool, override_path)) = open_override_pool(&conn) {
+          conn = override_pool.get().map_err(std::io::Error::other)?;
+          pool = override_pool;
+          db_path = Some(override_path);
+          locked = database_needs_key(&conn);
+        }
+      }
+
+      let prefs = get_window_prefs_from_conn(&conn).unwrap_or_default();
+      let ui_prefs = get_ui_prefs_from_conn(&conn).unwrap_or_default();
+      if !locked && ui_prefs.trash_retention_days > 0 {
+        match purge_trash_older_than_in(&conn, i64::from(ui_prefs.trash_retention_days)) {
+          Ok(0) => {}
+          Ok(purged) => eprintln!("Purged {purged} todos from the trash"),
+          Err(err) => eprintln!("Failed to purge the trash: {}", err.message()),
+        }
+      }
+      if !locked && ui_prefs.auto_archive_days > 0 {
+        match auto_archive_in(&conn, ui_prefs.auto_archive_days, Utc::now()) {
+          Ok(0) => {}
+          Ok(archived) => eprintln!("Auto-archived {archived} completed todos"),
+          Err(err) => eprintln!("Failed to auto-archive completed todos: {}", err.message()),
+        }
+      }
+      let clock_skew = detect_clock_skew(&conn).ok();
+      let autostart_disabled = get_meta(&conn, AUTOSTART_DISABLED_KEY)
+        .ok()
+        .flatten()
+        .is_some_and(|value| value == "true");
+      drop(conn);
+      app.manage(AppState {
+        db: RwLock::new(Database {
+          pool,
+          path: db_path,
+          passphrase: None,
+          locked,
+        }),
+        window_persistence_enabled: AtomicBool::new(true),
+        window_focused: AtomicBool::new(true),
+        undo_stack: Mutex::new(Vec::new()),
+        http_server: Mutex::new(None),
+      });
+
+      build_tray(app)?;
+
+      if let Some(window) = app.get_webview_window("main") {
+        let _ = apply_window_prefs(&window, &prefs);
+        if should_start_hidden(&prefs) {
+          let _ = window.hide();
+        }
+        attach_window_persistence(window, app.handle().clone());
+      }
+
+      if let Some(skew) = clock_skew.filter(|skew| skew.exceeds_threshold) {
+        let _ = app.emit("clock-skew-warning", skew);
+      }
+
+      if !sandbox && !locked && !autostart_disabled {
+        let _ = enable_autostart();
+      }
+
+      spawn_due_notification_loop(app.handle().clone());
+      spawn_daily_summary_loop(app.handle().clone());
+
+      if ui_prefs.http_server_enabled {
+        let state = app.state::<AppState>();
+        let _ = start_http_server(app.handle(), &state, &ui_prefs);
+      }
+
+      // A shortcut taken by another app must not stop the launch.
+      let registered = parse_quick_capture_shortcut(&ui_prefs.quick_capture_shortcut)
+        .and_then(|shortcut| register_quick_capture_shortcut(app.handle(), shortcut));
+      if let Err(err) = registered {
+        eprintln!("Failed to register quick capture shortcut: {}", err.message());
+      }
+
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      list_todos,
+      get_todo,
+      list_todos_with_order,
+      list_todos_by_recurrence_tags,
+      list_todos_grouped_by_recurrence,
+      retag_recurrence,
+      list_todos_by_energy,
+      list_contexts,
+      set_active_context,
+      get_active_context,
+      list_todos_by_context,
+      list_inbox_todos,
+      list_stale_todos,
+      list_todos_by_due_date,
+      count_todos,
+      count_hidden_completed,
+      list_overdue_todos,
+      list_due_today_todos,
+      list_todos_due_this_week,
+      list_todos_with_links,
+      search_todos,
+      replace_in_notes,
+      find_similar_titles,
+      create_todo,
+      create_todo_with_position,
+      duplicate_todo,
+      merge_todos,
+      deduplicate_todos,
+      update_todo,
+      set_energy,
+      set_due_relative,
+      set_context,
+      set_pinned,
+      set_note_collapsed,
+      set_focus_todo,
+      get_focus_todo,
+      render_note_html,
+      toggle_todo,
+      set_recurrence_check,
+      create_todos_bulk,
+      create_routine,
+      generate_occurrences,
+      collapse_series,
+      set_recurrence_until_bulk,
+      set_recurrence_max_count,
+      roll_recurring_todos,
+      complete_and_recur,
+      reset_recurring,
+      reschedule_recurrence_group,
+      get_daily_completion_heatmap,
+      completions_by_hour,
+      list_todos_in_range,
+      get_rolling_completion_average,
+      get_stats,
+      get_completion_stats,
+      get_procrastination_index,
+      recompute_scheduled_load,
+      get_scheduled_load,
+      due_date_histogram,
+      recommend_next,
+      get_frog,
+      get_usage_summary,
+      export_completion_history_csv,
+      export_opml,
+      export_todos,
+      export_todo,
+      import_todos,
+      import_markdown,
+      consume_daily_due_reminders,
+      mark_reminder_sent,
+      snooze_todo,
+      snooze_overdue,
+      delete_todo,
+      bulk_set_completed,
+      bulk_update,
+      bulk_delete,
+      preview_bulk,
+      undo_last,
+      archive_todo,
+      unarchive_todo,
+      archive_all_completed,
+      auto_archive_now,
+      list_archived_todos,
+      set_todo_tags,
+      list_tags,
+      tag_completion_stats,
+      list_todos_by_tag,
+      list_subtasks,
+      add_subtask,
+      toggle_subtask,
+      set_all_subtasks_completed,
+      check_subtask_consistency,
+      fix_subtask_consistency,
+      delete_subtask,
+      add_attachment,
+      list_attachments,
+      count_attachments,
+      remove_attachment,
+      start_timer,
+      stop_timer,
+      get_tracked_minutes,
+      add_dependency,
+      remove_dependency,
+      list_blocked_todos,
+      list_trashed_todos,
+      restore_todo,
+      restore_trashed_since,
+      purge_todo,
+      purge_trash_older_than,
+      changes_since,
+      get_todo_history,
+      get_delta,
+      prune_change_log,
+      list_by_edit_frequency,
+      request_wipe_token,
+      wipe_all_data,
+      reorder_todos,
+      swap_todo_order,
+      move_todo_to_index,
+      move_todo,
+      rebalance_sort_orders,
+      normalize_sort_orders,
+      migrate_legacy_todos_if_needed,
+      get_window_prefs,
+      save_window_prefs,
+      reset_window_prefs_only,
+      get_ui_prefs,
+      audit_accessibility,
+      get_effective_reduce_motion,
+      set_autostart,
+      get_autostart_enabled,
+      save_ui_prefs,
+      set_http_server,
+      get_http_server_token,
+      set_quick_capture_shortcut,
+      set_max_attachments_per_todo,
+      set_auto_archive_days,
+      set_trash_retention_days,
+      set_daily_summary,
+      quick_capture,
+      dismiss_quick_capture,
+      reset_ui_prefs,
+      format_display_date,
+      get_clock_skew,
+      get_database_path,
+      get_storage_info,
+      vacuum_database,
+      backup_database,
+      get_database_locked,
+      unlock_database,
+      set_database_passphrase,
+      set_database_path,
+      explain_list_query,
+      save_filter,
+      list_saved_filters,
+      delete_saved_filter,
+      run_saved_filter,
+      check_referential_integrity,
+      check_integrity,
+      prune_orphans,
+      get_sync_manifest,
+      apply_remote_changes,
+      set_panel_mode,
+      save_window_layout,
+      list_window_layouts,
+      apply_window_layout,
+      delete_window_layout,
+      set_panel_mode_size,
+      dock_window,
+      is_window_on_screen,
+      recenter_window,
+      set_window_size_class,
+      set_always_on_top,
+      set_click_through,
+      set_window_opacity,
+      set_window_persistence,
+      set_aspect_ratio,
+      set_start_minimized,
+    ])
+    .run(tauri::generate_context!())
+    .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_conn() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    init_connection(&mut conn).unwrap();
+    ensure_schema(&conn).unwrap();
+    conn
+  }
+
+  // One pooled in-memory connection, so every `db_conn` sees the same database.
+  fn test_state() -> AppState {
+    let pool = r2d2::Pool::builder()
+      .max_size(1)
+      .idle_timeout(None)
+      .max_lifetime(None)
+      .build(SqliteConnectionManager::memory().with_init(init_connection))
+      .unwrap();
+    ensure_schema(&pool.get().unwrap()).unwrap();
+
+    AppState {
+      db: RwLock::new(Database {
+        pool,
+        path: None,
+        passphrase: None,
+        locked: false,
+      }),
+      window_persistence_enabled: AtomicBool::new(true),
+      window_focused: AtomicBool::new(true),
+      undo_stack: Mutex::new(Vec::new()),
+      http_server: Mutex::new(None),
+    }
+  }
+
+  fn insert_test_todo(conn: &Connection, title: &str) -> Todo {
+    let todo = new_todo_from_input(
+      conn,
+      CreateTodoInput {
+        title: title.to_string(),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    insert_todo(conn, &todo).unwrap();
+    todo
+  }
+
+  fn day(value: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+  }
+
+  fn complete_test_todo(conn: &Connection, title: &str, completed_at: &str) -> Todo {
+    let mut todo = insert_test_todo(conn, title);
+    todo.completed = true;
+    todo.completed_at = Some(completed_at.to_string());
+    update_todo_row(conn, &todo).unwrap();
+    todo
+  }
+
+  fn test_attachment(todo_id: &str, path: &str) -> Attachment {
+    Attachment {
+      id: Uuid::new_v4().to_string(),
+      todo_id: todo_id.to_string(),
+      path: path.to_string(),
+      label: None,
+      created_at: now_iso(),
+    }
+  }
+
+  #[test]
+  fn completions_by_hour_buckets_by_utc_hour() {
+    let conn = test_conn();
+    complete_test_todo(&conn, "Early", "2024-03-10T06:15:00Z");
+    complete_test_todo(&conn, "Lunch", "2024-03-10T12:00:00Z");
+    complete_test_todo(&conn, "Also lunch", "2024-03-10T12:59:59Z");
+    complete_test_todo(&conn, "Outside range", "2024-03-12T12:00:00Z");
+
+    let buckets = completions_by_hour_in(&conn, &Utc, day("2024-03-10"), day("2024-03-11")).unwrap();
+
+    assert_eq!(buckets.len(), 24);
+    assert_eq!(buckets[6], 1);
+    assert_eq!(buckets[12], 2);
+    assert_eq!(buckets.iter().sum::<u32>(), 3);
+  }
+
+  #[test]
+  fn completions_by_hour_converts_to_the_local_timezone() {
+    let conn = test_conn();
+    // 02:30 UTC on the 11th is 21:30 on the 10th at UTC-5.
+    complete_test_todo(&conn, "Late night", "2024-03-11T02:30:00Z");
+    let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+
+    let local = completions_by_hour_in(&conn, &new_york, day("2024-03-10"), day("2024-03-10")).unwrap();
+    assert_eq!(local[21], 1);
+    assert_eq!(local.iter().sum::<u32>(), 1);
+
+    let utc = completions_by_hour_in(&conn, &Utc, day("2024-03-10"), day("2024-03-10")).unwrap();
+    assert_eq!(utc.iter().sum::<u32>(), 0);
+  }
+
+  #[test]
+  fn attachments_stop_at_the_limit_and_free_up_on_removal() {
+    let conn = test_conn();
+    let todo = insert_test_todo(&conn, "Report");
+
+    let mut added = Vec::new();
+    for index in 0..3 {
+      let attachment = test_attachment(&todo.id, &format!("/tmp/file-{index}.txt"));
+      insert_attachment_within_limit(&conn, &attachment, 3).unwrap();
+      added.push(attachment);
+    }
+    assert_eq!(count_attachments_in(&conn, &todo.id).unwrap(), 3);
+
+    let over = insert_attachment_within_limit(&conn, &test_attachment(&todo.id, "/tmp/extra.txt"), 3);
+    assert!(matches!(over, Err(AppError::LimitReached(_))));
+    assert_eq!(count_attachments_in(&conn, &todo.id).unwrap(), 3);
+
+    conn
+      .execute("DELETE FROM todo_attachments WHERE id = ?1", params![&added[0].id])
+      .unwrap();
+    insert_attachment_within_limit(&conn, &test_attachment(&todo.id, "/tmp/extra.txt"), 3).unwrap();
+    assert_eq!(count_attachments_in(&conn, &todo.id).unwrap(), 3);
+  }
+
+  fn insert_test_todo_due(conn: &Connection, title: &str, due_date: &str) -> Todo {
+    let mut todo = insert_test_todo(conn, title);
+    todo.due_date = Some(due_date.to_string());
+    update_todo_row(conn, &todo).unwrap();
+    todo
+  }
+
+  #[test]
+  fn recommend_next_prefers_the_most_overdue_open_todo() {
+    let conn = test_conn();
+    let today = local_today_naive();
+    let due = |days: i64| (today + Duration::days(days)).format("%Y-%m-%d").to_string();
+    insert_test_todo(&conn, "Someday");
+    insert_test_todo_due(&conn, "Next week", &due(7));
+    insert_test_todo_due(&conn, "Today", &due(0));
+    let overdue = insert_test_todo_due(&conn, "Late", &due(-2));
+    let mut finished = insert_test_todo_due(&conn, "Very late but done", &due(-10));
+    finished.completed = true;
+    update_todo_row(&conn, &finished).unwrap();
+
+    let picked = recommend_next_in(&conn, today).unwrap().unwrap();
+    assert_eq!(picked.id, overdue.id);
+  }
+
+  #[test]
+  fn recommend_next_scores_timestamped_due_dates() {
+    let conn = test_conn();
+    let today = local_today_naive();
+    insert_test_todo_due(&conn, "Tomorrow", &(today + Duration::days(1)).format("%Y-%m-%d").to_string());
+    let snoozed_past = (Local::now() - Duration::days(3)).to_rfc3339();
+    let snoozed = insert_test_todo_due(&conn, "Snoozed", &snoozed_past);
+
+    let picked = recommend_next_in(&conn, today).unwrap().unwrap();
+    assert_eq!(picked.id, snoozed.id);
+  }
+
+  #[test]
+  fn recommend_next_breaks_ties_by_id_and_handles_empty() {
+    let conn = test_conn();
+    let today = local_today_naive();
+    assert!(recommend_next_in(&conn, today).unwrap().is_none());
+
+    let first = insert_test_todo(&conn, "A");
+    let second = insert_test_todo(&conn, "B");
+    let picked = recommend_next_in(&conn, today).unwrap().unwrap();
+    assert_eq!(picked.id, first.id.clone().min(second.id.clone()));
+  }
+
+  fn trash_test_todo(conn: &Connection, title: &str, deleted_at: DateTime<Utc>) -> Todo {
+    let mut todo = insert_test_todo(conn, title);
+    todo.deleted_at = Some(deleted_at.to_rfc3339());
+    update_todo_row(conn, &todo).unwrap();
+    todo
+  }
+
+  #[test]
+  fn restore_trashed_since_only_restores_inside_the_window() {
+    let conn = test_conn();
+    let now = Utc::now();
+    let old = trash_test_todo(&conn, "Trashed yesterday", now - Duration::days(1));
+    let recent = trash_test_todo(&conn, "Trashed recently", now - Duration::minutes(30));
+    let just_now = trash_test_todo(&conn, "Trashed just now", now - Duration::minutes(1));
+
+    let restored = restore_trashed_since_in(&conn, now - Duration::hours(1)).unwrap();
+
+    assert_eq!(restored, 2);
+    assert!(get_todo_by_id(&conn, &old.id, false).unwrap().is_none());
+    assert!(get_todo_by_id(&conn, &recent.id, false).unwrap().is_some());
+    assert!(get_todo_by_id(&conn, &just_now.id, false).unwrap().is_some());
+    assert_eq!(restore_trashed_since_in(&conn, now - Duration::hours(1)).unwrap(), 0);
+  }
+
+  fn record_test_completion_day(conn: &Connection, todo_id: &str, event_day: &str) {
+    conn
+      .execute(
+        "INSERT INTO daily_completion_events (todo_id, event_day, created_at) VALUES (?1, ?2, ?3)",
+        params![todo_id, event_day, now_iso()],
+      )
+      .unwrap();
+  }
+
+  #[test]
+  fn completion_history_csv_quotes_titles_and_orders_by_day() {
+    let conn = test_conn();
+    let groceries = insert_test_todo(&conn, "Milk, eggs");
+    let plain = insert_test_todo(&conn, "Stretch");
+    record_test_completion_day(&conn, &plain.id, "2024-05-03");
+    record_test_completion_day(&conn, &groceries.id, "2024-05-01");
+    record_test_completion_day(&conn, "gone", "2024-05-02");
+    record_test_completion_day(&conn, &plain.id, "2024-06-01");
+
+    let csv = completion_history_csv_in(&conn, day("2024-05-01"), day("2024-05-31")).unwrap();
+
+    assert_eq!(
+      csv,
+      format!(
+        "todo_id,title,completed_date\n{},\"Milk, eggs\",2024-05-01\ngone,(deleted todo),2024-05-02\n{},Stretch,2024-05-03\n",
+        groceries.id, plain.id
+      )
+    );
+  }
+
+  #[test]
+  fn locale_formats_swap_month_and_day() {
+    let date = day("2024-03-09");
+
+    assert_eq!(format_date_for_locale(date, &DateLocale::EnUs), "03/09/2024");
+    assert_eq!(format_date_for_locale(date, &DateLocale::EnGb), "09/03/2024");
+    assert_eq!(format_date_for_locale(date, &DateLocale::DeDe), "09.03.2024");
+  }
+
+  #[test]
+  fn system_locale_resolves_from_posix_locale_names() {
+    let resolved = |value: &str| date_locale_from_posix(value).map(|locale| date_format_for_locale(&locale));
+
+    assert_eq!(resolved("en_US.UTF-8"), Some("%m/%d/%Y"));
+    assert_eq!(resolved("en-GB"), Some("%d/%m/%Y"));
+    assert_eq!(resolved("de_AT.UTF-8@euro"), Some("%d.%m.%Y"));
+    assert_eq!(resolved("ja_JP.eucJP"), Some("%Y/%m/%d"));
+    for unknown in ["C", "POSIX", "C.UTF-8", "fr_FR.UTF-8", "en_AU", ""] {
+      assert_eq!(resolved(unknown), None, "{unknown}");
+    }
+  }
+
+  #[test]
+  fn clock_skew_is_reported_for_future_stamped_rows() {
+    let conn = test_conn();
+    insert_test_todo(&conn, "Now");
+    let skew = detect_clock_skew(&conn).unwrap();
+    assert!(!skew.exceeds_threshold);
+
+    let mut future = insert_test_todo(&conn, "From the future");
+    future.updated_at = (Utc::now() + Duration::hours(2)).to_rfc3339();
+    update_todo_row(&conn, &future).unwrap();
+
+    let skew = detect_clock_skew(&conn).unwrap();
+    assert!(skew.exceeds_threshold);
+    assert!(skew.skew_seconds > 7_000);
+    assert_eq!(skew.latest_stored_at.as_deref(), Some(future.updated_at.as_str()));
+  }
+
+  #[test]
+  fn auto_archive_takes_old_completions_and_keeps_recent_ones() {
+    let conn = test_conn();
+    let now = Utc::now();
+    let old = complete_test_todo(&conn, "Done long ago", &(now - Duration::days(10)).to_rfc3339());
+    let recent = complete_test_todo(&conn, "Done yesterday", &(now - Duration::days(1)).to_rfc3339());
+    let open = insert_test_todo(&conn, "Still open");
+
+    assert!(should_auto_archive(&old, now, 7));
+    assert!(!should_auto_archive(&recent, now, 7));
+    assert!(!should_auto_archive(&open, now, 7));
+    assert!(!should_auto_archive(&old, now, 0));
+
+    assert_eq!(auto_archive_in(&conn, 7, now).unwrap(), 1);
+    assert!(get_todo_by_id(&conn, &old.id, false).unwrap().unwrap().archived);
+    assert!(!get_todo_by_id(&conn, &recent.id, false).unwrap().unwrap().archived);
+    assert_eq!(auto_archive_in(&conn, 7, now).unwrap(), 0);
+  }
+
+  #[test]
+  fn sync_manifest_lists_every_todo_including_tombstones() {
+    let conn = test_conn();
+    let kept = insert_test_todo(&conn, "Kept");
+    let trashed = insert_test_todo(&conn, "Trashed");
+    let deleted_at = (Utc::now() + Duration::seconds(5)).to_rfc3339();
+    assert_eq!(trash_todo_in(&conn, &trashed.id, &deleted_at).unwrap(), 1);
+
+    let manifest = sync_manifest_in(&conn).unwrap();
+
+    let mut expected = vec![(kept.id.clone(), kept.updated_at.clone()), (trashed.id.clone(), deleted_at)];
+    expected.sort();
+    assert_eq!(manifest, expected);
+  }
+
+  fn remote_copy(local: &Todo, title: &str, updated_at: DateTime<Utc>) -> Todo {
+    Todo {
+      title: title.to_string(),
+      updated_at: updated_at.to_rfc3339(),
+      ..local.clone()
+    }
+  }
+
+  #[test]
+  fn remote_changes_apply_when_newer_and_skip_when_older() {
+    let conn = test_conn();
+    let local = insert_test_todo(&conn, "Local title");
+    let local_at = DateTime::parse_from_rfc3339(&local.updated_at).unwrap().with_timezone(&Utc);
+
+    let older = remote_copy(&local, "Stale remote title", local_at - Duration::minutes(5));
+    let report = apply_remote_changes_in(&conn, vec![older], &[]).unwrap();
+    assert_eq!((report.applied, report.skipped), (0, 1));
+    assert_eq!(get_todo_by_id(&conn, &local.id, false).unwrap().unwrap().title, "Local title");
+
+    let newer = remote_copy(&local, "Fresh remote title", local_at + Duration::minutes(5));
+    let report = apply_remote_changes_in(&conn, vec![newer], &[]).unwrap();
+    assert_eq!((report.applied, report.skipped), (1, 0));
+    assert_eq!(get_todo_by_id(&conn, &local.id, false).unwrap().unwrap().title, "Fresh remote title");
+  }
+
+  #[test]
+  fn remote_changes_insert_unknown_ids() {
+    let conn = test_conn();
+    let template = insert_test_todo(&conn, "Template");
+    let incoming = Todo {
+      id: Uuid::new_v4().to_string(),
+      ..remote_copy(&template, "From another device", Utc::now())
+    };
+
+    let report = apply_remote_changes_in(&conn, vec![incoming.clone()], &[]).unwrap();
+
+    assert_eq!(report.applied, 1);
+    let inserted = get_todo_by_id(&conn, &incoming.id, false).unwrap().unwrap();
+    assert_eq!(inserted.title, "From another device");
+    assert!(inserted.sort_order < template.sort_order);
+  }
+
+  #[test]
+  fn remote_deletions_move_todos_to_the_trash() {
+    let conn = test_conn();
+    let doomed = insert_test_todo(&conn, "Deleted remotely");
+    let kept = insert_test_todo(&conn, "Kept");
+
+    let report = apply_remote_changes_in(&conn, Vec::new(), &[doomed.id.clone(), "unknown".to_string()]).unwrap();
+
+    assert_eq!(report.deleted, 1);
+    assert!(get_todo_by_id(&conn, &doomed.id, false).unwrap().is_none());
+    assert!(get_todo_by_id(&conn, &doomed.id, true).unwrap().unwrap().deleted_at.is_some());
+    assert!(get_todo_by_id(&conn, &kept.id, false).unwrap().is_some());
+  }
+
+  #[test]
+  fn window_saves_are_skipped_while_persistence_is_disabled() {
+    let state = test_state();
+    let defaults = WindowPrefs::default();
+
+    state.window_persistence_enabled.store(false, Ordering::SeqCst);
+    save_window_position_in(&state, 300.0, 200.0).unwrap();
+    save_window_size_in(&state, 900.0, 700.0).unwrap();
+    let prefs = get_window_prefs_from_conn(&db_conn(&state).unwrap()).unwrap();
+    assert_eq!((prefs.x, prefs.y), (defaults.x, defaults.y));
+    assert_eq!((prefs.width, prefs.height), (defaults.width, defaults.height));
+
+    state.window_persistence_enabled.store(true, Ordering::SeqCst);
+    save_window_position_in(&state, 300.0, 200.0).unwrap();
+    save_window_size_in(&state, 900.0, 700.0).unwrap();
+    let prefs = get_window_prefs_from_conn(&db_conn(&state).unwrap()).unwrap();
+    assert_eq!((prefs.x, prefs.y), (300.0, 200.0));
+    assert_eq!((prefs.width, prefs.height), (900.0, 700.0));
+  }
+
+  #[test]
+  fn extract_urls_handles_zero_one_and_many_links() {
+    assert!(extract_urls("Call the dentist").is_empty());
+    assert!(extract_urls("Nothing at http:// here").is_empty());
+    assert_eq!(extract_urls("See https://example.com/docs."), vec!["https://example.com/docs"]);
+    assert_eq!(
+      extract_urls("Spec (https://a.example/spec) and http://b.example/?q=1, plus https://c.example"),
+      vec!["https://a.example/spec", "http://b.example/?q=1", "https://c.example"]
+    );
+  }
+
+  #[test]
+  fn todos_with_links_returns_only_notes_with_urls() {
+    let conn = test_conn();
+    let mut linked = insert_test_todo(&conn, "Read docs");
+    linked.note = "Start at https://example.com and then http://example.org/faq".to_string();
+    update_todo_row(&conn, &linked).unwrap();
+    let mut unlinked = insert_test_todo(&conn, "Mention http without a link");
+    unlinked.note = "the http header".to_string();
+    update_todo_row(&conn, &unlinked).unwrap();
+    insert_test_todo(&conn, "No note");
+
+    let found = todos_with_links_in(&conn).unwrap();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].todo.id, linked.id);
+    assert_eq!(found[0].urls, vec!["https://example.com", "http://example.org/faq"]);
+  }
+
+  fn insert_recurring_test_todo(conn: &Connection, title: &str, recurrence_tag: &str, due_date: &str) -> Todo {
+    let mut todo = insert_test_todo_due(conn, title, due_date);
+    todo.recurrence_tag = recurrence_tag.to_string();
+    update_todo_row(conn, &todo).unwrap();
+    todo
+  }
+
+  #[test]
+  fn generate_occurrences_adds_successive_dates_in_one_series() {
+    let conn = test_conn();
+    let source = insert_recurring_test_todo(&conn, "Standup", RECURRENCE_WEEKLY, "2024-01-01");
+
+    let created = generate_occurrences_in(&conn, &source.id, 3).unwrap();
+
+    let dates: Vec<_> = created.iter().map(|todo| todo.due_date.clone().unwrap()).collect();
+    assert_eq!(dates, vec!["2024-01-08", "2024-01-15", "2024-01-22"]);
+    assert!(created.iter().all(|todo| todo.series_id.as_deref() == Some(source.id.as_str())));
+    let source = get_todo_by_id(&conn, &source.id, false).unwrap().unwrap();
+    assert_eq!(source.series_id.as_deref(), Some(source.id.as_str()));
+
+    let more = generate_occurrences_in(&conn, &source.id, 1).unwrap();
+    assert_eq!(more[0].due_date.as_deref(), Some("2024-01-29"));
+  }
+
+  #[test]
+  fn generate_occurrences_accepts_timestamped_due_dates() {
+    let conn = test_conn();
+    let due = Local.with_ymd_and_hms(2024, 1, 1, 15, 0, 0).unwrap().to_rfc3339();
+    let source = insert_recurring_test_todo(&conn, "Snoozed habit", RECURRENCE_DAILY, &due);
+
+    let created = generate_occurrences_in(&conn, &source.id, 2).unwrap();
+
+    let dates: Vec<_> = created.iter().map(|todo| todo.due_date.clone().unwrap()).collect();
+    assert_eq!(dates, vec!["2024-01-02", "2024-01-03"]);
+  }
+
+  #[test]
+  fn collapse_series_keeps_the_earliest_open_and_completed_members() {
+    let conn = test_conn();
+    let source = insert_recurring_test_todo(&conn, "Water plants", RECURRENCE_DAILY, "2024-02-01");
+    let generated = generate_occurrences_in(&conn, &source.id, 3).unwrap();
+    let mut done = generated[0].clone();
+    done.completed = true;
+    update_todo_row(&conn, &done).unwrap();
+
+    let removed = collapse_series_in(&conn, &source.id).unwrap();
+
+    assert_eq!(removed, 2);
+    let remaining: Vec<String> = conn
+      .prepare("SELECT id FROM todos WHERE series_id = ?1 AND deleted_at IS NULL ORDER BY due_date")
+      .unwrap()
+      .query_map(params![&source.id], |row| row.get(0))
+      .unwrap()
+      .map(Result::unwrap)
+      .collect();
+    assert_eq!(remaining, vec![source.id.clone(), done.id.clone()]);
+    assert!(get_todo_by_id(&conn, &generated[2].id, true).unwrap().unwrap().deleted_at.is_some());
+    assert_eq!(collapse_series_in(&conn, &source.id).unwrap(), 0);
+  }
+
+  fn tag_test_todo(conn: &Connection, title: &str, tags: &[&str], completed: bool) -> Todo {
+    let mut todo = insert_test_todo(conn, title);
+    todo.completed = completed;
+    update_todo_row(conn, &todo).unwrap();
+    let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+    replace_todo_tags_in(conn, &todo.id, &tags).unwrap();
+    todo
+  }
+
+  #[test]
+  fn tag_completion_stats_counts_and_ranks_by_ratio() {
+    let conn = test_conn();
+    tag_test_todo(&conn, "Invoice", &["work"], true);
+    tag_test_todo(&conn, "Slides", &["work"], false);
+    tag_test_todo(&conn, "Review", &["work", "urgent"], true);
+    tag_test_todo(&conn, "Gym", &["health"], false);
+    let trashed = tag_test_todo(&conn, "Old", &["archive"], true);
+    trash_todo_in(&conn, &trashed.id, &now_iso()).unwrap();
+
+    let stats = tag_completion_stats_in(&conn).unwrap();
+
+    let summary: Vec<(&str, u32, u32)> = stats
+      .iter()
+      .map(|entry| (entry.tag.as_str(), entry.total, entry.completed))
+      .collect();
+    assert_eq!(summary, vec![("urgent", 1, 1), ("work", 3, 2), ("health", 1, 0)]);
+    assert!((stats[1].ratio - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(stats[2].ratio, 0.0);
+  }
+
+  fn age_test_todo(conn: &Connection, todo: &Todo, created_at: &str) {
+    conn
+      .execute("UPDATE todos SET created_at = ?2 WHERE id = ?1", params![&todo.id, created_at])
+      .unwrap();
+  }
+
+  #[test]
+  fn get_frog_returns_oldest_open_todo() {
+    let conn = test_conn();
+    assert!(get_frog_in(&conn).unwrap().is_none());
+
+    let done = complete_test_todo(&conn, "Ancient but done", "2026-01-02T09:00:00Z");
+    age_test_todo(&conn, &done, "2026-01-01T09:00:00Z");
+    let archived = insert_test_todo(&conn, "Hidden");
+    age_test_todo(&conn, &archived, "2026-01-02T09:00:00Z");
+    conn
+      .execute("UPDATE todos SET archived = 1 WHERE id = ?1", params![&archived.id])
+      .unwrap();
+    let blocked = insert_test_todo(&conn, "Waiting");
+    age_test_todo(&conn, &blocked, "2026-01-03T09:00:00Z");
+    let oldest = insert_test_todo(&conn, "Oldest open");
+    age_test_todo(&conn, &oldest, "2026-01-04T09:00:00Z");
+    let newer = insert_test_todo(&conn, "Newer");
+    age_test_todo(&conn, &newer, "2026-02-01T09:00:00Z");
+    conn
+      .execute(
+        "INSERT INTO todo_dependencies (todo_id, depends_on_id) VALUES (?1, ?2)",
+        params![&blocked.id, &newer.id],
+      )
+      .unwrap();
+
+    assert_eq!(get_frog_in(&conn).unwrap().map(|todo| todo.id), Some(oldest.id));
+  }
+
+  #[test]
+  fn aspect_corrected_size_follows_width() {
+    assert_eq!(aspect_corrected_size(800.0, 300.0, 16.0 / 9.0), (800.0, 450.0));
+    assert_eq!(aspect_corrected_size(401.0, 900.0, 4.0 / 3.0), (401.0, 301.0));
+    assert_eq!(aspect_corrected_size(500.0, 500.0, 1.0), (500.0, 500.0));
+  }
+
+  #[test]
+  fn aspect_corrected_size_ignores_unusable_ratios() {
+    assert_eq!(aspect_corrected_size(800.0, 300.0, 0.0), (800.0, 300.0));
+    assert_eq!(aspect_corrected_size(800.0, 300.0, -1.5), (800.0, 300.0));
+    assert_eq!(aspect_corrected_size(800.0, 300.0, f64::NAN), (800.0, 300.0));
+    assert_eq!(aspect_corrected_size(0.0, 300.0, 2.0), (0.0, 300.0));
+  }
+
+  #[test]
+  fn set_recurrence_until_bulk_caps_series_and_reports_offenders() {
+    let conn = test_conn();
+    let early = insert_recurring_test_todo(&conn, "Standup", RECURRENCE_WEEKLY, "2024-03-01");
+    let on_day = insert_recurring_test_todo(&conn, "Retro", RECURRENCE_WEEKLY, "2024-03-31");
+    let late = insert_recurring_test_todo(&conn, "Planning", RECURRENCE_WEEKLY, "2024-04-02T09:00:00Z");
+
+    let report = set_recurrence_until_bulk_in(
+      &conn,
+      vec![early.id.clone(), on_day.id.clone(), late.id.clone(), "missing".to_string()],
+      day("2024-03-31"),
+    )
+    .unwrap();
+
+    assert_eq!(report.updated, 2);
+    assert_eq!(report.skipped_ids, vec![late.id.clone(), "missing".to_string()]);
+    let until_of = |id: &str| get_todo_by_id(&conn, id, false).unwrap().unwrap().recurrence_until;
+    assert_eq!(until_of(&early.id).as_deref(), Some("2024-03-31"));
+    assert_eq!(until_of(&on_day.id).as_deref(), Some("2024-03-31"));
+    assert_eq!(until_of(&late.id), None);
+  }
+
+  #[test]
+  fn most_active_weekday_picks_the_busiest_day() {
+    assert_eq!(most_active_weekday(&[]), None);
+    // 2024-01-01 is a Monday.
+    let days = [
+      day("2024-01-01"),
+      day("2024-01-03"),
+      day("2024-01-10"),
+      day("2024-01-17"),
+      day("2024-01-05"),
+      day("2024-01-12"),
+    ];
+    assert_eq!(most_active_weekday(&days), Some(Weekday::Wed));
+  }
+
+  #[test]
+  fn most_active_weekday_breaks_ties_towards_monday() {
+    let days = [day("2024-01-07"), day("2024-01-02"), day("2024-01-14"), day("2024-01-09")];
+    assert_eq!(most_active_weekday(&days), Some(Weekday::Tue));
+  }
+
+  #[test]
+  fn usage_summary_counts_this_week_only() {
+    let conn = test_conn();
+    let set_times = |todo: &Todo, created_at: &str, completed_at: Option<&str>| {
+      conn
+        .execute(
+          "UPDATE todos SET created_at = ?2, completed_at = ?3, completed = ?4 WHERE id = ?1",
+          params![&todo.id, created_at, completed_at, completed_at.is_some()],
+        )
+        .unwrap();
+    };
+    // Week of Monday 2024-01-08; "today" is Wednesday 2024-01-10.
+    let a = insert_test_todo(&conn, "A");
+    set_times(&a, "2024-01-08T12:00:00Z", Some("2024-01-10T12:00:00Z"));
+    let b = insert_test_todo(&conn, "B");
+    set_times(&b, "2024-01-10T12:00:00Z", None);
+    let c = insert_test_todo(&conn, "C");
+    set_times(&c, "2024-01-03T12:00:00Z", Some("2024-01-09T12:00:00Z"));
+    let d = insert_test_todo(&conn, "D");
+    set_times(&d, "2024-01-01T12:00:00Z", Some("2024-01-02T12:00:00Z"));
+
+    let summary = get_usage_summary_in(&conn, day("2024-01-10"), FirstDayOfWeek::Monday).unwrap();
+
+    assert_eq!(summary.created_this_week, 2);
+    assert_eq!(summary.completed_this_week, 2);
+    assert!((summary.average_completions_per_day - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(summary.most_active_weekday.as_deref(), Some("Wed"));
+  }
+
+  #[test]
+  fn note_format_round_trips_and_defaults_to_plain() {
+    let conn = test_conn();
+    ensure_schema(&conn).unwrap();
+    let plain = insert_test_todo(&conn, "Plain");
+    let markdown = new_todo_from_input(
+      &conn,
+      CreateTodoInput {
+        title: "Formatted".to_string(),
+        note: Some("**bold**".to_string()),
+        note_format: Some(NOTE_FORMAT_MARKDOWN.to_string()),
+        ..Default::default()
+      },
+    )
+    .unwrap();
+    insert_todo(&conn, &markdown).unwrap();
+
+    let format_of = |id: &str| get_todo_by_id(&conn, id, false).unwrap().unwrap().note_format;
+    assert_eq!(format_of(&plain.id), NOTE_FORMAT_PLAIN);
+    assert_eq!(format_of(&markdown.id), NOTE_FORMAT_MARKDOWN);
+    assert_eq!(normalize_note_format(Some("html".to_string())), NOTE_FORMAT_PLAIN);
+  }
+
+  #[test]
+  fn render_markdown_html_renders_and_sanitizes() {
+    assert_eq!(render_markdown_html("**bold** text"), "<p><strong>bold</strong> text</p>\n");
+
+    let html = render_markdown_html("<script>alert(1)</script>\n\n[x](javascript:alert(1))");
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(!html.contains("javascript:"));
+  }
+
+  fn edit_test_todo(conn: &Connection, todo: &Todo, times: usize) {
+    for edit in 0..times {
+      conn
+        .execute("UPDATE todos SET note = ?2 WHERE id = ?1", params![&todo.id, format!("edit {edit}")])
+        .unwrap();
+    }
+  }
+
+  #[test]
+  fn list_by_edit_frequency_ranks_churny_todos_first() {
+    let conn = test_conn();
+    let calm = insert_test_todo(&conn, "Calm");
+    let busy = insert_test_todo(&conn, "Busy");
+    let steady = insert_test_todo(&conn, "Steady");
+    insert_test_todo(&conn, "Untouched");
+    let trashed = insert_test_todo(&conn, "Trashed");
+    edit_test_todo(&conn, &calm, 1);
+    edit_test_todo(&conn, &busy, 4);
+    edit_test_todo(&conn, &steady, 2);
+    edit_test_todo(&conn, &trashed, 9);
+    trash_todo_in(&conn, &trashed.id, &now_iso()).unwrap();
+
+    let ranking: Vec<(String, u32)> = list_by_edit_frequency_in(&conn, 10)
+      .unwrap()
+      .into_iter()
+      .map(|entry| (entry.todo.title, entry.edit_count))
+      .collect();
+    assert_eq!(
+      ranking,
+      vec![("Busy".to_string(), 4), ("Steady".to_string(), 2), ("Calm".to_string(), 1)]
+    );
+
+    assert_eq!(list_by_edit_frequency_in(&conn, 1).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn start_minimized_round_trips_and_decides_launch_visibility() {
+    let conn = test_conn();
+    let defaults = get_window_prefs_from_conn(&conn).unwrap();
+    assert!(!defaults.start_minimized);
+    assert!(!should_start_hidden(&defaults));
+
+    let mut prefs = defaults;
+    prefs.start_minimized = true;
+    save_window_prefs_to_conn(&conn, &prefs).unwrap();
+
+    let stored = get_window_prefs_from_conn(&conn).unwrap();
+    assert!(stored.start_minimized);
+    assert!(should_start_hidden(&stored));
+  }
+
+  #[test]
+  fn list_todos_by_recurrence_tags_filters_to_the_selected_set() {
+    let conn = test_conn();
+    insert_recurring_test_todo(&conn, "Stretch", RECURRENCE_DAILY, "2024-01-01");
+    insert_recurring_test_todo(&conn, "Review", RECURRENCE_WEEKLY, "2024-01-01");
+    insert_recurring_test_todo(&conn, "Rent", RECURRENCE_MONTHLY, "2024-01-01");
+    insert_test_todo(&conn, "One-off");
+
+    let mut titles: Vec<String> =
+      list_todos_by_recurrence_tags_in(&conn, vec![" daily ".to_string(), "weekly".to_string()])
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Review", "Stretch"]);
+
+    assert_eq!(list_todos_by_recurrence_tags_in(&conn, Vec::new()).unwrap().len(), 4);
+  }
+
+  fn scheduled_load(conn: &Connection) -> Vec<(String, u32)> {
+    get_scheduled_load_in(conn, day("2024-01-01"), day("2024-12-31"))
+      .unwrap()
+      .into_iter()
+      .map(|entry| (entry.date, entry.count))
+      .collect()
+  }
 
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
-  let mut prefs = get_window_prefs_from_conn(&conn)?;
-  let inferred = infer_size_class_from_dimensions(width, height);
-  prefs.width = width;
-  prefs.height = height;
-  prefs.size_class = inferred;
-  prefs.mode = mode_from_size_class(&prefs.size_class);
-  save_window_prefs_to_conn(&conn, &prefs)
-}
+  #[test]
+  fn scheduled_load_tracks_dated_todos_and_recompute_heals_drift() {
+    let conn = test_conn();
+    insert_test_todo_due(&conn, "Taxes", "2024-04-15");
+    let dentist = insert_test_todo_due(&conn, "Dentist", "2024-04-15");
+    let report = insert_test_todo_due(&conn, "Report", "2024-05-01");
+    insert_test_todo(&conn, "Undated");
+    assert_eq!(
+      scheduled_load(&conn),
+      vec![("2024-04-15".to_string(), 2), ("2024-05-01".to_string(), 1)]
+    );
+
+    conn.execute("DELETE FROM todos WHERE id = ?1", params![&dentist.id]).unwrap();
+    conn
+      .execute("UPDATE todos SET completed = 1 WHERE id = ?1", params![&report.id])
+      .unwrap();
+    assert_eq!(scheduled_load(&conn), vec![("2024-04-15".to_string(), 1)]);
+
+    conn
+      .execute("INSERT INTO scheduled_load (day, open_count) VALUES ('2024-06-01', 3)", [])
+      .unwrap();
+    assert_eq!(rebuild_scheduled_load(&conn).unwrap(), 1);
+    assert_eq!(scheduled_load(&conn), vec![("2024-04-15".to_string(), 1)]);
+  }
 
-fn attach_window_persistence(window: WebviewWindow, app: AppHandle) {
-  window.on_window_event(move |event| match event {
-    WindowEvent::Moved(position) => {
-      let _ = save_window_position(&app, position.x as f64, position.y as f64);
+  #[test]
+  fn export_opml_is_well_formed_and_escapes_titles() {
+    use quick_xml::events::Event as XmlEvent;
+
+    let conn = test_conn();
+    let title = r#"Fix <b> & "quotes" 'n' stuff"#;
+    let mut todo = insert_test_todo(&conn, title);
+    todo.note = "line one\nline <two>".to_string();
+    todo.completed = true;
+    update_todo_row(&conn, &todo).unwrap();
+
+    let opml = export_opml_in(&conn).unwrap();
+
+    let mut reader = quick_xml::Reader::from_str(&opml);
+    let mut outlines = Vec::new();
+    loop {
+      match reader.read_event().expect("OPML should be well-formed XML") {
+        XmlEvent::Eof => break,
+        XmlEvent::Empty(element) if element.name().as_ref() == b"outline" => {
+          let attribute = |key: &[u8]| {
+            element
+              .try_get_attribute(key)
+              .unwrap()
+              .map(|value| value.unescape_value().unwrap().into_owned())
+          };
+          outlines.push((attribute(b"text"), attribute(b"_note"), attribute(b"_status")));
+        }
+        _ => {}
+      }
     }
-    WindowEvent::Resized(size) => {
-      let _ = save_window_size(&app, size.width as f64, size.height as f64);
+
+    assert_eq!(
+      outlines,
+      vec![(
+        Some(title.to_string()),
+        Some("line one\nline <two>".to_string()),
+        Some("checked".to_string())
+      )]
+    );
+  }
+
+  #[test]
+  fn normalize_energy_keeps_known_levels_only() {
+    assert_eq!(normalize_energy(Some(" high ".to_string())).as_deref(), Some(ENERGY_HIGH));
+    assert_eq!(normalize_energy(Some("medium".to_string())).as_deref(), Some(ENERGY_MEDIUM));
+    assert_eq!(normalize_energy(Some("low".to_string())).as_deref(), Some(ENERGY_LOW));
+    assert_eq!(normalize_energy(Some("none".to_string())), None);
+    assert_eq!(normalize_energy(Some("HIGH".to_string())), None);
+    assert_eq!(normalize_energy(None), None);
+  }
+
+  #[test]
+  fn list_todos_by_energy_filters_on_the_level() {
+    let conn = test_conn();
+    for (title, energy) in [("Deep work", Some("high")), ("Inbox", Some("low")), ("Errand", None)] {
+      let mut todo = insert_test_todo(&conn, title);
+      todo.energy = normalize_energy(energy.map(str::to_string));
+      update_todo_row(&conn, &todo).unwrap();
     }
-    _ => {}
-  });
-}
 
-#[cfg(target_os = "windows")]
-fn ensure_windows_autostart(key_name: &str) -> CommandResult<()> {
-  use winreg::enums::HKEY_CURRENT_USER;
-  use winreg::RegKey;
+    let titles = |level: &str| -> Vec<String> {
+      list_todos_by_energy_in(&conn, level.to_string())
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect()
+    };
+    assert_eq!(titles("high"), vec!["Deep work"]);
+    assert_eq!(titles("low"), vec!["Inbox"]);
+    assert_eq!(titles("none"), vec!["Errand"]);
+    assert_eq!(titles("bogus"), vec!["Errand"]);
+  }
 
-  let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-  let (run_key, _) = hkcu
-    .create_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Run")
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn completion_stats_ignore_trashed_todos() {
+    let conn = test_conn();
+    complete_test_todo(&conn, "Kept", "2024-03-10T12:00:00Z");
+    let trashed = complete_test_todo(&conn, "Trashed", "2024-03-10T12:30:00Z");
+    trash_todo_in(&conn, &trashed.id, &now_iso()).unwrap();
 
-  let current_exe = std::env::current_exe().map_err(|err| err.to_string())?;
-  let command = format!("\"{}\"", current_exe.display());
+    let buckets = completions_by_hour_in(&conn, &Utc, day("2024-03-10"), day("2024-03-10")).unwrap();
+    assert_eq!(buckets[12], 1);
 
-  run_key
-    .set_value(key_name, &command)
-    .map_err(|err| err.to_string())?;
+    let end_day = parse_iso_to_local_datetime("2024-03-10T12:00:00Z").unwrap().date_naive();
+    let average = get_rolling_completion_average_in(&conn, end_day, 1).unwrap();
+    assert_eq!(average, 1.0);
 
-  Ok(())
-}
+    let summary = get_usage_summary_in(&conn, end_day, FirstDayOfWeek::Monday).unwrap();
+    assert_eq!(summary.completed_this_week, 1);
+  }
 
-#[cfg(not(target_os = "windows"))]
-fn ensure_windows_autostart(_key_name: &str) -> CommandResult<()> {
-  Ok(())
-}
+  #[test]
+  fn restore_todo_only_reports_trashed_todos_as_restored() {
+    let conn = test_conn();
+    let trashed = trash_test_todo(&conn, "Oops", Utc::now());
+    let live = insert_test_todo(&conn, "Fine");
 
-#[tauri::command]
-fn list_todos(state: State<'_, AppState>) -> CommandResult<Vec<Todo>> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    let (restored, changed) = restore_todo_in(&conn, &trashed.id).unwrap();
+    assert!(changed);
+    assert!(restored.deleted_at.is_none());
+    assert!(get_todo_by_id(&conn, &trashed.id, false).unwrap().is_some());
 
-  let mut statement = conn
-    .prepare(
-      "SELECT id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, created_at, updated_at, reminder_enabled, last_reminded_on, sort_order
-       FROM todos ORDER BY sort_order ASC, created_at DESC",
-    )
-    .map_err(|err| err.to_string())?;
+    let (_, changed) = restore_todo_in(&conn, &live.id).unwrap();
+    assert!(!changed);
+  }
 
-  let rows = statement
-    .query_map([], map_todo_row)
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn subtask_state_disagrees_in_both_directions() {
+    assert!(subtask_state_disagrees(true, 3, 1));
+    assert!(subtask_state_disagrees(false, 2, 2));
+    assert!(!subtask_state_disagrees(true, 2, 2));
+    assert!(!subtask_state_disagrees(false, 2, 1));
+    assert!(!subtask_state_disagrees(true, 0, 0));
+    assert!(!subtask_state_disagrees(false, 0, 0));
+  }
 
-  let mut todos = Vec::new();
-  for row in rows {
-    todos.push(row.map_err(|err| err.to_string())?);
+  fn insert_test_subtask(conn: &Connection, todo_id: &str, title: &str, completed: bool) {
+    let now = now_iso();
+    conn
+      .execute(
+        "INSERT INTO subtasks (id, todo_id, title, completed, sort_order, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?5)",
+        params![Uuid::new_v4().to_string(), todo_id, title, to_db_bool(completed), &now],
+      )
+      .unwrap();
   }
 
-  Ok(todos)
-}
+  fn drifted_parent(conn: &Connection) -> Todo {
+    let parent = complete_test_todo(conn, "Move house", "2024-05-01T10:00:00Z");
+    insert_test_subtask(conn, &parent.id, "Pack", true);
+    insert_test_subtask(conn, &parent.id, "Book van", false);
+    let consistent = insert_test_todo(conn, "Groceries");
+    insert_test_subtask(conn, &consistent.id, "Milk", false);
+    parent
+  }
 
-#[tauri::command]
-fn create_todo(state: State<'_, AppState>, input: CreateTodoInput) -> CommandResult<Todo> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn subtask_consistency_flags_and_fixes_with_parent_wins() {
+    let conn = test_conn();
+    let parent = drifted_parent(&conn);
 
-  let trimmed_title = input.title.trim();
-  if trimmed_title.is_empty() {
-    return Err("Title cannot be empty".to_string());
+    let flagged = check_subtask_consistency_in(&conn).unwrap();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].todo.id, parent.id);
+    assert_eq!((flagged[0].subtask_count, flagged[0].completed_subtask_count), (2, 1));
+
+    assert_eq!(fix_subtask_consistency_in(&conn, SubtaskConsistencyPolicy::ParentWins).unwrap(), 1);
+    assert!(subtasks_of(&conn, &parent.id).unwrap().iter().all(|subtask| subtask.completed));
+    assert!(get_todo_by_id(&conn, &parent.id, false).unwrap().unwrap().completed);
+    assert!(check_subtask_consistency_in(&conn).unwrap().is_empty());
   }
 
-  let sort_order: i64 = conn
-    .query_row(
-      "SELECT COALESCE(MIN(sort_order), 0) - 1 FROM todos",
-      [],
-      |row| row.get(0),
-    )
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn subtask_consistency_fixes_with_subtasks_win() {
+    let conn = test_conn();
+    let parent = drifted_parent(&conn);
+
+    assert_eq!(fix_subtask_consistency_in(&conn, SubtaskConsistencyPolicy::SubtasksWin).unwrap(), 1);
+    let reopened = get_todo_by_id(&conn, &parent.id, false).unwrap().unwrap();
+    assert!(!reopened.completed);
+    assert_eq!(reopened.completed_at, None);
+    assert_eq!(subtasks_of(&conn, &parent.id).unwrap().iter().filter(|subtask| subtask.completed).count(), 1);
+    assert!(check_subtask_consistency_in(&conn).unwrap().is_empty());
+  }
 
-  let now = now_iso();
-  let todo = Todo {
-    id: Uuid::new_v4().to_string(),
-    title: trimmed_title.to_string(),
-    recurrence_tag: normalize_recurrence_tag(input.recurrence_tag),
-    recurrence_checked_at: None,
-    note: input.note.unwrap_or_default(),
-    completed: false,
-    due_date: normalize_date(input.due_date),
-    created_at: now.clone(),
-    updated_at: now,
-    reminder_enabled: true,
-    last_reminded_on: None,
-    sort_order,
-  };
+  fn sorted_titles(conn: &Connection, mode: SortMode) -> Vec<String> {
+    list_todos_from_conn(conn, false, &ListOptions::default(), mode, CompletedPlacement::Inline)
+      .unwrap()
+      .into_iter()
+      .map(|listed| listed.todo.title)
+      .collect()
+  }
 
-  conn
-    .execute(
-      "INSERT INTO todos
-       (id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, reminder_enabled, last_reminded_on, sort_order, created_at, updated_at)
-       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-      params![
-        &todo.id,
-        &todo.title,
-        &todo.recurrence_tag,
-        &todo.recurrence_checked_at,
-        &todo.note,
-        to_db_bool(todo.completed),
-        &todo.due_date,
-        to_db_bool(todo.reminder_enabled),
-        &todo.last_reminded_on,
-        todo.sort_order,
-        &todo.created_at,
-        &todo.updated_at,
-      ],
-    )
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn priority_desc_puts_high_first_and_keeps_manual_order_for_ties() {
+    let conn = test_conn();
+    for (title, priority) in [
+      ("Low", TodoPriority::Low),
+      ("Normal A", TodoPriority::Normal),
+      ("High", TodoPriority::High),
+      ("Normal B", TodoPriority::Normal),
+    ] {
+      let mut todo = insert_test_todo(&conn, title);
+      todo.priority = priority;
+      update_todo_row(&conn, &todo).unwrap();
+    }
+    let manual = sorted_titles(&conn, SortMode::Manual);
+    let normal_a = manual.iter().position(|title| title == "Normal A").unwrap();
+    let normal_b = manual.iter().position(|title| title == "Normal B").unwrap();
+    let normals = if normal_a < normal_b { ["Normal A", "Normal B"] } else { ["Normal B", "Normal A"] };
+
+    assert_eq!(
+      sorted_titles(&conn, SortMode::PriorityDesc),
+      vec!["High", normals[0], normals[1], "Low"]
+    );
+  }
 
-  Ok(todo)
-}
+  #[test]
+  fn estimate_asc_sorts_shortest_first_with_unestimated_last() {
+    let conn = test_conn();
+    for (title, estimate) in [("Unknown", None), ("Long", Some(90)), ("Quick", Some(5)), ("Medium", Some(30))] {
+      let mut todo = insert_test_todo(&conn, title);
+      todo.estimate_minutes = estimate;
+      update_todo_row(&conn, &todo).unwrap();
+    }
+    let done = complete_test_todo(&conn, "Done quick", "2024-01-01T09:00:00Z");
+    conn
+      .execute("UPDATE todos SET estimate_minutes = 1 WHERE id = ?1", params![&done.id])
+      .unwrap();
+
+    assert_eq!(
+      sorted_titles(&conn, SortMode::EstimateAsc),
+      vec!["Quick", "Medium", "Long", "Unknown", "Done quick"]
+    );
+  }
 
-#[tauri::command]
-fn update_todo(state: State<'_, AppState>, input: UpdateTodoInput) -> CommandResult<Todo> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn inbox_lists_only_bare_open_todos_oldest_first() {
+    let conn = test_conn();
+    let bare = insert_test_todo(&conn, "Bare");
+    age_test_todo(&conn, &bare, "2024-01-02T09:00:00Z");
+    let older = insert_test_todo(&conn, "Older bare");
+    age_test_todo(&conn, &older, "2024-01-01T09:00:00Z");
+
+    let mut categorized = insert_recurring_test_todo(&conn, "Categorized", RECURRENCE_WEEKLY, "2024-02-01");
+    categorized.energy = Some(ENERGY_HIGH.to_string());
+    update_todo_row(&conn, &categorized).unwrap();
+    let tagged = insert_test_todo(&conn, "Tagged");
+    replace_todo_tags_in(&conn, &tagged.id, &["work".to_string()]).unwrap();
+    insert_test_todo_due(&conn, "Dated", "2024-02-01");
+    let mut energetic = insert_test_todo(&conn, "Energetic");
+    energetic.energy = Some(ENERGY_LOW.to_string());
+    update_todo_row(&conn, &energetic).unwrap();
+    complete_test_todo(&conn, "Done", "2024-01-03T09:00:00Z");
+    let archived = insert_test_todo(&conn, "Archived");
+    conn
+      .execute("UPDATE todos SET archived = 1 WHERE id = ?1", params![&archived.id])
+      .unwrap();
+
+    let titles: Vec<String> = list_inbox_todos_in(&conn).unwrap().into_iter().map(|todo| todo.title).collect();
+    assert_eq!(titles, vec!["Older bare", "Bare"]);
+  }
 
-  let existing = get_todo_by_id(&conn, &input.id)?
-    .ok_or_else(|| format!("Todo not found: {}", input.id))?;
+  fn complete_recurring_test_todo(conn: &Connection, todo: &mut Todo) {
+    todo.completed = true;
+    todo.completed_at = Some(format!("{}T09:00:00Z", &todo.due_date.as_deref().unwrap()[..10]));
+    count_recurring_completion(todo);
+    update_todo_row(conn, todo).unwrap();
+  }
 
-  let mut updated = existing;
+  #[test]
+  fn rolling_stops_once_the_completion_count_reaches_the_max() {
+    let conn = test_conn();
+    let mut first = insert_recurring_test_todo(&conn, "Physio", RECURRENCE_DAILY, "2024-01-05");
+    first.recurrence_max_count = Some(2);
+    update_todo_row(&conn, &first).unwrap();
+    let today = day("2024-01-10");
+
+    complete_recurring_test_todo(&conn, &mut first);
+    let rolled = roll_recurring_todos_in(&conn, today).unwrap();
+    assert_eq!(rolled.len(), 1);
+    let mut second = rolled.into_iter().next().unwrap();
+    assert_eq!(second.due_date.as_deref(), Some("2024-01-06"));
+    assert_eq!(second.recurrence_completed_count, 1);
+
+    complete_recurring_test_todo(&conn, &mut second);
+    assert_eq!(second.recurrence_completed_count, 2);
+    assert!(roll_recurring_todos_in(&conn, today).unwrap().is_empty());
+    let rolled_at = get_todo_by_id(&conn, &second.id, false).unwrap().unwrap().rolled_at;
+    assert!(rolled_at.is_some());
+  }
 
-  if let Some(title) = input.title {
-    let trimmed = title.trim();
-    if trimmed.is_empty() {
-      return Err("Title cannot be empty".to_string());
-    }
-    updated.title = trimmed.to_string();
+  fn ids_of(todos: &[Todo]) -> Vec<String> {
+    let mut ids: Vec<String> = todos.iter().map(|todo| todo.id.clone()).collect();
+    ids.sort();
+    ids
   }
 
-  if let Some(recurrence_tag) = input.recurrence_tag {
-    updated.recurrence_tag = normalize_recurrence_tag(Some(recurrence_tag));
+  fn seed_bulk_preview_todos(conn: &Connection) {
+    insert_test_todo_due(conn, "Overdue", "2024-01-05");
+    insert_test_todo_due(conn, "Also overdue", "2024-01-09T08:00:00Z");
+    insert_test_todo_due(conn, "Today", "2024-01-10");
+    insert_test_todo(conn, "Undated");
+    let done = complete_test_todo(conn, "Done late", "2024-01-08T09:00:00Z");
+    conn
+      .execute("UPDATE todos SET due_date = '2024-01-02' WHERE id = ?1", params![&done.id])
+      .unwrap();
+    complete_test_todo(conn, "Done", "2024-01-09T09:00:00Z");
+    let archived = insert_test_todo_due(conn, "Archived overdue", "2024-01-03");
+    conn
+      .execute("UPDATE todos SET archived = 1 WHERE id = ?1", params![&archived.id])
+      .unwrap();
   }
 
-  if let Some(note) = input.note {
-    updated.note = note;
+  #[test]
+  fn preview_bulk_complete_matches_bulk_set_completed() {
+    let conn = test_conn();
+    seed_bulk_preview_todos(&conn);
+    let today = day("2024-01-10");
+
+    let preview = preview_bulk_in(&conn, BulkOperation::Complete, CountFilter::Overdue, today).unwrap();
+    let mut titles: Vec<&str> = preview.iter().map(|todo| todo.title.as_str()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Also overdue", "Overdue"]);
+
+    let open_before = ids_of(&todos_matching_filter(&conn, CountFilter::Incomplete, today).unwrap());
+    let targets = ids_of(&todos_matching_filter(&conn, CountFilter::Overdue, today).unwrap());
+    assert_eq!(bulk_set_completed_in(&conn, &targets, true).unwrap(), preview.len());
+    let open_after = ids_of(&todos_matching_filter(&conn, CountFilter::Incomplete, today).unwrap());
+    let completed: Vec<String> = open_before.into_iter().filter(|id| !open_after.contains(id)).collect();
+    assert_eq!(completed, ids_of(&preview));
   }
 
-  if let Some(completed) = input.completed {
-    updated.completed = completed;
+  #[test]
+  fn preview_bulk_delete_matches_bulk_delete() {
+    let conn = test_conn();
+    seed_bulk_preview_todos(&conn);
+    let today = day("2024-01-10");
+
+    let preview = preview_bulk_in(&conn, BulkOperation::Delete, CountFilter::Completed, today).unwrap();
+    assert_eq!(preview.len(), 2);
+
+    let targets = ids_of(&todos_matching_filter(&conn, CountFilter::Completed, today).unwrap());
+    let deleted = bulk_delete_in(&conn, &targets, &now_iso()).unwrap();
+    assert_eq!(ids_of(&deleted), ids_of(&preview));
+    assert!(todos_matching_filter(&conn, CountFilter::Completed, today).unwrap().is_empty());
   }
 
-  if let Some(due_date) = input.due_date {
-    updated.due_date = normalize_date(due_date);
+  #[test]
+  fn preview_bulk_archive_matches_archive_all_completed() {
+    let conn = test_conn();
+    seed_bulk_preview_todos(&conn);
+    let today = day("2024-01-10");
+
+    let preview = preview_bulk_in(&conn, BulkOperation::Archive, CountFilter::All, today).unwrap();
+    let mut titles: Vec<&str> = preview.iter().map(|todo| todo.title.as_str()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Done", "Done late"]);
+
+    assert_eq!(archive_all_completed_in(&conn).unwrap(), preview.len());
+    assert!(preview_bulk_in(&conn, BulkOperation::Archive, CountFilter::All, today).unwrap().is_empty());
   }
 
-  if let Some(reminder_enabled) = input.reminder_enabled {
-    updated.reminder_enabled = reminder_enabled;
+  #[test]
+  fn rolling_daily_average_over_a_full_window_counts_empty_days() {
+    let counts = [
+      (day("2024-03-01"), 9),
+      (day("2024-03-04"), 2),
+      (day("2024-03-05"), 5),
+      (day("2024-03-10"), 7),
+    ];
+    // 2024-03-04..=2024-03-10, with four empty days in between.
+    assert!((rolling_daily_average(&counts, day("2024-03-10"), 7) - 2.0).abs() < 1e-9);
   }
 
-  updated.updated_at = now_iso();
+  #[test]
+  fn rolling_daily_average_counts_empty_days_before_the_first_completion() {
+    let counts = [(day("2024-03-08"), 3), (day("2024-03-10"), 4)];
+    assert!((rolling_daily_average(&counts, day("2024-03-10"), 7) - 1.0).abs() < 1e-9);
+  }
 
-  conn
-    .execute(
-      "UPDATE todos
-       SET title = ?2, recurrence_tag = ?3, note = ?4, completed = ?5, due_date = ?6, updated_at = ?7, reminder_enabled = ?8
-       WHERE id = ?1",
-      params![
-        &updated.id,
-        &updated.title,
-        &updated.recurrence_tag,
-        &updated.note,
-        to_db_bool(updated.completed),
-        &updated.due_date,
-        &updated.updated_at,
-        to_db_bool(updated.reminder_enabled),
-      ],
-    )
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn rolling_daily_average_is_zero_without_completions_in_the_window() {
+    assert_eq!(rolling_daily_average(&[], day("2024-03-10"), 7), 0.0);
+    let counts = [(day("2024-01-01"), 4)];
+    assert_eq!(rolling_daily_average(&counts, day("2024-03-10"), 7), 0.0);
+  }
 
-  Ok(updated)
-}
+  #[test]
+  fn orphaned_subtasks_are_reported_and_pruned() {
+    let conn = test_conn();
+    let parent = insert_test_todo(&conn, "Parent");
+    insert_test_subtask(&conn, &parent.id, "Left behind", false);
+    insert_test_subtask(&conn, &insert_test_todo(&conn, "Kept").id, "Fine", false);
+    assert_eq!(referential_integrity_report(&conn).unwrap().orphans.len(), 0);
+
+    conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+    conn.execute("DELETE FROM todos WHERE id = ?1", params![&parent.id]).unwrap();
+    conn.execute_batch("PRAGMA foreign_keys = ON").unwrap();
+
+    let report = referential_integrity_report(&conn).unwrap();
+    let summary = |rows: &[DanglingRows]| -> Vec<(String, usize)> {
+      rows.iter().map(|row| (row.table.clone(), row.count)).collect()
+    };
+    assert_eq!(summary(&report.orphans), vec![("subtasks".to_string(), 1)]);
+    assert_eq!(summary(&report.foreign_key_violations), vec![("subtasks".to_string(), 1)]);
 
-#[tauri::command]
-fn toggle_todo(state: State<'_, AppState>, id: String) -> CommandResult<Todo> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    assert_eq!(summary(&prune_orphans_in(&conn).unwrap()), vec![("subtasks".to_string(), 1)]);
+    let report = referential_integrity_report(&conn).unwrap();
+    assert!(report.orphans.is_empty() && report.foreign_key_violations.is_empty());
+  }
 
-  let mut target = get_todo_by_id(&conn, &id)?.ok_or_else(|| format!("Todo not found: {id}"))?;
-  target.completed = !target.completed;
-  target.updated_at = now_iso();
+  #[test]
+  fn sandbox_starts_seeded_and_forgets_changes() {
+    let open_seeded = || {
+      let pool = open_sandbox_pool().unwrap();
+      let conn = pool.get().unwrap();
+      ensure_schema(&conn).unwrap();
+      seed_sandbox_todos(&conn).unwrap();
+      drop(conn);
+      pool
+    };
+    let count = |pool: &DbPool| -> i64 {
+      pool
+        .get()
+        .unwrap()
+        .query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0))
+        .unwrap()
+    };
 
-  conn
-    .execute(
-      "UPDATE todos SET completed = ?2, updated_at = ?3 WHERE id = ?1",
-      params![&target.id, to_db_bool(target.completed), &target.updated_at],
-    )
-    .map_err(|err| err.to_string())?;
+    let pool = open_seeded();
+    let seeded = count(&pool);
+    assert!(seeded > 0);
+    insert_test_todo(&pool.get().unwrap(), "Scratch");
+    assert_eq!(count(&pool), seeded + 1);
+    drop(pool);
+
+    let pool = open_seeded();
+    assert_eq!(count(&pool), seeded);
+    assert!(!std::path::Path::new("simple_todo_note_sandbox").exists());
+    assert!(!std::path::Path::new(SANDBOX_DB_URI).exists());
+  }
 
-  Ok(target)
-}
+  #[test]
+  fn daily_reminders_include_timestamped_due_dates_once_per_day() {
+    let conn = test_conn();
+    let today = local_today_naive();
+    let today_noon = today
+      .and_hms_opt(12, 0, 0)
+      .unwrap()
+      .and_local_timezone(Local)
+      .unwrap()
+      .to_rfc3339();
+    let day_key = |offset: i64| (today + Duration::days(offset)).format("%Y-%m-%d").to_string();
+    insert_test_todo_due(&conn, "Snoozed to today", &today_noon);
+    insert_test_todo_due(&conn, "Two days late", &day_key(-2));
+    insert_test_todo_due(&conn, "Tomorrow", &day_key(1));
+    insert_test_todo_due(&conn, "Garbage", "someday");
+
+    let reminders = consume_daily_due_reminders_in(&conn, today, Utc::now()).unwrap();
+    let summary: Vec<(&str, i64)> = reminders
+      .iter()
+      .map(|reminder| (reminder.title.as_str(), reminder.days_overdue))
+      .collect();
+    assert_eq!(summary, vec![("Two days late", 2), ("Snoozed to today", 0)]);
+
+    assert!(consume_daily_due_reminders_in(&conn, today, Utc::now()).unwrap().is_empty());
+  }
 
-#[tauri::command]
-fn set_recurrence_check(state: State<'_, AppState>, id: String, checked: bool) -> CommandResult<Todo> {
-  let mut conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn rolling_accepts_timestamped_due_dates() {
+    let conn = test_conn();
+    let mut todo = insert_recurring_test_todo(&conn, "Standup", RECURRENCE_DAILY, "2024-01-05T12:00:00Z");
+    complete_recurring_test_todo(&conn, &mut todo);
 
-  let mut target = get_todo_by_id(&conn, &id)?.ok_or_else(|| format!("Todo not found: {id}"))?;
+    let rolled = roll_recurring_todos_in(&conn, day("2024-01-10")).unwrap();
+    assert_eq!(rolled.len(), 1);
+    assert_eq!(rolled[0].due_date.as_deref(), Some("2024-01-06"));
+  }
 
-  if target.recurrence_tag == RECURRENCE_NONE {
-    return Err("Recurrence check is only available for recurring tasks".to_string());
+  fn audit_summary(prefs: &UiPrefs) -> (bool, bool, bool) {
+    let audit = audit_accessibility_prefs(prefs);
+    (audit.motion_reduced, audit.follows_system_motion, audit.high_readability)
   }
 
-  target.recurrence_checked_at = if checked { Some(now_iso()) } else { None };
-  target.updated_at = now_iso();
+  #[test]
+  fn accessibility_audit_of_default_prefs() {
+    let defaults = UiPrefs::default();
+    let expected_motion = matches!(defaults.motion_mode, MotionMode::Low);
+    let expected_readability = matches!(defaults.readability_mode, ReadabilityMode::Strong);
+    assert_eq!(audit_summary(&defaults), (expected_motion, true, expected_readability));
+  }
 
-  let tx = conn.transaction().map_err(|err| err.to_string())?;
+  #[test]
+  fn accessibility_audit_follows_overrides_and_modes() {
+    let mut prefs = UiPrefs {
+      reduce_motion_override: ReduceMotionOverride::On,
+      motion_mode: MotionMode::High,
+      readability_mode: ReadabilityMode::Strong,
+      ..UiPrefs::default()
+    };
+    assert_eq!(audit_summary(&prefs), (true, false, true));
 
-  tx
-    .execute(
-      "UPDATE todos SET recurrence_checked_at = ?2, updated_at = ?3 WHERE id = ?1",
-      params![&target.id, &target.recurrence_checked_at, &target.updated_at],
-    )
-    .map_err(|err| err.to_string())?;
+    prefs.reduce_motion_override = ReduceMotionOverride::Off;
+    prefs.motion_mode = MotionMode::Low;
+    prefs.readability_mode = ReadabilityMode::Pure;
+    assert_eq!(audit_summary(&prefs), (false, false, false));
 
-  if target.recurrence_tag == RECURRENCE_DAILY {
-    let event_day = local_day_key();
+    prefs.reduce_motion_override = ReduceMotionOverride::System;
+    assert_eq!(audit_summary(&prefs), (true, true, false));
+    prefs.motion_mode = MotionMode::Balanced;
+    assert_eq!(audit_summary(&prefs), (false, true, false));
+  }
 
-    if checked {
-      tx
-        .execute(
-          "INSERT INTO daily_completion_events (todo_id, event_day, created_at)
-           VALUES (?1, ?2, ?3)
-           ON CONFLICT(todo_id, event_day) DO NOTHING",
-          params![&target.id, &event_day, &target.updated_at],
-        )
-        .map_err(|err| err.to_string())?;
-    } else {
-      tx
-        .execute(
-          "DELETE FROM daily_completion_events
-           WHERE todo_id = ?1 AND event_day = ?2",
-          params![&target.id, &event_day],
-        )
-        .map_err(|err| err.to_string())?;
+  #[test]
+  fn create_routine_adds_one_recurring_todo_per_title() {
+    let conn = test_conn();
+    insert_test_todo(&conn, "Existing");
+    let titles = vec!["Stretch".to_string(), " Journal ".to_string(), "Plan the day".to_string()];
+
+    let created = create_routine_in(&conn, titles, "daily", Some("2024-06-03".to_string())).unwrap();
+
+    let summary: Vec<(&str, &str, Option<&str>)> = created
+      .iter()
+      .map(|todo| (todo.title.as_str(), todo.recurrence_tag.as_str(), todo.due_date.as_deref()))
+      .collect();
+    assert_eq!(
+      summary,
+      vec![
+        ("Stretch", RECURRENCE_DAILY, Some("2024-06-03")),
+        ("Journal", RECURRENCE_DAILY, Some("2024-06-03")),
+        ("Plan the day", RECURRENCE_DAILY, Some("2024-06-03")),
+      ]
+    );
+    let listed = sorted_titles(&conn, SortMode::Manual);
+    assert_eq!(listed, vec!["Stretch", "Journal", "Plan the day", "Existing"]);
+  }
+
+  #[test]
+  fn create_routine_rejects_bad_recurrences_and_blank_titles() {
+    let conn = test_conn();
+    let titles = || vec!["Stretch".to_string()];
+    for tag in ["none", "hourly", ""] {
+      assert!(matches!(create_routine_in(&conn, titles(), tag, None), Err(AppError::Validation(_))));
     }
+    let blank = vec!["Stretch".to_string(), "  ".to_string()];
+    assert!(matches!(create_routine_in(&conn, blank, "weekly", None), Err(AppError::Validation(_))));
+    assert!(matches!(create_routine_in(&conn, Vec::new(), "weekly", None), Err(AppError::Validation(_))));
   }
 
-  tx.commit().map_err(|err| err.to_string())?;
+  #[test]
+  fn explain_list_query_uses_an_index() {
+    let conn = test_conn();
+    for index in 0..20 {
+      let title = format!("Todo {index}");
+      if index % 3 == 0 {
+        complete_test_todo(&conn, &title, "2024-03-01T09:00:00Z");
+      } else {
+        insert_test_todo(&conn, &title);
+      }
+    }
 
-  Ok(target)
-}
+    for sort_by_priority in [false, true] {
+      let plan = explain_list_query_in(&conn, sort_by_priority, &ListOptions::default()).unwrap();
+      assert!(plan.iter().any(|row| row.contains("idx_todos_")), "{plan:?}");
+      assert!(!plan.iter().any(|row| row == "SCAN todos"), "{plan:?}");
+    }
+  }
 
-#[tauri::command]
-fn get_daily_completion_heatmap(
-  state: State<'_, AppState>,
-  days: u16,
-) -> CommandResult<Vec<DailyCompletionHeatmapDay>> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn sent_reminders_wait_out_the_grace_period() {
+    let conn = test_conn();
+    let today = day("2024-03-02");
+    let sent_at = Utc.with_ymd_and_hms(2024, 3, 2, 9, 0, 0).unwrap();
+    let grace = Duration::minutes(i64::from(DEFAULT_REMINDER_SNOOZE_MINUTES));
+    let todo = insert_test_todo_due(&conn, "Pay rent", "2024-03-01");
 
-  let clamped_days = days.clamp(1, 365) as i64;
-  let end_day = Local::now().date_naive();
-  let start_day = end_day - Duration::days(clamped_days - 1);
-  let start_key = start_day.format("%Y-%m-%d").to_string();
-  let end_key = end_day.format("%Y-%m-%d").to_string();
+    let remind_after = mark_reminder_sent_in(&conn, &todo.id, sent_at).unwrap();
+    assert_eq!(remind_after, (sent_at + grace).to_rfc3339());
 
-  let mut statement = conn
-    .prepare(
-      "SELECT event_day, COUNT(*) AS completion_count
-       FROM daily_completion_events
-       WHERE event_day >= ?1 AND event_day <= ?2
-       GROUP BY event_day
-       ORDER BY event_day ASC",
-    )
-    .map_err(|err| err.to_string())?;
+    let during_grace = sent_at + grace - Duration::minutes(1);
+    assert!(consume_daily_due_reminders_in(&conn, today, during_grace).unwrap().is_empty());
 
-  let rows = statement
-    .query_map(params![start_key, end_key], |row| {
-      let count: i64 = row.get(1)?;
+    let after_grace = sent_at + grace + Duration::minutes(1);
+    let reminders = consume_daily_due_reminders_in(&conn, today, after_grace).unwrap();
+    let titles: Vec<&str> = reminders.iter().map(|reminder| reminder.title.as_str()).collect();
+    assert_eq!(titles, vec!["Pay rent"]);
+  }
 
-      Ok(DailyCompletionHeatmapDay {
-        date: row.get(0)?,
-        count: if count < 0 { 0 } else { count as u32 },
-      })
-    })
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn marking_a_missing_reminder_is_not_found() {
+    let conn = test_conn();
+    assert!(matches!(mark_reminder_sent_in(&conn, "missing", Utc::now()), Err(AppError::NotFound(_))));
+  }
 
-  let mut output = Vec::new();
-  for row in rows {
-    output.push(row.map_err(|err| err.to_string())?);
+  #[test]
+  fn saved_filters_replay_the_inline_query() {
+    let conn = test_conn();
+    for index in 0..5 {
+      insert_test_todo(&conn, &format!("Open {index}"));
+    }
+    complete_test_todo(&conn, "Done", "2024-03-01T09:00:00Z");
+    let options = ListOptions {
+      limit: Some(3),
+      completed: Some(false),
+      ..ListOptions::default()
+    };
+
+    save_filter_in(&conn, "  First open  ", options.clone()).unwrap();
+    let saved = run_saved_filter_in(&conn, "First open").unwrap();
+    let inline =
+      list_todos_from_conn(&conn, false, &options, SortMode::default(), CompletedPlacement::default()).unwrap();
+
+    let ids = |todos: &[ListedTodo]| todos.iter().map(|listed| listed.todo.id.clone()).collect::<Vec<_>>();
+    assert_eq!(saved.len(), 3);
+    assert_eq!(ids(&saved), ids(&inline));
+    assert!(saved.iter().all(|listed| !listed.todo.completed));
   }
 
-  Ok(output)
-}
+  #[test]
+  fn saving_a_filter_again_replaces_its_options() {
+    let conn = test_conn();
+    let first = save_filter_in(&conn, "Work", ListOptions::default()).unwrap();
+    let completed = ListOptions {
+      completed: Some(true),
+      ..ListOptions::default()
+    };
+    save_filter_in(&conn, "Work", completed).unwrap();
+    save_filter_in(&conn, "Alpha", ListOptions::default()).unwrap();
+
+    let filters = list_saved_filters_in(&conn).unwrap();
+    let names: Vec<&str> = filters.iter().map(|filter| filter.name.as_str()).collect();
+    assert_eq!(names, vec!["Alpha", "Work"]);
+    assert_eq!(filters[1].options.completed, Some(true));
+    assert_eq!(filters[1].created_at, first.created_at);
+
+    delete_saved_filter_in(&conn, "Work").unwrap();
+    assert!(matches!(delete_saved_filter_in(&conn, "Work"), Err(AppError::NotFound(_))));
+    assert!(matches!(run_saved_filter_in(&conn, "Work"), Err(AppError::NotFound(_))));
+  }
 
-#[tauri::command]
-fn consume_daily_due_reminders(state: State<'_, AppState>) -> CommandResult<Vec<DueReminder>> {
-  let mut conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn saved_filters_reject_bad_input_and_unreadable_params() {
+    let conn = test_conn();
+    assert!(matches!(save_filter_in(&conn, " ", ListOptions::default()), Err(AppError::Validation(_))));
+    let negative = ListOptions {
+      offset: Some(-1),
+      ..ListOptions::default()
+    };
+    assert!(matches!(save_filter_in(&conn, "Paged", negative), Err(AppError::Validation(_))));
 
-  let today = local_today_naive();
-  let today_key = today.format("%Y-%m-%d").to_string();
-  let tx = conn.transaction().map_err(|err| err.to_string())?;
+    conn
+      .execute(
+        "INSERT INTO saved_filters (name, params, created_at) VALUES ('Broken', '{\"limit\":\"ten\"}', ?1)",
+        params![now_iso()],
+      )
+      .unwrap();
+    assert!(matches!(run_saved_filter_in(&conn, "Broken"), Err(AppError::Validation(_))));
+  }
 
-  let mut statement = tx
-    .prepare(
-      "SELECT id, title, due_date, recurrence_tag, recurrence_checked_at
-       FROM todos
-       WHERE reminder_enabled = 1
-         AND completed = 0
-         AND due_date IS NOT NULL
-         AND due_date <= ?1
-         AND (last_reminded_on IS NULL OR last_reminded_on <> ?1)
-       ORDER BY due_date ASC, sort_order ASC, created_at DESC",
-    )
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn similar_titles_group_only_near_duplicates() {
+    let conn = test_conn();
+    insert_test_todo(&conn, "Call the dentist");
+    insert_test_todo(&conn, "buy some milk");
+    insert_test_todo(&conn, "Renew passport");
+    insert_test_todo(&conn, "Buy Milk");
+    complete_test_todo(&conn, "Buy milk", "2024-03-01T09:00:00Z");
+
+    let groups = find_similar_titles_in(&conn, 0.5).unwrap();
+    let titles: Vec<Vec<&str>> = groups
+      .iter()
+      .map(|group| group.iter().map(|todo| todo.title.as_str()).collect())
+      .collect();
+    assert_eq!(titles, vec![vec!["Buy Milk", "buy some milk"]]);
+
+    assert!(find_similar_titles_in(&conn, 0.9).unwrap().is_empty());
+  }
 
-  let rows = statement
-    .query_map(params![&today_key], |row| {
-      let id: String = row.get(0)?;
-      let title: String = row.get(1)?;
-      let due_date: String = row.get(2)?;
-      let recurrence_tag: String = row.get(3)?;
-      let recurrence_checked_at: Option<String> = row.get(4)?;
+  #[test]
+  fn similar_titles_reject_out_of_range_thresholds() {
+    let conn = test_conn();
+    for threshold in [0.0, -0.5, 1.5, f64::NAN] {
+      assert!(matches!(find_similar_titles_in(&conn, threshold), Err(AppError::Validation(_))));
+    }
+  }
 
-      Ok((id, title, due_date, recurrence_tag, recurrence_checked_at))
-    })
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn todo_history_lists_every_operation_oldest_first() {
+    let conn = test_conn();
+    let mut todo = insert_test_todo(&conn, "Draft report");
+    let other = insert_test_todo(&conn, "Unrelated");
+    todo.title = "Draft the report".to_string();
+    update_todo_row(&conn, &todo).unwrap();
+    todo.note = "Include Q3 numbers".to_string();
+    update_todo_row(&conn, &todo).unwrap();
+    conn.execute("DELETE FROM todos WHERE id = ?1", params![&todo.id]).unwrap();
+
+    let history = get_todo_history_in(&conn, &todo.id).unwrap();
+    let ops: Vec<&str> = history.iter().map(|entry| entry.op.as_str()).collect();
+    assert_eq!(ops, vec!["insert", "update", "update", "delete"]);
+    assert!(history.iter().all(|entry| entry.todo_id == todo.id));
+    assert!(history.windows(2).all(|pair| pair[0].id < pair[1].id && pair[0].at <= pair[1].at));
+
+    assert_eq!(get_todo_history_in(&conn, &other.id).unwrap().len(), 1);
+    assert!(get_todo_history_in(&conn, "missing").unwrap().is_empty());
+  }
+
+  #[test]
+  fn normalize_context_trims_and_caps_length() {
+    assert_eq!(normalize_context(Some("  @home ".to_string())), Some("@home".to_string()));
+    assert_eq!(normalize_context(Some("   ".to_string())), None);
+    assert_eq!(normalize_context(None), None);
+
+    let long = format!("@{}", "x".repeat(MAX_CONTEXT_CHARS * 2));
+    let normalized = normalize_context(Some(long)).unwrap();
+    assert_eq!(normalized.chars().count(), MAX_CONTEXT_CHARS);
+    let cut_at_space = format!("{} tail", "y".repeat(MAX_CONTEXT_CHARS - 1));
+    assert_eq!(normalize_context(Some(cut_at_space)), Some("y".repeat(MAX_CONTEXT_CHARS - 1)));
+  }
+
+  #[test]
+  fn contexts_are_listed_once_and_filter_todos() {
+    let conn = test_conn();
+    let with_context = |title: &str, context: Option<&str>| {
+      let mut todo = insert_test_todo(&conn, title);
+      todo.context = normalize_context(context.map(str::to_string));
+      update_todo_row(&conn, &todo).unwrap();
+      todo
+    };
+    with_context("Vacuum", Some("@home"));
+    with_context("Email Sam", Some("@computer"));
+    with_context("Water plants", Some(" @home "));
+    with_context("Anywhere", None);
+    let mut trashed = with_context("Old call", Some("@phone"));
+    trashed.deleted_at = Some(now_iso());
+    update_todo_row(&conn, &trashed).unwrap();
+
+    assert_eq!(list_contexts_in(&conn).unwrap(), vec!["@computer", "@home"]);
+
+    let titles = |context: &str| -> Vec<String> {
+      list_todos_by_context_in(&conn, context.to_string())
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect()
+    };
+    assert_eq!(titles("@home "), vec!["Water plants", "Vacuum"]);
+    assert_eq!(titles(""), vec!["Anywhere"]);
+    assert!(titles("@phone").is_empty());
+  }
 
-  let mut reminders = Vec::new();
-  let mut reminder_ids = Vec::new();
+  #[test]
+  fn procrastination_score_weights_each_component() {
+    let score = procrastination_score(4.5, 2, Some(3));
+    assert_eq!(
+      (score.age_component, score.snooze_component, score.overdue_component, score.total),
+      (4.5, 6.0, 6.0, 16.5)
+    );
 
-  for row in rows {
-    let (id, title, due_date, recurrence_tag, recurrence_checked_at) =
-      row.map_err(|err| err.to_string())?;
+    let undated = procrastination_score(4.5, 2, None);
+    assert_eq!((undated.overdue_component, undated.total), (0.0, 10.5));
 
-    if is_recurrence_cycle_checked_at(&recurrence_tag, recurrence_checked_at.as_deref()) {
-      continue;
-    }
+    let not_yet_due = procrastination_score(1.0, 0, Some(-5));
+    assert_eq!((not_yet_due.overdue_component, not_yet_due.total), (0.0, 1.0));
 
-    let due_day = match NaiveDate::parse_from_str(&due_date, "%Y-%m-%d") {
-      Ok(day) => day,
-      Err(_) => continue,
-    };
+    let clock_skewed = procrastination_score(-2.0, -1, None);
+    assert_eq!(clock_skewed.total, 0.0);
+  }
 
-    let days_overdue = today.signed_duration_since(due_day).num_days();
-    if days_overdue < 0 {
-      continue;
-    }
+  #[test]
+  fn procrastination_index_ranks_by_avoidance() {
+    let conn = test_conn();
+    let now_at = Local.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+    let created = |days_ago: i64| (now_at - Duration::days(days_ago)).to_rfc3339();
+
+    let mut fresh = insert_test_todo(&conn, "Fresh");
+    fresh.created_at = created(1);
+    update_todo_row(&conn, &fresh).unwrap();
+    let mut snoozed = insert_test_todo(&conn, "Snoozed");
+    snoozed.created_at = created(2);
+    snoozed.snooze_count = 4;
+    update_todo_row(&conn, &snoozed).unwrap();
+    let mut overdue = insert_test_todo(&conn, "Overdue");
+    overdue.created_at = created(3);
+    overdue.due_date = Some("2024-03-02".to_string());
+    update_todo_row(&conn, &overdue).unwrap();
+    let mut done = complete_test_todo(&conn, "Done", "2024-03-09T09:00:00Z");
+    done.created_at = created(30);
+    update_todo_row(&conn, &done).unwrap();
+
+    let entries = get_procrastination_index_in(&conn, now_at).unwrap();
+    let ranked: Vec<(&str, f64)> = entries
+      .iter()
+      .map(|entry| (entry.todo.title.as_str(), entry.score.total))
+      .collect();
+    assert_eq!(ranked, vec![("Overdue", 19.0), ("Snoozed", 14.0), ("Fresh", 1.0)]);
+    assert_eq!(entries[0].score.overdue_component, 16.0);
+    assert_eq!(entries[1].score.snooze_component, 12.0);
+  }
 
-    reminder_ids.push(id.clone());
-    reminders.push(DueReminder {
-      id,
-      title,
-      due_date,
-      days_overdue,
-      recurrence_tag,
-    });
+  fn customize_both_prefs(conn: &Connection) {
+    let mut window = get_window_prefs_from_conn(conn).unwrap();
+    window.x = 123.0;
+    save_window_prefs_to_conn(conn, &window).unwrap();
+    let mut ui = get_ui_prefs_from_conn(conn).unwrap();
+    ui.sort_mode = SortMode::PriorityDesc;
+    save_ui_prefs_to_conn(conn, &ui).unwrap();
   }
 
-  drop(statement);
+  #[test]
+  fn resetting_ui_prefs_keeps_window_prefs_and_todos() {
+    let conn = test_conn();
+    insert_test_todo(&conn, "Keep me");
+    customize_both_prefs(&conn);
 
-  for id in &reminder_ids {
-    tx
-      .execute(
-        "UPDATE todos SET last_reminded_on = ?2 WHERE id = ?1",
-        params![id, &today_key],
-      )
-      .map_err(|err| err.to_string())?;
+    let reset = reset_ui_prefs_in(&conn).unwrap();
+
+    assert_eq!(reset.sort_mode, SortMode::Manual);
+    assert_eq!(get_ui_prefs_from_conn(&conn).unwrap().sort_mode, SortMode::Manual);
+    assert_eq!(get_window_prefs_from_conn(&conn).unwrap().x, 123.0);
+    assert_eq!(sorted_titles(&conn, SortMode::Manual), vec!["Keep me"]);
   }
 
-  tx.commit().map_err(|err| err.to_string())?;
+  #[test]
+  fn resetting_window_prefs_keeps_ui_prefs() {
+    let conn = test_conn();
+    customize_both_prefs(&conn);
 
-  Ok(reminders)
-}
+    let reset = reset_window_prefs_in(&conn).unwrap();
 
-#[tauri::command]
-fn delete_todo(state: State<'_, AppState>, id: String) -> CommandResult<()> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    let defaults = WindowPrefs::default();
+    assert_eq!(reset.x, defaults.x);
+    assert_eq!(get_window_prefs_from_conn(&conn).unwrap().x, defaults.x);
+    assert_eq!(get_ui_prefs_from_conn(&conn).unwrap().sort_mode, SortMode::PriorityDesc);
+  }
 
-  conn
-    .execute("DELETE FROM todos WHERE id = ?1", params![id])
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn monthly_recurrence_clamps_to_month_end() {
+    let cases = [
+      ("2023-01-31", "2023-02-28"),
+      ("2024-01-31", "2024-02-29"),
+      ("2024-01-30", "2024-02-29"),
+      ("2024-01-29", "2024-02-29"),
+      ("2023-02-28", "2023-03-28"),
+      ("2024-02-29", "2024-03-29"),
+      ("2024-03-31", "2024-04-30"),
+      ("2024-08-31", "2024-09-30"),
+      ("2023-12-31", "2024-01-31"),
+    ];
+    for (due, expected) in cases {
+      assert_eq!(next_due_date(RECURRENCE_MONTHLY, day(due)), Some(day(expected)), "from {due}");
+    }
+  }
 
-  Ok(())
-}
+  #[test]
+  fn monthly_steps_count_from_the_original_anchor() {
+    let steps: Vec<NaiveDate> = (1..=4)
+      .map(|step| nth_due_date(RECURRENCE_MONTHLY, day("2024-01-31"), step).unwrap())
+      .collect();
+    assert_eq!(steps, vec![day("2024-02-29"), day("2024-03-31"), day("2024-04-30"), day("2024-05-31")]);
+    assert_eq!(nth_due_date(RECURRENCE_MONTHLY, day("2023-01-31"), 13), Some(day("2024-02-29")));
 
-#[tauri::command]
-fn reorder_todos(state: State<'_, AppState>, ids: Vec<String>) -> CommandResult<()> {
-  let mut conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    let next = |completed_on: &str| {
+      next_anchored_due_date(RECURRENCE_MONTHLY, day("2024-01-31"), day(completed_on), RecurrenceAnchor::Schedule)
+    };
+    assert_eq!(next("2024-02-01"), Some(day("2024-02-29")));
+    assert_eq!(next("2024-03-01"), Some(day("2024-03-31")));
+  }
 
-  let tx = conn.transaction().map_err(|err| err.to_string())?;
-  let now = now_iso();
+  #[test]
+  fn weekly_recurrence_crosses_leap_day_and_year_end() {
+    assert_eq!(next_due_date(RECURRENCE_WEEKLY, day("2024-02-26")), Some(day("2024-03-04")));
+    assert_eq!(next_due_date(RECURRENCE_WEEKLY, day("2023-12-28")), Some(day("2024-01-04")));
+    assert_eq!(next_due_date(RECURRENCE_NONE, day("2024-02-26")), None);
+  }
 
-  for (index, id) in ids.iter().enumerate() {
-    tx
-      .execute(
-        "UPDATE todos SET sort_order = ?2, updated_at = ?3 WHERE id = ?1",
-        params![id, index as i64, &now],
-      )
-      .map_err(|err| err.to_string())?;
+  #[test]
+  fn normalize_recurrence_tag_accepts_weekly_and_monthly_only_when_known() {
+    let normalize = |value: &str| normalize_recurrence_tag(Some(value.to_string()));
+    assert_eq!(normalize("weekly"), RECURRENCE_WEEKLY);
+    assert_eq!(normalize(" monthly "), RECURRENCE_MONTHLY);
+    assert_eq!(normalize("yearly"), RECURRENCE_NONE);
+    assert_eq!(normalize_recurrence_tag(None), RECURRENCE_NONE);
   }
 
-  tx.commit().map_err(|err| err.to_string())?;
-  Ok(())
-}
+  #[test]
+  fn corrupted_window_prefs_fall_back_to_defaults_and_are_repaired() {
+    let conn = test_conn();
+    set_meta(&conn, WINDOW_PREFS_KEY, "{not json").unwrap();
 
-#[tauri::command]
-fn migrate_legacy_todos_if_needed(
-  state: State<'_, AppState>,
-  payload: Vec<LegacyTodo>,
-) -> CommandResult<MigrationResult> {
-  let mut conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    let prefs = get_window_prefs_from_conn(&conn).unwrap();
+    let defaults = WindowPrefs::default();
+    assert_eq!((prefs.x, prefs.y, prefs.width), (defaults.x, defaults.y, defaults.width));
 
-  let already_migrated = get_meta(&conn, MIGRATION_KEY)?.as_deref() == Some("true");
-  if already_migrated {
-    return Ok(MigrationResult {
-      migrated_count: 0,
-      already_migrated: true,
-    });
+    let stored = get_meta(&conn, WINDOW_PREFS_KEY).unwrap().unwrap();
+    assert_eq!(stored, serde_json::to_string(&defaults).unwrap());
   }
 
-  let tx = conn.transaction().map_err(|err| err.to_string())?;
-  let mut migrated_count = 0usize;
+  #[test]
+  fn corrupted_ui_prefs_fall_back_to_defaults_and_are_repaired() {
+    let conn = test_conn();
+    set_meta(&conn, UI_PREFS_KEY, "[1, 2, 3]").unwrap();
 
-  let min_sort: i64 = tx
-    .query_row("SELECT COALESCE(MIN(sort_order), 0) FROM todos", [], |row| row.get(0))
-    .map_err(|err| err.to_string())?;
+    let prefs = get_ui_prefs_from_conn(&conn).unwrap();
+    assert_eq!(prefs.sort_mode, SortMode::Manual);
 
-  let mut next_sort = min_sort - payload.len() as i64;
+    let stored = get_meta(&conn, UI_PREFS_KEY).unwrap().unwrap();
+    assert_eq!(stored, serde_json::to_string(&UiPrefs::default()).unwrap());
+    assert!(serde_json::from_str::<UiPrefs>(&stored).is_ok());
+  }
 
-  for legacy in payload {
-    let trimmed_title = legacy.title.trim();
-    if trimmed_title.is_empty() {
-      continue;
+  #[test]
+  fn schedule_anchor_catches_up_after_many_missed_intervals() {
+    let next = |tag: &str, due: &str, completed_on: &str, anchor: RecurrenceAnchor| {
+      next_anchored_due_date(tag, day(due), day(completed_on), anchor)
+    };
+
+    let cases = [
+      (RECURRENCE_DAILY, "2024-01-01", "2024-03-15", "2024-03-16"),
+      (RECURRENCE_WEEKLY, "2024-01-01", "2024-03-15", "2024-03-18"),
+      (RECURRENCE_BI_WEEKLY, "2024-01-01", "2024-03-15", "2024-03-25"),
+      (RECURRENCE_MONTHLY, "2023-01-31", "2024-03-15", "2024-03-31"),
+      (RECURRENCE_WEEKLY, "2024-01-01", "2024-01-08", "2024-01-15"),
+    ];
+    for (tag, due, completed_on, expected) in cases {
+      assert_eq!(next(tag, due, completed_on, RecurrenceAnchor::Schedule), Some(day(expected)), "{tag} from {due}");
     }
 
-    let id = if legacy.id.trim().is_empty() {
-      Uuid::new_v4().to_string()
-    } else {
-      legacy.id
-    };
+    assert_eq!(
+      next(RECURRENCE_WEEKLY, "2024-01-01", "2024-03-15", RecurrenceAnchor::Completion),
+      Some(day("2024-03-22"))
+    );
+    assert_eq!(next(RECURRENCE_NONE, "2024-01-01", "2024-03-15", RecurrenceAnchor::Schedule), None);
+  }
 
-    let created_at = if legacy.created_at.trim().is_empty() {
-      now_iso()
-    } else {
-      legacy.created_at
-    };
+  #[test]
+  fn count_todos_skips_archived_todos_for_every_filter() {
+    let conn = test_conn();
+    seed_bulk_preview_todos(&conn);
+    let today = day("2024-01-10");
+
+    let counts: Vec<i64> = [
+      CountFilter::All,
+      CountFilter::Incomplete,
+      CountFilter::Completed,
+      CountFilter::Overdue,
+      CountFilter::DueToday,
+    ]
+    .into_iter()
+    .map(|filter| {
+      let count = count_todos_in(&conn, filter, today).unwrap();
+      assert_eq!(count as usize, todos_matching_filter(&conn, filter, today).unwrap().len(), "{filter:?}");
+      count
+    })
+    .collect();
+    assert_eq!(counts, vec![6, 4, 2, 2, 1]);
+  }
 
-    let updated_at = if legacy.updated_at.trim().is_empty() {
-      created_at.clone()
-    } else {
-      legacy.updated_at
+  #[test]
+  fn length_limits_count_unicode_scalars_at_the_boundary() {
+    let at_limit = "🦀".repeat(MAX_TITLE_CHARS);
+    assert!(at_limit.len() > MAX_TITLE_CHARS);
+    assert!(check_length("Title", &at_limit, MAX_TITLE_CHARS).is_ok());
+
+    let one_over = format!("{at_limit}é");
+    match check_length("Title", &one_over, MAX_TITLE_CHARS) {
+      Err(AppError::Validation(message)) => {
+        assert!(message.contains("Title") && message.contains(&MAX_TITLE_CHARS.to_string()), "{message}");
+      }
+      other => panic!("expected a validation error, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn create_rejects_titles_and_notes_one_over_the_limit() {
+    let conn = test_conn();
+    let input = |title: String, note: Option<String>| CreateTodoInput {
+      title,
+      note,
+      ..Default::default()
     };
 
-    let inserted = tx
-      .execute(
-        "INSERT OR IGNORE INTO todos
-         (id, title, recurrence_tag, recurrence_checked_at, note, completed, due_date, sort_order, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-        params![
-          id,
-          trimmed_title,
-          normalize_recurrence_tag(legacy.recurrence_tag),
-          Option::<String>::None,
-          legacy.note,
-          to_db_bool(legacy.completed),
-          normalize_date(legacy.due_date),
-          next_sort,
-          created_at,
-          updated_at,
-        ],
-      )
-      .map_err(|err| err.to_string())?;
+    let title = "é".repeat(MAX_TITLE_CHARS);
+    let note = "ü".repeat(MAX_NOTE_CHARS);
+    let created = new_todo_from_input(&conn, input(title.clone(), Some(note.clone()))).unwrap();
+    assert_eq!(created.title.chars().count(), MAX_TITLE_CHARS);
+    assert_eq!(created.note.chars().count(), MAX_NOTE_CHARS);
 
-    if inserted > 0 {
-      migrated_count += 1;
-      next_sort += 1;
-    }
+    let long_title = new_todo_from_input(&conn, input(format!("{title}é"), None));
+    assert!(matches!(long_title, Err(AppError::Validation(message)) if message.starts_with("Title")));
+    let long_note = new_todo_from_input(&conn, input("Short".to_string(), Some(format!("{note}ü"))));
+    assert!(matches!(long_note, Err(AppError::Validation(message)) if message.starts_with("Note")));
   }
 
-  tx
-    .execute(
-      "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
-       ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-      params![MIGRATION_KEY, "true"],
-    )
-    .map_err(|err| err.to_string())?;
+  #[test]
+  fn commands_recover_after_a_panic_poisons_the_locks() {
+    let state = test_state();
+
+    std::thread::scope(|scope| {
+      let panicked = scope
+        .spawn(|| {
+          let _database = state.write_db();
+          let _undo = state.lock_undo_stack();
+          panic!("simulated crash while holding the locks");
+        })
+        .join();
+      assert!(panicked.is_err());
+    });
+    assert!(state.db.is_poisoned());
+    assert!(state.undo_stack.is_poisoned());
+
+    save_window_position_in(&state, 300.0, 200.0).unwrap();
+    let conn = db_conn(&state).unwrap();
+    let prefs = get_window_prefs_from_conn(&conn).unwrap();
+    assert_eq!((prefs.x, prefs.y), (300.0, 200.0));
+    insert_test_todo(&conn, "Still works");
+    drop(conn);
+    assert!(state.lock_undo_stack().is_empty());
+
+    assert!(!state.db.is_poisoned());
+    assert!(!state.undo_stack.is_poisoned());
+  }
 
-  tx.commit().map_err(|err| err.to_string())?;
+  #[test]
+  fn a_panic_mid_transaction_rolls_back_and_leaves_the_pool_usable() {
+    let state = test_state();
+
+    std::thread::scope(|scope| {
+      let panicked = scope
+        .spawn(|| {
+          let mut conn = db_conn(&state).unwrap();
+          let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate).unwrap();
+          insert_test_todo(&tx, "Never committed");
+          panic!("simulated crash inside a transaction");
+        })
+        .join();
+      assert!(panicked.is_err());
+    });
 
-  Ok(MigrationResult {
-    migrated_count,
-    already_migrated: false,
-  })
-}
+    let conn = db_conn(&state).unwrap();
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM todos", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 0);
+    insert_test_todo(&conn, "After the crash");
+  }
 
-#[tauri::command]
-fn get_window_prefs(state: State<'_, AppState>) -> CommandResult<WindowPrefs> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn reschedule_recurrence_group_moves_open_todos_onto_the_new_grid() {
+    let conn = test_conn();
+    let today = day("2024-03-13");
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let past = insert_recurring_test_todo(&conn, "Stand-up notes", RECURRENCE_DAILY, "2024-03-05");
+    let future = insert_recurring_test_todo(&conn, "Inbox zero", RECURRENCE_DAILY, "2024-03-20");
+    let mut undated = insert_test_todo(&conn, "Stretch");
+    undated.recurrence_tag = RECURRENCE_DAILY.to_string();
+    update_todo_row(&conn, &undated).unwrap();
+    let mut done = insert_recurring_test_todo(&conn, "Done", RECURRENCE_DAILY, "2024-03-01");
+    done.completed = true;
+    update_todo_row(&conn, &done).unwrap();
+    let mut archived = insert_recurring_test_todo(&conn, "Archived", RECURRENCE_DAILY, "2024-03-01");
+    archived.archived = true;
+    update_todo_row(&conn, &archived).unwrap();
+
+    let changed = reschedule_recurrence_group_in(&conn, "daily", " weekly ", utc, today).unwrap();
+    assert_eq!(changed, 3);
+
+    let fetch = |todo: &Todo| get_todo_by_id(&conn, &todo.id, false).unwrap().unwrap();
+    let summary = |todo: &Todo| {
+      let todo = fetch(todo);
+      (todo.recurrence_tag, todo.due_date)
+    };
+    assert_eq!(summary(&past), (RECURRENCE_WEEKLY.to_string(), Some("2024-03-19".to_string())));
+    assert_eq!(summary(&future), (RECURRENCE_WEEKLY.to_string(), Some("2024-03-20".to_string())));
+    assert_eq!(summary(&undated), (RECURRENCE_WEEKLY.to_string(), None));
+    assert_eq!(summary(&done), (RECURRENCE_DAILY.to_string(), Some("2024-03-01".to_string())));
+    assert_eq!(summary(&archived), (RECURRENCE_DAILY.to_string(), Some("2024-03-01".to_string())));
+  }
 
-  get_window_prefs_from_conn(&conn)
-}
+  #[test]
+  fn reschedule_recurrence_group_counts_only_changed_todos() {
+    let conn = test_conn();
+    let today = day("2024-03-13");
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let upcoming = insert_recurring_test_todo(&conn, "Review", RECURRENCE_WEEKLY, "2024-03-20");
+    let stale = insert_recurring_test_todo(&conn, "Plan", RECURRENCE_WEEKLY, "2024-03-04");
+
+    assert_eq!(reschedule_recurrence_group_in(&conn, "weekly", "weekly", utc, today).unwrap(), 1);
+    let unchanged = get_todo_by_id(&conn, &upcoming.id, false).unwrap().unwrap();
+    assert_eq!(unchanged.updated_at, upcoming.updated_at);
+    let advanced = get_todo_by_id(&conn, &stale.id, false).unwrap().unwrap();
+    assert_eq!(advanced.due_date.as_deref(), Some("2024-03-18"));
+
+    assert_eq!(reschedule_recurrence_group_in(&conn, "weekly", "weekly", utc, today).unwrap(), 0);
+  }
 
-#[tauri::command]
-fn save_window_prefs(state: State<'_, AppState>, input: WindowPrefs) -> CommandResult<()> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn reschedule_recurrence_group_rejects_none_and_unknown_tags() {
+    let conn = test_conn();
+    let today = day("2024-03-13");
+    let utc = FixedOffset::east_opt(0).unwrap();
+    insert_test_todo(&conn, "Plain");
+
+    for (from_tag, to_tag) in [("none", "weekly"), ("weekly", "none"), ("hourly", "weekly"), ("daily", "yearly")] {
+      assert!(
+        matches!(reschedule_recurrence_group_in(&conn, from_tag, to_tag, utc, today), Err(AppError::Validation(_))),
+        "{from_tag} -> {to_tag}"
+      );
+    }
+  }
 
-  let normalized = normalize_window_prefs(input);
-  save_window_prefs_to_conn(&conn, &normalized)
-}
+  #[test]
+  fn update_todo_in_does_not_revert_a_delete_that_lands_before_its_transaction() {
+    let path = std::env::temp_dir().join(format!("todo-lost-update-{}.db", Uuid::new_v4()));
+    let mut writer = Connection::open(&path).unwrap();
+    init_connection(&mut writer).unwrap();
+    ensure_schema(&writer).unwrap();
+    let mut deleter = Connection::open(&path).unwrap();
+    init_connection(&mut deleter).unwrap();
+    deleter.busy_timeout(std::time::Duration::ZERO).unwrap();
+
+    let todo = insert_test_todo(&writer, "Shared");
+    let input = |title: &str| -> UpdateTodoInput {
+      serde_json::from_value(serde_json::json!({ "id": todo.id, "title": title })).unwrap()
+    };
 
-#[tauri::command]
-fn get_ui_prefs(state: State<'_, AppState>) -> CommandResult<UiPrefs> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+    // While the update holds its write transaction the delete has to wait...
+    let tx = writer.transaction_with_behavior(TransactionBehavior::Immediate).unwrap();
+    let soft_delete = "UPDATE todos SET deleted_at = ?1 WHERE id = ?2";
+    assert!(deleter.execute(soft_delete, params![now_iso(), todo.id]).is_err());
+    update_todo_in(&tx, input("Renamed")).unwrap();
+    tx.commit().unwrap();
+
+    // ...and once it lands, a later update re-reads the row and sees it gone.
+    deleter.execute(soft_delete, params![now_iso(), todo.id]).unwrap();
+    let tx = writer.transaction_with_behavior(TransactionBehavior::Immediate).unwrap();
+    assert!(matches!(update_todo_in(&tx, input("Resurrected")), Err(AppError::NotFound(_))));
+    drop(tx);
+
+    let (title, deleted_at): (String, Option<String>) = writer
+      .query_row("SELECT title, deleted_at FROM todos WHERE id = ?1", params![todo.id], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+      })
+      .unwrap();
+    assert_eq!(title, "Renamed");
+    assert!(deleted_at.is_some());
 
-  get_ui_prefs_from_conn(&conn)
-}
+    drop(writer);
+    drop(deleter);
+    let _ = std::fs::remove_file(&path);
+  }
 
-#[tauri::command]
-fn save_ui_prefs(state: State<'_, AppState>, input: UiPrefs) -> CommandResult<()> {
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn authorize_http_request_only_accepts_loopback_hosts_without_lan() {
+    assert!(authorize_http_request(Some("127.0.0.1:47831"), None, 47831, None).is_ok());
+    assert!(authorize_http_request(Some("LOCALHOST:47831"), None, 47831, None).is_ok());
 
-  save_ui_prefs_to_conn(&conn, &input)
-}
+    for host in [None, Some("evil.example:47831"), Some("127.0.0.1:8080"), Some("localhost")] {
+      let (status, _) = authorize_http_request(host, None, 47831, None).unwrap_err();
+      assert_eq!(status, "403 Forbidden", "{host:?}");
+    }
+  }
 
-#[tauri::command]
-fn set_panel_mode(
-  state: State<'_, AppState>,
-  app: AppHandle,
-  mode: PanelMode,
-) -> CommandResult<WindowPrefs> {
-  // Compatibility alias. New sizing uses explicit window size classes.
-  let target_size_class = match mode {
-    PanelMode::Mini => WindowSizeClass::Mini,
-    PanelMode::Expanded => WindowSizeClass::Wide,
-  };
+  #[test]
+  fn authorize_http_request_requires_the_session_token_in_lan_mode() {
+    let token = Some("0123456789abcdef");
+    let ok = authorize_http_request(Some("192.168.1.20:47831"), Some("Bearer 0123456789abcdef"), 47831, token);
+    assert!(ok.is_ok());
 
-  set_window_size_class_inner(state, app, target_size_class)
-}
+    for authorization in [None, Some("Bearer wrong"), Some("0123456789abcdef"), Some("Bearer 0123456789abcdee")] {
+      let (status, _) = authorize_http_request(Some("127.0.0.1:47831"), authorization, 47831, token).unwrap_err();
+      assert_eq!(status, "401 Unauthorized", "{authorization:?}");
+    }
+  }
 
-fn set_window_size_class_inner(
-  state: State<'_, AppState>,
-  app: AppHandle,
-  size_class: WindowSizeClass,
-) -> CommandResult<WindowPrefs> {
-  let (target_width, target_height) = dimensions_for_size_class(&size_class);
+  #[test]
+  fn scheduled_load_keys_timestamped_due_dates_by_their_local_day() {
+    let conn = test_conn();
+    let late = "2024-04-15T23:30:00+00:00";
+    let noon = "2024-04-16T12:00:00+00:00";
+    let key = |value: &str| parse_due_day(value, &Local).unwrap().format("%Y-%m-%d").to_string();
+    insert_test_todo_due(&conn, "Plain", "2024-04-16");
+    let snoozed = insert_test_todo_due(&conn, "Snoozed", late);
+    insert_test_todo_due(&conn, "Noon", noon);
+
+    let mut expected: BTreeMap<String, u32> = BTreeMap::new();
+    for value in ["2024-04-16", late, noon] {
+      *expected.entry(key(value)).or_default() += 1;
+    }
+    let expected: Vec<(String, u32)> = expected.into_iter().collect();
+    assert_eq!(scheduled_load(&conn), expected);
 
-  if let Some(window) = app.get_webview_window("main") {
-    window
-      .set_size(Size::Logical(LogicalSize::new(target_width, target_height)))
-      .map_err(|err| err.to_string())?;
+    rebuild_scheduled_load(&conn).unwrap();
+    assert_eq!(scheduled_load(&conn), expected);
+
+    conn.execute("DELETE FROM todos WHERE id = ?1", params![&snoozed.id]).unwrap();
+    assert_eq!(scheduled_load(&conn), vec![("2024-04-16".to_string(), 2)]);
   }
 
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn count_hidden_completed_matches_the_rows_hidden_placement_leaves_out() {
+    let conn = test_conn();
+    let hidden = |options: &ListOptions| count_hidden_completed_in(&conn, CompletedPlacement::Hidden, options).unwrap();
+    let listed = |options: &ListOptions| {
+      list_todos_from_conn(&conn, false, options, SortMode::Manual, CompletedPlacement::Hidden)
+        .unwrap()
+        .len()
+    };
 
-  let mut prefs = get_window_prefs_from_conn(&conn)?;
-  prefs.size_class = size_class;
-  prefs.mode = mode_from_size_class(&prefs.size_class);
-  prefs.width = target_width;
-  prefs.height = target_height;
-  save_window_prefs_to_conn(&conn, &prefs)?;
+    for (title, context, done) in [
+      ("Open home", "home", false),
+      ("Done home", "home", true),
+      ("Done work", "work", true),
+      ("Done work too", "work", true),
+    ] {
+      let todo = if done {
+        complete_test_todo(&conn, title, "2024-01-01T09:00:00+00:00")
+      } else {
+        insert_test_todo(&conn, title)
+      };
+      replace_todo_tags_in(&conn, &todo.id, &[context.to_string()]).unwrap();
+    }
+    let archived = complete_test_todo(&conn, "Archived", "2024-01-01T09:00:00+00:00");
+    conn.execute("UPDATE todos SET archived = 1 WHERE id = ?1", params![archived.id]).unwrap();
 
-  Ok(prefs)
-}
+    let all = ListOptions::default();
+    assert_eq!((listed(&all), hidden(&all)), (1, 3));
 
-#[tauri::command]
-fn set_window_size_class(
-  state: State<'_, AppState>,
-  app: AppHandle,
-  size_class: WindowSizeClass,
-) -> CommandResult<WindowPrefs> {
-  set_window_size_class_inner(state, app, size_class)
-}
+    set_meta(&conn, ACTIVE_CONTEXT_KEY, "work").unwrap();
+    assert_eq!((listed(&all), hidden(&all)), (0, 2));
 
-#[tauri::command]
-fn set_always_on_top(
-  state: State<'_, AppState>,
-  app: AppHandle,
-  enabled: bool,
-) -> CommandResult<WindowPrefs> {
-  if let Some(window) = app.get_webview_window("main") {
-    window
-      .set_always_on_top(enabled)
-      .map_err(|err| err.to_string())?;
+    let paged = ListOptions { limit: Some(1), ..ListOptions::default() };
+    assert_eq!(hidden(&paged), 2);
+    let explicit = ListOptions { completed: Some(true), ..ListOptions::default() };
+    assert_eq!(hidden(&explicit), 0);
+    assert_eq!(count_hidden_completed_in(&conn, CompletedPlacement::Inline, &all).unwrap(), 0);
   }
 
-  let conn = state
-    .db
-    .lock()
-    .map_err(|_| "Failed to acquire database lock".to_string())?;
+  #[test]
+  fn get_delta_is_empty_while_the_list_hash_matches() {
+    let conn = test_conn();
+    insert_test_todo(&conn, "Steady");
+    let hash = list_hash_in(&conn).unwrap();
+
+    let delta = get_delta_in(&conn, "2000-01-01T00:00:00Z", &hash).unwrap();
+    assert!(delta.unchanged);
+    assert_eq!(delta.hash, hash);
+    assert_eq!(delta.cursor, "2000-01-01T00:00:00.000Z");
+    assert!(delta.changed.is_empty() && delta.deleted_ids.is_empty());
+  }
 
-  let mut prefs = get_window_prefs_from_conn(&conn)?;
-  prefs.always_on_top = enabled;
-  save_window_prefs_to_conn(&conn, &prefs)?;
+  #[test]
+  fn get_delta_lists_changes_and_tombstones_since_the_cursor() {
+    let conn = test_conn();
+    let untouched = insert_test_todo(&conn, "Untouched");
+    let mut edited = insert_test_todo(&conn, "Edited");
+    let trashed = insert_test_todo(&conn, "Trashed");
+    let purged = insert_test_todo(&conn, "Purged");
+    conn.execute("UPDATE change_log SET at = '2000-01-01T00:00:00.000Z'", []).unwrap();
+    let stale_hash = list_hash_in(&conn).unwrap();
+
+    edited.title = "Edited again".to_string();
+    update_todo_row(&conn, &edited).unwrap();
+    conn
+      .execute("UPDATE todos SET deleted_at = ?1 WHERE id = ?2", params![now_iso(), trashed.id])
+      .unwrap();
+    conn.execute("DELETE FROM todos WHERE id = ?1", params![purged.id]).unwrap();
+
+    let delta = get_delta_in(&conn, "2020-01-01T00:00:00Z", &stale_hash).unwrap();
+    assert!(!delta.unchanged);
+    assert_ne!(delta.hash, stale_hash);
+    assert_eq!(ids_of(&delta.changed), vec![edited.id.clone()]);
+    assert_eq!(delta.changed[0].title, "Edited again");
+    assert_eq!(delta.deleted_ids, vec![trashed.id.clone(), purged.id.clone()]);
+    assert!(!delta.changed.iter().any(|todo| todo.id == untouched.id));
+    assert!(delta.cursor.as_str() > "2020-01-01T00:00:00.000Z");
+
+    let caught_up = get_delta_in(&conn, &delta.cursor, &delta.hash).unwrap();
+    assert!(caught_up.unchanged);
+  }
 
-  Ok(prefs)
-}
+  #[test]
+  fn work_mode_scopes_secondary_views_to_todos_tagged_with_the_context() {
+    let conn = test_conn();
+    let office = insert_test_todo_due(&conn, "Report draft", "2024-01-05");
+    replace_todo_tags_in(&conn, &office.id, &["work".to_string()]).unwrap();
+    let mut personal = insert_test_todo_due(&conn, "Report taxes", "2024-01-05");
+    // The free-text context column plays no part in work mode.
+    personal.context = Some("work".to_string());
+    update_todo_row(&conn, &personal).unwrap();
+    insert_test_todo(&conn, "Bare idea");
+
+    let titles = |todos: Vec<Todo>| -> Vec<String> { todos.into_iter().map(|todo| todo.title).collect() };
+    let today = day("2024-01-10");
+    assert_eq!(count_todos_in(&conn, CountFilter::All, today).unwrap(), 3);
+    assert_eq!(search_todos_in(&conn, "report").unwrap().len(), 2);
+    assert_eq!(titles(list_inbox_todos_in(&conn).unwrap()), vec!["Bare idea"]);
+
+    set_meta(&conn, ACTIVE_CONTEXT_KEY, "work").unwrap();
+    assert_eq!(count_todos_in(&conn, CountFilter::All, today).unwrap(), 1);
+    assert_eq!(count_todos_in(&conn, CountFilter::Overdue, today).unwrap(), 1);
+    assert_eq!(titles(search_todos_in(&conn, "report").unwrap()), vec!["Report draft"]);
+    assert_eq!(titles(list_todos_by_due_date_in(&conn, true, false).unwrap()), vec!["Report draft"]);
+    assert!(list_inbox_todos_in(&conn).unwrap().is_empty());
+    assert_eq!(sorted_titles(&conn, SortMode::Manual), vec!["Report draft"]);
+  }
 
-fn main() {
-  tauri::Builder::default()
-    .setup(|app| {
-      let app_data_dir = app.path().app_data_dir().map_err(std::io::Error::other)?;
-      std::fs::create_dir_all(&app_data_dir).map_err(std::io::Error::other)?;
+  #[test]
+  fn archived_todos_stay_out_of_secondary_views() {
+    let conn = test_conn();
+    let mut todos = Vec::new();
+    for title in ["Filed report", "Live report"] {
+      let mut todo = insert_test_todo_due(&conn, title, "2024-04-15");
+      todo.note = "See https://example.com/report".to_string();
+      todo.context = Some("office".to_string());
+      update_todo_row(&conn, &todo).unwrap();
+      todos.push(todo);
+    }
+    conn
+      .execute("UPDATE todos SET archived = 1 WHERE id = ?1", params![todos[0].id])
+      .unwrap();
+
+    let live = vec!["Live report".to_string()];
+    let names = |todos: Vec<Todo>| -> Vec<String> { todos.into_iter().map(|todo| todo.title).collect() };
+    assert_eq!(names(search_todos_in(&conn, "report").unwrap()), live);
+    assert_eq!(names(list_todos_by_due_date_in(&conn, true, false).unwrap()), live);
+    assert_eq!(names(list_todos_by_context_in(&conn, "office".to_string()).unwrap()), live);
+    assert_eq!(todos_with_links_in(&conn).unwrap().len(), 1);
+    assert_eq!(scheduled_load(&conn), vec![("2024-04-15".to_string(), 1)]);
+
+    conn
+      .execute("UPDATE todos SET archived = 0 WHERE id = ?1", params![todos[0].id])
+      .unwrap();
+    assert_eq!(scheduled_load(&conn), vec![("2024-04-15".to_string(), 2)]);
+  }
 
-      let db_path = app_data_dir.join("simple_todo_note.db");
-      let conn = Connection::open(db_path).map_err(std::io::Error::other)?;
-      ensure_schema(&conn).map_err(std::io::Error::other)?;
+  #[test]
+  fn undoing_a_toggle_restores_completion_but_stamps_updated_at_now() {
+    let conn = test_conn();
+    let mut snapshot = insert_test_todo(&conn, "Water plants");
+    snapshot.updated_at = "2020-01-01T00:00:00+00:00".to_string();
+    let mut toggled = snapshot.clone();
+    toggled.completed = true;
+    toggled.completed_at = Some(now_iso());
+    update_todo_row(&conn, &toggled).unwrap();
+
+    let before = Utc::now();
+    let description = apply_undo_in(&conn, &UndoEntry::Toggle(Box::new(snapshot.clone()))).unwrap();
+    assert_eq!(description.restored_ids, vec![snapshot.id.clone()]);
+
+    let restored = get_todo_by_id(&conn, &snapshot.id, false).unwrap().unwrap();
+    assert!(!restored.completed);
+    assert_eq!(restored.completed_at, None);
+    let stamped = DateTime::parse_from_rfc3339(&restored.updated_at).unwrap();
+    assert!(stamped >= before - Duration::seconds(1));
+  }
 
-      let prefs = get_window_prefs_from_conn(&conn).unwrap_or_default();
-      app.manage(AppState { db: Mutex::new(conn) });
+  #[test]
+  fn reordering_spaces_sort_orders_and_leaves_updated_at_alone() {
+    let conn = test_conn();
+    let stamp = "2020-01-01T00:00:00+00:00";
+    let mut todos = Vec::new();
+    for title in ["A", "B", "C"] {
+      let mut todo = insert_test_todo(&conn, title);
+      todo.updated_at = stamp.to_string();
+      update_todo_row(&conn, &todo).unwrap();
+      todos.push(todo);
+    }
+    let ids: Vec<String> = todos.iter().rev().map(|todo| todo.id.clone()).collect();
+
+    let reordered = reorder_todos_in(&conn, &ids).unwrap();
+    let orders: Vec<(String, i64)> = reordered.iter().map(|todo| (todo.title.clone(), todo.sort_order)).collect();
+    assert_eq!(
+      orders,
+      vec![
+        ("C".to_string(), SORT_ORDER_GAP),
+        ("B".to_string(), 2 * SORT_ORDER_GAP),
+        ("A".to_string(), 3 * SORT_ORDER_GAP),
+      ]
+    );
+
+    let moved = move_todo_to_index_in(&conn, &todos[0].id, 0).unwrap();
+    let titles: Vec<&str> = moved.iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, vec!["A", "C", "B"]);
+
+    for todo in visible_todos_in_order(&conn).unwrap() {
+      assert_eq!(todo.updated_at, stamp, "{}", todo.title);
+    }
+  }
 
-      if let Some(window) = app.get_webview_window("main") {
-        let _ = apply_window_prefs(&window, &prefs);
-        attach_window_persistence(window, app.handle().clone());
-      }
+  #[test]
+  fn purge_trash_compares_deleted_at_as_an_instant_across_offsets() {
+    let conn = test_conn();
+    let now = Utc::now();
+    let far_east = FixedOffset::east_opt(14 * 3600).unwrap();
+    let mut rows = Vec::new();
+    for (title, deleted_at) in [
+      // Past the cutoff, but its +14:00 text sorts after the UTC cutoff.
+      ("Old east", (now - Duration::days(30) - Duration::hours(1)).with_timezone(&far_east).to_rfc3339()),
+      ("Old utc", (now - Duration::days(31)).to_rfc3339()),
+      ("Recent", (now - Duration::days(29)).to_rfc3339()),
+      ("Garbled", "last tuesday".to_string()),
+    ] {
+      let todo = insert_test_todo(&conn, title);
+      conn
+        .execute("UPDATE todos SET deleted_at = ?2 WHERE id = ?1", params![todo.id, deleted_at])
+        .unwrap();
+      rows.push(todo);
+    }
 
-      let _ = ensure_windows_autostart("SimpleTodoNote");
+    assert_eq!(purge_trash_older_than_in(&conn, 30).unwrap(), 2);
+    let left: Vec<String> = rows
+      .iter()
+      .filter(|todo| get_todo_by_id(&conn, &todo.id, true).unwrap().is_some())
+      .map(|todo| todo.title.clone())
+      .collect();
+    assert_eq!(left, vec!["Recent", "Garbled"]);
+  }
 
-      Ok(())
-    })
-    .invoke_handler(tauri::generate_handler![
-      list_todos,
-      create_todo,
-      update_todo,
-      toggle_todo,
-      set_recurrence_check,
-      get_daily_completion_heatmap,
-      consume_daily_due_reminders,
-      delete_todo,
-      reorder_todos,
-      migrate_legacy_todos_if_needed,
-      get_window_prefs,
-      save_window_prefs,
-      get_ui_prefs,
-      save_ui_prefs,
-      set_panel_mode,
-      set_window_size_class,
-      set_always_on_top,
-    ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+  #[test]
+  fn snooze_overdue_moves_each_due_date_by_the_snooze_length() {
+    let conn = test_conn();
+    let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+    let yesterday = Utc::now().with_timezone(&offset).date_naive() - Duration::days(1);
+    let plain = insert_test_todo_due(&conn, "Plain", &yesterday.format("%Y-%m-%d").to_string());
+    let stamped_at = yesterday.and_hms_opt(15, 30, 0).unwrap().and_local_timezone(offset).unwrap();
+    let stamped = insert_test_todo_due(&conn, "Stamped", &stamped_at.to_rfc3339());
+    let upcoming = insert_test_todo_due(&conn, "Upcoming", "2999-01-01");
+
+    assert_eq!(snooze_overdue_in(&conn, Duration::minutes(90), &offset).unwrap(), 2);
+
+    let due = |id: &str| get_todo_by_id(&conn, id, false).unwrap().unwrap().due_date.unwrap();
+    let midnight = yesterday.and_hms_opt(0, 0, 0).unwrap().and_local_timezone(offset).unwrap();
+    assert_eq!(due(&plain.id), (midnight + Duration::minutes(90)).to_rfc3339());
+    assert_eq!(due(&stamped.id), (stamped_at + Duration::minutes(90)).to_rfc3339());
+    assert_eq!(due(&upcoming.id), "2999-01-01");
+  }
+
+  #[test]
+  fn deduplicate_todos_groups_titles_after_normalizing_whitespace() {
+    let conn = test_conn();
+    let kept = insert_test_todo(&conn, "Buy milk");
+    let spaced = insert_test_todo(&conn, "Spaced");
+    conn
+      .execute("UPDATE todos SET title = ' Buy   milk\n' WHERE id = ?1", params![spaced.id])
+      .unwrap();
+    let other = insert_test_todo(&conn, "Buy Milk");
+
+    assert_eq!(deduplicate_todos_in(&conn, DedupeKey::TitleOnly, false).unwrap(), 1);
+    assert!(get_todo_by_id(&conn, &kept.id, false).unwrap().is_some());
+    assert!(get_todo_by_id(&conn, &spaced.id, false).unwrap().is_none());
+    assert!(get_todo_by_id(&conn, &spaced.id, true).unwrap().unwrap().deleted_at.is_some());
+    assert!(get_todo_by_id(&conn, &other.id, false).unwrap().is_some());
+  }
 }
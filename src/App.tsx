@@ -18,6 +18,7 @@ import {
   updateTodo as updateTodoRecord,
 } from './storage';
 import type {
+  AppError,
   DailyHeatmapDay,
   DueReminder,
   DeletedSnapshot,
@@ -45,6 +46,30 @@ const DEFAULT_UI_PREFS: UiPrefs = {
   motionMode: 'balanced',
   readabilityMode: 'adaptive',
   reduceMotionOverride: 'system',
+  locale: 'system',
+  dueNotificationsEnabled: true,
+  dueCheckIntervalMinutes: 5,
+  reminderSnoozeDefaultMinutes: 30,
+  recurrenceAnchor: 'schedule',
+  dimOnBlur: false,
+  blurredOpacity: 0.6,
+  autoCompleteParent: false,
+  lastFilter: 'all',
+  lastScrollOffset: 0,
+  httpServerEnabled: false,
+  httpServerPort: 47831,
+  httpServerLan: false,
+  sortMode: 'manual',
+  quickCaptureShortcut: 'CommandOrControl+Shift+Space',
+  firstDayOfWeek: 'monday',
+  dateFormat: 'locale',
+  completedPlacement: 'inline',
+  trashRetentionDays: 30,
+  maxAttachmentsPerTodo: 10,
+  autoArchiveDays: 0,
+  dailySummaryEnabled: false,
+  dailySummaryTime: '18:00',
+  dailySummaryTzOffsetMinutes: null,
 };
 
 const SIZE_CLASS_DIMENSIONS: Record<WindowSizeClass, { width: number; height: number }> = {
@@ -110,6 +135,9 @@ function getRecurrencePrefix(tag: RecurrenceTag): string {
   if (tag === 'bi-weekly') {
     return '[Bi-weekly] ';
   }
+  if (tag === 'monthly') {
+    return '[Monthly] ';
+  }
   return '';
 }
 
@@ -123,6 +151,9 @@ function getRecurrenceLabel(tag: RecurrenceTag): string {
   if (tag === 'bi-weekly') {
     return 'Bi-weekly';
   }
+  if (tag === 'monthly') {
+    return 'Monthly';
+  }
   return 'None';
 }
 
@@ -160,6 +191,13 @@ function isRecurringCycleChecked(todo: Todo, now: Date = new Date()): boolean {
     return isSameLocalDay(checkedAt, now);
   }
 
+  if (todo.recurrenceTag === 'monthly') {
+    const cycleEnd = new Date(checkedAt.getFullYear(), checkedAt.getMonth() + 1, 1);
+    const lastDay = new Date(cycleEnd.getFullYear(), cycleEnd.getMonth() + 1, 0).getDate();
+    cycleEnd.setDate(Math.min(checkedAt.getDate(), lastDay));
+    return now < cycleEnd;
+  }
+
   const elapsedMs = now.getTime() - checkedAt.getTime();
   const cycleDays = todo.recurrenceTag === 'weekly' ? 7 : 14;
   return elapsedMs < cycleDays * 24 * 60 * 60 * 1000;
@@ -175,6 +213,9 @@ function getCycleStatusLabel(tag: RecurrenceTag): string {
   if (tag === 'bi-weekly') {
     return 'Done this cycle';
   }
+  if (tag === 'monthly') {
+    return 'Done this month';
+  }
   return '';
 }
 
@@ -384,6 +425,15 @@ function toHeatmapLevel(count: number): 0 | 1 | 2 | 3 | 4 {
   return 4;
 }
 
+function isAppError(error: unknown): error is AppError {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    typeof (error as AppError).code === 'string' &&
+    typeof (error as AppError).message === 'string'
+  );
+}
+
 function toErrorMessage(error: unknown): string {
   if (error instanceof Error) {
     return error.message;
@@ -393,6 +443,10 @@ function toErrorMessage(error: unknown): string {
     return error;
   }
 
+  if (isAppError(error)) {
+    return error.message;
+  }
+
   return 'Something went wrong.';
 }
 
@@ -1256,6 +1310,7 @@ function App() {
                     <option value="daily">Daily</option>
                     <option value="weekly">Weekly</option>
                     <option value="bi-weekly">Bi-weekly</option>
+                    <option value="monthly">Monthly</option>
                   </select>
                   <input
                     className="create-date"
@@ -1419,6 +1474,7 @@ function App() {
                     <option value="daily">Daily</option>
                     <option value="weekly">Weekly</option>
                     <option value="bi-weekly">Bi-weekly</option>
+                    <option value="monthly">Monthly</option>
                   </select>
                 </div>
 
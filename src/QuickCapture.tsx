@@ -0,0 +1,57 @@
+import { FormEvent, KeyboardEvent, useState } from 'react';
+import { dismissQuickCapture, quickCapture } from './storage';
+
+function errorText(error: unknown): string {
+  if (error instanceof Error) {
+    return error.message;
+  }
+
+  if (error && typeof error === 'object' && 'message' in error && typeof error.message === 'string') {
+    return error.message;
+  }
+
+  return String(error);
+}
+
+export default function QuickCapture() {
+  const [title, setTitle] = useState('');
+  const [errorMessage, setErrorMessage] = useState<string | null>(null);
+
+  async function submit(event: FormEvent<HTMLFormElement>) {
+    event.preventDefault();
+    if (!title.trim()) {
+      return;
+    }
+
+    try {
+      await quickCapture(title);
+      setTitle('');
+      setErrorMessage(null);
+    } catch (error) {
+      setErrorMessage(errorText(error));
+    }
+  }
+
+  function handleKeyDown(event: KeyboardEvent<HTMLInputElement>) {
+    if (event.key === 'Escape') {
+      setTitle('');
+      void dismissQuickCapture();
+    }
+  }
+
+  return (
+    <form onSubmit={submit} className="quick-capture">
+      <input
+        className="create-title"
+        value={title}
+        onChange={(event) => setTitle(event.target.value)}
+        onKeyDown={handleKeyDown}
+        placeholder={errorMessage ?? 'Add a task...'}
+        aria-label="Task title"
+        aria-invalid={errorMessage !== null}
+        maxLength={160}
+        autoFocus
+      />
+    </form>
+  );
+}
@@ -0,0 +1,59 @@
+import { FormEvent, useState } from 'react';
+import { unlockDatabase } from './storage';
+
+function errorText(error: unknown): string {
+  if (error instanceof Error) {
+    return error.message;
+  }
+
+  if (error && typeof error === 'object' && 'message' in error && typeof error.message === 'string') {
+    return error.message;
+  }
+
+  return String(error);
+}
+
+type UnlockDatabaseProps = {
+  onUnlocked: () => void;
+};
+
+export default function UnlockDatabase({ onUnlocked }: UnlockDatabaseProps) {
+  const [passphrase, setPassphrase] = useState('');
+  const [errorMessage, setErrorMessage] = useState<string | null>(null);
+  const [unlocking, setUnlocking] = useState(false);
+
+  async function submit(event: FormEvent<HTMLFormElement>) {
+    event.preventDefault();
+    if (!passphrase) {
+      return;
+    }
+
+    setUnlocking(true);
+    try {
+      await unlockDatabase(passphrase);
+      onUnlocked();
+    } catch (error) {
+      setErrorMessage(errorText(error));
+      setUnlocking(false);
+    }
+  }
+
+  return (
+    <form onSubmit={submit} className="unlock-database">
+      <input
+        type="password"
+        className="create-title"
+        value={passphrase}
+        onChange={(event) => setPassphrase(event.target.value)}
+        placeholder="Database passphrase"
+        aria-label="Database passphrase"
+        aria-invalid={errorMessage !== null}
+        autoFocus
+      />
+      <button type="submit" className="create-submit" disabled={unlocking || !passphrase}>
+        Unlock
+      </button>
+      {errorMessage && <p className="error-banner">{errorMessage}</p>}
+    </form>
+  );
+}
@@ -1,10 +1,31 @@
-import React from 'react';
+import React, { useEffect, useState } from 'react';
 import ReactDOM from 'react-dom/client';
 import App from './App';
+import QuickCapture from './QuickCapture';
+import UnlockDatabase from './UnlockDatabase';
+import { currentWindowLabel, getDatabaseLocked } from './storage';
 import './styles.css';
 
+// The quick-capture window loads the same bundle and only needs the input.
+const isQuickCapture = currentWindowLabel() === 'quick-capture';
+
+// An encrypted database has to be unlocked before the app can load anything.
+function MainWindow() {
+  const [locked, setLocked] = useState<boolean | null>(null);
+
+  useEffect(() => {
+    getDatabaseLocked().then(setLocked, () => setLocked(false));
+  }, []);
+
+  if (locked === null) {
+    return null;
+  }
+
+  return locked ? <UnlockDatabase onUnlocked={() => setLocked(false)} /> : <App />;
+}
+
 ReactDOM.createRoot(document.getElementById('root') as HTMLElement).render(
   <React.StrictMode>
-    <App />
+    {isQuickCapture ? <QuickCapture /> : <MainWindow />}
   </React.StrictMode>,
 );
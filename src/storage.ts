@@ -1,14 +1,34 @@
 import type {
+  BulkOperation,
+  BulkPatch,
+  ChangeEntry,
+  CountFilter,
+  CreatedTodo,
   CreateTodoInput,
   DailyHeatmapDay,
+  DateField,
+  DedupeKey,
   DueReminder,
+  EditFrequencyEntry,
+  ExportFormat,
+  IntegrityReport,
   LegacyTodo,
+  ListedTodo,
+  ListOptions,
   MigrationResult,
   PanelMode,
   RecurrenceTag,
+  RelativeDueSpec,
+  SavedFilter,
+  SortMode,
+  StorageInfo,
+  TimeEntry,
   Todo,
+  TodoDelta,
+  TodoWithOrder,
   UiPrefs,
   UpdateTodoInput,
+  VacuumReport,
   WindowSizeClass,
   WindowPrefs,
 } from './types';
@@ -26,11 +46,16 @@ type TauriRuntime = {
   };
   __TAURI_INTERNALS__?: {
     invoke?: TauriInvoke;
+    metadata?: {
+      currentWindow?: {
+        label?: string;
+      };
+    };
   };
 };
 
 function isRecurrenceTag(value: unknown): value is RecurrenceTag {
-  return value === 'none' || value === 'daily' || value === 'weekly' || value === 'bi-weekly';
+  return value === 'none' || value === 'daily' || value === 'weekly' || value === 'bi-weekly' || value === 'monthly';
 }
 
 function isLegacyTodoLike(value: unknown): value is LegacyTodo {
@@ -66,6 +91,11 @@ function getInvoke(): TauriInvoke | null {
   return null;
 }
 
+export function currentWindowLabel(): string | null {
+  const runtime = window as Window & TauriRuntime;
+  return runtime.__TAURI_INTERNALS__?.metadata?.currentWindow?.label ?? null;
+}
+
 async function invokeCommand<T>(command: string, args?: Record<string, unknown>): Promise<T> {
   const invoke = getInvoke();
 
@@ -106,32 +136,88 @@ export async function migrateLegacyTodosIfNeeded(payload: LegacyTodo[]): Promise
   return invokeCommand<MigrationResult>('migrate_legacy_todos_if_needed', { payload });
 }
 
-export async function listTodos(): Promise<Todo[]> {
-  return invokeCommand<Todo[]>('list_todos');
+export async function listTodos(options?: ListOptions, sortMode?: SortMode): Promise<ListedTodo[]> {
+  return invokeCommand<ListedTodo[]>('list_todos', { options, sortMode });
+}
+
+export async function saveFilter(name: string, options: ListOptions): Promise<SavedFilter> {
+  return invokeCommand<SavedFilter>('save_filter', { name, options });
+}
+
+export async function listSavedFilters(): Promise<SavedFilter[]> {
+  return invokeCommand<SavedFilter[]>('list_saved_filters');
+}
+
+export async function deleteSavedFilter(name: string): Promise<void> {
+  return invokeCommand<void>('delete_saved_filter', { name });
+}
+
+export async function runSavedFilter(name: string): Promise<ListedTodo[]> {
+  return invokeCommand<ListedTodo[]>('run_saved_filter', { name });
+}
+
+export async function getTodo(id: string): Promise<Todo | null> {
+  return invokeCommand<Todo | null>('get_todo', { id });
+}
+
+export async function listTodosWithOrder(): Promise<TodoWithOrder[]> {
+  return invokeCommand<TodoWithOrder[]>('list_todos_with_order');
+}
+
+export async function countTodos(filter: CountFilter): Promise<number> {
+  return invokeCommand<number>('count_todos', { filter });
+}
+
+export async function countHiddenCompleted(options?: ListOptions): Promise<number> {
+  return invokeCommand<number>('count_hidden_completed', { options });
 }
 
 export async function createTodo(input: CreateTodoInput): Promise<Todo> {
   return invokeCommand<Todo>('create_todo', { input });
 }
 
+export async function createTodoWithPosition(input: CreateTodoInput): Promise<CreatedTodo> {
+  return invokeCommand<CreatedTodo>('create_todo_with_position', { input });
+}
+
+export async function createTodosBulk(lines: string[]): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('create_todos_bulk', { lines });
+}
+
 export async function updateTodo(input: UpdateTodoInput): Promise<Todo> {
   return invokeCommand<Todo>('update_todo', { input });
 }
 
-export async function toggleTodo(id: string): Promise<Todo> {
-  return invokeCommand<Todo>('toggle_todo', { id });
+export async function toggleTodo(id: string, cascade = false): Promise<Todo> {
+  return invokeCommand<Todo>('toggle_todo', { id, cascade });
 }
 
 export async function setRecurrenceCheck(id: string, checked: boolean): Promise<Todo> {
   return invokeCommand<Todo>('set_recurrence_check', { id, checked });
 }
 
-export async function deleteTodo(id: string): Promise<void> {
-  await invokeCommand('delete_todo', { id });
+export async function deleteTodo(id: string): Promise<number> {
+  return invokeCommand<number>('delete_todo', { id });
+}
+
+export async function mergeTodos(keepId: string, mergeId: string): Promise<Todo> {
+  return invokeCommand<Todo>('merge_todos', { keepId, mergeId });
 }
 
-export async function reorderTodos(ids: string[]): Promise<void> {
-  await invokeCommand('reorder_todos', { ids });
+export async function deduplicateTodos(by: DedupeKey, hardDelete = false): Promise<number> {
+  return invokeCommand<number>('deduplicate_todos', { by, hardDelete });
+}
+
+export async function reorderTodos(ids: string[]): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('reorder_todos', { ids });
+}
+
+export async function swapTodoOrder(aId: string, bId: string): Promise<void> {
+  return invokeCommand<void>('swap_todo_order', { aId, bId });
+}
+
+export async function moveTodoToIndex(id: string, index: number): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('move_todo_to_index', { id, index });
 }
 
 export async function getWindowPrefs(): Promise<WindowPrefs> {
@@ -142,6 +228,22 @@ export async function saveWindowPrefs(input: WindowPrefs): Promise<void> {
   await invokeCommand('save_window_prefs', { input });
 }
 
+export async function saveWindowLayout(name: string): Promise<WindowPrefs> {
+  return invokeCommand<WindowPrefs>('save_window_layout', { name });
+}
+
+export async function listWindowLayouts(): Promise<string[]> {
+  return invokeCommand<string[]>('list_window_layouts');
+}
+
+export async function applyWindowLayout(name: string): Promise<WindowPrefs> {
+  return invokeCommand<WindowPrefs>('apply_window_layout', { name });
+}
+
+export async function deleteWindowLayout(name: string): Promise<void> {
+  await invokeCommand('delete_window_layout', { name });
+}
+
 export async function setPanelMode(mode: PanelMode): Promise<WindowPrefs> {
   return invokeCommand<WindowPrefs>('set_panel_mode', { mode });
 }
@@ -154,6 +256,30 @@ export async function setAlwaysOnTop(enabled: boolean): Promise<WindowPrefs> {
   return invokeCommand<WindowPrefs>('set_always_on_top', { enabled });
 }
 
+export async function setClickThrough(enabled: boolean): Promise<WindowPrefs> {
+  return invokeCommand<WindowPrefs>('set_click_through', { enabled });
+}
+
+export async function isWindowOnScreen(): Promise<boolean> {
+  return invokeCommand<boolean>('is_window_on_screen');
+}
+
+export async function recenterWindow(): Promise<WindowPrefs> {
+  return invokeCommand<WindowPrefs>('recenter_window');
+}
+
+export async function setWindowOpacity(opacity: number): Promise<WindowPrefs> {
+  return invokeCommand<WindowPrefs>('set_window_opacity', { opacity });
+}
+
+export async function setHttpServer(enabled: boolean, port?: number, lan?: boolean): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_http_server', { enabled, port, lan });
+}
+
+export async function getHttpServerToken(): Promise<string | null> {
+  return invokeCommand<string | null>('get_http_server_token');
+}
+
 export async function getUiPrefs(): Promise<UiPrefs> {
   return invokeCommand<UiPrefs>('get_ui_prefs');
 }
@@ -166,6 +292,178 @@ export async function getDailyCompletionHeatmap(days: number): Promise<DailyHeat
   return invokeCommand<DailyHeatmapDay[]>('get_daily_completion_heatmap', { days });
 }
 
+export async function dueDateHistogram(start: string, end: string): Promise<DailyHeatmapDay[]> {
+  return invokeCommand<DailyHeatmapDay[]>('due_date_histogram', { start, end });
+}
+
 export async function consumeDailyDueReminders(): Promise<DueReminder[]> {
   return invokeCommand<DueReminder[]>('consume_daily_due_reminders');
 }
+
+export async function getStorageInfo(): Promise<StorageInfo> {
+  return invokeCommand<StorageInfo>('get_storage_info');
+}
+
+export async function vacuumDatabase(walCheckpoint = false): Promise<VacuumReport> {
+  return invokeCommand<VacuumReport>('vacuum_database', { walCheckpoint });
+}
+
+export async function changesSince(isoTimestamp: string): Promise<ChangeEntry[]> {
+  return invokeCommand<ChangeEntry[]>('changes_since', { isoTimestamp });
+}
+
+export async function getTodoHistory(id: string): Promise<ChangeEntry[]> {
+  return invokeCommand<ChangeEntry[]>('get_todo_history', { id });
+}
+
+export async function getDelta(since: string, knownHash: string): Promise<TodoDelta> {
+  return invokeCommand<TodoDelta>('get_delta', { since, knownHash });
+}
+
+export async function pruneChangeLog(before: string): Promise<number> {
+  return invokeCommand<number>('prune_change_log', { before });
+}
+
+export async function listByEditFrequency(limit: number): Promise<EditFrequencyEntry[]> {
+  return invokeCommand<EditFrequencyEntry[]>('list_by_edit_frequency', { limit });
+}
+
+export async function startTimer(id: string): Promise<TimeEntry> {
+  return invokeCommand<TimeEntry>('start_timer', { id });
+}
+
+export async function stopTimer(id: string): Promise<TimeEntry> {
+  return invokeCommand<TimeEntry>('stop_timer', { id });
+}
+
+export async function getTrackedMinutes(id: string): Promise<number> {
+  return invokeCommand<number>('get_tracked_minutes', { id });
+}
+
+export async function checkIntegrity(autoRepair = false): Promise<IntegrityReport> {
+  return invokeCommand<IntegrityReport>('check_integrity', { autoRepair });
+}
+
+export async function normalizeSortOrders(): Promise<number> {
+  return invokeCommand<number>('normalize_sort_orders');
+}
+
+export async function quickCapture(title: string): Promise<Todo> {
+  return invokeCommand<Todo>('quick_capture', { title });
+}
+
+export async function dismissQuickCapture(): Promise<void> {
+  await invokeCommand('dismiss_quick_capture');
+}
+
+export async function setQuickCaptureShortcut(shortcut: string): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_quick_capture_shortcut', { shortcut });
+}
+
+export async function listTodosDueThisWeek(): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('list_todos_due_this_week');
+}
+
+export async function snoozeOverdue(minutes: number, tzOffsetMinutes: number): Promise<number> {
+  return invokeCommand<number>('snooze_overdue', { minutes, tzOffsetMinutes });
+}
+
+export async function setDueRelative(id: string, spec: RelativeDueSpec, tzOffsetMinutes: number): Promise<Todo> {
+  return invokeCommand<Todo>('set_due_relative', { id, spec, tzOffsetMinutes });
+}
+
+export async function exportTodo(id: string, format: ExportFormat): Promise<string> {
+  return invokeCommand<string>('export_todo', { id, format });
+}
+
+export async function setTrashRetentionDays(days: number): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_trash_retention_days', { days });
+}
+
+export async function setMaxAttachmentsPerTodo(count: number): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_max_attachments_per_todo', { count });
+}
+
+export async function countAttachments(todoId: string): Promise<number> {
+  return invokeCommand<number>('count_attachments', { todoId });
+}
+
+export async function setAutoArchiveDays(days: number): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_auto_archive_days', { days });
+}
+
+export async function autoArchiveNow(): Promise<number> {
+  return invokeCommand<number>('auto_archive_now');
+}
+
+export async function setDailySummary(
+  enabled: boolean,
+  time: string,
+  tzOffsetMinutes: number | null = null,
+): Promise<UiPrefs> {
+  return invokeCommand<UiPrefs>('set_daily_summary', { enabled, time, tzOffsetMinutes });
+}
+
+export async function getEffectiveReduceMotion(): Promise<boolean> {
+  return invokeCommand<boolean>('get_effective_reduce_motion');
+}
+
+export async function setFocusTodo(id: string | null): Promise<Todo | null> {
+  return invokeCommand<Todo | null>('set_focus_todo', { id });
+}
+
+export async function getFocusTodo(): Promise<Todo | null> {
+  return invokeCommand<Todo | null>('get_focus_todo');
+}
+
+export async function setActiveContext(context: string | null): Promise<string | null> {
+  return invokeCommand<string | null>('set_active_context', { context });
+}
+
+export async function getActiveContext(): Promise<string | null> {
+  return invokeCommand<string | null>('get_active_context');
+}
+
+export async function listTodosInRange(field: DateField, start: string, end: string): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('list_todos_in_range', { field, start, end });
+}
+
+export async function listStaleTodos(inactiveDays: number): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('list_stale_todos', { inactiveDays });
+}
+
+export async function setNoteCollapsed(id: string, collapsed: boolean): Promise<Todo> {
+  return invokeCommand<Todo>('set_note_collapsed', { id, collapsed });
+}
+
+export async function getDatabaseLocked(): Promise<boolean> {
+  return invokeCommand<boolean>('get_database_locked');
+}
+
+export async function unlockDatabase(passphrase: string): Promise<void> {
+  return invokeCommand<void>('unlock_database', { passphrase });
+}
+
+export async function setDatabasePassphrase(passphrase: string): Promise<void> {
+  return invokeCommand<void>('set_database_passphrase', { passphrase });
+}
+
+export async function resetRecurring(id: string): Promise<Todo> {
+  return invokeCommand<Todo>('reset_recurring', { id });
+}
+
+export async function rescheduleRecurrenceGroup(
+  fromTag: RecurrenceTag,
+  toTag: RecurrenceTag,
+  tzOffsetMinutes: number,
+): Promise<number> {
+  return invokeCommand<number>('reschedule_recurrence_group', { fromTag, toTag, tzOffsetMinutes });
+}
+
+export async function bulkUpdate(ids: string[], patch: BulkPatch): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('bulk_update', { ids, patch });
+}
+
+export async function previewBulk(operation: BulkOperation, filter: CountFilter): Promise<Todo[]> {
+  return invokeCommand<Todo[]>('preview_bulk', { operation, filter });
+}
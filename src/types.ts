@@ -2,11 +2,40 @@ export type Filter = 'all' | 'open' | 'done';
 
 export type PanelMode = 'mini' | 'expanded';
 export type WindowSizeClass = 'mini' | 'standard' | 'wide';
+export type DockEdge = 'left' | 'right' | 'top' | 'bottom';
 export type MotionMode = 'balanced' | 'high' | 'low';
 export type ReadabilityMode = 'adaptive' | 'pure' | 'strong';
 export type ReduceMotionOverride = 'system' | 'on' | 'off';
+export type DateLocale = 'system' | 'en-US' | 'en-GB' | 'de-DE' | 'ja-JP';
+export type RecurrenceAnchor = 'schedule' | 'completion';
 
-export type RecurrenceTag = 'none' | 'daily' | 'weekly' | 'bi-weekly';
+export type FirstDayOfWeek = 'monday' | 'sunday';
+
+export type DateFormat = 'locale' | 'iso' | 'dayMonthYear' | 'monthDayYear';
+
+export type CompletedPlacement = 'inline' | 'bottom' | 'hidden';
+
+export type SortMode = 'manual' | 'dueDateAsc' | 'priorityThenDue' | 'createdDesc' | 'priorityDesc' | 'estimateAsc';
+
+export type DedupeKey = 'titleOnly' | 'titleAndDueDate';
+
+export type DateField = 'created' | 'updated' | 'completedDuring';
+
+export type RelativeDueSpec =
+  | { unit: 'minutes' | 'hours' | 'days' | 'weeks' | 'months'; amount: number }
+  | 'today'
+  | 'tomorrow'
+  | 'next_week';
+
+export type NoteFormat = 'plain' | 'markdown';
+export type ExportFormat = 'json' | 'markdown';
+export type CountFilter = 'all' | 'incomplete' | 'completed' | 'overdue' | 'dueToday';
+export type BulkOperation = 'delete' | 'complete' | 'archive';
+export type ImportStrategy = 'skip' | 'overwrite' | 'duplicate';
+
+export type RecurrenceTag = 'none' | 'daily' | 'weekly' | 'bi-weekly' | 'monthly';
+export type TodoPriority = 'low' | 'normal' | 'high';
+export type EnergyLevel = 'high' | 'medium' | 'low';
 
 export type Todo = {
   id: string;
@@ -19,9 +48,155 @@ export type Todo = {
   reminderEnabled: boolean;
   createdAt: string;
   updatedAt: string;
+  completedAt: string | null;
+  seriesId: string | null;
+  recurrenceUntil: string | null;
+  noteFormat: NoteFormat;
+  rolledAt: string | null;
+  priority: TodoPriority;
+  energy: EnergyLevel | null;
+  deletedAt: string | null;
+  recurrenceMaxCount: number | null;
+  recurrenceCompletedCount: number;
+  notifiedAt: string | null;
+  remindAfter: string | null;
+  context: string | null;
+  snoozeCount: number;
+  color: string | null;
+  archived: boolean;
+  pinned: boolean;
+  reminderOffsetMinutes: number | null;
+  estimateMinutes: number | null;
+  noteCollapsed: boolean;
+};
+
+export type ListedTodo = Todo & {
+  subtaskCount: number;
+  completedSubtaskCount: number;
+  tags: string[];
 };
 
-export type LegacyTodo = Omit<Todo, 'recurrenceTag' | 'recurrenceCheckedAt' | 'reminderEnabled'> & {
+export type TodoWithOrder = Todo & {
+  sortOrder: number;
+};
+
+export type CreatedTodo = {
+  todo: Todo;
+  insertedIndex: number | null;
+};
+
+export type GroupedTodos = Record<RecurrenceTag, Todo[]>;
+
+export type Subtask = {
+  id: string;
+  todoId: string;
+  title: string;
+  completed: boolean;
+  sortOrder: number;
+  createdAt: string;
+  updatedAt: string;
+};
+
+export type Attachment = {
+  id: string;
+  todoId: string;
+  path: string;
+  label: string | null;
+  createdAt: string;
+};
+
+export type TimeEntry = {
+  id: number;
+  todoId: string;
+  startedAt: string;
+  stoppedAt: string | null;
+};
+
+export type ChangeEntry = {
+  id: number;
+  todoId: string;
+  op: 'insert' | 'update' | 'delete';
+  at: string;
+};
+
+export type TodoDelta = {
+  hash: string;
+  cursor: string;
+  unchanged: boolean;
+  changed: Todo[];
+  deletedIds: string[];
+};
+
+export type EditFrequencyEntry = {
+  todo: Todo;
+  editCount: number;
+};
+
+export type DanglingRows = {
+  table: string;
+  count: number;
+};
+
+export type IntegrityReport = {
+  foreignKeyViolations: DanglingRows[];
+  orphans: DanglingRows[];
+  integrityErrors: string[];
+  duplicateSortOrders: number;
+  repaired: boolean;
+};
+
+export type StorageInfo = {
+  appDataDir: string;
+  databasePath: string | null;
+  databaseSizeBytes: number;
+  todoCount: number;
+};
+
+export type VacuumReport = {
+  sizeBeforeBytes: number;
+  sizeAfterBytes: number;
+  walCheckpointed: boolean;
+};
+
+export type ListOptions = {
+  limit?: number;
+  offset?: number;
+  completed?: boolean;
+  createdAfter?: string;
+};
+
+export type SavedFilter = {
+  name: string;
+  options: ListOptions;
+  createdAt: string;
+};
+
+export type LegacyTodo = Omit<
+  Todo,
+  | 'recurrenceTag'
+  | 'recurrenceCheckedAt'
+  | 'reminderEnabled'
+  | 'completedAt'
+  | 'seriesId'
+  | 'recurrenceUntil'
+  | 'noteFormat'
+  | 'rolledAt'
+  | 'priority'
+  | 'energy'
+  | 'deletedAt'
+  | 'recurrenceMaxCount'
+  | 'recurrenceCompletedCount'
+  | 'notifiedAt'
+  | 'remindAfter'
+  | 'context'
+  | 'snoozeCount'
+  | 'color'
+  | 'archived'
+  | 'pinned'
+  | 'reminderOffsetMinutes'
+  | 'estimateMinutes'
+  | 'noteCollapsed'
+> & {
   recurrenceTag?: RecurrenceTag;
   recurrenceCheckedAt?: string | null;
 };
@@ -31,6 +206,15 @@ export type CreateTodoInput = {
   recurrenceTag?: RecurrenceTag;
   note?: string;
   dueDate?: string | null;
+  noteFormat?: NoteFormat;
+  priority?: TodoPriority;
+  energy?: EnergyLevel | 'none';
+  context?: string;
+  color?: string;
+  reminderOffsetMinutes?: number | null;
+  estimateMinutes?: number | null;
+  noteCollapsed?: boolean;
+  rejectDuplicates?: boolean;
 };
 
 export type UpdateTodoInput = {
@@ -41,11 +225,51 @@ export type UpdateTodoInput = {
   completed?: boolean;
   dueDate?: string | null;
   reminderEnabled?: boolean;
+  noteFormat?: NoteFormat;
+  priority?: TodoPriority;
+  energy?: EnergyLevel | 'none';
+  context?: string;
+  color?: string;
+  reminderOffsetMinutes?: number | null;
+  estimateMinutes?: number | null;
+  noteCollapsed?: boolean;
+};
+
+export type BulkPatch = {
+  recurrenceTag?: RecurrenceTag;
+  dueDate?: string | null;
+  completed?: boolean;
+};
+
+export type ImportResult = {
+  imported: number;
+  skipped: number;
+  overwritten: number;
+};
+
+export type TodosChangedKind = 'created' | 'updated' | 'toggled' | 'deleted' | 'reordered';
+
+export type TodosChangedEvent = {
+  kind: TodosChangedKind;
+  id: string | null;
+};
+
+export type MigrationProgress = {
+  done: number;
+  total: number;
 };
 
 export type MigrationResult = {
   migratedCount: number;
   alreadyMigrated: boolean;
+  totalReceived: number;
+  skippedEmptyTitle: number;
+  skippedDuplicateId: number;
+};
+
+export type PanelSize = {
+  width: number;
+  height: number;
 };
 
 export type WindowPrefs = {
@@ -56,12 +280,45 @@ export type WindowPrefs = {
   mode: PanelMode;
   sizeClass: WindowSizeClass;
   alwaysOnTop: boolean;
+  aspectRatio?: number | null;
+  startMinimized?: boolean;
+  miniSize?: PanelSize;
+  expandedSize?: PanelSize;
+  dockEdge?: DockEdge | null;
+  alwaysOnTopMini?: boolean | null;
+  alwaysOnTopExpanded?: boolean | null;
+  opacity?: number;
+  ignoreCursorEvents?: boolean;
 };
 
 export type UiPrefs = {
   motionMode: MotionMode;
   readabilityMode: ReadabilityMode;
   reduceMotionOverride: ReduceMotionOverride;
+  locale: DateLocale;
+  dueNotificationsEnabled: boolean;
+  dueCheckIntervalMinutes: number;
+  reminderSnoozeDefaultMinutes: number;
+  recurrenceAnchor: RecurrenceAnchor;
+  dimOnBlur: boolean;
+  blurredOpacity: number;
+  autoCompleteParent: boolean;
+  lastFilter: Filter;
+  lastScrollOffset: number;
+  httpServerEnabled: boolean;
+  httpServerPort: number;
+  httpServerLan: boolean;
+  sortMode: SortMode;
+  quickCaptureShortcut: string;
+  firstDayOfWeek: FirstDayOfWeek;
+  dateFormat: DateFormat;
+  completedPlacement: CompletedPlacement;
+  trashRetentionDays: number;
+  maxAttachmentsPerTodo: number;
+  autoArchiveDays: number;
+  dailySummaryEnabled: boolean;
+  dailySummaryTime: string;
+  dailySummaryTzOffsetMinutes: number | null;
 };
 
 export type DeletedSnapshot = {
@@ -74,6 +331,13 @@ export type DailyHeatmapDay = {
   count: number;
 };
 
+export type DailySummary = {
+  date: string;
+  completedToday: number;
+  overdue: number;
+  dueTomorrow: number;
+};
+
 export type DueReminder = {
   id: string;
   title: string;
@@ -81,3 +345,11 @@ export type DueReminder = {
   daysOverdue: number;
   recurrenceTag: RecurrenceTag;
 };
+
+export type AppErrorCode = 'notFound' | 'validation' | 'conflict' | 'limitReached' | 'database' | 'lock' | 'serialization' | 'window' | 'io';
+
+export type AppError = {
+  code: AppErrorCode;
+  message: string;
+  existingId?: string;
+};